@@ -0,0 +1,152 @@
+use crate::services::agent_service::{self, AgentEvent, AgentHooks, AgentOutcome, AgentRequest, ApprovalMode};
+use crate::services::agent_tools::{ToolContext, ToolInfo, ToolRegistry};
+use crate::services::llm_adapter::{Reasoning, ReasoningEffort, ToolCall};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use super::llm::{resolve_attachments, ActiveStream};
+use async_trait::async_trait;
+use tauri::{AppHandle, Emitter, State, Window};
+
+/// Model turns an agent run may take unless the caller sets `max_steps`
+const DEFAULT_MAX_STEPS: usize = 25;
+
+/// Sends a run's events to the window that started it as `agent-{run_id}`, and asks
+/// that window for approvals
+struct WindowHooks<'a> {
+    window: &'a Window,
+    state: &'a AppState,
+    run_id: &'a str,
+}
+
+#[async_trait]
+impl AgentHooks for WindowHooks<'_> {
+    fn emit(&self, event: AgentEvent) {
+        self.window
+            .emit_to(self.window.label(), &format!("agent-{}", self.run_id), event)
+            .ok();
+    }
+
+    async fn approve(&self, step: usize, call: &ToolCall, arguments: &serde_json::Value) -> bool {
+        // Register before announcing so an immediate answer is not lost
+        let answer = self.state.request_approval(self.run_id, &call.id);
+        self.emit(AgentEvent::ApprovalRequired {
+            step,
+            call_id: call.id.clone(),
+            name: call.function.name.clone(),
+            arguments: arguments.clone(),
+        });
+        answer.await.unwrap_or(false)
+    }
+}
+
+/// Built-in tools plus the tools of the primary root's allowed MCP servers
+async fn agent_tools(state: &AppState, label: &str) -> Result<ToolRegistry, String> {
+    let mut registry = ToolRegistry::builtin();
+    for tool in state.mcp_for(label, false).await?.agent_tools() {
+        registry.register(tool);
+    }
+    Ok(registry)
+}
+
+/// Tools the agent can use in this window
+#[tauri::command]
+pub async fn list_agent_tools(
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<Vec<ToolInfo>>, String> {
+    match agent_tools(&state, window.label()).await {
+        Ok(registry) => Ok(ApiResponse::success(registry.infos())),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e)),
+    }
+}
+
+/// Run the agent loop in the backend: the model is called with the selected tools, their
+/// results are fed back, and progress is emitted as `agent-{run_id}` events until it
+/// finishes. Stop a run with `cancel_stream(run_id)`.
+#[tauri::command]
+pub async fn run_agent(
+    app: AppHandle,
+    window: Window,
+    state: State<'_, AppState>,
+    run_id: String,
+    messages: Vec<serde_json::Value>,
+    tools: Option<Vec<String>>,
+    model: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    reasoning_effort: Option<ReasoningEffort>,
+    reasoning_budget: Option<u32>,
+    max_steps: Option<usize>,
+    approval: Option<ApprovalMode>,
+    fallback_models: Option<Vec<String>>,
+) -> Result<ApiResponse<AgentOutcome>, String> {
+    let client = state.llm_client(window.label());
+
+    if !client.is_configured() {
+        return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
+    }
+
+    let registry = match agent_tools(&state, window.label()).await {
+        Ok(registry) => registry,
+        Err(e) => return Ok(ApiResponse::error("MCP_ERROR", &e)),
+    };
+    let registry = match tools {
+        Some(names) => registry.only(&names),
+        None => registry,
+    };
+    if registry.is_empty() {
+        return Ok(ApiResponse::error("INVALID_INPUT", "None of the requested tools exist"));
+    }
+
+    let messages = match resolve_attachments(&state, window.label(), &client, messages).await {
+        Ok(messages) => messages,
+        Err(e) => return Ok(ApiResponse::error("INVALID_ATTACHMENT", &e)),
+    };
+
+    let workspace = state.root_path(window.label(), None)?;
+    let active = ActiveStream::begin(&state, window.label(), &run_id);
+    let ctx = ToolContext {
+        app,
+        window: window.label().to_string(),
+        workspace,
+        cancel: active.handle.clone(),
+    };
+    let request = AgentRequest {
+        messages,
+        model: client.model_or_default(model),
+        temperature: temperature.unwrap_or(0.7),
+        max_tokens: max_tokens.unwrap_or(4096),
+        reasoning: Reasoning::new(reasoning_effort, reasoning_budget),
+        fallback_models: client.fallbacks_or_default(fallback_models),
+        max_steps: max_steps.unwrap_or(DEFAULT_MAX_STEPS),
+        approval: approval.unwrap_or_default(),
+    };
+    let hooks = WindowHooks {
+        window: &window,
+        state: &state,
+        run_id: &run_id,
+    };
+
+    let result = agent_service::run_agent(&client, &registry, &ctx, request, &hooks).await;
+    state.end_approvals(&run_id);
+
+    match result {
+        Ok(outcome) => Ok(ApiResponse::success(outcome)),
+        Err(e) => Ok(ApiResponse::error(e.code("LLM_ERROR"), &e.to_string())),
+    }
+}
+
+/// Approve or reject a tool call an agent run is waiting on
+#[tauri::command]
+pub async fn respond_tool_approval(
+    state: State<'_, AppState>,
+    run_id: String,
+    call_id: String,
+    approved: bool,
+) -> Result<ApiResponse<bool>, String> {
+    if state.resolve_approval(&run_id, &call_id, approved) {
+        Ok(ApiResponse::success(approved))
+    } else {
+        Ok(ApiResponse::error("APPROVAL_NOT_FOUND", "No tool call is waiting for this approval"))
+    }
+}
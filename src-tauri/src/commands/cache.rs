@@ -0,0 +1,48 @@
+use crate::services::cache_service::{CacheKind, CacheSettings, CacheStats};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use tauri::State;
+
+const CACHE_UNAVAILABLE: &str = "The response cache could not be opened";
+
+/// Entry counts, sizes and hit rates per kind, with the current settings
+#[tauri::command]
+pub async fn get_cache_stats(
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<CacheStats>, String> {
+    let Some(cache) = &state.cache else {
+        return Ok(ApiResponse::error("CACHE_UNAVAILABLE", CACHE_UNAVAILABLE));
+    };
+
+    Ok(ApiResponse::success(cache.stats()?))
+}
+
+#[tauri::command]
+pub async fn update_cache_settings(
+    state: State<'_, AppState>,
+    settings: CacheSettings,
+) -> Result<ApiResponse<CacheStats>, String> {
+    let Some(cache) = &state.cache else {
+        return Ok(ApiResponse::error("CACHE_UNAVAILABLE", CACHE_UNAVAILABLE));
+    };
+
+    if settings.max_size_mb == 0 {
+        return Ok(ApiResponse::error("INVALID_SETTINGS", "Cache size must be at least 1 MB"));
+    }
+    cache.set_settings(&settings)?;
+
+    Ok(ApiResponse::success(cache.stats()?))
+}
+
+/// Remove cached responses of one kind, or all of them. Returns the number removed.
+#[tauri::command]
+pub async fn clear_cache(
+    state: State<'_, AppState>,
+    kind: Option<CacheKind>,
+) -> Result<ApiResponse<usize>, String> {
+    let Some(cache) = &state.cache else {
+        return Ok(ApiResponse::error("CACHE_UNAVAILABLE", CACHE_UNAVAILABLE));
+    };
+
+    Ok(ApiResponse::success(cache.clear(kind)?))
+}
@@ -1,14 +1,23 @@
-use crate::services::config::{is_local_llm_base_url, ApiKeysConfig};
+use crate::services::config::ApiKeysConfig;
+use crate::services::provider_service::ProviderProfile;
+use crate::services::secrets_service::{self, KeySource, SecretsError};
+use crate::services::workspace_service::{Workspace, WorkspaceRoot};
 use crate::services::AppState;
 use super::file_ops::ApiResponse;
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
-use tauri::State;
+use tauri::{State, Window};
 
 #[derive(Debug, Serialize)]
 pub struct ConfigInfo {
+    /// Folder of the primary root
     #[serde(rename = "workspaceRoot")]
     pub workspace_root: String,
+    #[serde(rename = "workspaceRoots")]
+    pub workspace_roots: Vec<WorkspaceRoot>,
+    #[serde(rename = "primaryRootId")]
+    pub primary_root_id: String,
     #[serde(rename = "llmConfigured")]
     pub llm_configured: bool,
     #[serde(rename = "llmProvider")]
@@ -29,21 +38,26 @@ pub struct AppInfo {
 
 #[tauri::command]
 pub async fn get_config(
+    window: Window,
     state: State<'_, AppState>,
 ) -> Result<ApiResponse<ConfigInfo>, String> {
-    let config = state.config.lock().unwrap();
+    let workspace = state.with_window(window.label(), |w| w.workspace.clone());
+    let target = state.config.lock().unwrap().llm_target();
 
     Ok(ApiResponse::success(ConfigInfo {
-        workspace_root: config.workspace_root.to_string_lossy().to_string(),
-        llm_configured: config.llm_api_key.is_some() || config.llm_proxy_url.is_some() || is_local_llm_base_url(&config.llm_base_url),
-        llm_provider: config.llm_provider.clone(),
-        llm_model: config.llm_model.clone(),
-        llm_base_url: config.llm_base_url.clone(),
+        workspace_root: workspace.primary_root().to_string_lossy().to_string(),
+        workspace_roots: workspace.roots,
+        primary_root_id: workspace.primary_root_id,
+        llm_configured: target.is_configured(),
+        llm_provider: target.provider,
+        llm_model: target.model,
+        llm_base_url: target.base_url,
     }))
 }
 
 #[tauri::command]
 pub async fn set_workspace(
+    window: Window,
     state: State<'_, AppState>,
     path: String,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
@@ -57,20 +71,15 @@ pub async fn set_workspace(
         return Ok(ApiResponse::error("NOT_A_DIRECTORY", "Path is not a directory"));
     }
 
-    // Update config
-    {
-        let mut config = state.config.lock().unwrap();
-        config.set_workspace(new_path.clone());
-    }
-
-    // Update trash manager
-    {
-        let mut trash = state.trash.lock().unwrap();
-        trash.update_workspace(&new_path);
-    }
+    // Only this window switches folders; new windows start from the most recent one
+    let workspace = Workspace::single(new_path.clone());
+    let primary_root_id = workspace.primary_root_id.clone();
+    state.config.lock().unwrap().set_workspace(new_path);
+    state.replace_workspace(window.label(), workspace);
 
     Ok(ApiResponse::success(serde_json::json!({
-        "workspaceRoot": path
+        "workspaceRoot": path,
+        "primaryRootId": primary_root_id
     })))
 }
 
@@ -101,9 +110,7 @@ pub async fn clear_local_llm_config(state: State<'_, AppState>) -> Result<ApiRes
         "llmBaseUrl": config.llm_base_url,
         "llmModel": config.llm_model,
         "llmProvider": config.llm_provider,
-        "llmConfigured": config.llm_api_key.is_some()
-            || config.llm_proxy_url.is_some()
-            || crate::services::config::is_local_llm_base_url(&config.llm_base_url)
+        "llmConfigured": config.llm_target().is_configured()
     })))
 }
 
@@ -154,6 +161,36 @@ pub struct ApiKeysInfo {
     /// Whether the user has any API key configured
     #[serde(rename = "hasAnyKey")]
     pub has_any_key: bool,
+    /// Masked keys by provider (e.g. `sk-o…x9Qz`). Raw keys never leave the backend.
+    #[serde(rename = "maskedKeys")]
+    pub masked_keys: HashMap<String, String>,
+    /// True when the keys are encrypted with a passphrase that has not been entered yet
+    pub locked: bool,
+    /// How keys are encrypted on save; `None` until a passphrase is set on systems without a secret service
+    #[serde(rename = "keySource")]
+    pub key_source: Option<KeySource>,
+}
+
+impl ApiKeysInfo {
+    fn from_keys(keys: &ApiKeysConfig) -> Self {
+        Self {
+            configured_providers: keys.configured_providers(),
+            has_any_key: keys.has_any_key(),
+            masked_keys: keys.masked_keys(),
+            locked: false,
+            key_source: secrets_service::key_source(),
+        }
+    }
+
+    fn locked() -> Self {
+        Self {
+            configured_providers: Vec::new(),
+            has_any_key: false,
+            masked_keys: HashMap::new(),
+            locked: true,
+            key_source: secrets_service::key_source(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -164,54 +201,90 @@ pub struct SetApiKeyRequest {
 
 #[tauri::command]
 pub async fn get_api_keys_info() -> Result<ApiResponse<ApiKeysInfo>, String> {
-    let keys = ApiKeysConfig::load();
-    Ok(ApiResponse::success(ApiKeysInfo {
-        configured_providers: keys.configured_providers(),
-        has_any_key: keys.has_any_key(),
-    }))
+    match ApiKeysConfig::load_checked() {
+        Ok(keys) => Ok(ApiResponse::success(ApiKeysInfo::from_keys(&keys))),
+        Err(SecretsError::Locked) => Ok(ApiResponse::success(ApiKeysInfo::locked())),
+        Err(e) => Ok(ApiResponse::error("KEYS_ERROR", &e.to_string())),
+    }
 }
 
 #[tauri::command]
 pub async fn set_api_key(
+    state: State<'_, AppState>,
     provider: String,
     api_key: Option<String>,
 ) -> Result<ApiResponse<ApiKeysInfo>, String> {
-    let mut keys = ApiKeysConfig::load();
+    // Never fall back to empty keys here: saving them would overwrite a locked file
+    let mut keys = match ApiKeysConfig::load_checked() {
+        Ok(keys) => keys,
+        Err(SecretsError::Locked) => return Ok(ApiResponse::error("KEYS_LOCKED", &SecretsError::Locked.to_string())),
+        Err(e) => return Ok(ApiResponse::error("KEYS_ERROR", &e.to_string())),
+    };
 
     // Validate the key is not empty string if Some
     let key_to_set = api_key.filter(|k| !k.trim().is_empty());
 
+    let adding = key_to_set.is_some();
     keys.set_key(&provider, key_to_set);
-    keys.save().map_err(|e| e.to_string())?;
+    if let Err(e) = keys.save() {
+        return Ok(ApiResponse::error("KEYS_ERROR", &e));
+    }
 
-    Ok(ApiResponse::success(ApiKeysInfo {
-        configured_providers: keys.configured_providers(),
-        has_any_key: keys.has_any_key(),
-    }))
+    // Keys for the built-in providers get a profile so they can be selected right away
+    if adding {
+        let mut config = state.config.lock().unwrap();
+        if config.providers.ensure_builtin(&provider) {
+            if let Err(e) = config.providers.save() {
+                tracing::warn!("Could not save provider profiles: {}", e);
+            }
+        }
+    }
+
+    Ok(ApiResponse::success(ApiKeysInfo::from_keys(&keys)))
 }
 
 #[tauri::command]
 pub async fn get_api_key_for_provider(provider: String) -> Result<ApiResponse<serde_json::Value>, String> {
     let keys = ApiKeysConfig::load();
-    let has_key = keys.get_key(&provider).is_some();
+    let key = keys.get_key(&provider);
 
-    // Don't return the actual key for security, just whether it exists
+    // Only a masked form is returned; the raw key stays in the backend
     Ok(ApiResponse::success(serde_json::json!({
         "provider": provider,
-        "hasKey": has_key
+        "hasKey": key.is_some(),
+        "maskedKey": key.map(|k| secrets_service::mask_secret(&k))
     })))
 }
 
 #[tauri::command]
 pub async fn clear_api_key(provider: String) -> Result<ApiResponse<ApiKeysInfo>, String> {
-    let mut keys = ApiKeysConfig::load();
+    let mut keys = match ApiKeysConfig::load_checked() {
+        Ok(keys) => keys,
+        Err(SecretsError::Locked) => return Ok(ApiResponse::error("KEYS_LOCKED", &SecretsError::Locked.to_string())),
+        Err(e) => return Ok(ApiResponse::error("KEYS_ERROR", &e.to_string())),
+    };
     keys.set_key(&provider, None);
-    keys.save().map_err(|e| e.to_string())?;
+    if let Err(e) = keys.save() {
+        return Ok(ApiResponse::error("KEYS_ERROR", &e));
+    }
 
-    Ok(ApiResponse::success(ApiKeysInfo {
-        configured_providers: keys.configured_providers(),
-        has_any_key: keys.has_any_key(),
-    }))
+    Ok(ApiResponse::success(ApiKeysInfo::from_keys(&keys)))
+}
+
+/// Provide the passphrase used to encrypt API keys when no OS secret service is available.
+/// With no saved keys yet, this sets the passphrase for future saves.
+#[tauri::command]
+pub async fn unlock_api_keys(passphrase: String) -> Result<ApiResponse<ApiKeysInfo>, String> {
+    secrets_service::set_passphrase(Some(passphrase));
+
+    match ApiKeysConfig::load_checked() {
+        Ok(keys) => Ok(ApiResponse::success(ApiKeysInfo::from_keys(&keys))),
+        Err(SecretsError::Decrypt) => {
+            secrets_service::set_passphrase(None);
+            Ok(ApiResponse::error("WRONG_PASSPHRASE", "Wrong passphrase"))
+        }
+        Err(e) => Ok(ApiResponse::error("KEYS_ERROR", &e.to_string())),
+    }
 }
 
 /// Internal function to get API key for LLM service
@@ -219,3 +292,111 @@ pub fn get_api_key_internal(provider: &str) -> Option<String> {
     let keys = ApiKeysConfig::load();
     keys.get_key(provider)
 }
+
+// ============================================================================
+// Provider Profiles
+// ============================================================================
+
+#[derive(Debug, Serialize)]
+pub struct ProviderProfileInfo {
+    #[serde(flatten)]
+    pub profile: ProviderProfile,
+    /// Whether the key the profile refers to is saved
+    #[serde(rename = "hasKey")]
+    pub has_key: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProvidersInfo {
+    /// Active profile ID; `None` when the proxy/local settings are used
+    pub active: Option<String>,
+    pub profiles: Vec<ProviderProfileInfo>,
+}
+
+fn providers_info(state: &AppState) -> ProvidersInfo {
+    let providers = state.config.lock().unwrap().providers.clone();
+    let keys = ApiKeysConfig::load();
+
+    ProvidersInfo {
+        active: providers.active,
+        profiles: providers
+            .profiles
+            .into_iter()
+            .map(|mut profile| {
+                // Headers often carry credentials, so they are listed masked like keys
+                for value in profile.headers.values_mut() {
+                    *value = secrets_service::mask_secret(value);
+                }
+                ProviderProfileInfo {
+                    has_key: profile
+                        .key_ref
+                        .as_deref()
+                        .is_some_and(|r| keys.get_key(r).is_some()),
+                    profile,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[tauri::command]
+pub async fn list_provider_profiles(state: State<'_, AppState>) -> Result<ApiResponse<ProvidersInfo>, String> {
+    Ok(ApiResponse::success(providers_info(&state)))
+}
+
+/// Add a profile, or replace the one with the same ID
+#[tauri::command]
+pub async fn save_provider_profile(
+    state: State<'_, AppState>,
+    profile: ProviderProfile,
+) -> Result<ApiResponse<ProvidersInfo>, String> {
+    {
+        let mut config = state.config.lock().unwrap();
+        if let Err(e) = config.providers.upsert(profile) {
+            return Ok(ApiResponse::error("INVALID_PROFILE", &e));
+        }
+        if let Err(e) = config.providers.save() {
+            return Ok(ApiResponse::error("PROFILE_ERROR", &e));
+        }
+    }
+
+    Ok(ApiResponse::success(providers_info(&state)))
+}
+
+/// Delete a profile. Its saved key is kept, since other profiles may refer to it.
+#[tauri::command]
+pub async fn delete_provider_profile(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<ApiResponse<ProvidersInfo>, String> {
+    {
+        let mut config = state.config.lock().unwrap();
+        if !config.providers.remove(&id) {
+            return Ok(ApiResponse::error("PROFILE_NOT_FOUND", &format!("Provider profile not found: {}", id)));
+        }
+        if let Err(e) = config.providers.save() {
+            return Ok(ApiResponse::error("PROFILE_ERROR", &e));
+        }
+    }
+
+    Ok(ApiResponse::success(providers_info(&state)))
+}
+
+/// Switch LLM requests to a profile, or back to the proxy/local settings with `None`
+#[tauri::command]
+pub async fn set_active_provider(
+    state: State<'_, AppState>,
+    id: Option<String>,
+) -> Result<ApiResponse<ProvidersInfo>, String> {
+    {
+        let mut config = state.config.lock().unwrap();
+        if let Err(e) = config.providers.set_active(id.as_deref()) {
+            return Ok(ApiResponse::error("PROFILE_NOT_FOUND", &e));
+        }
+        if let Err(e) = config.providers.save() {
+            return Ok(ApiResponse::error("PROFILE_ERROR", &e));
+        }
+    }
+
+    Ok(ApiResponse::success(providers_info(&state)))
+}
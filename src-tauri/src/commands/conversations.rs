@@ -0,0 +1,173 @@
+use crate::services::AppState;
+use crate::services::conversation_service::{
+    Conversation, ConversationSummary, ConversationThread, ExportFormat, MessageSearchHit,
+    NewMessage, StoredMessage,
+};
+use super::file_ops::ApiResponse;
+use serde::Serialize;
+use tauri::{State, Window};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationListResult {
+    pub conversations: Vec<ConversationSummary>,
+    pub count: usize,
+    pub total: usize,
+}
+
+#[tauri::command]
+pub async fn create_conversation(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    title: Option<String>,
+    metadata: Option<serde_json::Value>,
+) -> Result<ApiResponse<Conversation>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    let conversation = store.create_conversation(title, metadata)?;
+    Ok(ApiResponse::success(conversation))
+}
+
+#[tauri::command]
+pub async fn list_conversations(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<ApiResponse<ConversationListResult>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    let (conversations, total) = store.list_conversations(limit.unwrap_or(50), offset.unwrap_or(0))?;
+    let count = conversations.len();
+
+    Ok(ApiResponse::success(ConversationListResult {
+        conversations,
+        count,
+        total,
+    }))
+}
+
+/// A conversation with the messages of its active branch
+#[tauri::command]
+pub async fn get_conversation(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    id: String,
+) -> Result<ApiResponse<ConversationThread>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    match store.thread(&id)? {
+        Some(thread) => Ok(ApiResponse::success(thread)),
+        None => Ok(ApiResponse::error("NOT_FOUND", "Conversation not found")),
+    }
+}
+
+#[tauri::command]
+pub async fn append_message(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    conversation_id: String,
+    message: NewMessage,
+) -> Result<ApiResponse<StoredMessage>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    match store.append_message(&conversation_id, message) {
+        Ok(message) => Ok(ApiResponse::success(message)),
+        Err(e) => Ok(ApiResponse::error("APPEND_ERROR", &e)),
+    }
+}
+
+/// Edit an earlier message, keeping the original in its own branch
+#[tauri::command]
+pub async fn edit_message(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    message_id: String,
+    content: serde_json::Value,
+) -> Result<ApiResponse<StoredMessage>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    match store.edit_message(&message_id, content) {
+        Ok(message) => Ok(ApiResponse::success(message)),
+        Err(e) => Ok(ApiResponse::error("EDIT_ERROR", &e)),
+    }
+}
+
+/// Show the branch through a message, such as a sibling from `siblingIds`
+#[tauri::command]
+pub async fn switch_branch(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    message_id: String,
+) -> Result<ApiResponse<ConversationThread>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    match store.switch_branch(&message_id) {
+        Ok(thread) => Ok(ApiResponse::success(thread)),
+        Err(e) => Ok(ApiResponse::error("NOT_FOUND", &e)),
+    }
+}
+
+#[tauri::command]
+pub async fn rename_conversation(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    id: String,
+    title: String,
+) -> Result<ApiResponse<bool>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    let renamed = store.rename_conversation(&id, title.trim())?;
+    Ok(ApiResponse::success(renamed))
+}
+
+#[tauri::command]
+pub async fn delete_conversation(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    id: String,
+) -> Result<ApiResponse<bool>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    let deleted = store.delete_conversation(&id)?;
+    Ok(ApiResponse::success(deleted))
+}
+
+#[tauri::command]
+pub async fn search_conversations(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<ApiResponse<Vec<MessageSearchHit>>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    let hits = store.search_messages(&query, limit.unwrap_or(20))?;
+    Ok(ApiResponse::success(hits))
+}
+
+/// The active branch as JSON or Markdown text
+#[tauri::command]
+pub async fn export_conversation(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    id: String,
+    format: ExportFormat,
+) -> Result<ApiResponse<String>, String> {
+    let store = state.memory_for(window.label(), root_id.as_deref())?.conversations();
+
+    match store.export(&id, format)? {
+        Some(exported) => Ok(ApiResponse::success(exported)),
+        None => Ok(ApiResponse::error("NOT_FOUND", "Conversation not found")),
+    }
+}
@@ -1,6 +1,6 @@
 use crate::services::{file_service, AppState};
 use serde::Serialize;
-use tauri::State;
+use tauri::{State, Window};
 
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
@@ -70,14 +70,14 @@ pub struct SearchResult {
 
 #[tauri::command]
 pub async fn list_directory(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
     recursive: Option<bool>,
     include_hidden: Option<bool>,
 ) -> Result<ApiResponse<ListResult>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match file_service::list_directory(
         &workspace,
@@ -95,13 +95,13 @@ pub async fn list_directory(
 
 #[tauri::command]
 pub async fn read_file(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
 ) -> Result<ApiResponse<ReadResult>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    let max_size = config.max_read_size;
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
+    let max_size = state.config.lock().unwrap().max_read_size;
 
     match file_service::read_file(&workspace, &path, max_size) {
         Ok((content, sha256, size)) => Ok(ApiResponse::success(ReadResult {
@@ -123,14 +123,14 @@ pub struct ReadBinaryResult {
 
 #[tauri::command]
 pub async fn read_file_binary(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
 ) -> Result<ApiResponse<ReadBinaryResult>, String> {
     use base64::{Engine as _, engine::general_purpose::STANDARD};
 
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let full_path = std::path::Path::new(&workspace).join(&path);
 
@@ -164,15 +164,15 @@ pub async fn read_file_binary(
 
 #[tauri::command]
 pub async fn write_file(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
     content: String,
     create_dirs: Option<bool>,
     overwrite: Option<bool>,
 ) -> Result<ApiResponse<WriteResult>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match file_service::write_file(
         &workspace,
@@ -193,13 +193,13 @@ pub async fn write_file(
 
 #[tauri::command]
 pub async fn create_directory(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
     recursive: Option<bool>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match file_service::create_directory(&workspace, &path, recursive.unwrap_or(true)) {
         Ok(created) => Ok(ApiResponse::success(serde_json::json!({
@@ -212,22 +212,26 @@ pub async fn create_directory(
 
 #[tauri::command]
 pub async fn delete_path(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
     recursive: Option<bool>,
     permanent: Option<bool>,
+    request_id: Option<String>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     // If not permanent, move to trash
     if !permanent.unwrap_or(false) {
-        let trash = state.trash.lock().unwrap();
         let full_path = file_service::resolve_path(&workspace, &path)
             .map_err(|e| e.to_string())?;
 
-        match trash.move_to_trash(&full_path, &path, &uuid::Uuid::new_v4().to_string()) {
+        let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        match state.with_trash(window.label(), root_id.as_deref(), |trash| {
+            trash.move_to_trash(&full_path, &path, &request_id)
+        })? {
             Ok((trash_id, _)) => Ok(ApiResponse::success(serde_json::json!({
                 "path": path,
                 "trashId": trash_id,
@@ -248,14 +252,14 @@ pub async fn delete_path(
 
 #[tauri::command]
 pub async fn move_path(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     from: String,
     to: String,
     overwrite: Option<bool>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match file_service::move_path(&workspace, &from, &to, overwrite.unwrap_or(false)) {
         Ok(()) => Ok(ApiResponse::success(serde_json::json!({
@@ -269,14 +273,14 @@ pub async fn move_path(
 
 #[tauri::command]
 pub async fn copy_path(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     from: String,
     to: String,
     overwrite: Option<bool>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match file_service::copy_path(&workspace, &from, &to, overwrite.unwrap_or(false)) {
         Ok(()) => Ok(ApiResponse::success(serde_json::json!({
@@ -290,13 +294,13 @@ pub async fn copy_path(
 
 #[tauri::command]
 pub async fn get_stat(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
     include_hash: Option<bool>,
 ) -> Result<ApiResponse<file_service::FileStat>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match file_service::get_stat(&workspace, &path, include_hash.unwrap_or(false)) {
         Ok(stat) => Ok(ApiResponse::success(stat)),
@@ -306,16 +310,16 @@ pub async fn get_stat(
 
 #[tauri::command]
 pub async fn search_files(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     query: String,
     path: Option<String>,
     case_sensitive: Option<bool>,
     max_results: Option<usize>,
 ) -> Result<ApiResponse<SearchResult>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    let default_max = config.max_search_results;
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
+    let default_max = state.config.lock().unwrap().max_search_results;
 
     let search_path = path.unwrap_or_else(|| ".".to_string());
     let max = max_results.unwrap_or(default_max);
@@ -342,12 +346,12 @@ pub async fn search_files(
 
 #[tauri::command]
 pub async fn exists(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
 ) -> Result<ApiResponse<bool>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let exists = file_service::exists(&workspace, &path);
     Ok(ApiResponse::success(exists))
@@ -1,21 +1,8 @@
+use crate::services::git_service::{self, run_git_command, GitStatusResult};
 use crate::services::AppState;
 use super::file_ops::ApiResponse;
 use serde::Serialize;
-use std::process::Command;
-use tauri::State;
-
-#[derive(Debug, Serialize)]
-pub struct GitChange {
-    pub path: String,
-    pub status: String,
-    pub staged: bool,
-}
-
-#[derive(Debug, Serialize)]
-pub struct GitStatusResult {
-    pub branch: Option<String>,
-    pub changes: Vec<GitChange>,
-}
+use tauri::{State, Window};
 
 #[derive(Debug, Serialize)]
 pub struct GitBranch {
@@ -32,28 +19,14 @@ pub struct GitCommit {
     pub message: String,
 }
 
-fn run_git_command(args: &[&str], cwd: &std::path::Path) -> Result<String, String> {
-    let output = Command::new("git")
-        .args(args)
-        .current_dir(cwd)
-        .output()
-        .map_err(|e| format!("Failed to run git: {}", e))?;
-
-    if output.status.success() {
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
-    } else {
-        Err(String::from_utf8_lossy(&output.stderr).to_string())
-    }
-}
-
 #[tauri::command]
 pub async fn git_status(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: Option<String>,
 ) -> Result<ApiResponse<GitStatusResult>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let cwd = if let Some(p) = path {
         workspace.join(p)
@@ -61,61 +34,20 @@ pub async fn git_status(
         workspace
     };
 
-    // Get branch name
-    let branch = run_git_command(&["rev-parse", "--abbrev-ref", "HEAD"], &cwd)
-        .ok()
-        .map(|s| s.trim().to_string());
-
-    // Get status
-    let status_output = run_git_command(&["status", "--porcelain"], &cwd)
-        .map_err(|e| format!("Git status failed: {}", e));
-
-    match status_output {
-        Ok(output) => {
-            let changes: Vec<GitChange> = output
-                .lines()
-                .filter_map(|line| {
-                    if line.len() < 4 {
-                        return None;
-                    }
-
-                    let index_status = line.chars().next().unwrap_or(' ');
-                    let worktree_status = line.chars().nth(1).unwrap_or(' ');
-                    let file_path = line[3..].to_string();
-
-                    let (status, staged) = match (index_status, worktree_status) {
-                        ('M', _) => ("modified", true),
-                        (_, 'M') => ("modified", false),
-                        ('A', _) => ("added", true),
-                        ('D', _) => ("deleted", true),
-                        (_, 'D') => ("deleted", false),
-                        ('R', _) => ("renamed", true),
-                        ('?', '?') => ("untracked", false),
-                        _ => ("unknown", false),
-                    };
-
-                    Some(GitChange {
-                        path: file_path,
-                        status: status.to_string(),
-                        staged,
-                    })
-                })
-                .collect();
-
-            Ok(ApiResponse::success(GitStatusResult { branch, changes }))
-        }
+    match git_service::status(&cwd) {
+        Ok(status) => Ok(ApiResponse::success(status)),
         Err(e) => Ok(ApiResponse::error("GIT_ERROR", &e)),
     }
 }
 
 #[tauri::command]
 pub async fn git_stage(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     paths: Vec<String>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let mut args = vec!["add"];
     let path_refs: Vec<&str> = paths.iter().map(|s| s.as_str()).collect();
@@ -131,12 +63,12 @@ pub async fn git_stage(
 
 #[tauri::command]
 pub async fn git_unstage(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     paths: Vec<String>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let mut args = vec!["reset", "HEAD", "--"];
     let path_refs: Vec<&str> = paths.iter().map(|s| s.as_str()).collect();
@@ -152,12 +84,12 @@ pub async fn git_unstage(
 
 #[tauri::command]
 pub async fn git_commit(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     message: String,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match run_git_command(&["commit", "-m", &message], &workspace) {
         Ok(output) => Ok(ApiResponse::success(serde_json::json!({
@@ -170,20 +102,14 @@ pub async fn git_commit(
 
 #[tauri::command]
 pub async fn git_diff(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: Option<String>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
-    let args: Vec<&str> = if let Some(ref p) = path {
-        vec!["diff", p]
-    } else {
-        vec!["diff"]
-    };
-
-    match run_git_command(&args, &workspace) {
+    match git_service::diff(&workspace, path.as_deref()) {
         Ok(diff) => Ok(ApiResponse::success(serde_json::json!({
             "diff": diff
         }))),
@@ -193,11 +119,11 @@ pub async fn git_diff(
 
 #[tauri::command]
 pub async fn git_branches(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     match run_git_command(&["branch", "-a"], &workspace) {
         Ok(output) => {
@@ -228,13 +154,13 @@ pub async fn git_branches(
 
 #[tauri::command]
 pub async fn git_checkout(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     name: String,
     create: Option<bool>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let args: Vec<&str> = if create.unwrap_or(false) {
         vec!["checkout", "-b", &name]
@@ -253,12 +179,12 @@ pub async fn git_checkout(
 
 #[tauri::command]
 pub async fn git_log(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     limit: Option<usize>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let limit_str = limit.unwrap_or(50).to_string();
     let args = vec![
@@ -1,44 +1,19 @@
-use crate::services::config::{is_local_llm_base_url, normalize_base_url_for_openai_api, ApiKeysConfig};
+use crate::services::attachment_service::{self, MessageContent};
+use crate::services::config::normalize_base_url_for_openai_api;
+use crate::services::llm_adapter::{ChatRequest, Reasoning, ReasoningEffort, StreamEvent};
+use crate::services::llm_client::{shared_http_client, LlmClient};
+use crate::services::llm_executor::SendError;
+use crate::services::provider_service::ProviderKind;
+use crate::services::stream_service::StreamHandle;
+use crate::services::structured_output::{self, StructuredRequest, StructuredResult};
+use crate::services::token_service::{self, ModelInfo};
+use crate::services::tool_calls::{self, StreamedToolCalls, ToolCallIssue};
 use crate::services::AppState;
 use super::file_ops::ApiResponse;
 use serde::{Deserialize, Serialize};
-use tauri::{State, AppHandle, Manager, Emitter};
-use futures::StreamExt;
-
-/// Get the API key for the current provider, checking user-saved keys first
-fn get_effective_api_key(config_api_key: Option<String>, base_url: &str) -> Option<String> {
-    let keys = ApiKeysConfig::load();
-
-    // Determine provider from base URL
-    let url_lower = base_url.to_lowercase();
-    let provider = if url_lower.contains("openrouter") {
-        "openrouter"
-    } else if url_lower.contains("openai.com") {
-        "openai"
-    } else if url_lower.contains("anthropic") {
-        "anthropic"
-    } else if url_lower.contains("generativelanguage.googleapis.com") || url_lower.contains("google") {
-        "google"
-    } else if url_lower.contains("groq") {
-        "groq"
-    } else if url_lower.contains("together") {
-        "together"
-    } else if url_lower.contains("deepseek") {
-        "deepseek"
-    } else {
-        ""
-    };
-
-    // First check user-saved API keys
-    if !provider.is_empty() {
-        if let Some(key) = keys.get_key(provider) {
-            return Some(key);
-        }
-    }
-
-    // Fall back to config/env API key
-    config_api_key
-}
+use tauri::{State, Window, Emitter};
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Debug, Serialize)]
 pub struct LlmConnectionResult {
@@ -52,65 +27,98 @@ pub struct LlmConnectionResult {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
     pub role: String,
-    pub content: String,
+    /// Text, or parts mixing text with images and files
+    pub content: MessageContent,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolCall {
-    pub id: String,
-    #[serde(rename = "type")]
-    pub call_type: String,
-    pub function: ToolFunction,
+pub use crate::services::llm_adapter::{ChatCompletionResult, TokenUsage, ToolCall};
+
+fn message_values(messages: &[ChatMessage]) -> Vec<serde_json::Value> {
+    messages
+        .iter()
+        .map(|m| serde_json::json!({ "role": m.role, "content": m.content }))
+        .collect()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ToolFunction {
-    pub name: String,
-    pub arguments: String,
+/// Read attached workspace files, download remote images for providers that cannot fetch
+/// them, and fit images to the provider's limits. Images are decoded and re-encoded, so
+/// that part runs off the async runtime.
+pub(crate) async fn resolve_attachments(
+    state: &AppState,
+    label: &str,
+    client: &LlmClient,
+    mut messages: Vec<serde_json::Value>,
+) -> Result<Vec<serde_json::Value>, String> {
+    if !attachment_service::has_parts(&messages) {
+        return Ok(messages);
+    }
+    let workspace = state.root_path(label, None).ok();
+    let limits = client.image_limits();
+    if !limits.remote_urls {
+        attachment_service::inline_remote_images(&mut messages, &shared_http_client(None))
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tokio::task::spawn_blocking(move || {
+        attachment_service::resolve_messages(&mut messages, workspace.as_deref(), limits)
+            .map(|_| messages)
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Attachment processing failed: {}", e)))
 }
 
-#[derive(Debug, Serialize)]
-pub struct ChatCompletionResult {
-    pub id: String,
-    pub model: String,
-    pub content: Option<String>,
-    #[serde(rename = "finishReason")]
-    pub finish_reason: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub usage: Option<TokenUsage>,
-    #[serde(rename = "toolCalls", skip_serializing_if = "Option::is_none")]
-    pub tool_calls: Option<Vec<ToolCall>>,
+/// Registers a stream in `AppState::streams` for as long as the command runs
+pub(crate) struct ActiveStream<'a> {
+    state: &'a AppState,
+    stream_id: String,
+    pub(crate) handle: Arc<StreamHandle>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct TokenUsage {
-    #[serde(rename = "promptTokens")]
-    pub prompt_tokens: u32,
-    #[serde(rename = "completionTokens")]
-    pub completion_tokens: u32,
-    #[serde(rename = "totalTokens")]
-    pub total_tokens: u32,
+impl<'a> ActiveStream<'a> {
+    pub(crate) fn begin(state: &'a AppState, label: &str, stream_id: &str) -> Self {
+        Self {
+            state,
+            stream_id: stream_id.to_string(),
+            handle: state.begin_stream(label, stream_id),
+        }
+    }
+
+    /// Resolves when `cancel_stream` is called or the window closes
+    pub(crate) async fn cancelled(&self) {
+        self.handle.cancelled().await
+    }
+}
+
+impl Drop for ActiveStream<'_> {
+    fn drop(&mut self) {
+        self.state.end_stream(&self.stream_id, &self.handle);
+    }
+}
+
+/// Stop a running stream. Its command returns the partial reply, after a final
+/// `done` event with `finishReason: "cancelled"`.
+#[tauri::command]
+pub async fn cancel_stream(
+    state: State<'_, AppState>,
+    stream_id: String,
+) -> Result<ApiResponse<bool>, String> {
+    if state.cancel_stream(&stream_id) {
+        Ok(ApiResponse::success(true))
+    } else {
+        Ok(ApiResponse::error("STREAM_NOT_FOUND", "No running stream with this ID"))
+    }
 }
 
 #[tauri::command]
 pub async fn test_llm_connection(
     state: State<'_, AppState>,
 ) -> Result<ApiResponse<LlmConnectionResult>, String> {
-    let (config_api_key, base_url, model, provider, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_model.clone(),
-            config.llm_provider.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
-
-    // Get effective API key (user-saved keys take precedence)
-    let api_key = get_effective_api_key(config_api_key, &base_url);
+    let client = state.config.lock().unwrap().llm_client();
+    let model = client.target().model.clone();
+    let provider = client.target().provider.clone();
 
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::success(LlmConnectionResult {
             connected: false,
             model,
@@ -119,57 +127,31 @@ pub async fn test_llm_connection(
         }));
     }
 
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
-    let request_body = serde_json::json!({
-        "model": model,
-        "messages": [{"role": "user", "content": "Hello"}],
-        "max_tokens": 10,
-        "stream": false
-    });
-
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
-
-    match req.send().await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                Ok(ApiResponse::success(LlmConnectionResult {
-                    connected: true,
-                    model,
-                    provider,
-                    message: None,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::success(LlmConnectionResult {
-                    connected: false,
-                    model,
-                    provider,
-                    message: Some(format!("HTTP {}: {}", status, error_text)),
-                }))
-            }
-        }
-        Err(e) => Ok(ApiResponse::success(LlmConnectionResult {
+    match client.check_connection().await {
+        Ok(()) => Ok(ApiResponse::success(LlmConnectionResult {
+            connected: true,
+            model,
+            provider,
+            message: None,
+        })),
+        Err(SendError::Request(e)) => Ok(ApiResponse::success(LlmConnectionResult {
             connected: false,
             model,
             provider,
             message: Some(format!("Connection error: {}", e)),
         })),
+        Err(e) => Ok(ApiResponse::success(LlmConnectionResult {
+            connected: false,
+            model,
+            provider,
+            message: Some(e.to_string()),
+        })),
     }
 }
 
+const LOCAL_CONNECT_SECS: u64 = 3;
+const LOCAL_LIST_TIMEOUT_SECS: u64 = 10;
+
 /// List models from a local server (Ollama or LM Studio). base_url should be e.g. http://localhost:11434/v1
 #[tauri::command]
 pub async fn list_local_models(base_url: String) -> Result<ApiResponse<Vec<String>>, String> {
@@ -179,12 +161,14 @@ pub async fn list_local_models(base_url: String) -> Result<ApiResponse<Vec<Strin
         return Ok(ApiResponse::success(vec![]));
     }
 
-    let client = reqwest::Client::new();
+    // A local server answers at once when it is up; the timeouts keep a dead one from hanging the picker
+    let client = shared_http_client(Some(LOCAL_CONNECT_SECS));
+    let timeout = Duration::from_secs(LOCAL_LIST_TIMEOUT_SECS);
 
     // Try Ollama first: base is .../v1, root is ...:11434, GET /api/tags
     let ollama_root = base.strip_suffix("/v1").unwrap_or(base);
     let ollama_url = format!("{}/api/tags", ollama_root);
-    if let Ok(res) = client.get(&ollama_url).send().await {
+    if let Ok(res) = client.get(&ollama_url).timeout(timeout).send().await {
         if res.status().is_success() {
             if let Ok(json) = res.json::<serde_json::Value>().await {
                 if let Some(models) = json.get("models").and_then(|m| m.as_array()) {
@@ -200,7 +184,7 @@ pub async fn list_local_models(base_url: String) -> Result<ApiResponse<Vec<Strin
 
     // Try OpenAI-compatible (LM Studio): GET /v1/models
     let openai_url = format!("{}/models", base);
-    if let Ok(res) = client.get(&openai_url).send().await {
+    if let Ok(res) = client.get(&openai_url).timeout(timeout).send().await {
         if res.status().is_success() {
             if let Ok(json) = res.json::<serde_json::Value>().await {
                 if let Some(data) = json.get("data").and_then(|d| d.as_array()) {
@@ -219,641 +203,453 @@ pub async fn list_local_models(base_url: String) -> Result<ApiResponse<Vec<Strin
 
 #[tauri::command]
 pub async fn chat_completion(
+    window: Window,
     state: State<'_, AppState>,
     messages: Vec<ChatMessage>,
     model: Option<String>,
     temperature: Option<f32>,
     max_tokens: Option<u32>,
+    reasoning_effort: Option<ReasoningEffort>,
+    reasoning_budget: Option<u32>,
+    fallback_models: Option<Vec<String>>,
+    cache: Option<bool>,
 ) -> Result<ApiResponse<ChatCompletionResult>, String> {
-    let (config_api_key, base_url, default_model, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_model.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
-
-    let api_key = get_effective_api_key(config_api_key, &base_url);
+    let client = state.llm_client(window.label());
 
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let use_model = model.unwrap_or(default_model);
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
-    let request_body = serde_json::json!({
-        "model": use_model,
-        "messages": messages,
-        "temperature": temperature.unwrap_or(0.7),
-        "max_tokens": max_tokens.unwrap_or(4096),
-        "stream": false
-    });
-
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
+    let use_model = client.model_or_default(model);
+    let messages = match resolve_attachments(&state, window.label(), &client, message_values(&messages)).await {
+        Ok(messages) => messages,
+        Err(e) => return Ok(ApiResponse::error("INVALID_ATTACHMENT", &e)),
+    };
+    let chat = ChatRequest {
+        model: &use_model,
+        messages: &messages,
+        tools: &[],
+        temperature: temperature.unwrap_or(0.7),
+        max_tokens: max_tokens.unwrap_or(4096),
+        stream: false,
+        reasoning: Reasoning::new(reasoning_effort, reasoning_budget),
+        response_format: None,
+    };
 
-    match req.send().await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-
-                let id = json["id"].as_str().unwrap_or("").to_string();
-                let model = json["model"].as_str().unwrap_or(&use_model).to_string();
-                let content = json["choices"][0]["message"]["content"]
-                    .as_str()
-                    .map(String::from);
-                let finish_reason = json["choices"][0]["finish_reason"]
-                    .as_str()
-                    .unwrap_or("stop")
-                    .to_string();
-
-                let usage = if let Some(usage_obj) = json["usage"].as_object() {
-                    Some(TokenUsage {
-                        prompt_tokens: usage_obj["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                        completion_tokens: usage_obj["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                        total_tokens: usage_obj["total_tokens"].as_u64().unwrap_or(0) as u32,
-                    })
-                } else {
-                    None
-                };
-
-                Ok(ApiResponse::success(ChatCompletionResult {
-                    id,
-                    model,
-                    content,
-                    finish_reason,
-                    usage,
-                    tool_calls: None,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::error("LLM_ERROR", &format!("HTTP {}: {}", status, error_text)))
-            }
-        }
-        Err(e) => Ok(ApiResponse::error("LLM_ERROR", &format!("Request failed: {}", e))),
+    let fallbacks = client.fallbacks_or_default(fallback_models);
+    let result = if cache.unwrap_or(false) {
+        client.chat_cached(&chat, &fallbacks).await
+    } else {
+        client.chat(&chat, &fallbacks).await
+    };
+    match result {
+        Ok(result) => Ok(ApiResponse::success(result)),
+        Err(e) => Ok(ApiResponse::error(e.code("LLM_ERROR"), &e.to_string())),
     }
 }
 
 #[tauri::command]
 pub async fn chat_completion_stream(
-    app: AppHandle,
+    window: Window,
     state: State<'_, AppState>,
     messages: Vec<ChatMessage>,
     model: Option<String>,
     temperature: Option<f32>,
     max_tokens: Option<u32>,
+    reasoning_effort: Option<ReasoningEffort>,
+    reasoning_budget: Option<u32>,
+    fallback_models: Option<Vec<String>>,
     stream_id: String,
 ) -> Result<ApiResponse<ChatCompletionResult>, String> {
-    // Get the main window for emitting events
-    let window = app.get_webview_window("main");
-    
-    let (config_api_key, base_url, default_model, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_model.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
+    let active = ActiveStream::begin(&state, window.label(), &stream_id);
 
-    let api_key = get_effective_api_key(config_api_key, &base_url);
+    let client = state.llm_client(window.label());
 
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let use_model = model.unwrap_or(default_model);
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
-    let request_body = serde_json::json!({
-        "model": use_model,
-        "messages": messages,
-        "temperature": temperature.unwrap_or(0.7),
-        "max_tokens": max_tokens.unwrap_or(4096),
-        "stream": true
-    });
-
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
-
-    let mut full_content = String::new();
-    let mut response_id = String::new();
-    let mut response_model = use_model.clone();
-    let mut finish_reason = "stop".to_string();
-    let mut usage: Option<TokenUsage> = None;
-
-    match req.send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                let mut stream = response.bytes_stream();
-                let mut buffer = String::new();
-
-                while let Some(chunk_result) = stream.next().await {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            buffer.push_str(&String::from_utf8_lossy(&chunk));
-                            
-                            // Process complete lines
-                            while let Some(newline_pos) = buffer.find('\n') {
-                                let line = buffer[..newline_pos].trim().to_string();
-                                buffer = buffer[newline_pos + 1..].to_string();
-                                
-                                if line.starts_with("data: ") {
-                                    let data = &line[6..];
-                                    if data == "[DONE]" {
-                                        // Stream complete
-                                        if let Some(ref win) = window {
-                                            win.emit(
-                                                &format!("stream-chunk-{}", stream_id),
-                                                serde_json::json!({
-                                                    "done": true,
-                                                    "usage": usage,
-                                                }),
-                                            ).ok();
-                                        }
-                                        break;
-                                    }
-
-                                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                                        // Extract content delta
-                                        if let Some(delta) = json["choices"][0]["delta"]["content"].as_str() {
-                                            if !delta.is_empty() {
-                                                full_content.push_str(delta);
-                                                if let Some(ref win) = window {
-                                                    win.emit(
-                                                        &format!("stream-chunk-{}", stream_id),
-                                                        serde_json::json!({
-                                                            "content": delta,
-                                                            "full": full_content.clone(),
-                                                        }),
-                                                    ).ok();
-                                                }
-                                            }
-                                        }
-
-                                        // Extract metadata
-                                        if let Some(id) = json["id"].as_str() {
-                                            if !id.is_empty() {
-                                                response_id = id.to_string();
-                                            }
-                                        }
-                                        if let Some(model_str) = json["model"].as_str() {
-                                            if !model_str.is_empty() {
-                                                response_model = model_str.to_string();
-                                            }
-                                        }
-                                        if let Some(finish) = json["choices"][0]["finish_reason"].as_str() {
-                                            if !finish.is_empty() {
-                                                finish_reason = finish.to_string();
-                                            }
-                                        }
-                                        if let Some(usage_obj) = json["usage"].as_object() {
-                                            usage = Some(TokenUsage {
-                                                prompt_tokens: usage_obj["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                                                completion_tokens: usage_obj["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                                                total_tokens: usage_obj["total_tokens"].as_u64().unwrap_or(0) as u32,
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            if let Some(ref win) = window {
-                                win.emit(
-                                    &format!("stream-error-{}", stream_id),
-                                    serde_json::json!({
-                                        "error": format!("Stream error: {}", e),
-                                    }),
-                                ).ok();
-                            }
-                            return Ok(ApiResponse::error("STREAM_ERROR", &format!("Stream error: {}", e)));
-                        }
-                    }
-                }
+    let use_model = client.model_or_default(model);
+    let messages = match resolve_attachments(&state, window.label(), &client, message_values(&messages)).await {
+        Ok(messages) => messages,
+        Err(e) => return Ok(ApiResponse::error("INVALID_ATTACHMENT", &e)),
+    };
+    let chat = ChatRequest {
+        model: &use_model,
+        messages: &messages,
+        tools: &[],
+        temperature: temperature.unwrap_or(0.7),
+        max_tokens: max_tokens.unwrap_or(4096),
+        stream: true,
+        reasoning: Reasoning::new(reasoning_effort, reasoning_budget),
+        response_format: None,
+    };
 
-                Ok(ApiResponse::success(ChatCompletionResult {
-                    id: response_id,
-                    model: response_model,
-                    content: Some(full_content),
-                    finish_reason,
-                    usage,
-                    tool_calls: None,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                if let Some(ref win) = window {
-                    win.emit(
-                        &format!("stream-error-{}", stream_id),
-                        serde_json::json!({
-                            "error": format!("HTTP {}: {}", status, error_text),
-                        }),
-                    ).ok();
-                }
-                Ok(ApiResponse::error("LLM_ERROR", &format!("HTTP {}: {}", status, error_text)))
-            }
-        }
-        Err(e) => {
-            if let Some(ref win) = window {
-                win.emit(
-                    &format!("stream-error-{}", stream_id),
-                    serde_json::json!({
-                        "error": format!("Request failed: {}", e),
-                    }),
-                ).ok();
-            }
-            Ok(ApiResponse::error("LLM_ERROR", &format!("Request failed: {}", e)))
-        }
-    }
+    stream_chat(&window, &client, &active, &chat, &client.fallbacks_or_default(fallback_models), &stream_id).await
 }
 
 /// Streaming chat completion with tools support
 /// Emits events for content chunks, tool calls, and thinking
 #[tauri::command]
 pub async fn chat_completion_stream_with_tools(
-    app: AppHandle,
+    window: Window,
     state: State<'_, AppState>,
     messages: Vec<serde_json::Value>,
     tools: Option<Vec<serde_json::Value>>,
     model: Option<String>,
     temperature: Option<f32>,
     max_tokens: Option<u32>,
+    reasoning_effort: Option<ReasoningEffort>,
+    reasoning_budget: Option<u32>,
+    fallback_models: Option<Vec<String>>,
+    mcp_tools: Option<bool>,
     stream_id: String,
 ) -> Result<ApiResponse<ChatCompletionResult>, String> {
-    let window = app.get_webview_window("main");
-
-    let (config_api_key, base_url, default_model, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_model.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
+    let active = ActiveStream::begin(&state, window.label(), &stream_id);
 
-    let api_key = get_effective_api_key(config_api_key, &base_url);
+    let client = state.llm_client(window.label());
 
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let use_model = model.unwrap_or(default_model);
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
-
-    let mut request_body = serde_json::json!({
-        "model": use_model,
-        "messages": messages,
-        "temperature": temperature.unwrap_or(0.7),
-        "max_tokens": max_tokens.unwrap_or(4096),
-        "stream": true
-    });
-    if let Some(t) = &tools {
-        if !t.is_empty() {
-            request_body["tools"] = serde_json::json!(t);
+    let use_model = client.model_or_default(model);
+    let messages = match resolve_attachments(&state, window.label(), &client, messages).await {
+        Ok(messages) => messages,
+        Err(e) => return Ok(ApiResponse::error("INVALID_ATTACHMENT", &e)),
+    };
+    let mut tools = tools.unwrap_or_default();
+    // When asked for, tools of the workspace's allowed MCP servers are offered as
+    // `mcp__<server>__<tool>`; run their calls with `call_mcp_tool`
+    if mcp_tools.unwrap_or(false) {
+        match state.mcp_for(window.label(), false).await {
+            Ok(hub) => tools.extend(hub.tool_definitions()),
+            Err(e) => return Ok(ApiResponse::error("MCP_ERROR", &e)),
         }
     }
+    let chat = ChatRequest {
+        model: &use_model,
+        messages: &messages,
+        tools: &tools,
+        temperature: temperature.unwrap_or(0.7),
+        max_tokens: max_tokens.unwrap_or(4096),
+        stream: true,
+        reasoning: Reasoning::new(reasoning_effort, reasoning_budget),
+        response_format: None,
+    };
 
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
+    stream_chat(&window, &client, &active, &chat, &client.fallbacks_or_default(fallback_models), &stream_id).await
+}
+
+/// Run a streamed chat for `chat_completion_stream` and `chat_completion_stream_with_tools`,
+/// forwarding it to the window: `stream-thinking-{id}` `started`, then chunk, thinking and
+/// tool events, then a final `done` chunk or a `stream-error-{id}`. Cancelling ends it with
+/// a `done` chunk whose `finishReason` is `cancelled`, and returns what arrived so far.
+async fn stream_chat(
+    window: &Window,
+    client: &LlmClient,
+    active: &ActiveStream<'_>,
+    chat: &ChatRequest<'_>,
+    fallback_models: &[String],
+    stream_id: &str,
+) -> Result<ApiResponse<ChatCompletionResult>, String> {
+    let tools = chat.tools;
+    let mut stream = match client.chat_stream(chat, fallback_models, &active.handle).await {
+        Ok(stream) => stream,
+        Err(SendError::Cancelled) => {
+            window.emit_to(
+                window.label(),
+                &format!("stream-chunk-{}", stream_id),
+                serde_json::json!({
+                    "done": true,
+                    "finishReason": "cancelled",
+                    "full": "",
+                }),
+            ).ok();
+            return Ok(ApiResponse::success(ChatCompletionResult {
+                id: String::new(),
+                model: chat.model.to_string(),
+                content: None,
+                finish_reason: "cancelled".to_string(),
+                usage: None,
+                tool_calls: None,
+                reasoning: None,
+                tool_call_issues: Vec::new(),
+            }));
+        }
+        Err(e) => {
+            window.emit_to(
+                window.label(),
+                &format!("stream-error-{}", stream_id),
+                serde_json::json!({ "error": e.to_string() }),
+            ).ok();
+            return Ok(ApiResponse::error(e.code("LLM_ERROR"), &e.to_string()));
+        }
+    };
 
     let mut full_content = String::new();
+    let mut full_reasoning = String::new();
     let mut response_id = String::new();
-    let mut response_model = use_model.clone();
+    let mut response_model = stream.model.clone();
     let mut finish_reason = "stop".to_string();
     let mut usage: Option<TokenUsage> = None;
-    let mut tool_calls: Vec<ToolCall> = Vec::new();
-    let mut current_tool_call_index: Option<usize> = None;
-
-    match req.send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                let mut stream = response.bytes_stream();
-                let mut buffer = String::new();
-
-                // Emit thinking started
-                if let Some(ref win) = window {
-                    win.emit(
-                        &format!("stream-thinking-{}", stream_id),
-                        serde_json::json!({ "status": "started" }),
-                    ).ok();
+    let mut streamed = StreamedToolCalls::new();
+
+    // Emit thinking started
+    window.emit_to(
+        window.label(),
+        &format!("stream-thinking-{}", stream_id),
+        serde_json::json!({ "status": "started" }),
+    ).ok();
+
+    'stream: loop {
+        let event = tokio::select! {
+            event = stream.next_event() => match event {
+                Some(event) => event,
+                None => break 'stream,
+            },
+            _ = active.cancelled() => {
+                // Leaving the loop drops the response body, which aborts the request
+                finish_reason = "cancelled".to_string();
+                // Calls still being written when the stream stopped are cut short too
+                let (tool_calls, _) = finish_tool_calls(window, stream_id, &mut streamed, tools, true);
+                window.emit_to(
+                    window.label(),
+                    &format!("stream-chunk-{}", stream_id),
+                    serde_json::json!({
+                        "done": true,
+                        "finishReason": finish_reason,
+                        "full": full_content.clone(),
+                        "usage": usage,
+                        "toolCalls": if tool_calls.is_empty() { None } else { Some(&tool_calls) },
+                    }),
+                ).ok();
+                break 'stream;
+            }
+        };
+        match event {
+            Ok(StreamEvent::Meta { id, model }) => {
+                if let Some(id) = id {
+                    response_id = id;
                 }
-
-                while let Some(chunk_result) = stream.next().await {
-                    match chunk_result {
-                        Ok(chunk) => {
-                            buffer.push_str(&String::from_utf8_lossy(&chunk));
-
-                            while let Some(newline_pos) = buffer.find('\n') {
-                                let line = buffer[..newline_pos].trim().to_string();
-                                buffer = buffer[newline_pos + 1..].to_string();
-
-                                if line.starts_with("data: ") {
-                                    let data = &line[6..];
-                                    if data == "[DONE]" {
-                                        if let Some(ref win) = window {
-                                            win.emit(
-                                                &format!("stream-chunk-{}", stream_id),
-                                                serde_json::json!({
-                                                    "done": true,
-                                                    "full": full_content.clone(),
-                                                    "usage": usage,
-                                                    "toolCalls": if tool_calls.is_empty() { None } else { Some(&tool_calls) },
-                                                }),
-                                            ).ok();
-                                        }
-                                        break;
-                                    }
-
-                                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                                        // Extract content delta
-                                        if let Some(delta) = json["choices"][0]["delta"]["content"].as_str() {
-                                            if !delta.is_empty() {
-                                                full_content.push_str(delta);
-                                                if let Some(ref win) = window {
-                                                    win.emit(
-                                                        &format!("stream-chunk-{}", stream_id),
-                                                        serde_json::json!({
-                                                            "content": delta,
-                                                            "full": full_content.clone(),
-                                                        }),
-                                                    ).ok();
-                                                }
-                                            }
-                                        }
-
-                                        // Handle tool calls in stream
-                                        if let Some(tc_array) = json["choices"][0]["delta"]["tool_calls"].as_array() {
-                                            for tc in tc_array {
-                                                let idx = tc["index"].as_u64().unwrap_or(0) as usize;
-
-                                                // Initialize tool call if new
-                                                if idx >= tool_calls.len() {
-                                                    let id = tc["id"].as_str().unwrap_or("").to_string();
-                                                    let name = tc["function"]["name"].as_str().unwrap_or("").to_string();
-                                                    tool_calls.push(ToolCall {
-                                                        id,
-                                                        call_type: "function".to_string(),
-                                                        function: ToolFunction {
-                                                            name: name.clone(),
-                                                            arguments: String::new(),
-                                                        },
-                                                    });
-
-                                                    // Emit tool call started
-                                                    if let Some(ref win) = window {
-                                                        win.emit(
-                                                            &format!("stream-tool-{}", stream_id),
-                                                            serde_json::json!({
-                                                                "status": "started",
-                                                                "index": idx,
-                                                                "name": name,
-                                                            }),
-                                                        ).ok();
-                                                    }
-                                                    current_tool_call_index = Some(idx);
-                                                }
-
-                                                // Append arguments
-                                                if let Some(args) = tc["function"]["arguments"].as_str() {
-                                                    if let Some(tc_ref) = tool_calls.get_mut(idx) {
-                                                        tc_ref.function.arguments.push_str(args);
-                                                    }
-                                                }
-                                            }
-                                        }
-
-                                        // Extract metadata
-                                        if let Some(id) = json["id"].as_str() {
-                                            if !id.is_empty() {
-                                                response_id = id.to_string();
-                                            }
-                                        }
-                                        if let Some(model_str) = json["model"].as_str() {
-                                            if !model_str.is_empty() {
-                                                response_model = model_str.to_string();
-                                            }
-                                        }
-                                        if let Some(finish) = json["choices"][0]["finish_reason"].as_str() {
-                                            if !finish.is_empty() {
-                                                finish_reason = finish.to_string();
-
-                                                // If finish reason is tool_calls, emit completion
-                                                if finish == "tool_calls" {
-                                                    if let Some(ref win) = window {
-                                                        win.emit(
-                                                            &format!("stream-tool-{}", stream_id),
-                                                            serde_json::json!({
-                                                                "status": "completed",
-                                                                "toolCalls": &tool_calls,
-                                                            }),
-                                                        ).ok();
-                                                    }
-                                                }
-                                            }
-                                        }
-                                        if let Some(usage_obj) = json["usage"].as_object() {
-                                            usage = Some(TokenUsage {
-                                                prompt_tokens: usage_obj["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                                                completion_tokens: usage_obj["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                                                total_tokens: usage_obj["total_tokens"].as_u64().unwrap_or(0) as u32,
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            if let Some(ref win) = window {
-                                win.emit(
-                                    &format!("stream-error-{}", stream_id),
-                                    serde_json::json!({ "error": format!("Stream error: {}", e) }),
-                                ).ok();
-                            }
-                            return Ok(ApiResponse::error("STREAM_ERROR", &format!("Stream error: {}", e)));
-                        }
-                    }
+                if let Some(model) = model {
+                    response_model = model;
                 }
-
-                Ok(ApiResponse::success(ChatCompletionResult {
-                    id: response_id,
-                    model: response_model,
-                    content: if full_content.is_empty() { None } else { Some(full_content) },
-                    finish_reason,
-                    usage,
-                    tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                if let Some(ref win) = window {
-                    win.emit(
-                        &format!("stream-error-{}", stream_id),
-                        serde_json::json!({ "error": format!("HTTP {}: {}", status, error_text) }),
-                    ).ok();
+            }
+            Ok(StreamEvent::Text(delta)) => {
+                full_content.push_str(&delta);
+                window.emit_to(
+                    window.label(),
+                    &format!("stream-chunk-{}", stream_id),
+                    serde_json::json!({
+                        "content": delta,
+                        "full": full_content.clone(),
+                    }),
+                ).ok();
+            }
+            Ok(StreamEvent::Reasoning(delta)) => {
+                emit_thinking(window, stream_id, &delta, &mut full_reasoning);
+            }
+            Ok(StreamEvent::ToolCallDelta { index, id, name, arguments }) => {
+                for event in streamed.push(index, id, name, &arguments) {
+                    window.emit_to(window.label(), &format!("stream-tool-{}", stream_id), event).ok();
                 }
-                Ok(ApiResponse::error("LLM_ERROR", &format!("HTTP {}: {}", status, error_text)))
             }
-        }
-        Err(e) => {
-            if let Some(ref win) = window {
-                win.emit(
+            Ok(StreamEvent::Finish(reason)) => {
+                finish_tool_calls(window, stream_id, &mut streamed, tools, reason == "length");
+                finish_reason = reason;
+            }
+            Ok(StreamEvent::Usage(u)) => usage = Some(u),
+            Ok(StreamEvent::Error(message)) => {
+                window.emit_to(
+                    window.label(),
                     &format!("stream-error-{}", stream_id),
-                    serde_json::json!({ "error": format!("Request failed: {}", e) }),
+                    serde_json::json!({ "error": message }),
                 ).ok();
+                return Ok(ApiResponse::error("LLM_ERROR", &message));
+            }
+            Ok(StreamEvent::Done) => {
+                let (tool_calls, tool_call_issues) =
+                    finish_tool_calls(window, stream_id, &mut streamed, tools, finish_reason == "length");
+                window.emit_to(
+                    window.label(),
+                    &format!("stream-chunk-{}", stream_id),
+                    serde_json::json!({
+                        "done": true,
+                        "finishReason": finish_reason,
+                        "full": full_content.clone(),
+                        "usage": usage,
+                        "toolCalls": if tool_calls.is_empty() { None } else { Some(&tool_calls) },
+                        "toolCallIssues": &tool_call_issues,
+                    }),
+                ).ok();
+                break 'stream;
+            }
+            Err(e) => {
+                window.emit_to(
+                    window.label(),
+                    &format!("stream-error-{}", stream_id),
+                    serde_json::json!({ "error": format!("Stream error: {}", e) }),
+                ).ok();
+                return Ok(ApiResponse::error("STREAM_ERROR", &format!("Stream error: {}", e)));
             }
-            Ok(ApiResponse::error("LLM_ERROR", &format!("Request failed: {}", e)))
         }
     }
+
+    // Streams that end without a finish event still return what arrived
+    let (tool_calls, tool_call_issues) =
+        finish_tool_calls(window, stream_id, &mut streamed, tools, finish_reason == "length");
+
+    Ok(ApiResponse::success(ChatCompletionResult {
+        id: response_id,
+        model: response_model,
+        content: if full_content.is_empty() && !tool_calls.is_empty() { None } else { Some(full_content) },
+        finish_reason,
+        usage,
+        tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+        reasoning: if full_reasoning.is_empty() { None } else { Some(full_reasoning) },
+        tool_call_issues,
+    }))
+}
+
+/// Send a chunk of the model's thinking as a `delta` event on `stream-thinking-{id}`
+fn emit_thinking(window: &Window, stream_id: &str, delta: &str, full_reasoning: &mut String) {
+    full_reasoning.push_str(delta);
+    window.emit_to(
+        window.label(),
+        &format!("stream-thinking-{}", stream_id),
+        serde_json::json!({
+            "status": "delta",
+            "content": delta,
+            "full": full_reasoning.as_str(),
+        }),
+    ).ok();
+}
+
+/// Check the streamed tool calls against `tools`. The first time, each call's
+/// `argumentsComplete` event is emitted, followed by `completed` with all of them.
+/// `truncated` is set when the reply stopped before the model finished it.
+fn finish_tool_calls(
+    window: &Window,
+    stream_id: &str,
+    streamed: &mut StreamedToolCalls,
+    tools: &[serde_json::Value],
+    truncated: bool,
+) -> (Vec<ToolCall>, Vec<ToolCallIssue>) {
+    let (calls, issues, events) = streamed.finish(tools, truncated);
+    if !events.is_empty() {
+        let event_name = format!("stream-tool-{}", stream_id);
+        for event in events {
+            window.emit_to(window.label(), &event_name, event).ok();
+        }
+        window.emit_to(
+            window.label(),
+            &event_name,
+            serde_json::json!({
+                "status": "completed",
+                "toolCalls": &calls,
+                "toolCallIssues": &issues,
+            }),
+        ).ok();
+    }
+    (calls, issues)
 }
 
 #[tauri::command]
 pub async fn chat_completion_with_tools(
+    window: Window,
     state: State<'_, AppState>,
     messages: Vec<serde_json::Value>,
     tools: Option<Vec<serde_json::Value>>,
     model: Option<String>,
     temperature: Option<f32>,
     max_tokens: Option<u32>,
+    reasoning_effort: Option<ReasoningEffort>,
+    reasoning_budget: Option<u32>,
+    fallback_models: Option<Vec<String>>,
+    cache: Option<bool>,
 ) -> Result<ApiResponse<ChatCompletionResult>, String> {
-    let (config_api_key, base_url, default_model, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_model.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
-
-    let api_key = get_effective_api_key(config_api_key, &base_url);
+    let client = state.llm_client(window.label());
 
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let use_model = model.unwrap_or(default_model);
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
+    let use_model = client.model_or_default(model);
+    let messages = match resolve_attachments(&state, window.label(), &client, messages).await {
+        Ok(messages) => messages,
+        Err(e) => return Ok(ApiResponse::error("INVALID_ATTACHMENT", &e)),
+    };
+    let tools = tools.unwrap_or_default();
+    let chat = ChatRequest {
+        model: &use_model,
+        messages: &messages,
+        tools: &tools,
+        temperature: temperature.unwrap_or(0.7),
+        max_tokens: max_tokens.unwrap_or(4096),
+        stream: false,
+        reasoning: Reasoning::new(reasoning_effort, reasoning_budget),
+        response_format: None,
+    };
 
-    let mut request_body = serde_json::json!({
-        "model": use_model,
-        "messages": messages,
-        "temperature": temperature.unwrap_or(0.7),
-        "max_tokens": max_tokens.unwrap_or(4096),
-        "stream": false
-    });
-    if let Some(t) = &tools {
-        if !t.is_empty() {
-            request_body["tools"] = serde_json::json!(t);
+    let fallbacks = client.fallbacks_or_default(fallback_models);
+    let result = if cache.unwrap_or(false) {
+        client.chat_cached(&chat, &fallbacks).await
+    } else {
+        client.chat(&chat, &fallbacks).await
+    };
+    match result {
+        Ok(mut result) => {
+            if let Some(calls) = result.tool_calls.as_mut() {
+                result.tool_call_issues = tool_calls::check_tool_calls(calls, &tools, result.finish_reason == "length");
+            }
+            Ok(ApiResponse::success(result))
         }
+        Err(e) => Ok(ApiResponse::error(e.code("LLM_ERROR"), &e.to_string())),
     }
+}
 
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
+/// Completion whose reply is JSON valid against `schema`, returned as `value`. Replies that
+/// fail validation are retried with the errors, up to `max_attempts` requests in total.
+#[tauri::command]
+pub async fn chat_completion_structured(
+    window: Window,
+    state: State<'_, AppState>,
+    messages: Vec<serde_json::Value>,
+    schema: serde_json::Value,
+    schema_name: Option<String>,
+    model: Option<String>,
+    temperature: Option<f32>,
+    max_tokens: Option<u32>,
+    max_attempts: Option<u32>,
+    fallback_models: Option<Vec<String>>,
+) -> Result<ApiResponse<StructuredResult>, String> {
+    let client = state.llm_client(window.label());
+
+    if !client.is_configured() {
+        return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    match req.send().await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-
-                let id = json["id"].as_str().unwrap_or("").to_string();
-                let model = json["model"].as_str().unwrap_or(&use_model).to_string();
-                let content = json["choices"][0]["message"]["content"]
-                    .as_str()
-                    .map(String::from);
-                let finish_reason = json["choices"][0]["finish_reason"]
-                    .as_str()
-                    .unwrap_or("stop")
-                    .to_string();
-
-                // Parse tool calls if present
-                let tool_calls = if let Some(calls) = json["choices"][0]["message"]["tool_calls"].as_array() {
-                    let parsed: Vec<ToolCall> = calls.iter().filter_map(|tc| {
-                        Some(ToolCall {
-                            id: tc["id"].as_str()?.to_string(),
-                            call_type: tc["type"].as_str().unwrap_or("function").to_string(),
-                            function: ToolFunction {
-                                name: tc["function"]["name"].as_str()?.to_string(),
-                                arguments: tc["function"]["arguments"].as_str().unwrap_or("{}").to_string(),
-                            },
-                        })
-                    }).collect();
-                    if parsed.is_empty() { None } else { Some(parsed) }
-                } else {
-                    None
-                };
-
-                let usage = if let Some(usage_obj) = json["usage"].as_object() {
-                    Some(TokenUsage {
-                        prompt_tokens: usage_obj["prompt_tokens"].as_u64().unwrap_or(0) as u32,
-                        completion_tokens: usage_obj["completion_tokens"].as_u64().unwrap_or(0) as u32,
-                        total_tokens: usage_obj["total_tokens"].as_u64().unwrap_or(0) as u32,
-                    })
-                } else {
-                    None
-                };
-
-                Ok(ApiResponse::success(ChatCompletionResult {
-                    id,
-                    model,
-                    content,
-                    finish_reason,
-                    usage,
-                    tool_calls,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::error("LLM_ERROR", &format!("HTTP {}: {}", status, error_text)))
-            }
-        }
-        Err(e) => Ok(ApiResponse::error("LLM_ERROR", &format!("Request failed: {}", e))),
+    let use_model = client.model_or_default(model);
+    let messages = match resolve_attachments(&state, window.label(), &client, messages).await {
+        Ok(messages) => messages,
+        Err(e) => return Ok(ApiResponse::error("INVALID_ATTACHMENT", &e)),
+    };
+    let fallbacks = client.fallbacks_or_default(fallback_models);
+    let request = StructuredRequest {
+        model: &use_model,
+        messages,
+        schema: &schema,
+        name: schema_name.as_deref().unwrap_or("response"),
+        temperature: temperature.unwrap_or(0.2),
+        max_tokens: max_tokens.unwrap_or(4096),
+        max_attempts: max_attempts.unwrap_or(structured_output::DEFAULT_ATTEMPTS),
+        fallback_models: &fallbacks,
+    };
+
+    match structured_output::complete_structured(&client, request).await {
+        Ok(result) => Ok(ApiResponse::success(result)),
+        Err(e) => Ok(ApiResponse::error(e.code(), &e.to_string())),
     }
 }
 
@@ -865,6 +661,7 @@ pub struct ImageGenerationResult {
 
 #[tauri::command]
 pub async fn generate_image(
+    window: Window,
     state: State<'_, AppState>,
     prompt: String,
     negative_prompt: Option<String>,
@@ -876,26 +673,16 @@ pub async fn generate_image(
     guidance_scale: Option<f32>,
     seed: Option<i64>,
 ) -> Result<ApiResponse<ImageGenerationResult>, String> {
-    let (config_api_key, base_url, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
+    let client = state.llm_client(window.label());
 
-    let api_key = get_effective_api_key(config_api_key, &base_url);
-
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let url = if use_proxy {
-        format!("{}/images/generations", base_url.trim_end_matches('/'))
-    } else {
-        "https://openrouter.ai/api/v1/images/generations".to_string()
-    };
+    // Image generation is only available through OpenAI-compatible endpoints
+    if client.target().kind != ProviderKind::Openai {
+        return Ok(ApiResponse::error("UNSUPPORTED", &format!("{} does not offer image generation; switch to another provider profile.", client.target().provider)));
+    }
 
     let mut request_body = serde_json::json!({
         "model": model,
@@ -918,57 +705,32 @@ pub async fn generate_image(
         request_body["seed"] = serde_json::json!(s);
     }
 
-    let client = reqwest::Client::new();
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body)
-        .timeout(std::time::Duration::from_secs(120));
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
-
-    match req.send().await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-
-                // Extract image URLs from response
-                let images: Vec<String> = if let Some(data) = json["data"].as_array() {
-                    data.iter()
-                        .filter_map(|item| {
-                            // Try to get URL first, then base64
-                            if let Some(url) = item["url"].as_str() {
-                                Some(url.to_string())
-                            } else if let Some(b64) = item["b64_json"].as_str() {
-                                Some(format!("data:image/png;base64,{}", b64))
-                            } else {
-                                None
-                            }
+    match client.generate_images(&model, &request_body).await {
+        Ok(json) => {
+            // Extract image URLs from response
+            let images: Vec<String> = if let Some(data) = json["data"].as_array() {
+                data.iter()
+                    .filter_map(|item| {
+                        // Try to get URL first, then base64
+                        item["url"].as_str().map(String::from).or_else(|| {
+                            item["b64_json"].as_str().map(|b64| format!("data:image/png;base64,{}", b64))
                         })
-                        .collect()
-                } else {
-                    vec![]
-                };
-
-                if images.is_empty() {
-                    return Ok(ApiResponse::error("IMAGE_ERROR", "No images returned from API"));
-                }
-
-                Ok(ApiResponse::success(ImageGenerationResult {
-                    images,
-                    model,
-                }))
+                    })
+                    .collect()
             } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::error("IMAGE_ERROR", &format!("HTTP {}: {}", status, error_text)))
+                vec![]
+            };
+
+            if images.is_empty() {
+                return Ok(ApiResponse::error("IMAGE_ERROR", "No images returned from API"));
             }
+
+            Ok(ApiResponse::success(ImageGenerationResult {
+                images,
+                model,
+            }))
         }
-        Err(e) => Ok(ApiResponse::error("IMAGE_ERROR", &format!("Request failed: {}", e))),
+        Err(e) => Ok(ApiResponse::error(e.code("IMAGE_ERROR"), &e.to_string())),
     }
 }
 
@@ -994,75 +756,36 @@ pub struct BatchEmbeddingResult {
 
 #[tauri::command]
 pub async fn create_embedding(
+    window: Window,
     state: State<'_, AppState>,
     text: String,
     model: Option<String>,
 ) -> Result<ApiResponse<EmbeddingResult>, String> {
-    let (config_api_key, base_url, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
-
-    let api_key = get_effective_api_key(config_api_key, &base_url);
+    let client = state.llm_client(window.label());
 
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let embedding_model = model.unwrap_or_else(|| "openai/text-embedding-3-small".to_string());
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/embeddings", base);
-    let request_body = serde_json::json!({ "model": embedding_model, "input": text });
-
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
+    match client.embeddings(model, &[text]).await {
+        Ok(result) => {
+            let embedding = result.vectors.into_iter().next().unwrap_or_default();
+            let dimensions = embedding.len();
 
-    match req.send().await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-
-                // Extract embedding from response
-                let embedding: Vec<f32> = json["data"][0]["embedding"]
-                    .as_array()
-                    .map(|arr| arr.iter().filter_map(|v| v.as_f64().map(|f| f as f32)).collect())
-                    .unwrap_or_default();
-
-                let dimensions = embedding.len();
-                let token_count = json["usage"]["total_tokens"].as_u64().map(|t| t as u32);
-
-                Ok(ApiResponse::success(EmbeddingResult {
-                    embedding,
-                    model: embedding_model,
-                    dimensions,
-                    token_count,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::error("EMBEDDING_ERROR", &format!("HTTP {}: {}", status, error_text)))
-            }
+            Ok(ApiResponse::success(EmbeddingResult {
+                embedding,
+                model: result.model,
+                dimensions,
+                token_count: result.token_count,
+            }))
         }
-        Err(e) => Ok(ApiResponse::error("EMBEDDING_ERROR", &format!("Request failed: {}", e))),
+        Err(e) => Ok(ApiResponse::error(e.code("EMBEDDING_ERROR"), &e.to_string())),
     }
 }
 
 #[tauri::command]
 pub async fn batch_create_embeddings(
+    window: Window,
     state: State<'_, AppState>,
     texts: Vec<String>,
     model: Option<String>,
@@ -1071,73 +794,67 @@ pub async fn batch_create_embeddings(
         return Ok(ApiResponse::error("EMBEDDING_ERROR", "No texts provided"));
     }
 
-    let (config_api_key, base_url, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
+    let client = state.llm_client(window.label());
 
-    let api_key = get_effective_api_key(config_api_key, &base_url);
-
-    if !use_proxy && api_key.is_none() && !is_local_llm_base_url(&base_url) {
+    if !client.is_configured() {
         return Ok(ApiResponse::error("LLM_NOT_CONFIGURED", "No API key configured. Add your API key in Settings > AI & Models."));
     }
 
-    let embedding_model = model.unwrap_or_else(|| "openai/text-embedding-3-small".to_string());
-    let client = reqwest::Client::new();
-    let base_norm = normalize_base_url_for_openai_api(&base_url);
-    let base = base_norm.trim_end_matches('/');
-    let url = format!("{}/embeddings", base);
-    let request_body = serde_json::json!({ "model": embedding_model, "input": texts });
-
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
+    match client.embeddings(model, &texts).await {
+        Ok(result) => {
+            let dimensions = result.vectors.first().map(|e| e.len()).unwrap_or(0);
+
+            Ok(ApiResponse::success(BatchEmbeddingResult {
+                embeddings: result.vectors,
+                model: result.model,
+                dimensions,
+                total_tokens: result.token_count,
+            }))
+        }
+        Err(e) => Ok(ApiResponse::error(e.code("EMBEDDING_ERROR"), &e.to_string())),
     }
+}
 
-    match req.send().await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-
-                // Extract all embeddings from response
-                let embeddings: Vec<Vec<f32>> = json["data"]
-                    .as_array()
-                    .map(|arr| {
-                        arr.iter()
-                            .filter_map(|item| {
-                                item["embedding"].as_array().map(|emb| {
-                                    emb.iter().filter_map(|v| v.as_f64().map(|f| f as f32)).collect()
-                                })
-                            })
-                            .collect()
-                    })
-                    .unwrap_or_default();
+// ==================== Token Commands ====================
 
-                let dimensions = embeddings.first().map(|e| e.len()).unwrap_or(0);
-                let total_tokens = json["usage"]["total_tokens"].as_u64().map(|t| t as u32);
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenCountResult {
+    pub tokens: usize,
+    #[serde(flatten)]
+    pub model_info: ModelInfo,
+}
 
-                Ok(ApiResponse::success(BatchEmbeddingResult {
-                    embeddings,
-                    model: embedding_model,
-                    dimensions,
-                    total_tokens,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::error("EMBEDDING_ERROR", &format!("HTTP {}: {}", status, error_text)))
-            }
-        }
-        Err(e) => Ok(ApiResponse::error("EMBEDDING_ERROR", &format!("Request failed: {}", e))),
-    }
+/// Count tokens in `text`, or in a chat request made of `messages` and `tools`, for `model`
+/// (default: the active model)
+#[tauri::command]
+pub async fn count_tokens(
+    state: State<'_, AppState>,
+    text: Option<String>,
+    messages: Option<Vec<serde_json::Value>>,
+    tools: Option<Vec<serde_json::Value>>,
+    model: Option<String>,
+) -> Result<ApiResponse<TokenCountResult>, String> {
+    let model = model.unwrap_or_else(|| state.config.lock().unwrap().llm_target().model);
+
+    let tokens = match (text, messages) {
+        (Some(text), _) => token_service::count_text(&model, &text),
+        (None, Some(messages)) => token_service::count_messages(&model, &messages, &tools.unwrap_or_default()),
+        (None, None) => return Ok(ApiResponse::error("INVALID_INPUT", "Provide text or messages to count")),
+    };
+
+    Ok(ApiResponse::success(TokenCountResult {
+        tokens,
+        model_info: token_service::model_info(&model),
+    }))
+}
+
+/// Context window and output limit for `model` (default: the active model)
+#[tauri::command]
+pub async fn get_model_info(
+    state: State<'_, AppState>,
+    model: Option<String>,
+) -> Result<ApiResponse<ModelInfo>, String> {
+    let model = model.unwrap_or_else(|| state.config.lock().unwrap().llm_target().model);
+    Ok(ApiResponse::success(token_service::model_info(&model)))
 }
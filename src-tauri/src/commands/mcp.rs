@@ -0,0 +1,200 @@
+use crate::services::mcp_client::{qualified_tool_name, McpConfig, McpHub, McpPrompt, McpResource, McpToolResult, McpTrust};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{State, Window};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolSummary {
+    /// Name the tool is offered to the model under
+    pub name: String,
+    /// Name on its server
+    pub server_tool: String,
+    pub description: Option<String>,
+    pub input_schema: serde_json::Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerStatus {
+    pub name: String,
+    pub connected: bool,
+    /// False until the user allows the server with `set_mcp_server_trust`
+    pub trusted: bool,
+    /// Command line or URL of a server waiting to be allowed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub server_info: serde_json::Value,
+    pub tools: Vec<McpToolSummary>,
+    pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
+}
+
+fn server_statuses(hub: &McpHub) -> Vec<McpServerStatus> {
+    let connected = hub.servers.iter().map(|server| McpServerStatus {
+        name: server.name.clone(),
+        connected: true,
+        trusted: true,
+        command: None,
+        error: None,
+        server_info: server.server_info.clone(),
+        tools: server
+            .tools
+            .iter()
+            .map(|tool| McpToolSummary {
+                name: qualified_tool_name(&server.name, &tool.name),
+                server_tool: tool.name.clone(),
+                description: tool.description.clone(),
+                input_schema: tool.input_schema.clone(),
+            })
+            .collect(),
+        resources: server.resources.clone(),
+        prompts: server.prompts.clone(),
+    });
+    let failed = hub.failures.iter().map(|failure| McpServerStatus {
+        name: failure.name.clone(),
+        connected: false,
+        trusted: true,
+        command: None,
+        error: Some(failure.error.clone()),
+        server_info: serde_json::Value::Null,
+        tools: Vec::new(),
+        resources: Vec::new(),
+        prompts: Vec::new(),
+    });
+    let untrusted = hub.untrusted.iter().map(|server| McpServerStatus {
+        name: server.name.clone(),
+        connected: false,
+        trusted: false,
+        command: Some(server.command.clone()),
+        error: None,
+        server_info: serde_json::Value::Null,
+        tools: Vec::new(),
+        resources: Vec::new(),
+        prompts: Vec::new(),
+    });
+    connected.chain(failed).chain(untrusted).collect()
+}
+
+/// MCP servers configured in the primary root's `.sentinelops/mcp.json`, with what they offer
+#[tauri::command]
+pub async fn list_mcp_servers(
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<Vec<McpServerStatus>>, String> {
+    match state.mcp_for(window.label(), false).await {
+        Ok(hub) => Ok(ApiResponse::success(server_statuses(&hub))),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e)),
+    }
+}
+
+/// Restart every server, picking up changes to `.sentinelops/mcp.json`
+#[tauri::command]
+pub async fn reload_mcp_servers(
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<Vec<McpServerStatus>>, String> {
+    match state.mcp_for(window.label(), true).await {
+        Ok(hub) => Ok(ApiResponse::success(server_statuses(&hub))),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e)),
+    }
+}
+
+/// Allow a server of the primary root to run, or withdraw that. The approval covers the
+/// server's current command; servers are reconnected either way.
+#[tauri::command]
+pub async fn set_mcp_server_trust(
+    window: Window,
+    state: State<'_, AppState>,
+    name: String,
+    trusted: bool,
+) -> Result<ApiResponse<Vec<McpServerStatus>>, String> {
+    let root = state.root_path(window.label(), None)?;
+    let config = match McpConfig::load(&root) {
+        Ok(config) => config,
+        Err(e) => return Ok(ApiResponse::error("MCP_ERROR", &e)),
+    };
+    let Some(server) = config.mcp_servers.get(&name) else {
+        return Ok(ApiResponse::error("NOT_FOUND", "No MCP server with this name"));
+    };
+
+    let mut trust = McpTrust::load();
+    trust.set_trusted(&root, &name, server, trusted);
+    if let Err(e) = trust.save() {
+        return Ok(ApiResponse::error("MCP_ERROR", &e));
+    }
+
+    match state.mcp_for(window.label(), true).await {
+        Ok(hub) => Ok(ApiResponse::success(server_statuses(&hub))),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e)),
+    }
+}
+
+/// Call a server tool by the qualified name it was offered to the model under
+#[tauri::command]
+pub async fn call_mcp_tool(
+    window: Window,
+    state: State<'_, AppState>,
+    name: String,
+    arguments: Option<serde_json::Value>,
+) -> Result<ApiResponse<McpToolResult>, String> {
+    let hub = match state.mcp_for(window.label(), false).await {
+        Ok(hub) => hub,
+        Err(e) => return Ok(ApiResponse::error("MCP_ERROR", &e)),
+    };
+    let arguments = arguments.unwrap_or_else(|| serde_json::json!({}));
+
+    match hub.call_tool(&name, arguments).await {
+        Ok(result) => Ok(ApiResponse::success(result)),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn read_mcp_resource(
+    window: Window,
+    state: State<'_, AppState>,
+    server: String,
+    uri: String,
+) -> Result<ApiResponse<serde_json::Value>, String> {
+    let hub = match state.mcp_for(window.label(), false).await {
+        Ok(hub) => hub,
+        Err(e) => return Ok(ApiResponse::error("MCP_ERROR", &e)),
+    };
+    let result = match hub.server(&server) {
+        Ok(client) => client.read_resource(&uri).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(contents) => Ok(ApiResponse::success(contents)),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn get_mcp_prompt(
+    window: Window,
+    state: State<'_, AppState>,
+    server: String,
+    name: String,
+    arguments: Option<HashMap<String, String>>,
+) -> Result<ApiResponse<serde_json::Value>, String> {
+    let hub = match state.mcp_for(window.label(), false).await {
+        Ok(hub) => hub,
+        Err(e) => return Ok(ApiResponse::error("MCP_ERROR", &e)),
+    };
+    let result = match hub.server(&server) {
+        Ok(client) => client.get_prompt(&name, arguments.unwrap_or_default()).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(prompt) => Ok(ApiResponse::success(prompt)),
+        Err(e) => Ok(ApiResponse::error("MCP_ERROR", &e.to_string())),
+    }
+}
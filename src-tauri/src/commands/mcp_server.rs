@@ -0,0 +1,99 @@
+use crate::services::mcp_server::{self, McpServer, McpServerSettings, ServerTool, ServerToolInfo};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use serde::Serialize;
+use tauri::{State, Window};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerInfo {
+    pub running: bool,
+    /// Where clients POST requests while the server runs
+    pub url: Option<String>,
+    /// Bearer token clients must send; a new one is issued on every start
+    pub token: Option<String>,
+    pub workspace: Option<String>,
+    pub settings: McpServerSettings,
+    pub tools: Vec<ServerToolInfo>,
+}
+
+fn server_info(state: &AppState) -> McpServerInfo {
+    let settings = McpServerSettings::load();
+    let endpoint = state.mcp_server.lock().unwrap();
+    match endpoint.as_ref() {
+        Some(endpoint) => McpServerInfo {
+            running: true,
+            url: Some(endpoint.url()),
+            token: Some(endpoint.token.clone()),
+            workspace: Some(endpoint.server.workspace().to_string_lossy().into_owned()),
+            tools: endpoint.server.tool_infos(),
+            settings,
+        },
+        None => McpServerInfo {
+            running: false,
+            url: None,
+            token: None,
+            workspace: None,
+            tools: mcp_server::tool_infos(&settings),
+            settings,
+        },
+    }
+}
+
+#[tauri::command]
+pub async fn get_mcp_server_status(
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<McpServerInfo>, String> {
+    Ok(ApiResponse::success(server_info(&state)))
+}
+
+/// Serve the window's primary root to other tools on localhost, replacing any running server
+#[tauri::command]
+pub async fn start_mcp_server(
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<McpServerInfo>, String> {
+    let workspace = state.root_path(window.label(), None)?;
+    let previous = state.mcp_server.lock().unwrap().take();
+    if let Some(previous) = previous {
+        previous.stop().await;
+    }
+
+    let settings = McpServerSettings::load();
+    let port = settings.port;
+    match mcp_server::serve_local(McpServer::new(workspace, settings), port).await {
+        Ok(endpoint) => {
+            *state.mcp_server.lock().unwrap() = Some(endpoint);
+            Ok(ApiResponse::success(server_info(&state)))
+        }
+        Err(e) => Ok(ApiResponse::error("MCP_SERVER_ERROR", &e)),
+    }
+}
+
+#[tauri::command]
+pub async fn stop_mcp_server(
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<McpServerInfo>, String> {
+    let endpoint = state.mcp_server.lock().unwrap().take();
+    if let Some(endpoint) = endpoint {
+        endpoint.stop().await;
+    }
+    Ok(ApiResponse::success(server_info(&state)))
+}
+
+/// Save the settings. Tool changes apply to a running server at once; a new port on
+/// the next start.
+#[tauri::command]
+pub async fn update_mcp_server_settings(
+    state: State<'_, AppState>,
+    settings: McpServerSettings,
+) -> Result<ApiResponse<McpServerInfo>, String> {
+    if let Some(name) = settings.tools.keys().find(|name| ServerTool::from_name(name).is_none()) {
+        return Ok(ApiResponse::error("INVALID_SETTINGS", &format!("Unknown tool: {}", name)));
+    }
+    settings.save()?;
+    if let Some(endpoint) = state.mcp_server.lock().unwrap().as_ref() {
+        endpoint.server.set_settings(settings);
+    }
+    Ok(ApiResponse::success(server_info(&state)))
+}
@@ -1,4 +1,6 @@
 use crate::services::AppState;
+use crate::services::conversation_service::content_text;
+use crate::services::structured_output::{self, StructuredRequest};
 use crate::services::memory_service::{
     Memory, MemoryWithScore, MemorySettings, MemoryStats, MemoryFilters,
     CreateMemoryInput, UpdateMemoryInput,
@@ -6,7 +8,7 @@ use crate::services::memory_service::{
 use super::file_ops::ApiResponse;
 use super::llm::create_embedding;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use tauri::{State, Window};
 
 // ==================== Response Types ====================
 
@@ -71,6 +73,8 @@ pub struct SearchMemoryRequest {
 #[serde(rename_all = "camelCase")]
 pub struct ExtractMemoriesRequest {
     pub conversation_id: String,
+    /// Read from the stored conversation's active branch when empty
+    #[serde(default)]
     pub messages: Vec<ConversationMessage>,
     pub model: Option<String>,
 }
@@ -78,6 +82,8 @@ pub struct ExtractMemoriesRequest {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConversationMessage {
+    /// Stored message ID, recorded as the source of memories taken from this message
+    pub id: Option<String>,
     pub role: String,
     pub content: String,
 }
@@ -97,49 +103,40 @@ pub struct UpdateSettingsRequest {
 
 #[tauri::command]
 pub async fn create_memory(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     request: CreateMemoryRequest,
 ) -> Result<ApiResponse<Memory>, String> {
-    // Ensure memory manager is initialized
-    state.get_or_init_memory()?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
-    // Create memory and get ID in a scope to drop the guard
-    let (memory_id, content_for_embedding) = {
-        let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-        let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
-
-        let input = CreateMemoryInput {
-            content: request.content.clone(),
-            summary: request.summary,
-            memory_type: request.memory_type,
-            tags: request.tags,
-            importance: request.importance,
-            is_pinned: request.is_pinned,
-            source_conversation_id: request.source_conversation_id,
-            source_message_ids: request.source_message_ids,
-            metadata: request.metadata,
-        };
+    let input = CreateMemoryInput {
+        content: request.content.clone(),
+        summary: request.summary,
+        memory_type: request.memory_type,
+        tags: request.tags,
+        importance: request.importance,
+        is_pinned: request.is_pinned,
+        source_conversation_id: request.source_conversation_id,
+        source_message_ids: request.source_message_ids,
+        metadata: request.metadata,
+    };
 
-        let memory = manager.create_memory(input)?;
-        (memory.id.clone(), request.content.clone())
-    }; // Guard dropped here
+    let memory_id = manager.create_memory(input)?.id;
+    let content_for_embedding = request.content;
 
     // Generate and store embedding if requested
     if request.generate_embedding.unwrap_or(true) {
         // Get embedding (async)
-        let embedding_result = create_embedding(state.clone(), content_for_embedding, None).await?;
+        let embedding_result = create_embedding(window.clone(), state.clone(), content_for_embedding, None).await?;
         if embedding_result.ok {
             if let Some(emb_data) = embedding_result.data {
-                let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-                let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
                 let _ = manager.store_embedding(&memory_id, &emb_data.embedding, &emb_data.model);
             }
         }
     }
 
     // Re-fetch memory to return updated state
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
     match manager.get_memory(&memory_id)? {
         Some(memory) => Ok(ApiResponse::success(memory)),
         None => Ok(ApiResponse::error("NOT_FOUND", "Memory not found after creation")),
@@ -148,13 +145,12 @@ pub async fn create_memory(
 
 #[tauri::command]
 pub async fn get_memory(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     id: String,
 ) -> Result<ApiResponse<Memory>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     match manager.get_memory(&id)? {
         Some(memory) => Ok(ApiResponse::success(memory)),
@@ -164,14 +160,13 @@ pub async fn get_memory(
 
 #[tauri::command]
 pub async fn update_memory(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     id: String,
     request: UpdateMemoryRequest,
 ) -> Result<ApiResponse<Memory>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let input = UpdateMemoryInput {
         content: request.content,
@@ -188,13 +183,12 @@ pub async fn update_memory(
 
 #[tauri::command]
 pub async fn delete_memory(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     id: String,
 ) -> Result<ApiResponse<bool>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let deleted = manager.delete_memory(&id)?;
     Ok(ApiResponse::success(deleted))
@@ -202,17 +196,16 @@ pub async fn delete_memory(
 
 #[tauri::command]
 pub async fn list_memories(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     memory_type: Option<String>,
     tags: Option<Vec<String>>,
     limit: Option<usize>,
     offset: Option<usize>,
     sort_by: Option<String>,
 ) -> Result<ApiResponse<MemoryListResult>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let filters = MemoryFilters {
         memory_type,
@@ -235,10 +228,12 @@ pub async fn list_memories(
 
 #[tauri::command]
 pub async fn search_memories(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     request: SearchMemoryRequest,
 ) -> Result<ApiResponse<MemorySearchResult>, String> {
-    state.get_or_init_memory()?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let limit = request.limit.unwrap_or(10);
     let threshold = request.threshold.unwrap_or(0.7);
@@ -246,7 +241,7 @@ pub async fn search_memories(
 
     // Try to get embedding for semantic search
     let query_embedding: Option<Vec<f32>> = if use_embedding {
-        let emb_result = create_embedding(state.clone(), request.query.clone(), None).await?;
+        let emb_result = create_embedding(window.clone(), state.clone(), request.query.clone(), None).await?;
         if emb_result.ok {
             emb_result.data.map(|d| d.embedding)
         } else {
@@ -256,9 +251,6 @@ pub async fn search_memories(
         None
     };
 
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
-
     let memories = manager.search_hybrid(
         &request.query,
         query_embedding.as_deref(),
@@ -283,32 +275,27 @@ pub async fn search_memories(
 
 #[tauri::command]
 pub async fn get_relevant_memories(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     context: String,
     limit: Option<usize>,
 ) -> Result<ApiResponse<Vec<MemoryWithScore>>, String> {
-    state.get_or_init_memory()?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let limit = limit.unwrap_or(5);
 
     // Get settings for threshold
-    let threshold = {
-        let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-        let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
-        manager.get_settings()?.similarity_threshold
-    };
+    let threshold = manager.get_settings()?.similarity_threshold;
 
     // Get embedding for context
-    let emb_result = create_embedding(state.clone(), context.clone(), None).await?;
+    let emb_result = create_embedding(window.clone(), state.clone(), context.clone(), None).await?;
     let query_embedding = if emb_result.ok {
         emb_result.data.map(|d| d.embedding)
     } else {
         None
     };
 
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
-
     let memories = manager.search_hybrid(&context, query_embedding.as_deref(), limit, threshold)?;
 
     // Increment access count for retrieved memories
@@ -319,19 +306,70 @@ pub async fn get_relevant_memories(
     Ok(ApiResponse::success(memories))
 }
 
+/// One memory as the extraction model returns it
+#[derive(Debug, Deserialize)]
+struct ExtractedMemory {
+    content: String,
+    summary: Option<String>,
+    tags: Option<Vec<String>>,
+    importance: Option<i32>,
+    /// Numbers of the messages the memory comes from, as shown in the prompt
+    #[serde(default)]
+    sources: Vec<usize>,
+}
+
+fn extracted_memories_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "content": { "type": "string", "minLength": 1 },
+                "summary": { "type": "string" },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "importance": { "type": "integer", "minimum": 1, "maximum": 10 },
+                "sources": { "type": "array", "items": { "type": "integer", "minimum": 1 } }
+            },
+            "required": ["content", "summary", "tags", "importance", "sources"]
+        }
+    })
+}
+
 #[tauri::command]
 pub async fn extract_memories(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     request: ExtractMemoriesRequest,
 ) -> Result<ApiResponse<Vec<Memory>>, String> {
-    if request.messages.len() < 4 {
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
+
+    let messages = if request.messages.is_empty() {
+        match manager.conversations().thread(&request.conversation_id)? {
+            Some(thread) => thread
+                .messages
+                .into_iter()
+                .map(|m| ConversationMessage {
+                    content: content_text(&m.message.content),
+                    id: Some(m.message.id),
+                    role: m.message.role,
+                })
+                .collect(),
+            None => return Ok(ApiResponse::error("NOT_FOUND", "Conversation not found")),
+        }
+    } else {
+        request.messages
+    };
+
+    if messages.len() < 4 {
         return Ok(ApiResponse::success(vec![]));
     }
 
-    // Build conversation text for extraction
-    let conversation_text: String = request.messages
+    // Build conversation text for extraction, numbered so memories can cite their sources
+    let conversation_text: String = messages
         .iter()
-        .map(|m| format!("{}: {}", m.role, m.content))
+        .enumerate()
+        .map(|(i, m)| format!("[{}] {}: {}", i + 1, m.role, m.content))
         .collect::<Vec<_>>()
         .join("\n\n");
 
@@ -346,13 +384,14 @@ Focus on:
 - Technical details about the codebase
 - User's goals and ongoing tasks
 
-For each memory, provide a JSON array with objects containing:
+For each memory, provide:
 - content: The information to remember (1-3 sentences)
 - summary: A brief title (5-10 words)
-- tags: Relevant categories as array (e.g., ["preferences", "architecture", "react"])
+- tags: Relevant categories (e.g., ["preferences", "architecture", "react"])
 - importance: 1-10 scale based on how useful this is for future conversations
+- sources: The numbers of the messages the information comes from
 
-Return ONLY a valid JSON array. If nothing worth remembering, return empty array [].
+If nothing is worth remembering, return an empty list.
 
 Conversation:
 {}
@@ -360,105 +399,67 @@ Conversation:
         conversation_text
     );
 
-    let (api_key, base_url, default_model, use_proxy) = {
-        let config = state.config.lock().unwrap();
-        (
-            config.llm_api_key.clone(),
-            config.llm_base_url.clone(),
-            config.llm_model.clone(),
-            config.llm_proxy_url.is_some(),
-        )
-    };
-
-    if !use_proxy && api_key.is_none() && !crate::services::config::is_local_llm_base_url(&base_url) {
-        return Ok(ApiResponse::success(vec![]));
-    }
-
-    let model = request.model.unwrap_or(default_model);
-    let client = reqwest::Client::new();
-    let base = base_url.trim_end_matches('/');
-    let url = format!("{}/chat/completions", base);
-    let request_body = serde_json::json!({
-        "model": model,
-        "messages": [
-            {"role": "system", "content": "You are a memory extraction assistant. Extract important information from conversations and return it as JSON."},
-            {"role": "user", "content": extraction_prompt}
-        ],
-        "temperature": 0.3,
-        "max_tokens": 2048
-    });
-
-    let mut req = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("HTTP-Referer", "https://sentinelops.app")
-        .header("X-Title", "SentinelOps")
-        .json(&request_body);
-    if let Some(k) = &api_key {
-        req = req.header("Authorization", format!("Bearer {}", k));
-    }
-
-    let response = req.send().await.map_err(|e| e.to_string())?;
+    let client = state.llm_client(window.label());
 
-    if !response.status().is_success() {
+    if !client.is_configured() {
         return Ok(ApiResponse::success(vec![]));
     }
 
-    let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-    let content = json["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or("[]");
-
-    // Parse extracted memories
-    let extracted: Vec<serde_json::Value> = serde_json::from_str(content).unwrap_or_default();
+    let model = client.model_or_default(request.model);
+    let prompt_messages = [
+        serde_json::json!({"role": "system", "content": "You are a memory extraction assistant. Extract important information from conversations and return it as JSON."}),
+        serde_json::json!({"role": "user", "content": extraction_prompt}),
+    ];
+    let schema = extracted_memories_schema();
+    let fallbacks = client.fallbacks_or_default(None);
+    let structured = StructuredRequest {
+        model: &model,
+        messages: prompt_messages.to_vec(),
+        schema: &schema,
+        name: "memories",
+        temperature: 0.3,
+        max_tokens: 2048,
+        max_attempts: structured_output::DEFAULT_ATTEMPTS,
+        fallback_models: &fallbacks,
+    };
 
-    // Ensure memory manager is ready
-    state.get_or_init_memory()?;
+    let extracted: Vec<ExtractedMemory> = match structured_output::complete_as(&client, structured).await {
+        Ok(extracted) => extracted,
+        // Extraction is best-effort: nothing is remembered, but the reason is logged
+        Err(e) => {
+            tracing::warn!("Memory extraction failed: {}", e);
+            return Ok(ApiResponse::success(vec![]));
+        }
+    };
 
     let mut created_memories = Vec::new();
 
     for item in extracted {
-        let content = item["content"].as_str().unwrap_or_default().to_string();
+        let content = item.content.trim().to_string();
         if content.is_empty() {
             continue;
         }
 
-        let summary = item["summary"].as_str().map(String::from);
-        let tags: Option<Vec<String>> = item["tags"].as_array().map(|arr| {
-            arr.iter().filter_map(|v| v.as_str().map(String::from)).collect()
-        });
-        let importance = item["importance"].as_i64().map(|i| i as i32);
-
-        // Create memory in a scope to release lock before await
-        let created_memory = {
-            let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-            let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
-
-            let input = CreateMemoryInput {
-                content: content.clone(),
-                summary,
-                memory_type: Some("auto".to_string()),
-                tags,
-                importance,
-                is_pinned: Some(false),
-                source_conversation_id: Some(request.conversation_id.clone()),
-                source_message_ids: None,
-                metadata: None,
-            };
-
-            manager.create_memory(input).ok()
-        }; // Guard dropped here
-
-        let Some(memory) = created_memory else {
+        let input = CreateMemoryInput {
+            content: content.clone(),
+            summary: item.summary,
+            memory_type: Some("auto".to_string()),
+            tags: item.tags,
+            importance: item.importance,
+            is_pinned: Some(false),
+            source_conversation_id: Some(request.conversation_id.clone()),
+            source_message_ids: source_ids(&messages, &item.sources),
+            metadata: None,
+        };
+
+        let Ok(memory) = manager.create_memory(input) else {
             continue;
         };
 
-        // Generate embedding for the new memory (async - guard already dropped)
-        let emb_result = create_embedding(state.clone(), content, None).await?;
+        // Generate embedding for the new memory
+        let emb_result = create_embedding(window.clone(), state.clone(), content, None).await?;
         if emb_result.ok {
             if let Some(emb_data) = emb_result.data {
-                let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-                let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
                 let _ = manager.store_embedding(&memory.id, &emb_data.embedding, &emb_data.model);
 
                 // Get updated memory
@@ -474,14 +475,22 @@ Conversation:
     Ok(ApiResponse::success(created_memories))
 }
 
+/// Stored IDs of the cited messages; numbers out of range or without an ID are skipped
+fn source_ids(messages: &[ConversationMessage], sources: &[usize]) -> Option<Vec<String>> {
+    let ids: Vec<String> = sources
+        .iter()
+        .filter_map(|n| messages.get(n.checked_sub(1)?)?.id.clone())
+        .collect();
+    (!ids.is_empty()).then_some(ids)
+}
+
 #[tauri::command]
 pub async fn get_memory_settings(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
 ) -> Result<ApiResponse<MemorySettings>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let settings = manager.get_settings()?;
     Ok(ApiResponse::success(settings))
@@ -489,13 +498,12 @@ pub async fn get_memory_settings(
 
 #[tauri::command]
 pub async fn update_memory_settings(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     settings: UpdateSettingsRequest,
 ) -> Result<ApiResponse<MemorySettings>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let updated = manager.update_settings(
         settings.auto_extract_enabled,
@@ -511,12 +519,11 @@ pub async fn update_memory_settings(
 
 #[tauri::command]
 pub async fn get_memory_stats(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
 ) -> Result<ApiResponse<MemoryStats>, String> {
-    state.get_or_init_memory()?;
-
-    let memory_guard = state.memory.lock().map_err(|e| e.to_string())?;
-    let manager = memory_guard.as_ref().ok_or("Memory manager not initialized")?;
+    let manager = state.memory_for(window.label(), root_id.as_deref())?;
 
     let stats = manager.get_stats()?;
     Ok(ApiResponse::success(stats))
@@ -4,8 +4,16 @@ pub mod git;
 pub mod terminal;
 pub mod config;
 pub mod llm;
+pub mod ollama;
 pub mod extensions;
 pub mod memory;
+pub mod conversations;
+pub mod usage;
+pub mod cache;
+pub mod agent;
+pub mod mcp;
+pub mod mcp_server;
 pub mod search;
 pub mod sqlite;
 pub mod window;
+pub mod workspace;
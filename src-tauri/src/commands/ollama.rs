@@ -0,0 +1,96 @@
+use crate::services::ollama_service::{ModelDetails, OllamaClient, OllamaHealth, PullProgress, RunningModel};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use super::llm::ActiveStream;
+use tauri::{Emitter, State, Window};
+
+/// Whether Ollama is running at `base_url` and, when `model` is given, has it
+#[tauri::command]
+pub async fn ollama_health(base_url: String, model: Option<String>) -> Result<ApiResponse<OllamaHealth>, String> {
+    let client = OllamaClient::new(&base_url);
+    Ok(ApiResponse::success(client.health(model.as_deref()).await))
+}
+
+/// Download a model. Progress is sent as `ollama-pull-{pullId}` events, ending with one
+/// that has `done: true`. Stop it with `cancel_stream(pullId)`.
+#[tauri::command]
+pub async fn ollama_pull(
+    window: Window,
+    state: State<'_, AppState>,
+    base_url: String,
+    model: String,
+    pull_id: String,
+) -> Result<ApiResponse<bool>, String> {
+    let client = OllamaClient::new(&base_url);
+    let active = ActiveStream::begin(&state, window.label(), &pull_id);
+    let event = format!("ollama-pull-{}", pull_id);
+    let emit = |payload: serde_json::Value| {
+        window.emit_to(window.label(), &event, payload).ok();
+    };
+
+    let on_progress = |progress: PullProgress| {
+        emit(serde_json::json!({
+            "done": false,
+            "status": progress.status,
+            "digest": progress.digest,
+            "total": progress.total,
+            "completed": progress.completed,
+            "percent": progress.percent(),
+        }));
+    };
+
+    // Dropping the pull when cancelled closes the connection, which stops the download
+    let result = tokio::select! {
+        result = client.pull(&model, on_progress) => Some(result),
+        _ = active.cancelled() => None,
+    };
+
+    match result {
+        Some(Ok(())) => {
+            emit(serde_json::json!({ "done": true, "status": "success" }));
+            Ok(ApiResponse::success(true))
+        }
+        Some(Err(e)) => {
+            emit(serde_json::json!({ "done": true, "status": "error", "error": e.to_string() }));
+            Ok(ApiResponse::error(e.code(), &e.to_string()))
+        }
+        None => {
+            emit(serde_json::json!({ "done": true, "status": "cancelled" }));
+            Ok(ApiResponse::success(false))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn ollama_delete(base_url: String, model: String) -> Result<ApiResponse<bool>, String> {
+    match OllamaClient::new(&base_url).delete(&model).await {
+        Ok(()) => Ok(ApiResponse::success(true)),
+        Err(e) => Ok(ApiResponse::error(e.code(), &e.to_string())),
+    }
+}
+
+/// Modelfile, parameters and context length of a model
+#[tauri::command]
+pub async fn ollama_show(base_url: String, model: String) -> Result<ApiResponse<ModelDetails>, String> {
+    match OllamaClient::new(&base_url).show(&model).await {
+        Ok(details) => Ok(ApiResponse::success(details)),
+        Err(e) => Ok(ApiResponse::error(e.code(), &e.to_string())),
+    }
+}
+
+/// Models loaded in memory
+#[tauri::command]
+pub async fn ollama_running(base_url: String) -> Result<ApiResponse<Vec<RunningModel>>, String> {
+    match OllamaClient::new(&base_url).running().await {
+        Ok(models) => Ok(ApiResponse::success(models)),
+        Err(e) => Ok(ApiResponse::error(e.code(), &e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn ollama_unload(base_url: String, model: String) -> Result<ApiResponse<bool>, String> {
+    match OllamaClient::new(&base_url).unload(&model).await {
+        Ok(()) => Ok(ApiResponse::success(true)),
+        Err(e) => Ok(ApiResponse::error(e.code(), &e.to_string())),
+    }
+}
@@ -1,18 +1,9 @@
+use crate::services::search_service::{self, WebSearchResult};
 use crate::services::AppState;
 use super::file_ops::ApiResponse;
 use serde::Serialize;
 use tauri::State;
 
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct WebSearchResult {
-    pub title: String,
-    pub url: String,
-    pub description: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub age: Option<String>,
-}
-
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WebSearchResponse {
@@ -42,55 +33,16 @@ pub async fn web_search(
         }
     };
 
-    let client = reqwest::Client::new();
-    let search_count = count.unwrap_or(10).min(20);
-
-    let url = format!(
-        "https://api.search.brave.com/res/v1/web/search?q={}&count={}",
-        urlencoding::encode(&query),
-        search_count
-    );
-
-    match client
-        .get(&url)
-        .header("Accept", "application/json")
-        .header("X-Subscription-Token", &api_key)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if response.status().is_success() {
-                let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-
-                let results: Vec<WebSearchResult> = json["web"]["results"]
-                    .as_array()
-                    .map(|arr| {
-                        arr.iter().filter_map(|item| {
-                            Some(WebSearchResult {
-                                title: item["title"].as_str()?.to_string(),
-                                url: item["url"].as_str()?.to_string(),
-                                description: item["description"].as_str()
-                                    .unwrap_or("")
-                                    .to_string(),
-                                age: item["age"].as_str().map(String::from),
-                            })
-                        }).collect()
-                    })
-                    .unwrap_or_default();
-
-                let count = results.len();
+    match search_service::brave_search(&api_key, &query, count.unwrap_or(10)).await {
+        Ok(results) => {
+            let count = results.len();
 
-                Ok(ApiResponse::success(WebSearchResponse {
-                    query,
-                    results,
-                    count,
-                }))
-            } else {
-                let status = response.status();
-                let error_text = response.text().await.unwrap_or_default();
-                Ok(ApiResponse::error("SEARCH_ERROR", &format!("HTTP {}: {}", status, error_text)))
-            }
+            Ok(ApiResponse::success(WebSearchResponse {
+                query,
+                results,
+                count,
+            }))
         }
-        Err(e) => Ok(ApiResponse::error("SEARCH_ERROR", &format!("Request failed: {}", e))),
+        Err(e) => Ok(ApiResponse::error("SEARCH_ERROR", &e)),
     }
 }
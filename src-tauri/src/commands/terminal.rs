@@ -1,7 +1,7 @@
 use crate::services::AppState;
 use super::file_ops::ApiResponse;
 use serde::Serialize;
-use tauri::State;
+use tauri::{State, Window};
 use std::path::PathBuf;
 use std::process::Command;
 
@@ -25,20 +25,23 @@ pub struct OutputResult {
 
 #[tauri::command]
 pub async fn execute_command(
+    window: Window,
     state: State<'_, AppState>,
     command: String,
     cwd: Option<String>,
     terminal_id: Option<String>,
     shell: Option<String>,
 ) -> Result<ApiResponse<ExecuteResult>, String> {
-    let mut terminals = state.terminals.lock().unwrap();
+    let result = state.with_window(window.label(), |w| {
+        w.terminals.execute(
+            &command,
+            cwd.as_deref(),
+            terminal_id.as_deref(),
+            shell.as_deref(),
+        )
+    });
 
-    match terminals.execute(
-        &command,
-        cwd.as_deref(),
-        terminal_id.as_deref(),
-        shell.as_deref(),
-    ) {
+    match result {
         Ok((id, pid)) => Ok(ApiResponse::success(ExecuteResult {
             terminal_id: id,
             pid,
@@ -49,12 +52,11 @@ pub async fn execute_command(
 
 #[tauri::command]
 pub async fn kill_terminal(
+    window: Window,
     state: State<'_, AppState>,
     terminal_id: String,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let mut terminals = state.terminals.lock().unwrap();
-
-    match terminals.kill(&terminal_id) {
+    match state.with_window(window.label(), |w| w.terminals.kill(&terminal_id)) {
         Ok(killed) => Ok(ApiResponse::success(serde_json::json!({
             "killed": killed
         }))),
@@ -64,12 +66,11 @@ pub async fn kill_terminal(
 
 #[tauri::command]
 pub async fn get_terminal_output(
+    window: Window,
     state: State<'_, AppState>,
     terminal_id: String,
 ) -> Result<ApiResponse<OutputResult>, String> {
-    let mut terminals = state.terminals.lock().unwrap();
-
-    match terminals.get_output(&terminal_id) {
+    match state.with_window(window.label(), |w| w.terminals.get_output(&terminal_id)) {
         Ok((output, is_running, cwd)) => Ok(ApiResponse::success(OutputResult {
             output,
             is_running,
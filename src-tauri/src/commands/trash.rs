@@ -1,7 +1,10 @@
+use crate::services::trash_service::{
+    RestoreConflict, RestoreResult, TrashBackendKind, TrashSettings, TrashStats,
+};
 use crate::services::AppState;
 use super::file_ops::ApiResponse;
 use serde::Serialize;
-use tauri::State;
+use tauri::{State, Window};
 
 #[derive(Debug, Serialize)]
 pub struct TrashListResult {
@@ -11,23 +14,27 @@ pub struct TrashListResult {
 
 #[tauri::command]
 pub async fn move_to_trash(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     path: String,
+    request_id: Option<String>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let config = state.config.lock().unwrap();
-    let workspace = config.workspace_root.clone();
-    drop(config);
+    let workspace = state.root_path(window.label(), root_id.as_deref())?;
 
     let full_path = crate::services::file_service::resolve_path(&workspace, &path)
         .map_err(|e| e.to_string())?;
 
-    let trash = state.trash.lock().unwrap();
-    let request_id = uuid::Uuid::new_v4().to_string();
+    // Callers deleting several paths at once pass a shared request ID so they can be restored together
+    let request_id = request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
-    match trash.move_to_trash(&full_path, &path, &request_id) {
+    match state.with_trash(window.label(), root_id.as_deref(), |trash| {
+        trash.move_to_trash(&full_path, &path, &request_id)
+    })? {
         Ok((trash_id, trash_path)) => Ok(ApiResponse::success(serde_json::json!({
             "trashId": trash_id,
-            "trashPath": trash_path.to_string_lossy()
+            "trashPath": trash_path.to_string_lossy(),
+            "requestId": request_id
         }))),
         Err(e) => Ok(ApiResponse::error("TRASH_ERROR", &e.to_string())),
     }
@@ -35,12 +42,12 @@ pub async fn move_to_trash(
 
 #[tauri::command]
 pub async fn list_trash(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     date: Option<String>,
 ) -> Result<ApiResponse<TrashListResult>, String> {
-    let trash = state.trash.lock().unwrap();
-
-    match trash.list(date.as_deref()) {
+    match state.with_trash(window.label(), root_id.as_deref(), |trash| trash.list(date.as_deref()))? {
         Ok(items) => {
             let count = items.len();
             Ok(ApiResponse::success(TrashListResult { items, count }))
@@ -51,33 +58,88 @@ pub async fn list_trash(
 
 #[tauri::command]
 pub async fn restore_from_trash(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     trash_id: String,
     to_path: Option<String>,
-) -> Result<ApiResponse<serde_json::Value>, String> {
-    let trash = state.trash.lock().unwrap();
+    on_conflict: Option<RestoreConflict>,
+) -> Result<ApiResponse<RestoreResult>, String> {
+    match state.with_trash(window.label(), root_id.as_deref(), |trash| {
+        trash.restore(&trash_id, to_path.as_deref(), on_conflict.unwrap_or_default())
+    })? {
+        Ok(result) => Ok(ApiResponse::success(result)),
+        Err(e) => Ok(ApiResponse::error("TRASH_ERROR", &e.to_string())),
+    }
+}
 
-    match trash.restore(&trash_id, to_path.as_ref().map(|s| std::path::Path::new(s))) {
-        Ok(restored_path) => Ok(ApiResponse::success(serde_json::json!({
-            "restored": true,
-            "toPath": restored_path.to_string_lossy()
-        }))),
+/// Restore everything deleted by one request (e.g. a multi-file delete)
+#[tauri::command]
+pub async fn restore_trash_request(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    request_id: String,
+    on_conflict: Option<RestoreConflict>,
+) -> Result<ApiResponse<Vec<RestoreResult>>, String> {
+    match state.with_trash(window.label(), root_id.as_deref(), |trash| {
+        trash.restore_request(&request_id, on_conflict.unwrap_or_default())
+    })? {
+        Ok(results) => Ok(ApiResponse::success(results)),
         Err(e) => Ok(ApiResponse::error("TRASH_ERROR", &e.to_string())),
     }
 }
 
 #[tauri::command]
 pub async fn purge_trash(
+    window: Window,
     state: State<'_, AppState>,
+    root_id: Option<String>,
     trash_id: Option<String>,
     older_than_days: Option<i64>,
 ) -> Result<ApiResponse<serde_json::Value>, String> {
-    let trash = state.trash.lock().unwrap();
-
-    match trash.purge(trash_id.as_deref(), older_than_days) {
+    match state.with_trash(window.label(), root_id.as_deref(), |trash| {
+        trash.purge(trash_id.as_deref(), older_than_days)
+    })? {
         Ok(purged) => Ok(ApiResponse::success(serde_json::json!({
             "purged": purged
         }))),
         Err(e) => Ok(ApiResponse::error("TRASH_ERROR", &e.to_string())),
     }
 }
+
+#[tauri::command]
+pub async fn trash_stats(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+) -> Result<ApiResponse<TrashStats>, String> {
+    let stats = state.with_trash(window.label(), root_id.as_deref(), |trash| trash.stats())?;
+    Ok(ApiResponse::success(stats))
+}
+
+#[tauri::command]
+pub async fn update_trash_settings(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: Option<String>,
+    max_size_bytes: Option<u64>,
+    max_age_days: Option<i64>,
+    backend: Option<TrashBackendKind>,
+) -> Result<ApiResponse<serde_json::Value>, String> {
+    state.with_trash(window.label(), root_id.as_deref(), |trash| {
+        let backend = backend.unwrap_or(trash.settings().backend);
+
+        match trash.set_settings(TrashSettings {
+            backend,
+            max_size_bytes,
+            max_age_days,
+        }) {
+            Ok(purged) => ApiResponse::success(serde_json::json!({
+                "settings": trash.settings(),
+                "purged": purged
+            })),
+            Err(e) => ApiResponse::error("TRASH_ERROR", &e.to_string()),
+        }
+    })
+}
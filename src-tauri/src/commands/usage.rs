@@ -0,0 +1,65 @@
+use crate::services::usage_service::{UsageAggregate, UsageBudget, UsageGroup};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use serde::Serialize;
+use tauri::State;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetStatus {
+    pub budget: UsageBudget,
+    pub today: UsageAggregate,
+}
+
+const LEDGER_UNAVAILABLE: &str = "The usage ledger could not be opened";
+
+/// Usage totals grouped by day, model or workspace, between two days (YYYY-MM-DD, inclusive)
+#[tauri::command]
+pub async fn get_usage_summary(
+    state: State<'_, AppState>,
+    group_by: UsageGroup,
+    since: Option<String>,
+    until: Option<String>,
+) -> Result<ApiResponse<Vec<UsageAggregate>>, String> {
+    let Some(ledger) = &state.usage else {
+        return Ok(ApiResponse::error("USAGE_UNAVAILABLE", LEDGER_UNAVAILABLE));
+    };
+
+    let summary = ledger.summary(group_by, since.as_deref(), until.as_deref())?;
+    Ok(ApiResponse::success(summary))
+}
+
+#[tauri::command]
+pub async fn get_usage_budget(
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<BudgetStatus>, String> {
+    let Some(ledger) = &state.usage else {
+        return Ok(ApiResponse::error("USAGE_UNAVAILABLE", LEDGER_UNAVAILABLE));
+    };
+
+    Ok(ApiResponse::success(BudgetStatus {
+        budget: ledger.budget()?,
+        today: ledger.today()?,
+    }))
+}
+
+/// Set the daily limits; `None` fields remove a limit
+#[tauri::command]
+pub async fn set_usage_budget(
+    state: State<'_, AppState>,
+    budget: UsageBudget,
+) -> Result<ApiResponse<BudgetStatus>, String> {
+    let Some(ledger) = &state.usage else {
+        return Ok(ApiResponse::error("USAGE_UNAVAILABLE", LEDGER_UNAVAILABLE));
+    };
+
+    if budget.daily_cost_usd.is_some_and(|c| c < 0.0) {
+        return Ok(ApiResponse::error("INVALID_BUDGET", "Budget cannot be negative"));
+    }
+    ledger.set_budget(&budget)?;
+
+    Ok(ApiResponse::success(BudgetStatus {
+        budget,
+        today: ledger.today()?,
+    }))
+}
@@ -0,0 +1,122 @@
+use crate::services::workspace_service::{
+    Workspace, WorkspaceError, WorkspaceRoot, WORKSPACE_FILE_EXTENSION,
+};
+use crate::services::AppState;
+use super::file_ops::ApiResponse;
+use std::path::PathBuf;
+use tauri::{State, Window};
+
+#[tauri::command]
+pub async fn get_workspace(
+    window: Window,
+    state: State<'_, AppState>,
+) -> Result<ApiResponse<Workspace>, String> {
+    let workspace = state.with_window(window.label(), |w| w.workspace.clone());
+    Ok(ApiResponse::success(workspace))
+}
+
+#[tauri::command]
+pub async fn add_workspace_root(
+    window: Window,
+    state: State<'_, AppState>,
+    path: String,
+    name: Option<String>,
+) -> Result<ApiResponse<WorkspaceRoot>, String> {
+    let path = PathBuf::from(&path);
+    let path = path.canonicalize().unwrap_or(path);
+
+    let result = state.with_window(window.label(), |w| {
+        let root = w.workspace.add_root(path, name)?;
+        w.workspace.save_if_backed()?;
+        Ok::<_, WorkspaceError>(root)
+    });
+
+    match result {
+        Ok(root) => Ok(ApiResponse::success(root)),
+        Err(e) => Ok(ApiResponse::error("WORKSPACE_ERROR", &e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn remove_workspace_root(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: String,
+) -> Result<ApiResponse<WorkspaceRoot>, String> {
+    let result = state.with_window(window.label(), |w| {
+        let removed = w.workspace.remove_root(&root_id)?;
+        w.workspace.save_if_backed()?;
+        Ok::<_, WorkspaceError>(removed)
+    });
+
+    match result {
+        Ok(removed) => {
+            if !state.is_root_open(&removed.path) {
+                state.forget_root(&removed.path);
+            }
+            Ok(ApiResponse::success(removed))
+        }
+        Err(e) => Ok(ApiResponse::error("WORKSPACE_ERROR", &e.to_string())),
+    }
+}
+
+#[tauri::command]
+pub async fn set_primary_root(
+    window: Window,
+    state: State<'_, AppState>,
+    root_id: String,
+) -> Result<ApiResponse<Workspace>, String> {
+    let result = state.with_window(window.label(), |w| {
+        w.workspace.set_primary(&root_id)?;
+        w.workspace.save_if_backed()?;
+        Ok::<_, WorkspaceError>(w.workspace.clone())
+    });
+
+    match result {
+        Ok(workspace) => Ok(ApiResponse::success(workspace)),
+        Err(e) => Ok(ApiResponse::error("WORKSPACE_ERROR", &e.to_string())),
+    }
+}
+
+/// Replace this window's roots with those listed in a workspace file
+#[tauri::command]
+pub async fn open_workspace_file(
+    window: Window,
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<ApiResponse<Workspace>, String> {
+    let workspace = match Workspace::load(&PathBuf::from(&path)) {
+        Ok(workspace) => workspace,
+        Err(e) => return Ok(ApiResponse::error("WORKSPACE_ERROR", &e.to_string())),
+    };
+
+    state.replace_workspace(window.label(), workspace.clone());
+    Ok(ApiResponse::success(workspace))
+}
+
+/// Save this window's roots to a workspace file. Without a path, the file the
+/// workspace was opened from is overwritten.
+#[tauri::command]
+pub async fn save_workspace_file(
+    window: Window,
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> Result<ApiResponse<Workspace>, String> {
+    state.with_window(window.label(), |w| {
+        let file = match path.map(PathBuf::from).or_else(|| w.workspace.file.clone()) {
+            Some(file) if file.extension().is_none() => file.with_extension(WORKSPACE_FILE_EXTENSION),
+            Some(file) => file,
+            None => {
+                return Ok(ApiResponse::error(
+                    "NO_WORKSPACE_FILE",
+                    "Workspace has not been saved yet; a path is required",
+                ))
+            }
+        };
+
+        match w.workspace.save(&file) {
+            Ok(()) => Ok(ApiResponse::success(w.workspace.clone())),
+            Err(e) => Ok(ApiResponse::error("WORKSPACE_ERROR", &e.to_string())),
+        }
+    })
+}
@@ -5,6 +5,24 @@ use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // `--mcp-stdio` serves a workspace to another process over stdin/stdout instead of
+    // opening a window. Stdout carries the protocol, so nothing else may print to it.
+    if let Some(workspace) = services::mcp_server::stdio_workspace_from_args() {
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .init();
+        let server = services::mcp_server::McpServer::new(
+            workspace,
+            services::mcp_server::McpServerSettings::load(),
+        );
+        if let Err(e) = tauri::async_runtime::block_on(services::mcp_server::serve_stdio(server)) {
+            eprintln!("MCP server stopped: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Load .env: project root (dev) -> exe dir -> config dir -> cwd
     let mut loaded = false;
 
@@ -113,6 +131,7 @@ pub fn run() {
             let app_handle = app.handle().clone();
             let config = services::config::Config::new(&app_handle);
             app.manage(services::AppState::new(config));
+            services::trash_service::spawn_auto_purge(app_handle.clone());
 
             #[cfg(debug_assertions)]
             {
@@ -122,6 +141,12 @@ pub fn run() {
 
             Ok(())
         })
+        .on_window_event(|window, event| {
+            // Each window owns its workspace, terminals and streams; release them with it
+            if let tauri::WindowEvent::Destroyed = event {
+                window.state::<services::AppState>().close_window(window.label());
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             // File operations
             commands::file_ops::list_directory,
@@ -139,7 +164,10 @@ pub fn run() {
             commands::trash::move_to_trash,
             commands::trash::list_trash,
             commands::trash::restore_from_trash,
+            commands::trash::restore_trash_request,
             commands::trash::purge_trash,
+            commands::trash::trash_stats,
+            commands::trash::update_trash_settings,
             // Git operations
             commands::git::git_status,
             commands::git::git_stage,
@@ -161,11 +189,24 @@ pub fn run() {
             commands::config::set_proxy_url,
             commands::config::clear_local_llm_config,
             commands::config::get_app_info,
+            // Workspace operations
+            commands::workspace::get_workspace,
+            commands::workspace::add_workspace_root,
+            commands::workspace::remove_workspace_root,
+            commands::workspace::set_primary_root,
+            commands::workspace::open_workspace_file,
+            commands::workspace::save_workspace_file,
             // API Keys operations
             commands::config::get_api_keys_info,
             commands::config::set_api_key,
             commands::config::get_api_key_for_provider,
             commands::config::clear_api_key,
+            commands::config::unlock_api_keys,
+            // Provider profiles
+            commands::config::list_provider_profiles,
+            commands::config::save_provider_profile,
+            commands::config::delete_provider_profile,
+            commands::config::set_active_provider,
             // LLM operations
             commands::llm::test_llm_connection,
             commands::llm::list_local_models,
@@ -173,9 +214,44 @@ pub fn run() {
             commands::llm::chat_completion_stream,
             commands::llm::chat_completion_with_tools,
             commands::llm::chat_completion_stream_with_tools,
+            commands::llm::chat_completion_structured,
+            commands::llm::cancel_stream,
             commands::llm::generate_image,
             commands::llm::create_embedding,
             commands::llm::batch_create_embeddings,
+            commands::llm::count_tokens,
+            commands::llm::get_model_info,
+            // Local models (Ollama)
+            commands::ollama::ollama_health,
+            commands::ollama::ollama_pull,
+            commands::ollama::ollama_delete,
+            commands::ollama::ollama_show,
+            commands::ollama::ollama_running,
+            commands::ollama::ollama_unload,
+            // Agent runtime
+            commands::agent::run_agent,
+            commands::agent::respond_tool_approval,
+            commands::agent::list_agent_tools,
+            // MCP servers
+            commands::mcp::list_mcp_servers,
+            commands::mcp::reload_mcp_servers,
+            commands::mcp::set_mcp_server_trust,
+            commands::mcp::call_mcp_tool,
+            commands::mcp::read_mcp_resource,
+            commands::mcp::get_mcp_prompt,
+            // MCP server mode
+            commands::mcp_server::get_mcp_server_status,
+            commands::mcp_server::start_mcp_server,
+            commands::mcp_server::stop_mcp_server,
+            commands::mcp_server::update_mcp_server_settings,
+            // Usage ledger
+            commands::usage::get_usage_summary,
+            commands::usage::get_usage_budget,
+            commands::usage::set_usage_budget,
+            // Response cache
+            commands::cache::get_cache_stats,
+            commands::cache::update_cache_settings,
+            commands::cache::clear_cache,
             // Memory operations
             commands::memory::create_memory,
             commands::memory::get_memory,
@@ -188,6 +264,17 @@ pub fn run() {
             commands::memory::get_memory_settings,
             commands::memory::update_memory_settings,
             commands::memory::get_memory_stats,
+            // Conversations
+            commands::conversations::create_conversation,
+            commands::conversations::list_conversations,
+            commands::conversations::get_conversation,
+            commands::conversations::append_message,
+            commands::conversations::edit_message,
+            commands::conversations::switch_branch,
+            commands::conversations::rename_conversation,
+            commands::conversations::delete_conversation,
+            commands::conversations::search_conversations,
+            commands::conversations::export_conversation,
             // Extension operations
             commands::extensions::list_vscode_extensions,
             commands::extensions::list_installed_extensions,
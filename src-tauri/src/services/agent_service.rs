@@ -0,0 +1,371 @@
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+use super::agent_tools::{Tool, ToolContext, ToolError, ToolRegistry};
+use super::llm_adapter::{ChatRequest, Reasoning, ToolCall};
+use super::llm_client::LlmClient;
+use super::llm_executor::SendError;
+use super::tool_calls::{self, ToolCallEvent, ToolCallIssue};
+
+/// Tool results longer than this are cut before they are sent back to the model
+const MAX_RESULT_CHARS: usize = 30_000;
+
+/// The result recorded for calls that had not finished when the run was cancelled
+const CANCELLED_RESULT: &str = "Cancelled: the user stopped the run before this tool call finished.";
+
+/// Which tool calls wait for the user's approval
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ApprovalMode {
+    /// Only tools that ask for approval themselves: writes, deletes, `run_command` and MCP tools
+    #[default]
+    Default,
+    /// Every tool that is not read-only
+    Writes,
+    /// Run everything without asking
+    Never,
+}
+
+pub struct AgentRequest {
+    pub messages: Vec<Value>,
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub reasoning: Option<Reasoning>,
+    pub fallback_models: Vec<String>,
+    /// Model turns allowed before the run stops
+    pub max_steps: usize,
+    pub approval: ApprovalMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StopReason {
+    /// The model answered without calling a tool
+    Done,
+    StepLimit,
+    Cancelled,
+}
+
+/// Progress of a run, emitted as it happens
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum AgentEvent {
+    #[serde(rename_all = "camelCase")]
+    StepStarted { step: usize },
+    /// The model's reply for a step, before any of its tool calls run
+    #[serde(rename_all = "camelCase")]
+    Message {
+        step: usize,
+        content: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        reasoning: Option<String>,
+        tool_calls: Vec<ToolCall>,
+    },
+    #[serde(rename_all = "camelCase")]
+    ApprovalRequired {
+        step: usize,
+        call_id: String,
+        name: String,
+        arguments: Value,
+    },
+    /// A tool call of the step moving through its lifecycle
+    #[serde(rename_all = "camelCase")]
+    Tool { step: usize, event: ToolCallEvent },
+    #[serde(rename_all = "camelCase")]
+    Finished {
+        steps: usize,
+        reason: StopReason,
+        content: Option<String>,
+    },
+}
+
+/// Where a run reports progress and asks for approval
+#[async_trait]
+pub trait AgentHooks: Send + Sync {
+    fn emit(&self, event: AgentEvent);
+    /// Decide whether a call may run, announcing the request with an `ApprovalRequired`
+    /// event if the user is asked. Rejected calls are reported to the model as such.
+    async fn approve(&self, step: usize, call: &ToolCall, arguments: &Value) -> bool;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentOutcome {
+    /// The model's final answer
+    pub content: Option<String>,
+    pub steps: usize,
+    pub reason: StopReason,
+    /// The conversation including every assistant turn and tool result of the run
+    pub messages: Vec<Value>,
+}
+
+/// Run the model with `tools` until it answers without calling one, the step limit is
+/// reached or `ctx.cancel` fires. Consecutive read-only calls of a step run concurrently,
+/// the others one at a time; results are reported and appended to the conversation in
+/// call order for the next step.
+pub async fn run_agent(
+    client: &LlmClient,
+    tools: &ToolRegistry,
+    ctx: &ToolContext,
+    mut request: AgentRequest,
+    hooks: &dyn AgentHooks,
+) -> Result<AgentOutcome, SendError> {
+    let definitions = tools.definitions();
+    let mut messages = std::mem::take(&mut request.messages);
+    let mut content = None;
+    let mut steps = 0;
+
+    let reason = 'run: loop {
+        if steps >= request.max_steps {
+            break 'run StopReason::StepLimit;
+        }
+        steps += 1;
+        hooks.emit(AgentEvent::StepStarted { step: steps });
+
+        let chat = ChatRequest {
+            model: &request.model,
+            messages: &messages,
+            tools: &definitions,
+            temperature: request.temperature,
+            max_tokens: request.max_tokens,
+            stream: false,
+            reasoning: request.reasoning,
+            response_format: None,
+        };
+        let reply = tokio::select! {
+            reply = client.chat(&chat, &request.fallback_models) => reply?,
+            _ = ctx.cancel.cancelled() => break 'run StopReason::Cancelled,
+        };
+
+        let mut tool_calls = reply.tool_calls.unwrap_or_default();
+        // Repaired arguments go into the conversation; calls that failed checks, or that
+        // needed repair because the reply hit the output limit, are answered with the
+        // problem instead of being run
+        let issues = tool_calls::check_tool_calls(&mut tool_calls, &definitions, reply.finish_reason == "length");
+        content = reply.content;
+        hooks.emit(AgentEvent::Message {
+            step: steps,
+            content: content.clone(),
+            reasoning: reply.reasoning,
+            tool_calls: tool_calls.clone(),
+        });
+
+        let mut assistant = json!({ "role": "assistant", "content": content });
+        if !tool_calls.is_empty() {
+            assistant["tool_calls"] = json!(tool_calls);
+        }
+        messages.push(assistant);
+
+        if tool_calls.is_empty() {
+            break 'run StopReason::Done;
+        }
+
+        let issue_for = |call: &ToolCall| issues.iter().find(|issue| issue.call_id == call.id);
+        for (index, call) in tool_calls.iter().enumerate() {
+            hooks.emit(AgentEvent::Tool {
+                step: steps,
+                event: ToolCallEvent::started(index, call),
+            });
+            hooks.emit(AgentEvent::Tool {
+                step: steps,
+                event: ToolCallEvent::arguments_complete(index, call, issue_for(call).cloned()),
+            });
+        }
+
+        let parallel = |call: &ToolCall| {
+            issue_for(call).is_none()
+                && tools
+                    .get(&call.function.name)
+                    .is_some_and(|tool| tool.read_only() && !needs_approval(request.approval, tool.as_ref()))
+        };
+        let mut start = 0;
+        while start < tool_calls.len() {
+            let mut end = start + 1;
+            if parallel(&tool_calls[start]) {
+                while end < tool_calls.len() && parallel(&tool_calls[end]) {
+                    end += 1;
+                }
+            }
+
+            let batch = (start..end).map(|index| {
+                let call = &tool_calls[index];
+                run_tool_call(tools, ctx, &request, call, index, issue_for(call), steps, hooks)
+            });
+            let outputs = tokio::select! {
+                outputs = join_all(batch) => outputs,
+                _ = ctx.cancel.cancelled() => {
+                    for (index, call) in tool_calls.iter().enumerate().skip(start) {
+                        hooks.emit(AgentEvent::Tool {
+                            step: steps,
+                            event: ToolCallEvent::finished(index, call, false, CANCELLED_RESULT.to_string()),
+                        });
+                    }
+                    break 'run StopReason::Cancelled;
+                }
+            };
+
+            for (index, (ok, output)) in (start..end).zip(outputs) {
+                let call = &tool_calls[index];
+                let output = truncate_output(output);
+                hooks.emit(AgentEvent::Tool {
+                    step: steps,
+                    event: ToolCallEvent::finished(index, call, ok, output.clone()),
+                });
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": call.id,
+                    "content": output,
+                }));
+            }
+            start = end;
+        }
+    };
+
+    if reason == StopReason::Cancelled {
+        answer_pending_calls(&mut messages);
+    }
+    hooks.emit(AgentEvent::Finished {
+        steps,
+        reason,
+        content: content.clone(),
+    });
+    Ok(AgentOutcome {
+        content,
+        steps,
+        reason,
+        messages,
+    })
+}
+
+/// Give every call of the last assistant turn that has no result yet a cancelled one.
+/// Providers reject conversations with unanswered calls, so this keeps `messages`
+/// usable for the next request.
+fn answer_pending_calls(messages: &mut Vec<Value>) {
+    let Some(turn) = messages.iter().rposition(|message| message["role"] == "assistant") else {
+        return;
+    };
+    let answered: HashSet<&str> = messages[turn + 1..]
+        .iter()
+        .filter_map(|message| message["tool_call_id"].as_str())
+        .collect();
+    let pending: Vec<String> = messages[turn]["tool_calls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|call| call["id"].as_str())
+        .filter(|id| !answered.contains(id))
+        .map(String::from)
+        .collect();
+    for id in pending {
+        messages.push(json!({
+            "role": "tool",
+            "tool_call_id": id,
+            "content": CANCELLED_RESULT,
+        }));
+    }
+}
+
+fn needs_approval(mode: ApprovalMode, tool: &dyn Tool) -> bool {
+    match mode {
+        ApprovalMode::Default => tool.needs_approval(),
+        ApprovalMode::Writes => tool.needs_approval() || !tool.read_only(),
+        ApprovalMode::Never => false,
+    }
+}
+
+/// Run one call, returning whether it succeeded and the text sent back to the model.
+/// Failures, including an `issue` found when checking the call, are reported to the
+/// model rather than ending the run, so it can correct itself.
+#[allow(clippy::too_many_arguments)]
+async fn run_tool_call(
+    tools: &ToolRegistry,
+    ctx: &ToolContext,
+    request: &AgentRequest,
+    call: &ToolCall,
+    index: usize,
+    issue: Option<&ToolCallIssue>,
+    step: usize,
+    hooks: &dyn AgentHooks,
+) -> (bool, String) {
+    if let Some(issue) = issue {
+        return (false, issue.message.clone());
+    }
+    let Some(tool) = tools.get(&call.function.name) else {
+        return (false, format!("Error: Unknown tool: {}", call.function.name));
+    };
+    let arguments = tool_calls::parse_arguments(&call.function.arguments).map_or_else(|_| json!({}), |parsed| parsed.value);
+
+    if needs_approval(request.approval, tool.as_ref()) && !hooks.approve(step, call, &arguments).await {
+        return (false, "The user rejected this tool call.".to_string());
+    }
+
+    hooks.emit(AgentEvent::Tool {
+        step,
+        event: ToolCallEvent::executing(index, call),
+    });
+    let outcome = if tool.blocking() {
+        // Running it on the blocking pool lets the other calls of a read-only batch proceed
+        let ctx = ctx.clone();
+        let runtime = tokio::runtime::Handle::current();
+        tokio::task::spawn_blocking(move || runtime.block_on(tool.call(&ctx, arguments)))
+            .await
+            .unwrap_or_else(|e| Err(ToolError::Failed(format!("Tool panicked: {}", e))))
+    } else {
+        tool.call(ctx, arguments).await
+    };
+    match outcome {
+        Ok(output) => (true, output),
+        Err(e) => (false, format!("Error: {}", e)),
+    }
+}
+
+fn truncate_output(output: String) -> String {
+    if output.len() <= MAX_RESULT_CHARS {
+        return output;
+    }
+    let mut end = MAX_RESULT_CHARS;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n...(truncated, {} more bytes)",
+        &output[..end],
+        output.len() - end
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_runs_answer_every_pending_call() {
+        let mut messages = vec![
+            json!({ "role": "user", "content": "tidy up" }),
+            json!({
+                "role": "assistant",
+                "content": null,
+                "tool_calls": [{ "id": "a" }, { "id": "b" }, { "id": "c" }],
+            }),
+            json!({ "role": "tool", "tool_call_id": "a", "content": "done" }),
+        ];
+        answer_pending_calls(&mut messages);
+
+        let results: Vec<_> = messages[2..]
+            .iter()
+            .map(|m| (m["tool_call_id"].as_str().unwrap(), m["content"].as_str().unwrap()))
+            .collect();
+        assert_eq!(results, vec![("a", "done"), ("b", CANCELLED_RESULT), ("c", CANCELLED_RESULT)]);
+
+        // Nothing is pending once every call has a result, or when the last turn called nothing
+        answer_pending_calls(&mut messages);
+        assert_eq!(messages.len(), 5);
+        messages.push(json!({ "role": "assistant", "content": "all done" }));
+        answer_pending_calls(&mut messages);
+        assert_eq!(messages.len(), 6);
+    }
+}
@@ -0,0 +1,617 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use thiserror::Error;
+
+use super::file_service::{self, FileError};
+use super::stream_service::StreamHandle;
+use super::{git_service, search_service, AppState};
+
+#[derive(Error, Debug)]
+pub enum ToolError {
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<FileError> for ToolError {
+    fn from(e: FileError) -> Self {
+        ToolError::Failed(e.to_string())
+    }
+}
+
+/// What a tool call runs against: the window that started the agent and its primary root
+#[derive(Clone)]
+pub struct ToolContext {
+    pub app: AppHandle,
+    pub window: String,
+    pub workspace: PathBuf,
+    /// Fires when the agent run is cancelled
+    pub cancel: Arc<StreamHandle>,
+}
+
+impl ToolContext {
+    pub fn state(&self) -> tauri::State<'_, AppState> {
+        self.app.state::<AppState>()
+    }
+
+    /// Check a path the model gave against the workspace before handing it to `file_service`
+    fn sandboxed(&self, path: &str) -> Result<PathBuf, FileError> {
+        file_service::resolve_within(&self.workspace, path)
+    }
+}
+
+/// A capability the model can call. Arguments arrive as the JSON object described by
+/// `parameters`; the returned text is sent back to the model as the tool result.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    /// JSON Schema of the arguments object
+    fn parameters(&self) -> Value;
+    /// Read-only tools never change the workspace or run anything
+    fn read_only(&self) -> bool {
+        false
+    }
+    /// Whether calls wait for the user's approval by default
+    fn needs_approval(&self) -> bool {
+        false
+    }
+    /// Tools whose `call` does file or git work synchronously are run on the blocking
+    /// pool, so a batch of them overlaps instead of stalling the runtime
+    fn blocking(&self) -> bool {
+        false
+    }
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError>;
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolInfo {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value,
+    pub read_only: bool,
+    pub needs_approval: bool,
+}
+
+/// Tools offered to the model, in the order they are listed
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    /// The built-in workspace, terminal, git and web tools
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        registry.register(Arc::new(ReadFile));
+        registry.register(Arc::new(WriteFile));
+        registry.register(Arc::new(DeleteFile));
+        registry.register(Arc::new(ListDirectory));
+        registry.register(Arc::new(CreateDirectory));
+        registry.register(Arc::new(SearchFiles));
+        registry.register(Arc::new(RunCommand));
+        registry.register(Arc::new(GitStatus));
+        registry.register(Arc::new(GitDiff));
+        registry.register(Arc::new(WebSearch));
+        registry
+    }
+
+    /// Add a tool, replacing any tool with the same name
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        match self.tools.iter_mut().find(|t| t.name() == tool.name()) {
+            Some(existing) => *existing = tool,
+            None => self.tools.push(tool),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|t| t.name() == name).cloned()
+    }
+
+    /// Only the named tools; unknown names are ignored
+    pub fn only(&self, names: &[String]) -> Self {
+        Self {
+            tools: self
+                .tools
+                .iter()
+                .filter(|t| names.iter().any(|n| n == t.name()))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Tool definitions in the OpenAI format the adapters translate from
+    pub fn definitions(&self) -> Vec<Value> {
+        self.tools
+            .iter()
+            .map(|t| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.name(),
+                        "description": t.description(),
+                        "parameters": t.parameters(),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub fn infos(&self) -> Vec<ToolInfo> {
+        self.tools
+            .iter()
+            .map(|t| ToolInfo {
+                name: t.name().to_string(),
+                description: t.description().to_string(),
+                parameters: t.parameters(),
+                read_only: t.read_only(),
+                needs_approval: t.needs_approval(),
+            })
+            .collect()
+    }
+}
+
+fn str_arg<'a>(args: &'a Value, key: &str) -> Result<&'a str, ToolError> {
+    args[key]
+        .as_str()
+        .ok_or_else(|| ToolError::InvalidArguments(format!("`{}` must be a string", key)))
+}
+
+fn opt_str_arg<'a>(args: &'a Value, key: &str) -> Option<&'a str> {
+    args[key].as_str().filter(|s| !s.is_empty())
+}
+
+/// Schema for tools taking string arguments, given as (name, description) pairs
+fn string_params(properties: &[(&str, &str)], required: &[&str]) -> Value {
+    let properties: serde_json::Map<String, Value> = properties
+        .iter()
+        .map(|(name, description)| {
+            (name.to_string(), json!({ "type": "string", "description": description }))
+        })
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+struct ReadFile;
+
+#[async_trait]
+impl Tool for ReadFile {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+    fn description(&self) -> &str {
+        "Read the contents of a file at the given path"
+    }
+    fn parameters(&self) -> Value {
+        string_params(&[("path", "The file path relative to workspace root")], &["path"])
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = str_arg(&args, "path")?;
+        ctx.sandboxed(path)?;
+        let max_size = ctx.state().config.lock().unwrap().max_read_size;
+        let (content, _, _) = file_service::read_file(&ctx.workspace, path, max_size)?;
+        Ok(content)
+    }
+}
+
+struct WriteFile;
+
+#[async_trait]
+impl Tool for WriteFile {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+    fn description(&self) -> &str {
+        "Create or overwrite a file with the given content"
+    }
+    fn parameters(&self) -> Value {
+        string_params(
+            &[
+                ("path", "The file path relative to workspace root"),
+                ("content", "The content to write to the file"),
+            ],
+            &["path", "content"],
+        )
+    }
+    fn needs_approval(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = str_arg(&args, "path")?;
+        ctx.sandboxed(path)?;
+        let (_, _, bytes_written) =
+            file_service::write_file(&ctx.workspace, path, str_arg(&args, "content")?, true, true)?;
+        Ok(format!("File written successfully: {} ({} bytes)", path, bytes_written))
+    }
+}
+
+struct DeleteFile;
+
+#[async_trait]
+impl Tool for DeleteFile {
+    fn name(&self) -> &str {
+        "delete_file"
+    }
+    fn description(&self) -> &str {
+        "Delete a file or directory (moves to trash)"
+    }
+    fn parameters(&self) -> Value {
+        string_params(&[("path", "The file or directory path to delete")], &["path"])
+    }
+    fn needs_approval(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = str_arg(&args, "path")?;
+        let full_path = ctx.sandboxed(path)?;
+        let request_id = uuid::Uuid::new_v4().to_string();
+        ctx.state()
+            .with_trash(&ctx.window, None, |trash| trash.move_to_trash(&full_path, path, &request_id))
+            .map_err(ToolError::Failed)?
+            .map_err(|e| ToolError::Failed(e.to_string()))?;
+        Ok(format!("File deleted: {}", path))
+    }
+}
+
+struct ListDirectory;
+
+#[async_trait]
+impl Tool for ListDirectory {
+    fn name(&self) -> &str {
+        "list_directory"
+    }
+    fn description(&self) -> &str {
+        "List all files and directories in a given path"
+    }
+    fn parameters(&self) -> Value {
+        string_params(
+            &[("path", "The directory path to list (use \".\" for workspace root)")],
+            &["path"],
+        )
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = opt_str_arg(&args, "path").unwrap_or(".");
+        ctx.sandboxed(path)?;
+        let entries = file_service::list_directory(&ctx.workspace, path, false, false)?;
+        if entries.is_empty() {
+            return Ok("(empty directory)".to_string());
+        }
+        Ok(entries
+            .iter()
+            .map(|e| {
+                let icon = if e.file_type == "directory" { "📁" } else { "📄" };
+                format!("{} {}", icon, e.name)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+}
+
+struct CreateDirectory;
+
+#[async_trait]
+impl Tool for CreateDirectory {
+    fn name(&self) -> &str {
+        "create_directory"
+    }
+    fn description(&self) -> &str {
+        "Create a new directory"
+    }
+    fn parameters(&self) -> Value {
+        string_params(&[("path", "The directory path to create")], &["path"])
+    }
+    fn needs_approval(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = str_arg(&args, "path")?;
+        ctx.sandboxed(path)?;
+        file_service::create_directory(&ctx.workspace, path, true)?;
+        Ok(format!("Directory created: {}", path))
+    }
+}
+
+/// Matches listed in a `search_files` result; the rest are only counted
+const SEARCH_MATCHES_SHOWN: usize = 20;
+
+struct SearchFiles;
+
+#[async_trait]
+impl Tool for SearchFiles {
+    fn name(&self) -> &str {
+        "search_files"
+    }
+    fn description(&self) -> &str {
+        "Search for text content across files in the workspace"
+    }
+    fn parameters(&self) -> Value {
+        string_params(
+            &[
+                ("query", "The text to search for"),
+                ("path", "Directory to search in (optional, defaults to workspace root)"),
+            ],
+            &["query"],
+        )
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = opt_str_arg(&args, "path").unwrap_or(".");
+        ctx.sandboxed(path)?;
+        let max = ctx.state().config.lock().unwrap().max_search_results;
+        let matches = file_service::search_files(
+            &ctx.workspace,
+            str_arg(&args, "query")?,
+            path,
+            false,
+            max,
+        )?;
+        if matches.is_empty() {
+            return Ok("No matches found".to_string());
+        }
+        let shown = matches
+            .iter()
+            .take(SEARCH_MATCHES_SHOWN)
+            .map(|m| format!("{}:{}: {}", m.path, m.line, m.text.trim()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let truncated = if matches.len() >= max { "\n...(truncated)" } else { "" };
+        Ok(format!("Found {} matches:\n{}{}", matches.len(), shown, truncated))
+    }
+}
+
+/// How long `run_command` waits for a command before stopping it
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A command started in the window's terminals. Killed when dropped before it finished,
+/// which is how a cancelled agent run stops it.
+struct RunningCommand<'a> {
+    ctx: &'a ToolContext,
+    terminal_id: String,
+    finished: bool,
+}
+
+impl Drop for RunningCommand<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self
+                .ctx
+                .state()
+                .with_window(&self.ctx.window, |w| w.terminals.kill(&self.terminal_id));
+        }
+    }
+}
+
+struct RunCommand;
+
+#[async_trait]
+impl Tool for RunCommand {
+    fn name(&self) -> &str {
+        "run_command"
+    }
+    fn description(&self) -> &str {
+        "Execute a terminal/shell command. Commands require user approval before execution. Use for tasks like npm install, git commands, build scripts, or any shell commands."
+    }
+    fn parameters(&self) -> Value {
+        string_params(
+            &[
+                ("command", "The shell command to execute"),
+                ("workingDirectory", "Working directory for the command (optional, defaults to workspace root)"),
+                ("reason", "Brief explanation of why this command is needed"),
+            ],
+            &["command"],
+        )
+    }
+    fn needs_approval(&self) -> bool {
+        true
+    }
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let command = str_arg(&args, "command")?;
+        let cwd = match opt_str_arg(&args, "workingDirectory") {
+            Some(dir) => ctx.sandboxed(dir)?,
+            None => ctx.workspace.clone(),
+        };
+        let cwd = cwd.to_string_lossy();
+
+        let state = ctx.state();
+        let (terminal_id, _) = state
+            .with_window(&ctx.window, |w| w.terminals.execute(command, Some(cwd.as_ref()), None, None))
+            .map_err(|e| ToolError::Failed(e.to_string()))?;
+        let mut running = RunningCommand { ctx, terminal_id, finished: false };
+
+        let started = Instant::now();
+        loop {
+            tokio::time::sleep(COMMAND_POLL_INTERVAL).await;
+            let (output, is_running, _) = state
+                .with_window(&ctx.window, |w| w.terminals.get_output(&running.terminal_id))
+                .map_err(|e| ToolError::Failed(e.to_string()))?;
+
+            if !is_running {
+                running.finished = true;
+                if output.is_empty() {
+                    return Ok("(Command completed with no output)".to_string());
+                }
+                return Ok(output);
+            }
+            if started.elapsed() >= COMMAND_TIMEOUT {
+                return Err(ToolError::Failed(format!(
+                    "Command did not finish within {}s and was stopped. Output so far:\n{}",
+                    COMMAND_TIMEOUT.as_secs(),
+                    output
+                )));
+            }
+        }
+    }
+}
+
+struct GitStatus;
+
+#[async_trait]
+impl Tool for GitStatus {
+    fn name(&self) -> &str {
+        "git_status"
+    }
+    fn description(&self) -> &str {
+        "Show the current git branch and the changed files in the workspace"
+    }
+    fn parameters(&self) -> Value {
+        string_params(&[], &[])
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, _args: Value) -> Result<String, ToolError> {
+        let status = git_service::status(&ctx.workspace).map_err(ToolError::Failed)?;
+        let mut lines = vec![format!("Branch: {}", status.branch.as_deref().unwrap_or("(unknown)"))];
+        if status.changes.is_empty() {
+            lines.push("Working tree clean".to_string());
+        }
+        for change in &status.changes {
+            let staged = if change.staged { " (staged)" } else { "" };
+            lines.push(format!("{}: {}{}", change.status, change.path, staged));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+struct GitDiff;
+
+#[async_trait]
+impl Tool for GitDiff {
+    fn name(&self) -> &str {
+        "git_diff"
+    }
+    fn description(&self) -> &str {
+        "Show unstaged changes in the workspace, optionally for a single path"
+    }
+    fn parameters(&self) -> Value {
+        string_params(&[("path", "File or directory to diff (optional)")], &[])
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    fn blocking(&self) -> bool {
+        true
+    }
+
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let path = opt_str_arg(&args, "path");
+        if let Some(path) = path {
+            ctx.sandboxed(path)?;
+        }
+        let diff = git_service::diff(&ctx.workspace, path).map_err(ToolError::Failed)?;
+        if diff.is_empty() {
+            return Ok("No unstaged changes".to_string());
+        }
+        Ok(diff)
+    }
+}
+
+struct WebSearch;
+
+#[async_trait]
+impl Tool for WebSearch {
+    fn name(&self) -> &str {
+        "web_search"
+    }
+    fn description(&self) -> &str {
+        "Search the web for current information. Use for finding documentation, news, tutorials, or any information that might be more current than your training data."
+    }
+    fn parameters(&self) -> Value {
+        string_params(
+            &[
+                ("query", "The search query"),
+                ("count", "Number of results to return (default 5, max 10)"),
+            ],
+            &["query"],
+        )
+    }
+    fn read_only(&self) -> bool {
+        true
+    }
+    async fn call(&self, ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let query = str_arg(&args, "query")?;
+        let count = args["count"]
+            .as_u64()
+            .or_else(|| args["count"].as_str()?.parse().ok())
+            .unwrap_or(5)
+            .min(10) as u32;
+        let api_key = ctx
+            .state()
+            .config
+            .lock()
+            .unwrap()
+            .brave_api_key
+            .clone()
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| ToolError::Failed("Brave Search API key not configured".to_string()))?;
+
+        let results = search_service::brave_search(&api_key, query, count)
+            .await
+            .map_err(ToolError::Failed)?;
+        if results.is_empty() {
+            return Ok("No search results found".to_string());
+        }
+        let formatted = results
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                let age = r.age.as_ref().map(|age| format!(" ({})", age)).unwrap_or_default();
+                format!("{}. **{}**\n   URL: {}\n   {}{}", i + 1, r.title, r.url, r.description, age)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        Ok(format!("Web search results for \"{}\":\n\n{}", query, formatted))
+    }
+}
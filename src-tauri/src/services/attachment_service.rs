@@ -0,0 +1,481 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+
+use super::file_service::{resolve_within, FileError};
+
+/// Largest file read from the workspace for an attachment
+const MAX_ATTACHMENT_SIZE: u64 = 20 * 1024 * 1024;
+/// Largest text file inlined into a message
+const MAX_TEXT_ATTACHMENT: usize = 512 * 1024;
+/// Images are not shrunk below this edge length to meet the byte limit
+const MIN_IMAGE_EDGE: u32 = 256;
+const JPEG_QUALITY: u8 = 85;
+/// How long downloading one remote image may take
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum AttachmentError {
+    #[error("Open a workspace folder to attach files by path")]
+    NoWorkspace,
+    #[error(transparent)]
+    File(#[from] FileError),
+    #[error("Unsupported image {0}: {1}")]
+    UnsupportedImage(String, String),
+    #[error("Cannot attach {0}: only text files, PDFs and images can be sent")]
+    UnsupportedFile(String),
+    #[error("{0} is too large to attach")]
+    TooLarge(String),
+    #[error("Invalid attachment: {0}")]
+    Invalid(String),
+    #[error("Could not download image {0}: {1}")]
+    Download(String, String),
+}
+
+/// What a provider accepts for one image
+#[derive(Debug, Clone, Copy)]
+pub struct ImageLimits {
+    /// Longest side in pixels; larger images are downscaled
+    pub max_edge: u32,
+    /// Size of the encoded image, before base64
+    pub max_bytes: usize,
+    /// The provider downloads remote image URLs itself. When it does not, they are
+    /// fetched with `inline_remote_images` and sent as data.
+    pub remote_urls: bool,
+}
+
+/// Message content as the frontend sends it: plain text or a list of parts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+/// One part of a message. `resolve_messages` turns these into OpenAI content parts,
+/// which each provider adapter maps to its own format.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    /// An image from a data URL, a remote URL or a path in the workspace
+    Image {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        /// OpenAI detail level: low, high or auto
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        detail: Option<String>,
+    },
+    /// OpenAI's image part, accepted as is apart from downscaling data URLs
+    ImageUrl { image_url: Value },
+    /// A file from the workspace, or one passed as a data URL with its name.
+    /// Text files are inlined, PDFs are sent as documents and images as images.
+    File {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+/// Whether any message has content parts that may need resolving
+pub fn has_parts(messages: &[Value]) -> bool {
+    messages.iter().any(|m| m["content"].is_array())
+}
+
+/// Replace attachment parts with OpenAI content parts: workspace files are read, images
+/// are downscaled and re-encoded to fit `limits`, and text files are inlined. Parts this
+/// module does not know are left alone.
+pub fn resolve_messages(messages: &mut [Value], workspace: Option<&Path>, limits: ImageLimits) -> Result<(), AttachmentError> {
+    for message in messages {
+        let Some(parts) = message.get_mut("content").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        let resolved = parts
+            .drain(..)
+            .map(|part| resolve_part(part, workspace, limits))
+            .collect::<Result<Vec<_>, _>>()?;
+        *parts = resolved;
+    }
+    Ok(())
+}
+
+/// Download every remote image in `messages` and replace its URL with a data URL, for
+/// providers that only take inline images. Run this before `resolve_messages`, which then
+/// fits the images to the provider's limits.
+pub async fn inline_remote_images(messages: &mut [Value], http: &reqwest::Client) -> Result<(), AttachmentError> {
+    for message in messages {
+        let Some(parts) = message.get_mut("content").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for part in parts {
+            let Some(url) = remote_image_url(part) else {
+                continue;
+            };
+            let data = download_image(http, url.as_str().unwrap_or_default()).await?;
+            *url = json!(data);
+        }
+    }
+    Ok(())
+}
+
+/// The URL of an image part, in either the frontend's or OpenAI's shape, when it is remote
+fn remote_image_url(part: &mut Value) -> Option<&mut Value> {
+    let url = match part["type"].as_str()? {
+        "image" => part.get_mut("url")?,
+        "image_url" if part["image_url"].is_string() => part.get_mut("image_url")?,
+        "image_url" => part.get_mut("image_url")?.get_mut("url")?,
+        _ => return None,
+    };
+    let remote = url.as_str().is_some_and(|url| url.starts_with("http://") || url.starts_with("https://"));
+    remote.then_some(url)
+}
+
+async fn download_image(http: &reqwest::Client, url: &str) -> Result<String, AttachmentError> {
+    let failed = |e: reqwest::Error| AttachmentError::Download(url.to_string(), e.to_string());
+    let mut response = http
+        .get(url)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(failed)?;
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(failed)? {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() as u64 > MAX_ATTACHMENT_SIZE {
+            return Err(AttachmentError::TooLarge(url.to_string()));
+        }
+    }
+    let format = image::guess_format(&bytes)
+        .map_err(|e| AttachmentError::UnsupportedImage(url.to_string(), e.to_string()))?;
+    Ok(data_url(format.to_mime_type(), &bytes))
+}
+
+fn resolve_part(part: Value, workspace: Option<&Path>, limits: ImageLimits) -> Result<Value, AttachmentError> {
+    // Already in OpenAI's file format
+    if part["type"] == "file" && part["file"].is_object() {
+        return Ok(part);
+    }
+    let Ok(typed) = serde_json::from_value::<ContentPart>(part.clone()) else {
+        return Ok(part);
+    };
+
+    match typed {
+        ContentPart::Text { .. } => Ok(part),
+        ContentPart::Image { url, path, detail } => {
+            let url = match (path, url) {
+                (Some(path), _) => {
+                    let bytes = read_workspace_file(workspace, &path)?;
+                    image_data_url(&bytes, &path, limits)?
+                }
+                (None, Some(url)) => prepare_image_url(url, limits)?,
+                (None, None) => return Err(AttachmentError::Invalid("an image part needs a url or path".to_string())),
+            };
+            Ok(image_part(url, detail))
+        }
+        ContentPart::ImageUrl { image_url } => {
+            let Some(url) = image_url["url"].as_str().or(image_url.as_str()) else {
+                return Err(AttachmentError::Invalid("image_url has no url".to_string()));
+            };
+            let detail = image_url["detail"].as_str().map(String::from);
+            Ok(image_part(prepare_image_url(url.to_string(), limits)?, detail))
+        }
+        ContentPart::File { path, data, name } => {
+            let (name, bytes) = match (path, data) {
+                (Some(path), _) => {
+                    let bytes = read_workspace_file(workspace, &path)?;
+                    (name.unwrap_or(path), bytes)
+                }
+                (None, Some(data)) => {
+                    let (_, bytes) = decode_data_url(&data)?;
+                    (name.unwrap_or_else(|| "attachment".to_string()), bytes)
+                }
+                (None, None) => return Err(AttachmentError::Invalid("a file part needs a path or data".to_string())),
+            };
+            file_part(&name, bytes, limits)
+        }
+    }
+}
+
+fn image_part(url: String, detail: Option<String>) -> Value {
+    let mut image_url = json!({ "url": url });
+    if let Some(detail) = detail {
+        image_url["detail"] = json!(detail);
+    }
+    json!({ "type": "image_url", "image_url": image_url })
+}
+
+/// Data URLs are downscaled to fit; remote URLs are left for the provider to fetch, or were
+/// inlined by `inline_remote_images` when it does not
+fn prepare_image_url(url: String, limits: ImageLimits) -> Result<String, AttachmentError> {
+    if !url.starts_with("data:") {
+        return Ok(url);
+    }
+    let (_, bytes) = decode_data_url(&url)?;
+    image_data_url(&bytes, "image", limits)
+}
+
+fn file_part(name: &str, bytes: Vec<u8>, limits: ImageLimits) -> Result<Value, AttachmentError> {
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    if extension == "pdf" || bytes.starts_with(b"%PDF-") {
+        return Ok(json!({
+            "type": "file",
+            "file": {
+                "filename": file_name(name),
+                "file_data": data_url("application/pdf", &bytes)
+            }
+        }));
+    }
+    if image::guess_format(&bytes).is_ok() {
+        return Ok(image_part(image_data_url(&bytes, name, limits)?, None));
+    }
+
+    let text = match String::from_utf8(bytes) {
+        Ok(text) if !text.contains('\0') => text,
+        _ => return Err(AttachmentError::UnsupportedFile(name.to_string())),
+    };
+    if text.len() > MAX_TEXT_ATTACHMENT {
+        return Err(AttachmentError::TooLarge(name.to_string()));
+    }
+    Ok(json!({
+        "type": "text",
+        "text": format!("File: {}\n```{}\n{}\n```", name, extension, text.trim_end())
+    }))
+}
+
+fn file_name(name: &str) -> String {
+    Path::new(name)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(name)
+        .to_string()
+}
+
+fn read_workspace_file(workspace: Option<&Path>, path: &str) -> Result<Vec<u8>, AttachmentError> {
+    let workspace = workspace.ok_or(AttachmentError::NoWorkspace)?;
+    let full_path = resolve_within(workspace, path)?;
+    let metadata = std::fs::metadata(&full_path).map_err(|_| FileError::NotFound(path.to_string()))?;
+    if metadata.is_dir() {
+        return Err(FileError::IsDirectory(path.to_string()).into());
+    }
+    if metadata.len() > MAX_ATTACHMENT_SIZE {
+        return Err(FileError::TooLarge(path.to_string(), MAX_ATTACHMENT_SIZE as usize).into());
+    }
+    Ok(std::fs::read(&full_path).map_err(FileError::from)?)
+}
+
+fn decode_data_url(url: &str) -> Result<(String, Vec<u8>), AttachmentError> {
+    let (mime_type, data) = url
+        .strip_prefix("data:")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .ok_or_else(|| AttachmentError::Invalid("expected a base64 data URL".to_string()))?;
+    let bytes = STANDARD
+        .decode(data.trim())
+        .map_err(|e| AttachmentError::Invalid(format!("bad base64 data: {}", e)))?;
+    Ok((mime_type.to_string(), bytes))
+}
+
+fn data_url(mime_type: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime_type, STANDARD.encode(bytes))
+}
+
+/// Encode an image as a data URL within `limits`. Images already small enough in a format
+/// every provider reads are sent unchanged; others are downscaled and re-encoded as JPEG,
+/// or PNG when they have transparency.
+pub fn image_data_url(bytes: &[u8], name: &str, limits: ImageLimits) -> Result<String, AttachmentError> {
+    let unsupported = |e: image::ImageError| AttachmentError::UnsupportedImage(name.to_string(), e.to_string());
+    let format = image::guess_format(bytes).map_err(unsupported)?;
+    let image = image::load_from_memory_with_format(bytes, format).map_err(unsupported)?;
+
+    let (width, height) = image.dimensions();
+    let readable = matches!(format, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif | ImageFormat::WebP);
+    if readable && width.max(height) <= limits.max_edge && bytes.len() <= limits.max_bytes {
+        return Ok(data_url(format.to_mime_type(), bytes));
+    }
+
+    let mut image = if width.max(height) > limits.max_edge {
+        image.resize(limits.max_edge, limits.max_edge, FilterType::Triangle)
+    } else {
+        image
+    };
+    loop {
+        let (mime_type, encoded) = encode_image(&image).map_err(unsupported)?;
+        if encoded.len() <= limits.max_bytes {
+            return Ok(data_url(mime_type, &encoded));
+        }
+        // Shrink by a quarter until the encoding fits
+        let (width, height) = image.dimensions();
+        if width.max(height) <= MIN_IMAGE_EDGE {
+            return Err(AttachmentError::TooLarge(name.to_string()));
+        }
+        image = image.resize(width * 3 / 4, height * 3 / 4, FilterType::Triangle);
+    }
+}
+
+fn encode_image(image: &DynamicImage) -> Result<(&'static str, Vec<u8>), image::ImageError> {
+    let mut out = Vec::new();
+    if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut out), ImageFormat::Png)?;
+        Ok(("image/png", out))
+    } else {
+        JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY).encode_image(&image.to_rgb8())?;
+        Ok(("image/jpeg", out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_server::{Reply, TestServer};
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    const LIMITS: ImageLimits = ImageLimits { max_edge: 512, max_bytes: 1_000_000, remote_urls: true };
+
+    /// A PNG of noise, which compresses badly
+    fn noisy_png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            let n = (x.wrapping_mul(7_919) ^ y.wrapping_mul(104_729)).wrapping_mul(2_654_435_761);
+            Rgb([n as u8, (n >> 8) as u8, (n >> 16) as u8])
+        });
+        png(DynamicImage::ImageRgb8(image))
+    }
+
+    fn png(image: DynamicImage) -> Vec<u8> {
+        let mut out = Vec::new();
+        image.write_to(&mut Cursor::new(&mut out), ImageFormat::Png).unwrap();
+        out
+    }
+
+    fn decode(url: &str) -> (String, DynamicImage) {
+        let (mime_type, bytes) = decode_data_url(url).unwrap();
+        (mime_type, image::load_from_memory(&bytes).unwrap())
+    }
+
+    fn workspace() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("attachment-test-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn small_images_are_sent_unchanged() {
+        let bytes = noisy_png(64, 32);
+        assert_eq!(image_data_url(&bytes, "a.png", LIMITS).unwrap(), data_url("image/png", &bytes));
+    }
+
+    #[test]
+    fn large_images_are_downscaled_and_reencoded() {
+        let (mime_type, image) = decode(&image_data_url(&noisy_png(1024, 256), "wide.png", LIMITS).unwrap());
+        assert_eq!(mime_type, "image/jpeg");
+        assert_eq!(image.dimensions(), (512, 128));
+
+        // Transparency survives as PNG
+        let clear = DynamicImage::ImageRgba8(RgbaImage::from_pixel(800, 800, Rgba([0, 0, 0, 0])));
+        let (mime_type, image) = decode(&image_data_url(&png(clear), "clear.png", LIMITS).unwrap());
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(image.dimensions(), (512, 512));
+    }
+
+    #[test]
+    fn images_shrink_until_they_fit_the_byte_limit() {
+        let limits = ImageLimits { max_edge: 2048, max_bytes: 60_000, remote_urls: true };
+        let url = image_data_url(&noisy_png(1024, 1024), "noise.png", limits).unwrap();
+        let (_, bytes) = decode_data_url(&url).unwrap();
+        assert!(bytes.len() <= limits.max_bytes, "{}", bytes.len());
+        let (_, image) = decode(&url);
+        assert!(image.width() < 1024 && image.width() >= MIN_IMAGE_EDGE * 3 / 4, "{}", image.width());
+
+        let tiny = ImageLimits { max_edge: 2048, max_bytes: 100, remote_urls: true };
+        assert!(matches!(image_data_url(&noisy_png(600, 600), "noise.png", tiny), Err(AttachmentError::TooLarge(_))));
+        assert!(matches!(image_data_url(b"not an image", "x.png", LIMITS), Err(AttachmentError::UnsupportedImage(..))));
+    }
+
+    #[test]
+    fn parts_are_mapped_to_openai_content_parts() {
+        let dir = workspace();
+        std::fs::write(dir.join("notes.md"), "# Notes\n").unwrap();
+        std::fs::write(dir.join("pic.png"), noisy_png(16, 16)).unwrap();
+        let pdf = data_url("application/pdf", b"%PDF-1.4 ...");
+        let mut messages = vec![json!({
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "look" },
+                { "type": "file", "path": "notes.md" },
+                { "type": "image", "path": "pic.png", "detail": "low" },
+                { "type": "file", "data": pdf, "name": "docs/spec.pdf" },
+                { "type": "image_url", "image_url": "https://example.com/a.png" },
+                { "type": "input_audio", "input_audio": {} }
+            ]
+        })];
+        resolve_messages(&mut messages, Some(&dir), LIMITS).unwrap();
+
+        let parts = messages[0]["content"].as_array().unwrap();
+        assert_eq!(parts[0], json!({ "type": "text", "text": "look" }));
+        assert_eq!(parts[1], json!({ "type": "text", "text": "File: notes.md\n```md\n# Notes\n```" }));
+        assert_eq!(parts[2]["image_url"]["detail"], "low");
+        assert!(parts[2]["image_url"]["url"].as_str().unwrap().starts_with("data:image/png;base64,"));
+        assert_eq!(parts[3], json!({ "type": "file", "file": { "filename": "spec.pdf", "file_data": pdf } }));
+        assert_eq!(parts[4], json!({ "type": "image_url", "image_url": { "url": "https://example.com/a.png" } }));
+        assert_eq!(parts[5]["type"], "input_audio");
+
+        let binary = json!({ "type": "file", "data": data_url("application/octet-stream", &[0, 1, 2]), "name": "a.bin" });
+        let mut binary = vec![json!({ "role": "user", "content": [binary] })];
+        assert!(matches!(resolve_messages(&mut binary, Some(&dir), LIMITS), Err(AttachmentError::UnsupportedFile(_))));
+        let mut by_path = vec![json!({ "role": "user", "content": [{ "type": "file", "path": "notes.md" }] })];
+        assert!(matches!(resolve_messages(&mut by_path, None, LIMITS), Err(AttachmentError::NoWorkspace)));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn remote_images_are_downloaded_as_data_urls() {
+        let image = noisy_png(8, 8);
+        let body = image.clone();
+        let server = TestServer::start(move |req| match req.path.as_str() {
+            "/a.png" => Reply::bytes(200, "image/png", body.clone()),
+            "/page" => Reply::text(200, "<html></html>"),
+            _ => Reply::text(404, "not found"),
+        })
+        .await;
+        let http = reqwest::Client::new();
+
+        let mut messages = vec![json!({
+            "role": "user",
+            "content": [
+                { "type": "image", "url": format!("{}/a.png", server.url) },
+                { "type": "image_url", "image_url": { "url": format!("{}/a.png", server.url), "detail": "high" } },
+                { "type": "image_url", "image_url": "data:image/png;base64,AAAA" }
+            ]
+        })];
+        inline_remote_images(&mut messages, &http).await.unwrap();
+        let expected = data_url("image/png", &image);
+        assert_eq!(messages[0]["content"][0]["url"], expected);
+        assert_eq!(messages[0]["content"][1]["image_url"], json!({ "url": expected, "detail": "high" }));
+        assert_eq!(messages[0]["content"][2]["image_url"], "data:image/png;base64,AAAA");
+        assert_eq!(server.requests().len(), 2);
+
+        let part = |path: &str| {
+            let url = format!("{}{}", server.url, path);
+            vec![json!({ "role": "user", "content": [{ "type": "image", "url": url }] })]
+        };
+        assert!(matches!(inline_remote_images(&mut part("/missing.png"), &http).await, Err(AttachmentError::Download(..))));
+        assert!(matches!(inline_remote_images(&mut part("/page"), &http).await, Err(AttachmentError::UnsupportedImage(..))));
+    }
+}
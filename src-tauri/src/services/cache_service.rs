@@ -0,0 +1,331 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// What a cache entry holds
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheKind {
+    Embedding,
+    Completion,
+}
+
+impl CacheKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheKind::Embedding => "embedding",
+            CacheKind::Completion => "completion",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    pub embedding_ttl_days: u32,
+    pub completion_ttl_hours: u32,
+    /// Least recently used entries are evicted beyond this size
+    pub max_size_mb: u32,
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            embedding_ttl_days: 30,
+            completion_ttl_hours: 24,
+            max_size_mb: 256,
+        }
+    }
+}
+
+impl CacheSettings {
+    fn ttl_secs(&self, kind: CacheKind) -> i64 {
+        match kind {
+            CacheKind::Embedding => self.embedding_ttl_days as i64 * 86_400,
+            CacheKind::Completion => self.completion_ttl_hours as i64 * 3_600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindStats {
+    pub entries: u64,
+    pub size_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub embedding: KindStats,
+    pub completion: KindStats,
+    pub settings: CacheSettings,
+}
+
+/// Content address for a cached response: a hash of everything that shapes it
+pub fn cache_key(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn now_secs() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+/// Cache for deterministic LLM responses, stored in the app config dir as `cache.db` and
+/// shared by every window's `LlmClient`.
+#[derive(Clone)]
+pub struct ResponseCache {
+    db_path: PathBuf,
+}
+
+impl ResponseCache {
+    pub fn open() -> Result<Self, String> {
+        let dir = dirs::config_dir()
+            .ok_or("No config directory")?
+            .join("SentinelOps");
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+        let cache = Self {
+            db_path: dir.join("cache.db"),
+        };
+        cache.init_database()?;
+        Ok(cache)
+    }
+
+    fn get_connection(&self) -> Result<Connection, String> {
+        Connection::open(&self.db_path)
+            .map_err(|e| format!("Failed to open database: {}", e))
+    }
+
+    fn init_database(&self) -> Result<(), String> {
+        let conn = self.get_connection()?;
+
+        conn.execute_batch(r#"
+            CREATE TABLE IF NOT EXISTS cache_entries (
+                key TEXT PRIMARY KEY,
+                kind TEXT NOT NULL CHECK(kind IN ('embedding', 'completion')),
+                model TEXT NOT NULL,
+                value BLOB NOT NULL,
+                size INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                last_used_at INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_cache_last_used ON cache_entries(last_used_at);
+            CREATE INDEX IF NOT EXISTS idx_cache_expires ON cache_entries(expires_at);
+
+            CREATE TABLE IF NOT EXISTS cache_counters (
+                kind TEXT PRIMARY KEY,
+                hits INTEGER NOT NULL DEFAULT 0,
+                misses INTEGER NOT NULL DEFAULT 0
+            );
+
+            -- Single-row table holding the settings as JSON
+            CREATE TABLE IF NOT EXISTS cache_settings (
+                id INTEGER PRIMARY KEY CHECK(id = 1),
+                settings TEXT NOT NULL
+            );
+        "#).map_err(|e| format!("Failed to create tables: {}", e))?;
+
+        Ok(())
+    }
+
+    fn read_settings(conn: &Connection) -> Result<CacheSettings, String> {
+        let json: Option<String> = conn
+            .query_row("SELECT settings FROM cache_settings WHERE id = 1", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to read cache settings: {}", e))?;
+        Ok(json
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn set_settings(&self, settings: &CacheSettings) -> Result<(), String> {
+        let conn = self.get_connection()?;
+        let json = serde_json::to_string(settings).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO cache_settings (id, settings) VALUES (1, ?1)
+             ON CONFLICT(id) DO UPDATE SET settings = ?1",
+            params![json],
+        ).map_err(|e| format!("Failed to save cache settings: {}", e))?;
+        Self::evict(&conn, settings)
+    }
+
+    pub fn get(&self, kind: CacheKind, key: &str) -> Option<Vec<u8>> {
+        self.get_many(kind, &[key.to_string()]).pop().flatten()
+    }
+
+    /// Look up several keys at once, counting hits and misses. Returns nothing when the cache
+    /// is disabled or unreadable, so callers simply fall through to the provider.
+    pub fn get_many(&self, kind: CacheKind, keys: &[String]) -> Vec<Option<Vec<u8>>> {
+        match self.try_get_many(kind, keys) {
+            Ok(values) => values,
+            Err(e) => {
+                tracing::warn!("Cache lookup failed: {}", e);
+                vec![None; keys.len()]
+            }
+        }
+    }
+
+    fn try_get_many(&self, kind: CacheKind, keys: &[String]) -> Result<Vec<Option<Vec<u8>>>, String> {
+        let conn = self.get_connection()?;
+        if !Self::read_settings(&conn)?.enabled {
+            return Ok(vec![None; keys.len()]);
+        }
+
+        let now = now_secs();
+        let mut select = conn
+            .prepare("SELECT value FROM cache_entries WHERE key = ?1 AND kind = ?2 AND expires_at > ?3")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let mut touch = conn
+            .prepare("UPDATE cache_entries SET last_used_at = ?2 WHERE key = ?1")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            let value: Option<Vec<u8>> = select
+                .query_row(params![key, kind.as_str(), now], |row| row.get(0))
+                .optional()
+                .map_err(|e| format!("Failed to read cache: {}", e))?;
+            if value.is_some() {
+                touch.execute(params![key, now]).map_err(|e| format!("Failed to update cache: {}", e))?;
+            }
+            values.push(value);
+        }
+
+        let hits = values.iter().filter(|v| v.is_some()).count() as i64;
+        conn.execute(
+            "INSERT INTO cache_counters (kind, hits, misses) VALUES (?1, ?2, ?3)
+             ON CONFLICT(kind) DO UPDATE SET hits = hits + ?2, misses = misses + ?3",
+            params![kind.as_str(), hits, keys.len() as i64 - hits],
+        ).map_err(|e| format!("Failed to update cache counters: {}", e))?;
+
+        Ok(values)
+    }
+
+    pub fn put(&self, kind: CacheKind, key: &str, model: &str, value: &[u8]) {
+        self.put_many(kind, model, &[(key.to_string(), value.to_vec())]);
+    }
+
+    /// Store entries, then evict expired and least recently used ones beyond the size limit.
+    /// Failures are logged: a cache that cannot be written only costs a repeated request.
+    pub fn put_many(&self, kind: CacheKind, model: &str, entries: &[(String, Vec<u8>)]) {
+        if let Err(e) = self.try_put_many(kind, model, entries) {
+            tracing::warn!("Cache write failed: {}", e);
+        }
+    }
+
+    fn try_put_many(&self, kind: CacheKind, model: &str, entries: &[(String, Vec<u8>)]) -> Result<(), String> {
+        let mut conn = self.get_connection()?;
+        let settings = Self::read_settings(&conn)?;
+        if !settings.enabled || entries.is_empty() {
+            return Ok(());
+        }
+
+        let now = now_secs();
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        {
+            let mut insert = tx
+                .prepare(
+                    "INSERT OR REPLACE INTO cache_entries
+                     (key, kind, model, value, size, created_at, expires_at, last_used_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?6)",
+                )
+                .map_err(|e| format!("Failed to prepare insert: {}", e))?;
+            for (key, value) in entries {
+                insert
+                    .execute(params![
+                        key,
+                        kind.as_str(),
+                        model,
+                        value,
+                        value.len() as i64,
+                        now,
+                        now + settings.ttl_secs(kind),
+                    ])
+                    .map_err(|e| format!("Failed to write cache: {}", e))?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Self::evict(&conn, &settings)
+    }
+
+    fn evict(conn: &Connection, settings: &CacheSettings) -> Result<(), String> {
+        let max_bytes = settings.max_size_mb as i64 * 1024 * 1024;
+        conn.execute("DELETE FROM cache_entries WHERE expires_at <= ?1", params![now_secs()])
+            .map_err(|e| format!("Failed to evict cache entries: {}", e))?;
+        conn.execute(
+            r#"DELETE FROM cache_entries WHERE key IN (
+                   SELECT key FROM (
+                       SELECT key, SUM(size) OVER (ORDER BY last_used_at DESC, created_at DESC) AS running
+                       FROM cache_entries
+                   ) WHERE running > ?1
+               )"#,
+            params![max_bytes],
+        ).map_err(|e| format!("Failed to evict cache entries: {}", e))?;
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Result<CacheStats, String> {
+        let conn = self.get_connection()?;
+        let kind_stats = |kind: CacheKind| -> Result<KindStats, String> {
+            let (entries, size_bytes): (i64, i64) = conn
+                .query_row(
+                    "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM cache_entries WHERE kind = ?1 AND expires_at > ?2",
+                    params![kind.as_str(), now_secs()],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|e| format!("Failed to read cache stats: {}", e))?;
+            let (hits, misses): (i64, i64) = conn
+                .query_row(
+                    "SELECT hits, misses FROM cache_counters WHERE kind = ?1",
+                    params![kind.as_str()],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(|e| format!("Failed to read cache stats: {}", e))?
+                .unwrap_or((0, 0));
+            let lookups = hits + misses;
+            Ok(KindStats {
+                entries: entries as u64,
+                size_bytes: size_bytes as u64,
+                hits: hits as u64,
+                misses: misses as u64,
+                hit_rate: if lookups > 0 { hits as f64 / lookups as f64 } else { 0.0 },
+            })
+        };
+
+        Ok(CacheStats {
+            embedding: kind_stats(CacheKind::Embedding)?,
+            completion: kind_stats(CacheKind::Completion)?,
+            settings: Self::read_settings(&conn)?,
+        })
+    }
+
+    /// Remove entries of one kind, or all of them, and reset the hit counters.
+    /// Returns the number of entries removed.
+    pub fn clear(&self, kind: Option<CacheKind>) -> Result<usize, String> {
+        let conn = self.get_connection()?;
+        let kind = kind.map(|k| k.as_str());
+        let removed = conn
+            .execute("DELETE FROM cache_entries WHERE ?1 IS NULL OR kind = ?1", params![kind])
+            .map_err(|e| format!("Failed to clear cache: {}", e))?;
+        conn.execute("DELETE FROM cache_counters WHERE ?1 IS NULL OR kind = ?1", params![kind])
+            .map_err(|e| format!("Failed to clear cache: {}", e))?;
+        Ok(removed)
+    }
+}
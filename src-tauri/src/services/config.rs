@@ -1,8 +1,16 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use std::fs;
+use std::sync::Mutex;
 use tauri::AppHandle;
 
+use super::llm_client::LlmClient;
+use super::llm_executor::RetryPolicy;
+use super::provider_service::{LlmTarget, ProviderKind, ProviderTimeouts, ProvidersConfig};
+use super::secrets_service::{self, SecretsError};
+use super::workspace_service::Workspace;
+
 /// User-saved LLM config. Stored in app config dir.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmUserConfig {
@@ -18,23 +26,13 @@ pub struct LlmUserConfig {
     pub active_provider: Option<String>,
 }
 
-/// API keys for different AI providers. Stored separately for security.
+/// API keys by name, stored encrypted separately from other settings. Provider profiles
+/// refer to a key through `keyRef`; the built-in names (openrouter, openai, ...) keep the
+/// same JSON shape as the old fixed-field file.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ApiKeysConfig {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub openrouter: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub openai: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub anthropic: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub google: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub groq: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub together: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub deepseek: Option<String>,
+    #[serde(flatten)]
+    keys: BTreeMap<String, String>,
 }
 
 fn llm_config_path() -> Option<PathBuf> {
@@ -45,83 +43,85 @@ fn api_keys_path() -> Option<PathBuf> {
     dirs::config_dir().map(|d| d.join("SentinelOps").join("api_keys.json"))
 }
 
+/// Keys as last loaded or saved, so requests do not decrypt the file each time.
+/// Filled once the file can be read, i.e. after unlocking when a passphrase is used.
+static DECRYPTED_KEYS: Mutex<Option<ApiKeysConfig>> = Mutex::new(None);
+
 impl ApiKeysConfig {
+    /// Load saved keys, or no keys when the file is missing or still locked
     pub fn load() -> Self {
-        if let Some(path) = api_keys_path() {
-            if path.exists() {
-                if let Ok(json) = fs::read_to_string(&path) {
-                    if let Ok(config) = serde_json::from_str(&json) {
-                        return config;
-                    }
-                }
+        Self::load_checked().unwrap_or_else(|e| {
+            tracing::warn!("Could not load API keys: {}", e);
+            Self::default()
+        })
+    }
+
+    /// Load and decrypt saved keys. Legacy plaintext files are re-saved encrypted
+    /// as soon as an encryption key is available.
+    pub fn load_checked() -> Result<Self, SecretsError> {
+        if let Some(keys) = DECRYPTED_KEYS.lock().unwrap().as_ref() {
+            return Ok(keys.clone());
+        }
+        let Some(path) = api_keys_path() else {
+            return Ok(Self::default());
+        };
+        let Some(contents) = secrets_service::read(&path)? else {
+            return Ok(Self::default());
+        };
+
+        let keys: Self = serde_json::from_slice(&contents.plaintext)?;
+        if contents.needs_migration {
+            match secrets_service::write(&path, &contents.plaintext) {
+                Ok(()) => tracing::info!("Encrypted plaintext API keys file"),
+                Err(e) => tracing::warn!("API keys are still stored in plaintext: {}", e),
             }
         }
-        Self::default()
+        *DECRYPTED_KEYS.lock().unwrap() = Some(keys.clone());
+        Ok(keys)
     }
 
     pub fn save(&self) -> Result<(), String> {
         if let Some(path) = api_keys_path() {
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-            }
-            let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
-            fs::write(&path, json).map_err(|e| e.to_string())?;
+            let json = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+            secrets_service::write(&path, &json).map_err(|e| e.to_string())?;
         }
+        *DECRYPTED_KEYS.lock().unwrap() = Some(self.clone());
         Ok(())
     }
 
-    pub fn get_key(&self, provider: &str) -> Option<String> {
-        match provider.to_lowercase().as_str() {
-            "openrouter" => self.openrouter.clone(),
-            "openai" => self.openai.clone(),
-            "anthropic" => self.anthropic.clone(),
-            "google" => self.google.clone(),
-            "groq" => self.groq.clone(),
-            "together" => self.together.clone(),
-            "deepseek" => self.deepseek.clone(),
-            _ => None,
-        }
+    pub fn get_key(&self, name: &str) -> Option<String> {
+        self.keys.get(name.trim()).cloned()
     }
 
-    pub fn set_key(&mut self, provider: &str, key: Option<String>) {
-        match provider.to_lowercase().as_str() {
-            "openrouter" => self.openrouter = key,
-            "openai" => self.openai = key,
-            "anthropic" => self.anthropic = key,
-            "google" => self.google = key,
-            "groq" => self.groq = key,
-            "together" => self.together = key,
-            "deepseek" => self.deepseek = key,
-            _ => {}
-        }
+    pub fn set_key(&mut self, name: &str, key: Option<String>) {
+        let name = name.trim().to_string();
+        match key {
+            Some(key) => self.keys.insert(name, key),
+            None => self.keys.remove(&name),
+        };
     }
 
     pub fn has_any_key(&self) -> bool {
-        self.openrouter.is_some() ||
-        self.openai.is_some() ||
-        self.anthropic.is_some() ||
-        self.google.is_some() ||
-        self.groq.is_some() ||
-        self.together.is_some() ||
-        self.deepseek.is_some()
+        !self.keys.is_empty()
     }
 
+    /// Names of all saved keys
     pub fn configured_providers(&self) -> Vec<String> {
-        let mut providers = Vec::new();
-        if self.openrouter.is_some() { providers.push("openrouter".to_string()); }
-        if self.openai.is_some() { providers.push("openai".to_string()); }
-        if self.anthropic.is_some() { providers.push("anthropic".to_string()); }
-        if self.google.is_some() { providers.push("google".to_string()); }
-        if self.groq.is_some() { providers.push("groq".to_string()); }
-        if self.together.is_some() { providers.push("together".to_string()); }
-        if self.deepseek.is_some() { providers.push("deepseek".to_string()); }
-        providers
+        self.keys.keys().cloned().collect()
+    }
+
+    /// Masked form of every configured key, safe to send to the webview
+    pub fn masked_keys(&self) -> HashMap<String, String> {
+        self.keys
+            .iter()
+            .map(|(name, key)| (name.clone(), secrets_service::mask_secret(key)))
+            .collect()
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    pub workspace_root: PathBuf,
+    pub workspace: Workspace,
     pub llm_base_url: String,
     pub llm_api_key: Option<String>,
     pub llm_proxy_url: Option<String>,
@@ -130,13 +130,17 @@ pub struct Config {
     pub brave_api_key: Option<String>,
     pub max_read_size: usize,
     pub max_search_results: usize,
+    /// Named provider profiles. When one is active it replaces the proxy/local/env settings above.
+    pub providers: ProvidersConfig,
 }
 
 impl Config {
     pub fn new(_app_handle: &AppHandle) -> Self {
         // Default workspace to user's Desktop
-        let workspace_root = dirs::desktop_dir()
-            .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| PathBuf::from(".")));
+        let workspace = Workspace::single(
+            dirs::desktop_dir()
+                .unwrap_or_else(|| dirs::home_dir().unwrap_or_else(|| PathBuf::from("."))),
+        );
 
         let brave_api_key = std::env::var("BRAVE_API_KEY").ok();
 
@@ -150,7 +154,8 @@ impl Config {
                             let base = proxy.trim().trim_end_matches('/').to_string();
                             if !base.is_empty() {
                                 return Self {
-                                    workspace_root,
+                                    workspace,
+                                    providers: ProvidersConfig::load_or_migrate(&base),
                                     llm_base_url: base.clone(),
                                     llm_api_key: None,
                                     llm_proxy_url: Some(base),
@@ -171,9 +176,10 @@ impl Config {
                                     .map(|m| m.trim().to_string())
                                     .unwrap_or_else(|| "llama3.2".to_string());
                                 let model = if model.is_empty() { "llama3.2".to_string() } else { model };
-                                let provider = detect_provider(&base);
+                                let provider = legacy_provider_label(&base);
                                 return Self {
-                                    workspace_root,
+                                    workspace,
+                                    providers: ProvidersConfig::load_or_migrate(&base),
                                     llm_base_url: base,
                                     llm_api_key: None,
                                     llm_proxy_url: None,
@@ -209,7 +215,7 @@ impl Config {
             let llm_base_url = std::env::var("LLM_BASE_URL")
                 .unwrap_or_else(|_| "https://openrouter.ai/api/v1".to_string());
             let llm_api_key = std::env::var("LLM_API_KEY").ok();
-            let llm_provider = detect_provider(&llm_base_url);
+            let llm_provider = legacy_provider_label(&llm_base_url);
             (llm_base_url, llm_api_key, llm_provider)
         };
 
@@ -220,9 +226,10 @@ impl Config {
                 "meta-llama/llama-3.1-8b-instruct".to_string()
             }
         });
+        let providers = ProvidersConfig::load_or_migrate(&llm_base_url);
 
         Self {
-            workspace_root,
+            workspace,
             llm_base_url,
             llm_api_key,
             llm_proxy_url,
@@ -231,6 +238,7 @@ impl Config {
             brave_api_key,
             max_read_size: 2 * 1024 * 1024,
             max_search_results: 200,
+            providers,
         }
     }
 
@@ -249,7 +257,8 @@ impl Config {
         self.llm_model = model.clone();
         self.llm_api_key = None;
         self.llm_proxy_url = None;
-        self.llm_provider = detect_provider(&base);
+        self.llm_provider = legacy_provider_label(&base);
+        self.deactivate_profile()?;
 
         if let Some(path) = llm_config_path() {
             if let Some(parent) = path.parent() {
@@ -281,6 +290,7 @@ impl Config {
         self.llm_provider = "Proxy".to_string();
         self.llm_model = std::env::var("LLM_MODEL")
             .unwrap_or_else(|_| "meta-llama/llama-3.1-8b-instruct".to_string());
+        self.deactivate_profile()?;
 
         if let Some(path) = llm_config_path() {
             if let Some(parent) = path.parent() {
@@ -299,8 +309,9 @@ impl Config {
         Ok(())
     }
 
+    /// Set the single-root workspace that new windows open with
     pub fn set_workspace(&mut self, path: PathBuf) {
-        self.workspace_root = path;
+        self.workspace = Workspace::single(path);
     }
 
     /// Clear user-saved local config and reload from env (LLM_PROXY_URL, LLM_API_KEY, LLM_BASE_URL, LLM_MODEL)
@@ -311,9 +322,62 @@ impl Config {
             }
         }
         self.apply_env_llm_config();
+        self.deactivate_profile()
+    }
+
+    /// Stop using the active provider profile so the proxy/local/env settings apply
+    fn deactivate_profile(&mut self) -> Result<(), String> {
+        if self.providers.active.take().is_some() {
+            self.providers.save()?;
+        }
         Ok(())
     }
 
+    /// Client for `llm_target()`. It owns its settings, so the config lock can be released before sending.
+    pub fn llm_client(&self) -> LlmClient {
+        LlmClient::new(self.llm_target())
+    }
+
+    /// Resolve where LLM requests go: the active profile, otherwise the proxy/local/env settings
+    pub fn llm_target(&self) -> LlmTarget {
+        if let Some(profile) = self.providers.active_profile() {
+            let api_key = profile
+                .key_ref
+                .as_deref()
+                .and_then(|r| ApiKeysConfig::load().get_key(r));
+            return LlmTarget {
+                profile_id: Some(profile.id.clone()),
+                provider: profile.name.clone(),
+                kind: profile.kind,
+                base_url: profile.base_url.clone(),
+                api_key,
+                model: profile
+                    .default_model
+                    .clone()
+                    .unwrap_or_else(|| self.llm_model.clone()),
+                headers: profile.headers.clone(),
+                timeouts: profile.timeouts.clone(),
+                retry: profile.retry.clone(),
+                fallback_models: profile.fallback_models.clone(),
+                keyless: profile.key_ref.is_none(),
+            };
+        }
+
+        LlmTarget {
+            profile_id: None,
+            provider: self.llm_provider.clone(),
+            kind: ProviderKind::Openai,
+            base_url: self.llm_base_url.clone(),
+            api_key: self.llm_api_key.clone(),
+            model: self.llm_model.clone(),
+            headers: BTreeMap::new(),
+            timeouts: ProviderTimeouts::default(),
+            retry: RetryPolicy::default(),
+            fallback_models: Vec::new(),
+            keyless: self.llm_proxy_url.is_some(),
+        }
+    }
+
     fn apply_env_llm_config(&mut self) {
         const DEFAULT_PROXY_URL: &str = "https://sentinelops.onrender.com";
         let explicit_proxy = std::env::var("LLM_PROXY_URL").ok().filter(|s| !s.is_empty());
@@ -333,7 +397,7 @@ impl Config {
             let llm_base_url = std::env::var("LLM_BASE_URL")
                 .unwrap_or_else(|_| "https://openrouter.ai/api/v1".to_string());
             let llm_api_key = std::env::var("LLM_API_KEY").ok();
-            let llm_provider = detect_provider(&llm_base_url);
+            let llm_provider = legacy_provider_label(&llm_base_url);
             (llm_base_url, llm_api_key, llm_provider)
         };
 
@@ -353,21 +417,9 @@ impl Config {
     }
 }
 
-fn detect_provider(base_url: &str) -> String {
-    let url = base_url.to_lowercase();
-    if url.contains("openai.com") {
-        "OpenAI".to_string()
-    } else if url.contains("openrouter") {
-        "OpenRouter".to_string()
-    } else if url.contains("together") {
-        "Together AI".to_string()
-    } else if url.contains("groq") {
-        "Groq".to_string()
-    } else if url.contains("deepinfra") {
-        "DeepInfra".to_string()
-    } else if url.contains("anthropic") {
-        "Anthropic".to_string()
-    } else if url.contains("localhost") || url.contains("127.0.0.1") {
+/// Label for settings that do not come from a provider profile
+fn legacy_provider_label(base_url: &str) -> String {
+    if is_local_llm_base_url(base_url) {
         "Local (Ollama/LM Studio)".to_string()
     } else {
         "Custom".to_string()
@@ -0,0 +1,751 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use chrono::Utc;
+
+use super::llm_adapter::TokenUsage;
+
+/// Characters of the first user message used as a conversation's title
+const TITLE_LENGTH: usize = 80;
+const PREVIEW_LENGTH: usize = 200;
+
+/// A stored conversation. Its messages form a tree: editing an earlier message starts a
+/// new branch beside it, and `active_leaf_id` is the last message of the branch shown.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    pub id: String,
+    pub workspace_id: String,
+    pub title: Option<String>,
+    pub active_leaf_id: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub metadata: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSummary {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    /// Messages in every branch
+    pub message_count: i64,
+    /// Start of the active branch's last message
+    pub preview: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredMessage {
+    pub id: String,
+    pub conversation_id: String,
+    /// Previous message in the branch; `None` for the first message
+    pub parent_id: Option<String>,
+    pub role: String,
+    /// Text, or content parts as sent to the model
+    pub content: Value,
+    pub tool_calls: Option<Value>,
+    pub tool_call_id: Option<String>,
+    /// Model that wrote an assistant message
+    pub model: Option<String>,
+    pub usage: Option<TokenUsage>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadMessage {
+    #[serde(flatten)]
+    pub message: StoredMessage,
+    /// Messages sharing this one's parent, oldest first and including this one.
+    /// More than one means the message was edited into separate branches.
+    pub sibling_ids: Vec<String>,
+}
+
+/// A conversation with the messages of its active branch, in order
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationThread {
+    pub conversation: Conversation,
+    pub messages: Vec<ThreadMessage>,
+}
+
+/// Input for adding a message
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewMessage {
+    pub role: String,
+    pub content: Value,
+    pub tool_calls: Option<Value>,
+    pub tool_call_id: Option<String>,
+    pub model: Option<String>,
+    pub usage: Option<TokenUsage>,
+    /// Message to continue from; the end of the active branch when unset
+    pub parent_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSearchHit {
+    pub conversation_id: String,
+    pub conversation_title: Option<String>,
+    pub message_id: String,
+    pub role: String,
+    /// Matching text with the terms wrapped in `[` `]`
+    pub snippet: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Markdown,
+}
+
+/// Conversations of a workspace, stored next to its memories in `.sentinelops/memory.db`.
+#[derive(Clone)]
+pub struct ConversationStore {
+    db_path: PathBuf,
+    workspace_id: String,
+}
+
+impl ConversationStore {
+    pub(crate) fn new(db_path: PathBuf, workspace_id: String) -> Self {
+        Self { db_path, workspace_id }
+    }
+
+    /// Create the conversation tables in a memory database
+    pub(crate) fn init_database(conn: &Connection) -> Result<(), String> {
+        conn.execute_batch(r#"
+            CREATE TABLE IF NOT EXISTS conversations (
+                id TEXT PRIMARY KEY,
+                workspace_id TEXT NOT NULL,
+                title TEXT,
+                active_leaf_id TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                metadata TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS conversation_messages (
+                id TEXT PRIMARY KEY,
+                conversation_id TEXT NOT NULL,
+                parent_id TEXT,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                -- Plain text of the content, for search and previews
+                text TEXT NOT NULL,
+                tool_calls TEXT,
+                tool_call_id TEXT,
+                model TEXT,
+                prompt_tokens INTEGER,
+                completion_tokens INTEGER,
+                reasoning_tokens INTEGER,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_conversations_workspace ON conversations(workspace_id, updated_at DESC);
+            CREATE INDEX IF NOT EXISTS idx_conversation_messages_conversation ON conversation_messages(conversation_id, created_at);
+            CREATE INDEX IF NOT EXISTS idx_conversation_messages_parent ON conversation_messages(parent_id);
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS conversation_messages_fts USING fts5(
+                text,
+                content=conversation_messages,
+                content_rowid=rowid
+            );
+        "#).map_err(|e| format!("Failed to create conversation tables: {}", e))?;
+
+        let trigger_exists: bool = conn
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type='trigger' AND name='conversation_messages_ai'",
+                [],
+                |_| Ok(true),
+            )
+            .unwrap_or(false);
+
+        // Messages are never updated, only added and deleted
+        if !trigger_exists {
+            conn.execute_batch(r#"
+                CREATE TRIGGER conversation_messages_ai AFTER INSERT ON conversation_messages BEGIN
+                    INSERT INTO conversation_messages_fts(rowid, text) VALUES (NEW.rowid, NEW.text);
+                END;
+
+                CREATE TRIGGER conversation_messages_ad AFTER DELETE ON conversation_messages BEGIN
+                    INSERT INTO conversation_messages_fts(conversation_messages_fts, rowid, text)
+                    VALUES ('delete', OLD.rowid, OLD.text);
+                END;
+            "#).map_err(|e| format!("Failed to create triggers: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn get_connection(&self) -> Result<Connection, String> {
+        Connection::open(&self.db_path)
+            .map_err(|e| format!("Failed to open database: {}", e))
+    }
+
+    pub fn create_conversation(&self, title: Option<String>, metadata: Option<Value>) -> Result<Conversation, String> {
+        let conn = self.get_connection()?;
+        let id = uuid::Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        let metadata_json = metadata.as_ref().map(|m| m.to_string());
+
+        conn.execute(
+            r#"INSERT INTO conversations (id, workspace_id, title, created_at, updated_at, metadata)
+               VALUES (?1, ?2, ?3, ?4, ?4, ?5)"#,
+            params![&id, &self.workspace_id, &title, &now, &metadata_json],
+        ).map_err(|e| format!("Failed to create conversation: {}", e))?;
+
+        self.conversation(&conn, &id)?
+            .ok_or_else(|| "Conversation not found after creation".to_string())
+    }
+
+    /// Conversations, most recently updated first, with the total count
+    pub fn list_conversations(&self, limit: usize, offset: usize) -> Result<(Vec<ConversationSummary>, usize), String> {
+        let conn = self.get_connection()?;
+
+        let total: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM conversations WHERE workspace_id = ?1",
+                params![&self.workspace_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count conversations: {}", e))?;
+
+        let mut stmt = conn.prepare(r#"
+            SELECT c.id, c.workspace_id, c.title, c.active_leaf_id, c.created_at, c.updated_at, c.metadata,
+                (SELECT COUNT(*) FROM conversation_messages m WHERE m.conversation_id = c.id),
+                (SELECT m.text FROM conversation_messages m WHERE m.id = c.active_leaf_id)
+            FROM conversations c
+            WHERE c.workspace_id = ?1
+            ORDER BY c.updated_at DESC
+            LIMIT ?2 OFFSET ?3
+        "#).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let conversations = stmt
+            .query_map(params![&self.workspace_id, limit as i64, offset as i64], |row| {
+                Ok(ConversationSummary {
+                    conversation: row_to_conversation(row)?,
+                    message_count: row.get(7)?,
+                    preview: row.get::<_, Option<String>>(8)?.map(|text| truncate(&text, PREVIEW_LENGTH)),
+                })
+            })
+            .map_err(|e| format!("Failed to list conversations: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read conversations: {}", e))?;
+
+        Ok((conversations, total as usize))
+    }
+
+    pub fn rename_conversation(&self, id: &str, title: &str) -> Result<bool, String> {
+        let conn = self.get_connection()?;
+        let updated = conn.execute(
+            "UPDATE conversations SET title = ?1, updated_at = ?2 WHERE id = ?3 AND workspace_id = ?4",
+            params![title, Utc::now().to_rfc3339(), id, &self.workspace_id],
+        ).map_err(|e| format!("Failed to rename conversation: {}", e))?;
+        Ok(updated > 0)
+    }
+
+    /// Delete a conversation with the messages of every branch
+    pub fn delete_conversation(&self, id: &str) -> Result<bool, String> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let deleted = tx.execute(
+            "DELETE FROM conversations WHERE id = ?1 AND workspace_id = ?2",
+            params![id, &self.workspace_id],
+        ).map_err(|e| format!("Failed to delete conversation: {}", e))?;
+        if deleted > 0 {
+            tx.execute("DELETE FROM conversation_messages WHERE conversation_id = ?1", params![id])
+                .map_err(|e| format!("Failed to delete messages: {}", e))?;
+        }
+
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        Ok(deleted > 0)
+    }
+
+    /// Add a message after `input.parent_id`, or at the end of the active branch, and make
+    /// it the end of the active branch. An untitled conversation is named after its first
+    /// user message.
+    pub fn append_message(&self, conversation_id: &str, input: NewMessage) -> Result<StoredMessage, String> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let conversation = self.conversation(&tx, conversation_id)?
+            .ok_or_else(|| "Conversation not found".to_string())?;
+
+        let parent_id = match input.parent_id {
+            Some(parent_id) => {
+                let parent = message(&tx, &parent_id)?;
+                if parent.map(|p| p.conversation_id).as_deref() != Some(conversation_id) {
+                    return Err("Parent message is not part of this conversation".to_string());
+                }
+                Some(parent_id)
+            }
+            None => conversation.active_leaf_id,
+        };
+
+        let message = StoredMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            conversation_id: conversation_id.to_string(),
+            parent_id,
+            role: input.role,
+            content: input.content,
+            tool_calls: input.tool_calls,
+            tool_call_id: input.tool_call_id,
+            model: input.model,
+            usage: input.usage,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        insert_message(&tx, &message)?;
+
+        let title = match conversation.title {
+            None if message.role == "user" => Some(truncate(&content_text(&message.content), TITLE_LENGTH)),
+            title => title,
+        };
+        tx.execute(
+            "UPDATE conversations SET active_leaf_id = ?1, title = ?2, updated_at = ?3 WHERE id = ?4",
+            params![&message.id, &title, &message.created_at, conversation_id],
+        ).map_err(|e| format!("Failed to update conversation: {}", e))?;
+
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        Ok(message)
+    }
+
+    /// Edit a message by adding a sibling with the new content, starting a new branch.
+    /// The original and the messages after it stay in their own branch.
+    pub fn edit_message(&self, message_id: &str, content: Value) -> Result<StoredMessage, String> {
+        let mut conn = self.get_connection()?;
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let original = self.owned_message(&tx, message_id)?;
+
+        let message = StoredMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            conversation_id: original.conversation_id,
+            parent_id: original.parent_id,
+            role: original.role,
+            content,
+            tool_calls: None,
+            tool_call_id: None,
+            model: None,
+            usage: None,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        insert_message(&tx, &message)?;
+        tx.execute(
+            "UPDATE conversations SET active_leaf_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![&message.id, &message.created_at, &message.conversation_id],
+        ).map_err(|e| format!("Failed to update conversation: {}", e))?;
+
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        Ok(message)
+    }
+
+    /// Show the branch through `message_id`, following the newest reply at each step
+    pub fn switch_branch(&self, message_id: &str) -> Result<ConversationThread, String> {
+        let conn = self.get_connection()?;
+        let start = self.owned_message(&conn, message_id)?;
+
+        let mut leaf = start.id;
+        while let Some(child) = conn
+            .query_row(
+                "SELECT id FROM conversation_messages WHERE parent_id = ?1 ORDER BY created_at DESC, rowid DESC LIMIT 1",
+                params![&leaf],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read messages: {}", e))?
+        {
+            leaf = child;
+        }
+
+        conn.execute(
+            "UPDATE conversations SET active_leaf_id = ?1 WHERE id = ?2",
+            params![&leaf, &start.conversation_id],
+        ).map_err(|e| format!("Failed to update conversation: {}", e))?;
+
+        self.thread_with(&conn, &start.conversation_id)?
+            .ok_or_else(|| "Conversation not found".to_string())
+    }
+
+    /// The conversation with its active branch
+    pub fn thread(&self, conversation_id: &str) -> Result<Option<ConversationThread>, String> {
+        let conn = self.get_connection()?;
+        self.thread_with(&conn, conversation_id)
+    }
+
+    /// Messages matching every word of `query`, best match first
+    pub fn search_messages(&self, query: &str, limit: usize) -> Result<Vec<MessageSearchHit>, String> {
+        let Some(query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+        let conn = self.get_connection()?;
+
+        let mut stmt = conn.prepare(r#"
+            SELECT m.conversation_id, c.title, m.id, m.role,
+                snippet(conversation_messages_fts, 0, '[', ']', '…', 16), m.created_at
+            FROM conversation_messages_fts
+            JOIN conversation_messages m ON conversation_messages_fts.rowid = m.rowid
+            JOIN conversations c ON c.id = m.conversation_id
+            WHERE conversation_messages_fts MATCH ?1 AND c.workspace_id = ?2
+            ORDER BY bm25(conversation_messages_fts)
+            LIMIT ?3
+        "#).map_err(|e| format!("Failed to prepare search query: {}", e))?;
+
+        let hits = stmt
+            .query_map(params![&query, &self.workspace_id, limit as i64], |row| {
+                Ok(MessageSearchHit {
+                    conversation_id: row.get(0)?,
+                    conversation_title: row.get(1)?,
+                    message_id: row.get(2)?,
+                    role: row.get(3)?,
+                    snippet: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })
+            .map_err(|e| format!("Failed to search: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read results: {}", e))?;
+
+        Ok(hits)
+    }
+
+    /// The active branch as JSON or Markdown
+    pub fn export(&self, conversation_id: &str, format: ExportFormat) -> Result<Option<String>, String> {
+        let Some(thread) = self.thread(conversation_id)? else {
+            return Ok(None);
+        };
+
+        let exported = match format {
+            ExportFormat::Json => serde_json::to_string_pretty(&thread)
+                .map_err(|e| format!("Failed to serialize conversation: {}", e))?,
+            ExportFormat::Markdown => {
+                let mut out = format!(
+                    "# {}\n\n",
+                    thread.conversation.title.as_deref().unwrap_or("Conversation")
+                );
+                for ThreadMessage { message, .. } in &thread.messages {
+                    let heading = match (&message.role[..], &message.model) {
+                        ("assistant", Some(model)) => format!("Assistant ({})", model),
+                        (role, _) => capitalize(role),
+                    };
+                    out.push_str(&format!("## {}\n\n", heading));
+                    let text = content_text(&message.content);
+                    if !text.is_empty() {
+                        out.push_str(&text);
+                        out.push_str("\n\n");
+                    }
+                    for call in message.tool_calls.iter().flat_map(|calls| calls.as_array().into_iter().flatten()) {
+                        out.push_str(&format!(
+                            "Tool call `{}`:\n\n```json\n{}\n```\n\n",
+                            call["function"]["name"].as_str().unwrap_or("tool"),
+                            call["function"]["arguments"].as_str().unwrap_or("{}")
+                        ));
+                    }
+                }
+                out
+            }
+        };
+        Ok(Some(exported))
+    }
+
+    fn conversation(&self, conn: &Connection, id: &str) -> Result<Option<Conversation>, String> {
+        conn.query_row(
+            r#"SELECT id, workspace_id, title, active_leaf_id, created_at, updated_at, metadata
+               FROM conversations WHERE id = ?1 AND workspace_id = ?2"#,
+            params![id, &self.workspace_id],
+            row_to_conversation,
+        )
+        .optional()
+        .map_err(|e| format!("Failed to get conversation: {}", e))
+    }
+
+    /// A message of one of this workspace's conversations
+    fn owned_message(&self, conn: &Connection, id: &str) -> Result<StoredMessage, String> {
+        let message = message(conn, id)?.ok_or_else(|| "Message not found".to_string())?;
+        self.conversation(conn, &message.conversation_id)?
+            .ok_or_else(|| "Message not found".to_string())?;
+        Ok(message)
+    }
+
+    fn thread_with(&self, conn: &Connection, conversation_id: &str) -> Result<Option<ConversationThread>, String> {
+        let Some(conversation) = self.conversation(conn, conversation_id)? else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM conversation_messages WHERE conversation_id = ?1 ORDER BY created_at, rowid",
+            MESSAGE_COLUMNS
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let all = stmt
+            .query_map(params![conversation_id], row_to_message)
+            .map_err(|e| format!("Failed to read messages: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read messages: {}", e))?;
+
+        let mut children: HashMap<Option<&str>, Vec<&str>> = HashMap::new();
+        for message in &all {
+            children.entry(message.parent_id.as_deref()).or_default().push(&message.id);
+        }
+        let by_id: HashMap<&str, &StoredMessage> = all.iter().map(|m| (m.id.as_str(), m)).collect();
+
+        // Walk up from the leaf; ids are checked so a damaged tree cannot loop
+        let mut branch = Vec::new();
+        let mut next = conversation.active_leaf_id.as_deref();
+        while let Some(message) = next.and_then(|id| by_id.get(id)) {
+            if branch.len() > all.len() {
+                break;
+            }
+            branch.push(ThreadMessage {
+                message: (*message).clone(),
+                sibling_ids: children
+                    .get(&message.parent_id.as_deref())
+                    .map(|ids| ids.iter().map(|id| id.to_string()).collect())
+                    .unwrap_or_default(),
+            });
+            next = message.parent_id.as_deref();
+        }
+        branch.reverse();
+
+        Ok(Some(ConversationThread {
+            conversation,
+            messages: branch,
+        }))
+    }
+}
+
+const MESSAGE_COLUMNS: &str = "id, conversation_id, parent_id, role, content, tool_calls, tool_call_id, model, \
+    prompt_tokens, completion_tokens, reasoning_tokens, created_at";
+
+fn message(conn: &Connection, id: &str) -> Result<Option<StoredMessage>, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM conversation_messages WHERE id = ?1", MESSAGE_COLUMNS),
+        params![id],
+        row_to_message,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to get message: {}", e))
+}
+
+fn insert_message(conn: &Connection, message: &StoredMessage) -> Result<(), String> {
+    let usage = message.usage.as_ref();
+    conn.execute(
+        r#"INSERT INTO conversation_messages (
+            id, conversation_id, parent_id, role, content, text, tool_calls, tool_call_id, model,
+            prompt_tokens, completion_tokens, reasoning_tokens, created_at
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"#,
+        params![
+            &message.id,
+            &message.conversation_id,
+            &message.parent_id,
+            &message.role,
+            message.content.to_string(),
+            content_text(&message.content),
+            message.tool_calls.as_ref().map(|calls| calls.to_string()),
+            &message.tool_call_id,
+            &message.model,
+            usage.map(|u| u.prompt_tokens),
+            usage.map(|u| u.completion_tokens),
+            usage.and_then(|u| u.reasoning_tokens),
+            &message.created_at,
+        ],
+    ).map_err(|e| format!("Failed to save message: {}", e))?;
+    Ok(())
+}
+
+fn row_to_conversation(row: &rusqlite::Row) -> Result<Conversation, rusqlite::Error> {
+    let metadata: Option<String> = row.get(6)?;
+    Ok(Conversation {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        title: row.get(2)?,
+        active_leaf_id: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
+    })
+}
+
+fn row_to_message(row: &rusqlite::Row) -> Result<StoredMessage, rusqlite::Error> {
+    let content: String = row.get(4)?;
+    let tool_calls: Option<String> = row.get(5)?;
+    let prompt_tokens: Option<u32> = row.get(8)?;
+    let completion_tokens: Option<u32> = row.get(9)?;
+    let reasoning_tokens: Option<u32> = row.get(10)?;
+
+    Ok(StoredMessage {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        parent_id: row.get(2)?,
+        role: row.get(3)?,
+        content: serde_json::from_str(&content).unwrap_or(Value::String(content)),
+        tool_calls: tool_calls.and_then(|calls| serde_json::from_str(&calls).ok()),
+        tool_call_id: row.get(6)?,
+        model: row.get(7)?,
+        usage: match (prompt_tokens, completion_tokens) {
+            (Some(prompt_tokens), Some(completion_tokens)) => Some(TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+                total_tokens: prompt_tokens + completion_tokens,
+                reasoning_tokens,
+            }),
+            _ => None,
+        },
+        created_at: row.get(11)?,
+    })
+}
+
+/// Plain text of message content: text parts joined, attachments named
+pub fn content_text(content: &Value) -> String {
+    match content {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part["type"].as_str()? {
+                "text" => part["text"].as_str().map(String::from),
+                "image" | "image_url" => Some("[image]".to_string()),
+                "file" => Some(format!(
+                    "[file: {}]",
+                    part["name"].as_str().or(part["path"].as_str()).or(part["file"]["filename"].as_str()).unwrap_or("attachment")
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+/// Quote each word so punctuation in user input is not read as FTS5 syntax
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn store() -> (ConversationStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("conversation-test-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("memory.db");
+        ConversationStore::init_database(&Connection::open(&db_path).unwrap()).unwrap();
+        (ConversationStore::new(db_path, "workspace".to_string()), dir)
+    }
+
+    fn text(role: &str, content: &str) -> NewMessage {
+        NewMessage {
+            role: role.to_string(),
+            content: json!(content),
+            tool_calls: None,
+            tool_call_id: None,
+            model: None,
+            usage: None,
+            parent_id: None,
+        }
+    }
+
+    /// Contents of the active branch
+    fn branch(thread: &ConversationThread) -> Vec<&str> {
+        thread.messages.iter().map(|m| m.message.content.as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn edits_start_branches_that_can_be_switched() {
+        let (store, dir) = store();
+        let id = store.create_conversation(None, None).unwrap().id;
+        store.append_message(&id, text("user", "hello")).unwrap();
+        store.append_message(&id, text("assistant", "hi")).unwrap();
+        let first = store.append_message(&id, text("user", "first question")).unwrap();
+        store.append_message(&id, text("assistant", "first answer")).unwrap();
+
+        let edited = store.edit_message(&first.id, json!("second question")).unwrap();
+        assert_eq!(edited.parent_id, first.parent_id);
+        let thread = store.thread(&id).unwrap().unwrap();
+        assert_eq!(thread.conversation.title.as_deref(), Some("hello"));
+        assert_eq!(branch(&thread), vec!["hello", "hi", "second question"]);
+        assert_eq!(thread.messages[2].sibling_ids, vec![first.id.clone(), edited.id.clone()]);
+        assert_eq!(thread.messages[0].sibling_ids.len(), 1);
+        store.append_message(&id, text("assistant", "second answer")).unwrap();
+
+        // Switching follows the chosen message to the newest reply of its branch
+        let thread = store.switch_branch(&first.id).unwrap();
+        assert_eq!(branch(&thread), vec!["hello", "hi", "first question", "first answer"]);
+        let thread = store.switch_branch(&thread.messages[0].message.id).unwrap();
+        assert_eq!(branch(&thread), vec!["hello", "hi", "second question", "second answer"]);
+        assert_eq!(branch(&store.thread(&id).unwrap().unwrap()), branch(&thread));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn messages_stay_within_their_conversation_and_workspace() {
+        let (store, dir) = store();
+        let a = store.create_conversation(Some("a".to_string()), None).unwrap().id;
+        let b = store.create_conversation(Some("b".to_string()), None).unwrap().id;
+        let in_a = store.append_message(&a, text("user", "hello")).unwrap();
+
+        let mut reply = text("assistant", "hi");
+        reply.parent_id = Some(in_a.id.clone());
+        assert!(store.append_message(&b, reply).is_err());
+        assert!(store.thread(&b).unwrap().unwrap().messages.is_empty());
+        assert!(store.thread("missing").unwrap().is_none());
+
+        let other = ConversationStore::new(store.db_path.clone(), "other".to_string());
+        assert!(other.thread(&a).unwrap().is_none());
+        assert!(other.edit_message(&in_a.id, json!("changed")).is_err());
+        assert!(other.switch_branch(&in_a.id).is_err());
+        assert!(other.search_messages("hello", 10).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn search_index_follows_inserts_and_deletes() {
+        let (store, dir) = store();
+        let id = store.create_conversation(None, None).unwrap().id;
+        let question = store.append_message(&id, text("user", "How do I configure the proxy?")).unwrap();
+        store.edit_message(&question.id, json!([
+            { "type": "text", "text": "How do I configure the firewall?" },
+            { "type": "file", "path": "rules.conf" }
+        ])).unwrap();
+
+        let hits = store.search_messages("proxy", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_id, question.id);
+        assert_eq!(hits[0].snippet, "How do I configure the [proxy]?");
+        assert_eq!(store.search_messages("configure", 10).unwrap().len(), 2);
+        assert_eq!(store.search_messages("rules.conf", 10).unwrap().len(), 1);
+        // Punctuation is quoted rather than read as query syntax
+        assert!(store.search_messages("proxy\" OR", 10).unwrap().is_empty());
+
+        assert!(store.delete_conversation(&id).unwrap());
+        assert!(store.search_messages("configure", 10).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
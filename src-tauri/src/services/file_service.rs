@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::fs;
 use std::io::Read;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use thiserror::Error;
 use walkdir::WalkDir;
 
@@ -22,6 +22,8 @@ pub enum FileError {
     Io(#[from] std::io::Error),
     #[error("Path invalid: {0}")]
     InvalidPath(String),
+    #[error("Path is outside the workspace: {0}")]
+    OutsideWorkspace(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -105,6 +107,67 @@ pub fn resolve_path(workspace: &Path, rel_path: &str) -> Result<PathBuf, FileErr
     Ok(canonical)
 }
 
+/// Resolve a path like `resolve_path`, rejecting anything that ends up outside the
+/// workspace, whether through `..`, an absolute path or a symlink
+pub fn resolve_within(workspace: &Path, rel_path: &str) -> Result<PathBuf, FileError> {
+    let root = workspace.canonicalize()?;
+    let path = normalize_path(&resolve_path(workspace, rel_path)?);
+
+    // Paths that do not exist yet are checked through their nearest existing ancestor
+    let mut existing = path.clone();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        match existing.file_name() {
+            Some(name) => missing.push(name.to_os_string()),
+            None => break,
+        }
+        existing.pop();
+    }
+    let mut resolved = existing.canonicalize().unwrap_or(existing);
+    resolved.extend(missing.iter().rev());
+
+    if resolved.starts_with(&root) {
+        Ok(resolved)
+    } else {
+        Err(FileError::OutsideWorkspace(rel_path.to_string()))
+    }
+}
+
+/// Lexically normalize a path, resolving `.` and `..` without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Rename a file or directory, falling back to copy-and-delete when the
+/// destination is on another filesystem
+pub fn rename_or_copy(from: &Path, to: &Path) -> Result<(), FileError> {
+    match fs::rename(from, to) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    if from.is_dir() {
+        copy_dir_recursive(from, to)?;
+        fs::remove_dir_all(from)?;
+    } else {
+        fs::copy(from, to)?;
+        fs::remove_file(from)?;
+    }
+
+    Ok(())
+}
+
 /// List directory contents
 pub fn list_directory(
     workspace: &Path,
@@ -317,6 +380,8 @@ pub fn search_files(
 
     for entry in WalkDir::new(&path)
         .into_iter()
+        // Don't surface trashed copies of files in search results
+        .filter_entry(|e| !(e.file_type().is_dir() && e.file_name() == ".trash"))
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
     {
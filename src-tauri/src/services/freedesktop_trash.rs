@@ -0,0 +1,284 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+
+use super::file_service::{normalize_path, rename_or_copy};
+use super::trash_service::{dir_size, TrashBackend, TrashError, TrashMetadata};
+
+const TRASH_INFO_EXT: &str = "trashinfo";
+const DELETION_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+const REQUEST_ID_KEY: &str = "X-SentinelOps-RequestId";
+const SHA256_KEY: &str = "X-SentinelOps-Sha256";
+
+/// The user's home trash as described by the freedesktop.org Trash specification:
+/// `$XDG_DATA_HOME/Trash/files/<name>` holds the item and `info/<name>.trashinfo`
+/// records its original path and deletion date. Only items that came from the
+/// workspace are listed, so other desktop trash contents are left alone.
+pub struct FreedesktopTrash {
+    workspace: PathBuf,
+    files_dir: PathBuf,
+    info_dir: PathBuf,
+    /// Parsed `.trashinfo` files by name, with the modification time they were read at.
+    /// `None` marks items from outside the workspace. Saves re-reading every file and
+    /// walking every trashed directory on each listing.
+    scanned: RefCell<HashMap<String, (SystemTime, Option<TrashMetadata>)>>,
+}
+
+/// The fields of a `.trashinfo` file, with the path relative to the workspace
+struct TrashInfo {
+    rel_path: String,
+    deletion_date: Option<String>,
+    request_id: Option<String>,
+    sha256: Option<String>,
+}
+
+impl FreedesktopTrash {
+    /// The spec only applies to XDG desktops (Linux and the BSDs)
+    pub fn is_supported() -> bool {
+        cfg!(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ))
+    }
+
+    pub fn new(workspace: &Path) -> Option<Self> {
+        // dirs::data_dir honours $XDG_DATA_HOME and defaults to ~/.local/share
+        let trash_dir = dirs::data_dir()?.join("Trash");
+        let files_dir = trash_dir.join("files");
+        let info_dir = trash_dir.join("info");
+        fs::create_dir_all(&files_dir).ok()?;
+        fs::create_dir_all(&info_dir).ok()?;
+
+        let workspace = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| normalize_path(workspace));
+
+        Some(Self {
+            workspace,
+            files_dir,
+            info_dir,
+            scanned: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn info_path(&self, name: &str) -> PathBuf {
+        self.info_dir.join(format!("{}.{}", name, TRASH_INFO_EXT))
+    }
+
+    /// Reserve a unique name by creating its `.trashinfo` file exclusively, as the spec requires
+    fn reserve_name(&self, source_path: &Path, contents: &str) -> Result<String, TrashError> {
+        let stem = source_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "item".to_string());
+        let ext = source_path
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+
+        let mut n = 1;
+        loop {
+            let name = if n == 1 {
+                format!("{}{}", stem, ext)
+            } else {
+                format!("{}.{}{}", stem, n, ext)
+            };
+            n += 1;
+
+            if self.files_dir.join(&name).exists() {
+                continue;
+            }
+
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.info_path(&name))
+            {
+                Ok(mut file) => {
+                    file.write_all(contents.as_bytes())?;
+                    return Ok(name);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Parse one `.trashinfo` file, returning `None` for items outside the workspace
+    fn parse_info(&self, name: &str) -> Option<TrashInfo> {
+        let content = fs::read_to_string(self.info_path(name)).ok()?;
+
+        let mut original = None;
+        let mut deletion_date = None;
+        let mut request_id = None;
+        let mut sha256 = None;
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "Path" => original = Some(decode_path(value.trim())),
+                "DeletionDate" => deletion_date = Some(value.trim().to_string()),
+                REQUEST_ID_KEY => request_id = Some(value.trim().to_string()),
+                SHA256_KEY => sha256 = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+
+        // Relative paths in the home trash are relative to the home directory
+        let original = original?;
+        let original = if original.is_absolute() {
+            original
+        } else {
+            dirs::home_dir()?.join(original)
+        };
+        let rel_path = normalize_path(&original)
+            .strip_prefix(&self.workspace)
+            .ok()?
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        Some(TrashInfo { rel_path, deletion_date, request_id, sha256 })
+    }
+
+    /// Metadata for an item trashed from the workspace that is still in the trash
+    fn read_info(&self, name: &str) -> Option<TrashMetadata> {
+        let TrashInfo { rel_path, deletion_date, request_id, sha256 } = self.parse_info(name)?;
+
+        let deleted_at = deletion_date
+            .and_then(|d| NaiveDateTime::parse_from_str(&d, DELETION_DATE_FORMAT).ok())
+            .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+            .map(|dt| dt.with_timezone(&Utc).to_rfc3339())
+            .unwrap_or_default();
+
+        let item_path = self.files_dir.join(name);
+        let meta = fs::symlink_metadata(&item_path).ok()?;
+        let (item_type, size) = if meta.is_dir() {
+            ("directory", dir_size(&item_path))
+        } else {
+            ("file", meta.len())
+        };
+
+        Some(TrashMetadata {
+            trash_id: name.to_string(),
+            original_path: rel_path,
+            deleted_at,
+            item_type: item_type.to_string(),
+            size,
+            sha256,
+            request_id: request_id.unwrap_or_else(|| name.to_string()),
+        })
+    }
+}
+
+impl TrashBackend for FreedesktopTrash {
+    fn trash(&mut self, source_path: &Path, metadata: TrashMetadata) -> Result<(String, PathBuf), TrashError> {
+        let absolute = normalize_path(source_path);
+
+        let mut info = format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n{}={}\n",
+            encode_path(&absolute),
+            Local::now().format(DELETION_DATE_FORMAT),
+            REQUEST_ID_KEY,
+            metadata.request_id,
+        );
+        if let Some(sha) = &metadata.sha256 {
+            info.push_str(&format!("{}={}\n", SHA256_KEY, sha));
+        }
+
+        let name = self.reserve_name(source_path, &info)?;
+        let trash_path = self.files_dir.join(&name);
+
+        if let Err(e) = rename_or_copy(source_path, &trash_path) {
+            let _ = fs::remove_file(self.info_path(&name));
+            return Err(e.into());
+        }
+
+        Ok((name, trash_path))
+    }
+
+    fn items(&self) -> Vec<TrashMetadata> {
+        let Ok(entries) = fs::read_dir(&self.info_dir) else {
+            return Vec::new();
+        };
+
+        let mut scanned = self.scanned.borrow_mut();
+        let mut present = HashMap::new();
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if path.extension().and_then(|x| x.to_str()) != Some(TRASH_INFO_EXT) {
+                continue;
+            }
+            let Some(name) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let modified = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let item = match scanned.remove(&name) {
+                Some((at, item)) if at == modified => item,
+                _ => self.read_info(&name),
+            };
+            present.insert(name, (modified, item));
+        }
+        // Whatever was not seen again has left the trash
+        *scanned = present;
+
+        scanned.values().filter_map(|(_, item)| item.clone()).collect()
+    }
+
+    fn item_path(&self, trash_id: &str) -> Option<PathBuf> {
+        if !is_valid_name(trash_id) {
+            return None;
+        }
+        let path = self.files_dir.join(trash_id);
+        fs::symlink_metadata(&path).is_ok().then_some(path)
+    }
+
+    fn remove(&mut self, trash_id: &str) -> Result<bool, TrashError> {
+        // The system trash is shared; only touch entries trashed from this workspace
+        if !is_valid_name(trash_id) || self.parse_info(trash_id).is_none() {
+            return Ok(false);
+        }
+
+        let info_path = self.info_path(trash_id);
+        let item_path = self.files_dir.join(trash_id);
+        match fs::symlink_metadata(&item_path) {
+            Ok(meta) if meta.is_dir() => fs::remove_dir_all(&item_path)?,
+            Ok(_) => fs::remove_file(&item_path)?,
+            Err(_) => {}
+        }
+        fs::remove_file(&info_path)?;
+        self.scanned.borrow_mut().remove(trash_id);
+
+        Ok(true)
+    }
+}
+
+/// Trash IDs are file names inside the trash; reject anything that could escape it
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/') && !name.contains('\\')
+}
+
+/// URL-escape each path segment, keeping the separators
+fn encode_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn decode_path(value: &str) -> PathBuf {
+    PathBuf::from(
+        urlencoding::decode(value)
+            .map(|s| s.into_owned())
+            .unwrap_or_else(|_| value.to_string()),
+    )
+}
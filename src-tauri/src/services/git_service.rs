@@ -0,0 +1,86 @@
+use serde::Serialize;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Serialize)]
+pub struct GitChange {
+    pub path: String,
+    pub status: String,
+    pub staged: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitStatusResult {
+    pub branch: Option<String>,
+    pub changes: Vec<GitChange>,
+}
+
+/// Run git in `cwd`, returning stdout on success and stderr on failure
+pub fn run_git_command(args: &[&str], cwd: &Path) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).to_string())
+    }
+}
+
+/// Current branch and working tree changes of the repository at `cwd`
+pub fn status(cwd: &Path) -> Result<GitStatusResult, String> {
+    // Get branch name
+    let branch = run_git_command(&["rev-parse", "--abbrev-ref", "HEAD"], cwd)
+        .ok()
+        .map(|s| s.trim().to_string());
+
+    // Get status
+    let output = run_git_command(&["status", "--porcelain"], cwd)
+        .map_err(|e| format!("Git status failed: {}", e))?;
+
+    let changes: Vec<GitChange> = output
+        .lines()
+        .filter_map(|line| {
+            if line.len() < 4 {
+                return None;
+            }
+
+            let index_status = line.chars().next().unwrap_or(' ');
+            let worktree_status = line.chars().nth(1).unwrap_or(' ');
+            let file_path = line[3..].to_string();
+
+            let (status, staged) = match (index_status, worktree_status) {
+                ('M', _) => ("modified", true),
+                (_, 'M') => ("modified", false),
+                ('A', _) => ("added", true),
+                ('D', _) => ("deleted", true),
+                (_, 'D') => ("deleted", false),
+                ('R', _) => ("renamed", true),
+                ('?', '?') => ("untracked", false),
+                _ => ("unknown", false),
+            };
+
+            Some(GitChange {
+                path: file_path,
+                status: status.to_string(),
+                staged,
+            })
+        })
+        .collect();
+
+    Ok(GitStatusResult { branch, changes })
+}
+
+/// Unstaged changes in the repository at `cwd`, optionally limited to one path
+pub fn diff(cwd: &Path, path: Option<&str>) -> Result<String, String> {
+    let args: Vec<&str> = if let Some(p) = path {
+        vec!["diff", p]
+    } else {
+        vec!["diff"]
+    };
+
+    run_git_command(&args, cwd)
+}
@@ -0,0 +1,1242 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use super::attachment_service::ImageLimits;
+use super::provider_service::{LlmTarget, ProviderKind};
+use super::tool_calls::ToolCallIssue;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub function: ToolFunction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolFunction {
+    pub name: String,
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenUsage {
+    #[serde(rename = "promptTokens")]
+    pub prompt_tokens: u32,
+    #[serde(rename = "completionTokens")]
+    pub completion_tokens: u32,
+    #[serde(rename = "totalTokens")]
+    pub total_tokens: u32,
+    /// Part of `completion_tokens` spent thinking, when the model reasons before answering
+    #[serde(rename = "reasoningTokens", default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_tokens: Option<u32>,
+}
+
+impl TokenUsage {
+    fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+            reasoning_tokens: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatCompletionResult {
+    pub id: String,
+    pub model: String,
+    pub content: Option<String>,
+    #[serde(rename = "finishReason")]
+    pub finish_reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+    #[serde(rename = "toolCalls", skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    /// The model's thinking, when the provider returns it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// Tool calls whose arguments failed checking, each with a message for the model
+    #[serde(rename = "toolCallIssues", default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_call_issues: Vec<ToolCallIssue>,
+}
+
+/// Provider-neutral chat request. Messages and tools use the OpenAI format the frontend sends.
+pub struct ChatRequest<'a> {
+    pub model: &'a str,
+    pub messages: &'a [Value],
+    pub tools: &'a [Value],
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub stream: bool,
+    /// Let reasoning models think before answering; `None` keeps the provider's default
+    pub reasoning: Option<Reasoning>,
+    /// Constrain the reply to JSON matching a schema
+    pub response_format: Option<&'a ResponseFormat>,
+}
+
+/// A JSON Schema the reply must follow. OpenAI-compatible endpoints get `response_format`,
+/// Gemini a response schema, and Anthropic a tool named `name` that it is forced to call.
+#[derive(Debug, Clone)]
+pub struct ResponseFormat {
+    pub name: String,
+    /// Object schema
+    pub schema: Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+}
+
+/// How much a model may think. Providers take either an effort level or a token budget;
+/// whichever the caller leaves out is derived from the other.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reasoning {
+    pub effort: Option<ReasoningEffort>,
+    pub budget_tokens: Option<u32>,
+}
+
+/// Smallest thinking budget Anthropic accepts
+const MIN_THINKING_BUDGET: u32 = 1024;
+
+impl Reasoning {
+    /// `None` when neither an effort nor a budget was requested
+    pub fn new(effort: Option<ReasoningEffort>, budget_tokens: Option<u32>) -> Option<Self> {
+        (effort.is_some() || budget_tokens.is_some()).then_some(Self { effort, budget_tokens })
+    }
+
+    pub fn effort(&self) -> ReasoningEffort {
+        match (self.effort, self.budget_tokens) {
+            (Some(effort), _) => effort,
+            (None, Some(budget)) if budget < 2048 => ReasoningEffort::Low,
+            (None, Some(budget)) if budget < 8192 => ReasoningEffort::Medium,
+            _ => ReasoningEffort::High,
+        }
+    }
+
+    pub fn budget_tokens(&self) -> u32 {
+        match (self.budget_tokens, self.effort) {
+            (Some(budget), _) => budget,
+            (None, Some(ReasoningEffort::Low)) => 1024,
+            (None, Some(ReasoningEffort::Medium)) | (None, None) => 4096,
+            (None, Some(ReasoningEffort::High)) => 16384,
+        }
+    }
+
+    /// Cache key component
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.effort().as_str(), self.budget_tokens())
+    }
+}
+
+/// One step of a streamed reply, already translated to the OpenAI vocabulary
+#[derive(Debug)]
+pub enum StreamEvent {
+    Meta { id: Option<String>, model: Option<String> },
+    Text(String),
+    /// Thinking the model does before or between answers
+    Reasoning(String),
+    /// A new tool call when `id`/`name` are set, otherwise more argument JSON for `index`
+    ToolCallDelta {
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: String,
+    },
+    /// OpenAI finish reason: stop, length or tool_calls
+    Finish(String),
+    Usage(TokenUsage),
+    Error(String),
+    Done,
+}
+
+/// Translates between the OpenAI-shaped requests the app builds and a provider's wire format
+pub trait ProviderAdapter: Send + Sync {
+    /// Chat endpoint, relative to the profile's base URL
+    fn chat_path(&self, req: &ChatRequest) -> String;
+    fn chat_body(&self, req: &ChatRequest) -> Value;
+    /// Parse a non-streaming reply; `model` is used when the reply does not name one
+    fn parse_reply(&self, json: &Value, model: &str) -> ChatCompletionResult;
+    /// Parse the `data:` payload of one server-sent event
+    fn parse_stream_data(&mut self, data: &str) -> Vec<StreamEvent>;
+    /// Size images are scaled to before they are sent
+    fn image_limits(&self) -> ImageLimits;
+
+    /// Model used for embeddings when the caller does not pick one
+    fn default_embedding_model(&self) -> &'static str;
+    /// Embeddings endpoint for `count` inputs, or `None` when the provider has no embeddings API
+    fn embeddings_path(&self, model: &str, count: usize) -> Option<String>;
+    fn embeddings_body(&self, model: &str, input: &[String]) -> Value;
+    /// Vectors in input order, plus the token count when the provider reports one
+    fn parse_embeddings(&self, json: &Value) -> (Vec<Vec<f32>>, Option<u32>);
+}
+
+pub fn adapter_for(target: &LlmTarget) -> Box<dyn ProviderAdapter> {
+    match target.kind {
+        ProviderKind::Anthropic => Box::new(AnthropicAdapter::default()),
+        ProviderKind::Google => Box::new(GeminiAdapter::default()),
+        ProviderKind::Openai => Box::new(OpenAiAdapter {
+            // The SentinelOps proxy forwards to OpenRouter
+            openrouter: target.base_url.contains("openrouter.ai") || (target.keyless && target.profile_id.is_none()),
+        }),
+    }
+}
+
+fn non_empty(value: &Value) -> Option<String> {
+    value.as_str().filter(|s| !s.is_empty()).map(String::from)
+}
+
+fn float_vector(values: &Value) -> Option<Vec<f32>> {
+    values
+        .as_array()
+        .map(|arr| arr.iter().filter_map(|v| v.as_f64().map(|f| f as f32)).collect())
+}
+
+// ============================================================================
+// OpenAI-compatible
+// ============================================================================
+
+pub struct OpenAiAdapter {
+    /// OpenRouter takes a `reasoning` object instead of OpenAI's `reasoning_effort`
+    openrouter: bool,
+}
+
+impl ProviderAdapter for OpenAiAdapter {
+    fn chat_path(&self, _req: &ChatRequest) -> String {
+        "chat/completions".to_string()
+    }
+
+    fn chat_body(&self, req: &ChatRequest) -> Value {
+        let mut body = json!({
+            "model": req.model,
+            "messages": req.messages,
+            "temperature": req.temperature,
+            "max_tokens": req.max_tokens,
+            "stream": req.stream
+        });
+        if !req.tools.is_empty() {
+            body["tools"] = json!(req.tools);
+        }
+        if let Some(format) = req.response_format {
+            body["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": { "name": format.name, "schema": format.schema }
+            });
+        }
+        if let Some(reasoning) = req.reasoning {
+            if self.openrouter {
+                body["reasoning"] = match reasoning.budget_tokens {
+                    Some(budget) => json!({ "max_tokens": budget }),
+                    None => json!({ "effort": reasoning.effort().as_str() }),
+                };
+            } else {
+                body["reasoning_effort"] = json!(reasoning.effort().as_str());
+            }
+        }
+        body
+    }
+
+    fn parse_reply(&self, json: &Value, model: &str) -> ChatCompletionResult {
+        let message = &json["choices"][0]["message"];
+        let tool_calls: Vec<ToolCall> = message["tool_calls"]
+            .as_array()
+            .map(|calls| {
+                calls
+                    .iter()
+                    .filter_map(|tc| {
+                        Some(ToolCall {
+                            id: tc["id"].as_str()?.to_string(),
+                            call_type: tc["type"].as_str().unwrap_or("function").to_string(),
+                            function: ToolFunction {
+                                name: tc["function"]["name"].as_str()?.to_string(),
+                                arguments: tc["function"]["arguments"].as_str().unwrap_or("{}").to_string(),
+                            },
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        ChatCompletionResult {
+            id: json["id"].as_str().unwrap_or("").to_string(),
+            model: json["model"].as_str().unwrap_or(model).to_string(),
+            content: message["content"].as_str().map(String::from),
+            finish_reason: json["choices"][0]["finish_reason"]
+                .as_str()
+                .unwrap_or("stop")
+                .to_string(),
+            usage: openai_usage(&json["usage"]),
+            tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+            reasoning: openai_reasoning(message),
+            tool_call_issues: Vec::new(),
+        }
+    }
+
+    fn parse_stream_data(&mut self, data: &str) -> Vec<StreamEvent> {
+        if data == "[DONE]" {
+            return vec![StreamEvent::Done];
+        }
+        let Ok(json) = serde_json::from_str::<Value>(data) else {
+            return Vec::new();
+        };
+
+        let mut events = vec![StreamEvent::Meta {
+            id: non_empty(&json["id"]),
+            model: non_empty(&json["model"]),
+        }];
+        let delta = &json["choices"][0]["delta"];
+
+        if let Some(thinking) = openai_reasoning(delta) {
+            events.push(StreamEvent::Reasoning(thinking));
+        }
+        if let Some(text) = non_empty(&delta["content"]) {
+            events.push(StreamEvent::Text(text));
+        }
+        if let Some(calls) = delta["tool_calls"].as_array() {
+            for tc in calls {
+                events.push(StreamEvent::ToolCallDelta {
+                    index: tc["index"].as_u64().unwrap_or(0) as usize,
+                    id: non_empty(&tc["id"]),
+                    name: non_empty(&tc["function"]["name"]),
+                    arguments: tc["function"]["arguments"].as_str().unwrap_or("").to_string(),
+                });
+            }
+        }
+        if let Some(finish) = non_empty(&json["choices"][0]["finish_reason"]) {
+            events.push(StreamEvent::Finish(finish));
+        }
+        if let Some(usage) = openai_usage(&json["usage"]) {
+            events.push(StreamEvent::Usage(usage));
+        }
+        events
+    }
+
+    fn image_limits(&self) -> ImageLimits {
+        // Routers may forward to Claude, which rejects images over 5 MB of base64
+        ImageLimits { max_edge: 2048, max_bytes: 3_750_000, remote_urls: true }
+    }
+
+    fn default_embedding_model(&self) -> &'static str {
+        "openai/text-embedding-3-small"
+    }
+
+    fn embeddings_path(&self, _model: &str, _count: usize) -> Option<String> {
+        Some("embeddings".to_string())
+    }
+
+    fn embeddings_body(&self, model: &str, input: &[String]) -> Value {
+        match input {
+            [text] => json!({ "model": model, "input": text }),
+            texts => json!({ "model": model, "input": texts }),
+        }
+    }
+
+    fn parse_embeddings(&self, json: &Value) -> (Vec<Vec<f32>>, Option<u32>) {
+        let embeddings = json["data"]
+            .as_array()
+            .map(|arr| arr.iter().filter_map(|item| float_vector(&item["embedding"])).collect())
+            .unwrap_or_default();
+        (embeddings, json["usage"]["total_tokens"].as_u64().map(|t| t as u32))
+    }
+}
+
+fn openai_usage(usage: &Value) -> Option<TokenUsage> {
+    let usage = usage.as_object()?;
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    Some(TokenUsage {
+        prompt_tokens: count("prompt_tokens"),
+        completion_tokens: count("completion_tokens"),
+        total_tokens: count("total_tokens"),
+        reasoning_tokens: usage
+            .get("completion_tokens_details")
+            .and_then(|details| details["reasoning_tokens"].as_u64())
+            .map(|t| t as u32),
+    })
+}
+
+/// OpenRouter sends thinking as `reasoning`, DeepSeek and most local servers as `reasoning_content`
+fn openai_reasoning(message: &Value) -> Option<String> {
+    non_empty(&message["reasoning"]).or_else(|| non_empty(&message["reasoning_content"]))
+}
+
+// ============================================================================
+// Anthropic Messages API
+// ============================================================================
+
+/// Native adapter for `POST /v1/messages`
+#[derive(Default)]
+pub struct AnthropicAdapter {
+    /// Content block index → tool call index, for routing `input_json_delta`s
+    tool_blocks: HashMap<usize, usize>,
+    input_tokens: u32,
+}
+
+impl ProviderAdapter for AnthropicAdapter {
+    fn chat_path(&self, _req: &ChatRequest) -> String {
+        "messages".to_string()
+    }
+
+    fn chat_body(&self, req: &ChatRequest) -> Value {
+        let (system, messages) = anthropic_messages(req.messages);
+        let mut body = json!({
+            "model": req.model,
+            "messages": messages,
+            "temperature": req.temperature,
+            "max_tokens": req.max_tokens,
+            "stream": req.stream
+        });
+        // Thinking blocks are not sent back, and the API rejects a tool use turn
+        // continued with thinking enabled but without them. Forced tool calls rule out
+        // thinking as well.
+        let continues_tool_use = req
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m["role"] == "assistant")
+            .is_some_and(|m| m["tool_calls"].as_array().is_some_and(|calls| !calls.is_empty()));
+        let thinking = req.reasoning.filter(|_| !continues_tool_use && req.response_format.is_none());
+        if let Some(reasoning) = thinking {
+            let budget = reasoning.budget_tokens().max(MIN_THINKING_BUDGET);
+            body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget });
+            // The budget counts toward max_tokens, and thinking requires the default temperature
+            body["max_tokens"] = json!(req.max_tokens.saturating_add(budget));
+            if let Some(obj) = body.as_object_mut() {
+                obj.remove("temperature");
+            }
+        }
+        if let Some(system) = system {
+            body["system"] = json!(system);
+        }
+
+        let mut tools: Vec<Value> = req
+            .tools
+            .iter()
+            .filter_map(|tool| {
+                let function = &tool["function"];
+                let mut converted = json!({
+                    "name": function["name"].as_str()?,
+                    "input_schema": if function["parameters"].is_object() {
+                        function["parameters"].clone()
+                    } else {
+                        json!({ "type": "object", "properties": {} })
+                    }
+                });
+                if let Some(description) = function["description"].as_str() {
+                    converted["description"] = json!(description);
+                }
+                Some(converted)
+            })
+            .collect();
+        // No JSON mode; a forced tool call whose input is the reply does the same
+        if let Some(format) = req.response_format {
+            tools.push(json!({
+                "name": format.name,
+                "description": "Give your reply by calling this tool. Its input is the reply.",
+                "input_schema": format.schema
+            }));
+            body["tool_choice"] = json!({ "type": "tool", "name": format.name });
+        }
+        if !tools.is_empty() {
+            body["tools"] = json!(tools);
+        }
+        body
+    }
+
+    fn parse_reply(&self, json: &Value, model: &str) -> ChatCompletionResult {
+        let mut text = String::new();
+        let mut thinking = String::new();
+        let mut tool_calls = Vec::new();
+        for block in json["content"].as_array().into_iter().flatten() {
+            match block["type"].as_str() {
+                Some("text") => text.push_str(block["text"].as_str().unwrap_or("")),
+                Some("thinking") => thinking.push_str(block["thinking"].as_str().unwrap_or("")),
+                Some("tool_use") => tool_calls.push(ToolCall {
+                    id: block["id"].as_str().unwrap_or("").to_string(),
+                    call_type: "function".to_string(),
+                    function: ToolFunction {
+                        name: block["name"].as_str().unwrap_or("").to_string(),
+                        arguments: block["input"].to_string(),
+                    },
+                }),
+                _ => {}
+            }
+        }
+
+        let usage = &json["usage"];
+        ChatCompletionResult {
+            id: json["id"].as_str().unwrap_or("").to_string(),
+            model: json["model"].as_str().unwrap_or(model).to_string(),
+            content: if text.is_empty() && !tool_calls.is_empty() { None } else { Some(text) },
+            finish_reason: finish_reason(json["stop_reason"].as_str()),
+            usage: usage.is_object().then(|| {
+                TokenUsage::new(
+                    usage["input_tokens"].as_u64().unwrap_or(0) as u32,
+                    usage["output_tokens"].as_u64().unwrap_or(0) as u32,
+                )
+            }),
+            tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+            reasoning: if thinking.is_empty() { None } else { Some(thinking) },
+            tool_call_issues: Vec::new(),
+        }
+    }
+
+    fn parse_stream_data(&mut self, data: &str) -> Vec<StreamEvent> {
+        let Ok(json) = serde_json::from_str::<Value>(data) else {
+            return Vec::new();
+        };
+
+        match json["type"].as_str().unwrap_or("") {
+            "message_start" => {
+                let message = &json["message"];
+                self.input_tokens = message["usage"]["input_tokens"].as_u64().unwrap_or(0) as u32;
+                vec![StreamEvent::Meta {
+                    id: non_empty(&message["id"]),
+                    model: non_empty(&message["model"]),
+                }]
+            }
+            "content_block_start" => {
+                let block = &json["content_block"];
+                let block_index = json["index"].as_u64().unwrap_or(0) as usize;
+                match block["type"].as_str() {
+                    Some("tool_use") => {
+                        let index = self.tool_blocks.len();
+                        self.tool_blocks.insert(block_index, index);
+                        vec![StreamEvent::ToolCallDelta {
+                            index,
+                            id: non_empty(&block["id"]),
+                            name: non_empty(&block["name"]),
+                            arguments: String::new(),
+                        }]
+                    }
+                    Some("text") => non_empty(&block["text"]).map(StreamEvent::Text).into_iter().collect(),
+                    Some("thinking") => non_empty(&block["thinking"]).map(StreamEvent::Reasoning).into_iter().collect(),
+                    _ => Vec::new(),
+                }
+            }
+            "content_block_delta" => {
+                let delta = &json["delta"];
+                let block_index = json["index"].as_u64().unwrap_or(0) as usize;
+                match delta["type"].as_str() {
+                    Some("text_delta") => non_empty(&delta["text"]).map(StreamEvent::Text).into_iter().collect(),
+                    Some("thinking_delta") => non_empty(&delta["thinking"]).map(StreamEvent::Reasoning).into_iter().collect(),
+                    Some("input_json_delta") => match self.tool_blocks.get(&block_index) {
+                        Some(&index) => vec![StreamEvent::ToolCallDelta {
+                            index,
+                            id: None,
+                            name: None,
+                            arguments: delta["partial_json"].as_str().unwrap_or("").to_string(),
+                        }],
+                        None => Vec::new(),
+                    },
+                    _ => Vec::new(),
+                }
+            }
+            "message_delta" => {
+                let mut events = Vec::new();
+                if let Some(reason) = json["delta"]["stop_reason"].as_str() {
+                    events.push(StreamEvent::Finish(finish_reason(Some(reason))));
+                }
+                if let Some(output) = json["usage"]["output_tokens"].as_u64() {
+                    events.push(StreamEvent::Usage(TokenUsage::new(self.input_tokens, output as u32)));
+                }
+                events
+            }
+            "message_stop" => vec![StreamEvent::Done],
+            "error" => vec![StreamEvent::Error(
+                json["error"]["message"].as_str().unwrap_or("Unknown error").to_string(),
+            )],
+            _ => Vec::new(),
+        }
+    }
+
+    fn image_limits(&self) -> ImageLimits {
+        // Larger images are downscaled by the API anyway; the limit is 5 MB of base64
+        ImageLimits { max_edge: 1568, max_bytes: 3_750_000, remote_urls: true }
+    }
+
+    fn default_embedding_model(&self) -> &'static str {
+        ""
+    }
+
+    fn embeddings_path(&self, _model: &str, _count: usize) -> Option<String> {
+        None
+    }
+
+    fn embeddings_body(&self, _model: &str, _input: &[String]) -> Value {
+        Value::Null
+    }
+
+    fn parse_embeddings(&self, _json: &Value) -> (Vec<Vec<f32>>, Option<u32>) {
+        (Vec::new(), None)
+    }
+}
+
+fn finish_reason(stop_reason: Option<&str>) -> String {
+    match stop_reason {
+        Some("max_tokens") => "length",
+        Some("tool_use") => "tool_calls",
+        _ => "stop",
+    }
+    .to_string()
+}
+
+/// Split OpenAI messages into Anthropic's separate system prompt and alternating turns.
+/// Tool results become `tool_result` blocks in a user turn; consecutive turns from the
+/// same role are merged since the API rejects them.
+fn anthropic_messages(messages: &[Value]) -> (Option<String>, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut turns: Vec<(String, Vec<Value>)> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match message["role"].as_str().unwrap_or("user") {
+            "system" | "developer" => {
+                system.extend(anthropic_content(&message["content"]).into_iter().filter_map(|b| {
+                    b["text"].as_str().map(String::from)
+                }));
+                continue;
+            }
+            "assistant" => {
+                let mut blocks = anthropic_content(&message["content"]);
+                for tc in message["tool_calls"].as_array().into_iter().flatten() {
+                    let arguments = tc["function"]["arguments"].as_str().unwrap_or("{}");
+                    blocks.push(json!({
+                        "type": "tool_use",
+                        "id": tc["id"],
+                        "name": tc["function"]["name"],
+                        "input": serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| json!({}))
+                    }));
+                }
+                ("assistant", blocks)
+            }
+            "tool" => {
+                let content = match &message["content"] {
+                    Value::String(s) => json!(s),
+                    other => json!(anthropic_content(other)),
+                };
+                (
+                    "user",
+                    vec![json!({
+                        "type": "tool_result",
+                        "tool_use_id": message["tool_call_id"],
+                        "content": content
+                    })],
+                )
+            }
+            _ => ("user", anthropic_content(&message["content"])),
+        };
+
+        if blocks.is_empty() {
+            continue;
+        }
+        match turns.last_mut() {
+            Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+            _ => turns.push((role.to_string(), blocks)),
+        }
+    }
+
+    let system = if system.is_empty() { None } else { Some(system.join("\n\n")) };
+    let turns = turns
+        .into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect();
+    (system, turns)
+}
+
+/// Convert OpenAI message content (a string or content parts) to Anthropic content blocks
+fn anthropic_content(content: &Value) -> Vec<Value> {
+    match content {
+        Value::String(text) if !text.is_empty() => vec![json!({ "type": "text", "text": text })],
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part["type"].as_str()? {
+                "text" => Some(json!({ "type": "text", "text": part["text"].as_str()? })),
+                "image_url" => {
+                    let url = part["image_url"]["url"].as_str().or(part["image_url"].as_str())?;
+                    Some(json!({ "type": "image", "source": anthropic_image_source(url)? }))
+                }
+                "file" => {
+                    let (media_type, data) = part["file"]["file_data"].as_str()?.strip_prefix("data:")?.split_once(";base64,")?;
+                    let mut document = json!({
+                        "type": "document",
+                        "source": { "type": "base64", "media_type": media_type, "data": data }
+                    });
+                    if let Some(filename) = part["file"]["filename"].as_str() {
+                        document["title"] = json!(filename);
+                    }
+                    Some(document)
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn anthropic_image_source(url: &str) -> Option<Value> {
+    match url.strip_prefix("data:") {
+        Some(rest) => {
+            let (media_type, data) = rest.split_once(";base64,")?;
+            Some(json!({ "type": "base64", "media_type": media_type, "data": data }))
+        }
+        None => Some(json!({ "type": "url", "url": url })),
+    }
+}
+
+// ============================================================================
+// Google Gemini
+// ============================================================================
+
+/// Native adapter for `models/{model}:generateContent` and `:streamGenerateContent`
+#[derive(Default)]
+pub struct GeminiAdapter {
+    /// Function calls seen so far in a stream; Gemini sends each one whole
+    tool_calls: usize,
+    saw_function_call: bool,
+}
+
+/// Gemini model names are used in the URL path, with or without the `models/` prefix
+fn gemini_model(model: &str) -> &str {
+    model.strip_prefix("models/").unwrap_or(model)
+}
+
+impl ProviderAdapter for GeminiAdapter {
+    fn chat_path(&self, req: &ChatRequest) -> String {
+        if req.stream {
+            format!("models/{}:streamGenerateContent?alt=sse", gemini_model(req.model))
+        } else {
+            format!("models/{}:generateContent", gemini_model(req.model))
+        }
+    }
+
+    fn chat_body(&self, req: &ChatRequest) -> Value {
+        let (system, contents) = gemini_contents(req.messages);
+        let mut body = json!({
+            "contents": contents,
+            "generationConfig": {
+                "temperature": req.temperature,
+                "maxOutputTokens": req.max_tokens
+            }
+        });
+        if let Some(format) = req.response_format {
+            body["generationConfig"]["responseMimeType"] = json!("application/json");
+            body["generationConfig"]["responseSchema"] = gemini_schema(&format.schema);
+        }
+        if let Some(reasoning) = req.reasoning {
+            body["generationConfig"]["thinkingConfig"] = json!({
+                "thinkingBudget": reasoning.budget_tokens(),
+                "includeThoughts": true
+            });
+        }
+        if let Some(system) = system {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+
+        let declarations: Vec<Value> = req
+            .tools
+            .iter()
+            .filter_map(|tool| {
+                let function = &tool["function"];
+                let mut declaration = json!({ "name": function["name"].as_str()? });
+                if let Some(description) = function["description"].as_str() {
+                    declaration["description"] = json!(description);
+                }
+                // Gemini rejects parameterless functions that declare an empty object schema
+                if function["parameters"]["properties"].as_object().is_some_and(|p| !p.is_empty()) {
+                    declaration["parameters"] = gemini_schema(&function["parameters"]);
+                }
+                Some(declaration)
+            })
+            .collect();
+        if !declarations.is_empty() {
+            body["tools"] = json!([{ "functionDeclarations": declarations }]);
+        }
+        body
+    }
+
+    fn parse_reply(&self, json: &Value, model: &str) -> ChatCompletionResult {
+        let candidate = &json["candidates"][0];
+        let mut text = String::new();
+        let mut thinking = String::new();
+        let mut tool_calls = Vec::new();
+        for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+            if let Some(t) = part["text"].as_str() {
+                // Thought summaries are not part of the answer
+                if part["thought"].as_bool().unwrap_or(false) {
+                    thinking.push_str(t);
+                } else {
+                    text.push_str(t);
+                }
+            } else if part["functionCall"].is_object() {
+                tool_calls.push(gemini_tool_call(&part["functionCall"], tool_calls.len()));
+            }
+        }
+
+        let finish_reason = if tool_calls.is_empty() {
+            gemini_finish_reason(candidate["finishReason"].as_str())
+        } else {
+            "tool_calls".to_string()
+        };
+
+        ChatCompletionResult {
+            id: json["responseId"].as_str().unwrap_or("").to_string(),
+            model: json["modelVersion"].as_str().unwrap_or(model).to_string(),
+            content: if text.is_empty() && !tool_calls.is_empty() { None } else { Some(text) },
+            finish_reason,
+            usage: gemini_usage(&json["usageMetadata"]),
+            tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+            reasoning: if thinking.is_empty() { None } else { Some(thinking) },
+            tool_call_issues: Vec::new(),
+        }
+    }
+
+    fn parse_stream_data(&mut self, data: &str) -> Vec<StreamEvent> {
+        let Ok(json) = serde_json::from_str::<Value>(data) else {
+            return Vec::new();
+        };
+        if json["error"].is_object() {
+            return vec![StreamEvent::Error(
+                json["error"]["message"].as_str().unwrap_or("Unknown error").to_string(),
+            )];
+        }
+
+        let candidate = &json["candidates"][0];
+        let mut events = vec![StreamEvent::Meta {
+            id: non_empty(&json["responseId"]),
+            model: non_empty(&json["modelVersion"]),
+        }];
+
+        for part in candidate["content"]["parts"].as_array().into_iter().flatten() {
+            if let Some(text) = non_empty(&part["text"]) {
+                if part["thought"].as_bool().unwrap_or(false) {
+                    events.push(StreamEvent::Reasoning(text));
+                } else {
+                    events.push(StreamEvent::Text(text));
+                }
+            } else if part["functionCall"].is_object() {
+                let call = gemini_tool_call(&part["functionCall"], self.tool_calls);
+                events.push(StreamEvent::ToolCallDelta {
+                    index: self.tool_calls,
+                    id: Some(call.id),
+                    name: Some(call.function.name),
+                    arguments: call.function.arguments,
+                });
+                self.tool_calls += 1;
+                self.saw_function_call = true;
+            }
+        }
+
+        // The stream has no terminator; the chunk carrying finishReason is the last one
+        if let Some(reason) = candidate["finishReason"].as_str() {
+            events.push(StreamEvent::Finish(if self.saw_function_call {
+                "tool_calls".to_string()
+            } else {
+                gemini_finish_reason(Some(reason))
+            }));
+            if let Some(usage) = gemini_usage(&json["usageMetadata"]) {
+                events.push(StreamEvent::Usage(usage));
+            }
+            events.push(StreamEvent::Done);
+        }
+        events
+    }
+
+    fn image_limits(&self) -> ImageLimits {
+        // Inline data shares the 20 MB request limit with the rest of the conversation.
+        // `fileData` only takes Gemini Files API URIs, so remote images are sent inline.
+        ImageLimits { max_edge: 3072, max_bytes: 7_000_000, remote_urls: false }
+    }
+
+    fn default_embedding_model(&self) -> &'static str {
+        "text-embedding-004"
+    }
+
+    fn embeddings_path(&self, model: &str, count: usize) -> Option<String> {
+        let model = gemini_model(model);
+        Some(if count == 1 {
+            format!("models/{}:embedContent", model)
+        } else {
+            format!("models/{}:batchEmbedContents", model)
+        })
+    }
+
+    fn embeddings_body(&self, model: &str, input: &[String]) -> Value {
+        let model = gemini_model(model);
+        match input {
+            [text] => json!({ "content": { "parts": [{ "text": text }] } }),
+            texts => json!({
+                "requests": texts
+                    .iter()
+                    .map(|text| json!({
+                        "model": format!("models/{}", model),
+                        "content": { "parts": [{ "text": text }] }
+                    }))
+                    .collect::<Vec<_>>()
+            }),
+        }
+    }
+
+    fn parse_embeddings(&self, json: &Value) -> (Vec<Vec<f32>>, Option<u32>) {
+        let embeddings = match json["embeddings"].as_array() {
+            Some(list) => list.iter().filter_map(|e| float_vector(&e["values"])).collect(),
+            None => float_vector(&json["embedding"]["values"]).into_iter().collect(),
+        };
+        (embeddings, None)
+    }
+}
+
+fn gemini_tool_call(call: &Value, index: usize) -> ToolCall {
+    ToolCall {
+        id: non_empty(&call["id"]).unwrap_or_else(|| format!("call_{}", index)),
+        call_type: "function".to_string(),
+        function: ToolFunction {
+            name: call["name"].as_str().unwrap_or("").to_string(),
+            arguments: if call["args"].is_object() {
+                call["args"].to_string()
+            } else {
+                "{}".to_string()
+            },
+        },
+    }
+}
+
+fn gemini_finish_reason(reason: Option<&str>) -> String {
+    match reason {
+        Some("MAX_TOKENS") => "length",
+        Some("SAFETY") | Some("RECITATION") | Some("BLOCKLIST") | Some("PROHIBITED_CONTENT") => "content_filter",
+        _ => "stop",
+    }
+    .to_string()
+}
+
+fn gemini_usage(usage: &Value) -> Option<TokenUsage> {
+    let usage = usage.as_object()?;
+    let count = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    let thoughts = usage.get("thoughtsTokenCount").and_then(|v| v.as_u64()).map(|t| t as u32);
+    // Thoughts are billed as output but counted separately from the candidates
+    Some(TokenUsage {
+        prompt_tokens: count("promptTokenCount"),
+        completion_tokens: count("candidatesTokenCount") + thoughts.unwrap_or(0),
+        total_tokens: count("totalTokenCount"),
+        reasoning_tokens: thoughts,
+    })
+}
+
+/// Convert OpenAI messages to Gemini `contents` plus a system instruction.
+/// Gemini identifies function responses by name, so tool results are matched to the
+/// assistant tool call that produced them.
+fn gemini_contents(messages: &[Value]) -> (Option<String>, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut call_names: HashMap<String, String> = HashMap::new();
+    let mut contents: Vec<(String, Vec<Value>)> = Vec::new();
+
+    for message in messages {
+        let (role, parts) = match message["role"].as_str().unwrap_or("user") {
+            "system" | "developer" => {
+                system.extend(gemini_parts(&message["content"]).into_iter().filter_map(|p| {
+                    p["text"].as_str().map(String::from)
+                }));
+                continue;
+            }
+            "assistant" => {
+                let mut parts = gemini_parts(&message["content"]);
+                for tc in message["tool_calls"].as_array().into_iter().flatten() {
+                    let name = tc["function"]["name"].as_str().unwrap_or("").to_string();
+                    if let Some(id) = tc["id"].as_str() {
+                        call_names.insert(id.to_string(), name.clone());
+                    }
+                    let arguments = tc["function"]["arguments"].as_str().unwrap_or("{}");
+                    parts.push(json!({
+                        "functionCall": {
+                            "name": name,
+                            "args": serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| json!({}))
+                        }
+                    }));
+                }
+                ("model", parts)
+            }
+            "tool" => {
+                let name = message["tool_call_id"]
+                    .as_str()
+                    .and_then(|id| call_names.get(id).cloned())
+                    .or_else(|| message["name"].as_str().map(String::from))
+                    .unwrap_or_default();
+                let text = match &message["content"] {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                // The response must be an object; wrap anything else
+                let response = match serde_json::from_str::<Value>(&text) {
+                    Ok(value @ Value::Object(_)) => value,
+                    _ => json!({ "content": text }),
+                };
+                ("user", vec![json!({ "functionResponse": { "name": name, "response": response } })])
+            }
+            _ => ("user", gemini_parts(&message["content"])),
+        };
+
+        if parts.is_empty() {
+            continue;
+        }
+        match contents.last_mut() {
+            Some((last_role, last_parts)) if *last_role == role => last_parts.extend(parts),
+            _ => contents.push((role.to_string(), parts)),
+        }
+    }
+
+    let system = if system.is_empty() { None } else { Some(system.join("\n\n")) };
+    let contents = contents
+        .into_iter()
+        .map(|(role, parts)| json!({ "role": role, "parts": parts }))
+        .collect();
+    (system, contents)
+}
+
+/// Convert OpenAI message content to Gemini parts. Other remote images were inlined by
+/// `inline_remote_images`, so image URLs left are Files API URIs and go in `fileData`.
+fn gemini_parts(content: &Value) -> Vec<Value> {
+    match content {
+        Value::String(text) if !text.is_empty() => vec![json!({ "text": text })],
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part["type"].as_str()? {
+                "text" => Some(json!({ "text": part["text"].as_str()? })),
+                "image_url" => {
+                    let url = part["image_url"]["url"].as_str().or(part["image_url"].as_str())?;
+                    Some(match url.strip_prefix("data:") {
+                        Some(rest) => {
+                            let (mime_type, data) = rest.split_once(";base64,")?;
+                            json!({ "inlineData": { "mimeType": mime_type, "data": data } })
+                        }
+                        None => {
+                            let path = url.split(['?', '#']).next().unwrap_or(url);
+                            let mime_type = image::ImageFormat::from_path(path).map_or("image/jpeg", |f| f.to_mime_type());
+                            json!({ "fileData": { "mimeType": mime_type, "fileUri": url } })
+                        }
+                    })
+                }
+                "file" => {
+                    let (mime_type, data) = part["file"]["file_data"].as_str()?.strip_prefix("data:")?.split_once(";base64,")?;
+                    Some(json!({ "inlineData": { "mimeType": mime_type, "data": data } }))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Drop JSON Schema keywords outside the OpenAPI subset Gemini accepts
+fn gemini_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| !matches!(key.as_str(), "$schema" | "$id" | "additionalProperties" | "default"))
+                .map(|(key, value)| {
+                    let value = match (key.as_str(), value) {
+                        // Property names are not keywords; only their schemas are cleaned
+                        ("properties", Value::Object(props)) => Value::Object(
+                            props.iter().map(|(name, s)| (name.clone(), gemini_schema(s))).collect(),
+                        ),
+                        _ => gemini_schema(value),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(gemini_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::llm_client::LlmClient;
+    use crate::services::llm_executor::{RetryPolicy, SendError};
+    use crate::services::provider_service::ProviderTimeouts;
+    use crate::services::stream_service::StreamHandle;
+    use crate::services::test_server::{Reply, TestServer};
+    use std::collections::BTreeMap;
+
+    fn gemini_client(server: &TestServer) -> LlmClient {
+        LlmClient::new(LlmTarget {
+            profile_id: Some("gemini".to_string()),
+            provider: "Gemini".to_string(),
+            kind: ProviderKind::Google,
+            base_url: format!("{}/v1beta", server.url),
+            api_key: Some("test-key".to_string()),
+            model: "gemini-2.0-flash".to_string(),
+            headers: BTreeMap::new(),
+            timeouts: ProviderTimeouts::default(),
+            retry: RetryPolicy { max_retries: 0, ..RetryPolicy::default() },
+            fallback_models: Vec::new(),
+            keyless: false,
+        })
+    }
+
+    fn chat_request<'a>(messages: &'a [Value], tools: &'a [Value], stream: bool) -> ChatRequest<'a> {
+        ChatRequest {
+            model: "gemini-2.0-flash",
+            messages,
+            tools,
+            temperature: 0.2,
+            max_tokens: 256,
+            stream,
+            reasoning: None,
+            response_format: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn gemini_generate_content_round_trip() {
+        let server = TestServer::start(|_| {
+            Reply::json(
+                200,
+                json!({
+                    "responseId": "resp-1",
+                    "modelVersion": "gemini-2.0-flash-001",
+                    "candidates": [{
+                        "content": { "role": "model", "parts": [
+                            { "text": "Let me look.", "thought": true },
+                            { "functionCall": { "name": "read_file", "args": { "path": "a.txt" } } }
+                        ] },
+                        "finishReason": "STOP"
+                    }],
+                    "usageMetadata": { "promptTokenCount": 12, "candidatesTokenCount": 5, "thoughtsTokenCount": 3, "totalTokenCount": 20 }
+                }),
+            )
+        })
+        .await;
+        let messages = vec![
+            json!({ "role": "system", "content": "Be brief." }),
+            json!({ "role": "user", "content": "Open a.txt" }),
+        ];
+        let tools = vec![json!({ "type": "function", "function": {
+            "name": "read_file",
+            "description": "Read a file",
+            "parameters": { "type": "object", "properties": { "path": { "type": "string" } }, "required": ["path"] }
+        } })];
+
+        let reply = gemini_client(&server)
+            .chat(&chat_request(&messages, &tools, false), &[])
+            .await
+            .unwrap();
+
+        assert_eq!(reply.id, "resp-1");
+        assert_eq!(reply.model, "gemini-2.0-flash-001");
+        assert_eq!(reply.finish_reason, "tool_calls");
+        assert_eq!(reply.content, None);
+        assert_eq!(reply.reasoning.as_deref(), Some("Let me look."));
+        let calls = reply.tool_calls.unwrap();
+        assert_eq!(calls[0].function.name, "read_file");
+        assert_eq!(serde_json::from_str::<Value>(&calls[0].function.arguments).unwrap(), json!({ "path": "a.txt" }));
+        let usage = reply.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens, usage.reasoning_tokens), (12, 8, Some(3)));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/v1beta/models/gemini-2.0-flash:generateContent");
+        assert_eq!(requests[0].header("x-goog-api-key"), Some("test-key"));
+        let body = requests[0].json();
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        assert_eq!(body["contents"], json!([{ "role": "user", "parts": [{ "text": "Open a.txt" }] }]));
+        assert_eq!(body["tools"][0]["functionDeclarations"][0]["name"], "read_file");
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], 256);
+    }
+
+    #[tokio::test]
+    async fn gemini_stream_generate_content_yields_events_in_order() {
+        let server = TestServer::start(|_| {
+            Reply::sse(&[
+                json!({ "responseId": "resp-2", "candidates": [{ "content": { "parts": [{ "text": "Hel" }] } }] }),
+                json!({ "candidates": [{ "content": { "parts": [{ "text": "lo" }] } }] }),
+                json!({
+                    "candidates": [{ "content": { "parts": [{ "text": "!" }] }, "finishReason": "MAX_TOKENS" }],
+                    "usageMetadata": { "promptTokenCount": 4, "candidatesTokenCount": 3, "totalTokenCount": 7 }
+                }),
+            ])
+        })
+        .await;
+        let messages = vec![json!({ "role": "user", "content": "Say hello" })];
+        let cancel = StreamHandle::new("main");
+
+        let mut stream = gemini_client(&server)
+            .chat_stream(&chat_request(&messages, &[], true), &[], &cancel)
+            .await
+            .unwrap();
+        let mut text = String::new();
+        let mut finish = None;
+        let mut usage = None;
+        let mut done = false;
+        while let Some(event) = stream.next_event().await {
+            match event.unwrap() {
+                StreamEvent::Text(delta) => text.push_str(&delta),
+                StreamEvent::Finish(reason) => finish = Some(reason),
+                StreamEvent::Usage(u) => usage = Some(u),
+                StreamEvent::Done => done = true,
+                _ => {}
+            }
+        }
+
+        assert_eq!(text, "Hello!");
+        assert_eq!(finish.as_deref(), Some("length"));
+        assert_eq!(usage.unwrap().total_tokens, 7);
+        assert!(done);
+        assert_eq!(server.requests()[0].path, "/v1beta/models/gemini-2.0-flash:streamGenerateContent?alt=sse");
+    }
+
+    #[tokio::test]
+    async fn gemini_embeddings_use_single_and_batch_endpoints() {
+        let server = TestServer::start(|request| {
+            if request.path.ends_with(":batchEmbedContents") {
+                Reply::json(200, json!({ "embeddings": [{ "values": [1.0, 0.0] }, { "values": [0.0, 1.0] }] }))
+            } else {
+                Reply::json(200, json!({ "embedding": { "values": [0.5, 0.5] } }))
+            }
+        })
+        .await;
+        let client = gemini_client(&server);
+
+        let single = client.embeddings(None, &["one".to_string()]).await.unwrap();
+        assert_eq!(single.model, "text-embedding-004");
+        assert_eq!(single.vectors, vec![vec![0.5, 0.5]]);
+
+        let batch = client
+            .embeddings(Some("models/text-embedding-004".to_string()), &["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(batch.vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1beta/models/text-embedding-004:embedContent");
+        assert_eq!(requests[0].json(), json!({ "content": { "parts": [{ "text": "one" }] } }));
+        assert_eq!(requests[1].path, "/v1beta/models/text-embedding-004:batchEmbedContents");
+        assert_eq!(requests[1].json()["requests"][1]["model"], "models/text-embedding-004");
+    }
+
+    #[tokio::test]
+    async fn embeddings_reject_a_reply_with_missing_vectors() {
+        let server = TestServer::start(|_| Reply::json(200, json!({ "embeddings": [{ "values": [1.0] }] }))).await;
+        let result = gemini_client(&server)
+            .embeddings(None, &["a".to_string(), "b".to_string()])
+            .await;
+        assert!(matches!(result, Err(SendError::InvalidResponse(_))));
+    }
+}
@@ -0,0 +1,618 @@
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use super::attachment_service::ImageLimits;
+use super::cache_service::{cache_key, CacheKind, ResponseCache};
+use super::config::is_local_llm_base_url;
+use super::llm_adapter::{adapter_for, ChatCompletionResult, ChatRequest, ProviderAdapter, StreamEvent, TokenUsage};
+use super::memory_service::{bytes_to_f32_vec, f32_vec_to_bytes};
+use super::llm_executor::{send_chat, send_with_retry, RetryPolicy, SendError};
+use super::provider_service::LlmTarget;
+use super::stream_service::StreamHandle;
+use super::token_service::{count_text, fit_to_context};
+use super::usage_service::{cost_usd, CallStatus, UsageEntry, UsageKind, UsageLedger};
+
+/// HTTP clients shared by every LLM request so connections are pooled. reqwest only sets the
+/// connect timeout per client, so there is one client per distinct timeout.
+pub(crate) fn shared_http_client(connect_secs: Option<u64>) -> reqwest::Client {
+    static CLIENTS: OnceLock<Mutex<HashMap<Option<u64>, reqwest::Client>>> = OnceLock::new();
+    let mut clients = CLIENTS.get_or_init(Default::default).lock().unwrap();
+    clients
+        .entry(connect_secs)
+        .or_insert_with(|| {
+            let mut builder = reqwest::Client::builder();
+            if let Some(secs) = connect_secs {
+                builder = builder.connect_timeout(Duration::from_secs(secs));
+            }
+            builder.build().unwrap_or_default()
+        })
+        .clone()
+}
+
+/// The one path for talking to the selected provider: chat, streamed chat, embeddings and
+/// raw JSON endpoints. Requests go through the retrying executor and the provider adapter.
+pub struct LlmClient {
+    target: LlmTarget,
+    usage: Option<UsageScope>,
+    cache: Option<ResponseCache>,
+}
+
+/// Where a client's calls are recorded
+#[derive(Clone)]
+struct UsageScope {
+    ledger: UsageLedger,
+    workspace: Option<String>,
+}
+
+pub struct Embeddings {
+    pub model: String,
+    /// Vectors in input order
+    pub vectors: Vec<Vec<f32>>,
+    pub token_count: Option<u32>,
+}
+
+/// A call in flight. Consumed when the call ends, writing its entry to the ledger.
+struct UsageTicket {
+    scope: UsageScope,
+    kind: UsageKind,
+    provider: String,
+    model: String,
+    local: bool,
+    started: Instant,
+    /// Part of the completion tokens spent thinking
+    reasoning_tokens: u32,
+}
+
+impl UsageTicket {
+    fn finish(self, prompt_tokens: u32, completion_tokens: u32, images: u32, status: CallStatus) {
+        // Local models run on the user's machine and cost nothing
+        let cost_usd = if self.local {
+            Some(0.0)
+        } else {
+            cost_usd(&self.model, prompt_tokens, completion_tokens, images)
+        };
+        let entry = UsageEntry {
+            kind: self.kind,
+            provider: self.provider,
+            model: self.model,
+            workspace: self.scope.workspace,
+            prompt_tokens,
+            completion_tokens,
+            reasoning_tokens: self.reasoning_tokens,
+            images,
+            latency: self.started.elapsed(),
+            status,
+            cost_usd,
+        };
+        if let Err(e) = self.scope.ledger.record(&entry) {
+            tracing::warn!("Could not record LLM usage: {}", e);
+        }
+    }
+}
+
+fn status_of(error: &SendError) -> CallStatus {
+    match error {
+        SendError::Cancelled => CallStatus::Cancelled,
+        _ => CallStatus::Error,
+    }
+}
+
+impl LlmClient {
+    pub fn new(target: LlmTarget) -> Self {
+        Self { target, usage: None, cache: None }
+    }
+
+    /// Record calls in `ledger` under `workspace` and enforce its daily budget
+    pub fn with_usage(mut self, ledger: UsageLedger, workspace: Option<String>) -> Self {
+        self.usage = Some(UsageScope { ledger, workspace });
+        self
+    }
+
+    /// Answer embeddings, and completions sent through `chat_cached`, from `cache`
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// What makes one endpoint's answers interchangeable: its protocol and base URL
+    fn cache_scope(&self) -> String {
+        format!("{:?}|{}", self.target.kind, self.target.base_url)
+    }
+
+    pub fn target(&self) -> &LlmTarget {
+        &self.target
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.target.is_configured()
+    }
+
+    /// Size images must be scaled to for this provider
+    pub fn image_limits(&self) -> ImageLimits {
+        adapter_for(&self.target).image_limits()
+    }
+
+    /// The requested model, or the profile's default
+    pub fn model_or_default(&self, model: Option<String>) -> String {
+        model.unwrap_or_else(|| self.target.model.clone())
+    }
+
+    /// The requested fallback models, or the profile's list
+    pub fn fallbacks_or_default(&self, fallback_models: Option<Vec<String>>) -> Vec<String> {
+        fallback_models.unwrap_or_else(|| self.target.fallback_models.clone())
+    }
+
+    /// Refuse to send once today's budget is spent. Local models are never limited.
+    fn check_budget(&self) -> Result<(), SendError> {
+        match &self.usage {
+            Some(scope) if !is_local_llm_base_url(&self.target.base_url) => {
+                scope.ledger.check_budget().map_err(SendError::BudgetExceeded)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn ticket(&self, kind: UsageKind, model: &str) -> Option<UsageTicket> {
+        self.usage.as_ref().map(|scope| UsageTicket {
+            scope: scope.clone(),
+            kind,
+            provider: self.target.provider.clone(),
+            model: model.to_string(),
+            local: is_local_llm_base_url(&self.target.base_url),
+            started: Instant::now(),
+            reasoning_tokens: 0,
+        })
+    }
+
+    /// Send a one-token request without retrying, to check the endpoint and key
+    pub async fn check_connection(&self) -> Result<(), SendError> {
+        let adapter = adapter_for(&self.target);
+        let messages = [serde_json::json!({"role": "user", "content": "Hello"})];
+        let chat = ChatRequest {
+            model: &self.target.model,
+            messages: &messages,
+            tools: &[],
+            temperature: 0.7,
+            max_tokens: 10,
+            stream: false,
+            reasoning: None,
+            response_format: None,
+        };
+        let path = adapter.chat_path(&chat);
+        let body = adapter.chat_body(&chat);
+        let no_retry = RetryPolicy { max_retries: 0, ..self.target.retry.clone() };
+        send_with_retry(&no_retry, None, || self.target.post(&path).json(&body)).await?;
+        Ok(())
+    }
+
+    /// Send a chat request. Histories longer than the model's context are trimmed first,
+    /// and `max_tokens` is capped to the room left after the prompt.
+    pub async fn chat(&self, chat: &ChatRequest<'_>, fallback_models: &[String]) -> Result<ChatCompletionResult, SendError> {
+        self.check_budget()?;
+        let adapter = adapter_for(&self.target);
+        let fitted = fit_to_context(chat.model, chat.messages, chat.tools, chat.max_tokens);
+        let chat = ChatRequest {
+            messages: fitted.messages.as_deref().unwrap_or(chat.messages),
+            max_tokens: fitted.max_tokens,
+            ..*chat
+        };
+        let mut ticket = self.ticket(UsageKind::Completion, chat.model);
+
+        let mut result = async {
+            let (response, used_model) = send_chat(&self.target, adapter.as_ref(), &chat, fallback_models, None).await?;
+            if let Some(ticket) = ticket.as_mut() {
+                ticket.model = used_model.clone();
+            }
+            let json: Value = response.json().await?;
+            Ok::<_, SendError>(adapter.parse_reply(&json, &used_model))
+        }
+        .await;
+
+        if let Ok(reply) = result.as_mut() {
+            if let Some(usage) = reply.usage.as_mut() {
+                estimate_reasoning_tokens(usage, &reply.model, reply.reasoning.as_deref());
+            }
+        }
+
+        if let Some(mut ticket) = ticket {
+            match &result {
+                Ok(reply) => {
+                    let (prompt, completion) = match &reply.usage {
+                        Some(usage) => {
+                            ticket.reasoning_tokens = usage.reasoning_tokens.unwrap_or(0);
+                            (usage.prompt_tokens, usage.completion_tokens)
+                        }
+                        None => {
+                            let thinking = count_text(&ticket.model, reply.reasoning.as_deref().unwrap_or_default()) as u32;
+                            ticket.reasoning_tokens = thinking;
+                            let answer = count_text(&ticket.model, reply.content.as_deref().unwrap_or_default()) as u32;
+                            (fitted.prompt_tokens as u32, answer + thinking)
+                        }
+                    };
+                    ticket.finish(prompt, completion, 0, CallStatus::Ok);
+                }
+                Err(e) => ticket.finish(0, 0, 0, status_of(e)),
+            }
+        }
+        result
+    }
+
+    /// Send a chat request, answering repeats of an identical request from the cache.
+    /// Callers opt in for deterministic prompts, such as those at temperature 0.
+    pub async fn chat_cached(&self, chat: &ChatRequest<'_>, fallback_models: &[String]) -> Result<ChatCompletionResult, SendError> {
+        let Some(cache) = &self.cache else {
+            return self.chat(chat, fallback_models).await;
+        };
+        let messages = serde_json::to_string(chat.messages).unwrap_or_default();
+        let tools = serde_json::to_string(chat.tools).unwrap_or_default();
+        let key = cache_key(&[
+            CacheKind::Completion.as_str(),
+            &self.cache_scope(),
+            chat.model,
+            &messages,
+            &tools,
+            &chat.temperature.to_string(),
+            &chat.max_tokens.to_string(),
+            &chat.reasoning.map(|r| r.key()).unwrap_or_default(),
+            &chat.response_format.map(|f| format!("{}:{}", f.name, f.schema)).unwrap_or_default(),
+        ]);
+
+        if let Some(reply) = cache
+            .get(CacheKind::Completion, &key)
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        {
+            return Ok(reply);
+        }
+
+        let reply = self.chat(chat, fallback_models).await?;
+        // A reply cut off at the token limit is not worth repeating
+        if reply.finish_reason != "length" {
+            if let Ok(bytes) = serde_json::to_vec(&reply) {
+                cache.put(CacheKind::Completion, &key, &reply.model, &bytes);
+            }
+        }
+        Ok(reply)
+    }
+
+    /// Start a streamed chat, trimmed like `chat`. Waiting for the response ends early with
+    /// `SendError::Cancelled` when `cancel` fires.
+    pub async fn chat_stream(
+        &self,
+        chat: &ChatRequest<'_>,
+        fallback_models: &[String],
+        cancel: &StreamHandle,
+    ) -> Result<ChatStream, SendError> {
+        self.check_budget()?;
+        let adapter = adapter_for(&self.target);
+        let fitted = fit_to_context(chat.model, chat.messages, chat.tools, chat.max_tokens);
+        let chat = ChatRequest {
+            messages: fitted.messages.as_deref().unwrap_or(chat.messages),
+            max_tokens: fitted.max_tokens,
+            ..*chat
+        };
+        let mut ticket = self.ticket(UsageKind::Completion, chat.model);
+
+        let (response, model) = match send_chat(&self.target, adapter.as_ref(), &chat, fallback_models, Some(cancel)).await {
+            Ok(sent) => sent,
+            Err(e) => {
+                if let Some(ticket) = ticket {
+                    ticket.finish(0, 0, 0, status_of(&e));
+                }
+                return Err(e);
+            }
+        };
+        if let Some(ticket) = ticket.as_mut() {
+            ticket.model = model.clone();
+        }
+
+        Ok(ChatStream {
+            model,
+            response,
+            adapter,
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+            finished: false,
+            ticket,
+            prompt_estimate: fitted.prompt_tokens as u32,
+            output: String::new(),
+            reasoning: String::new(),
+            usage: None,
+            done: false,
+            failed: false,
+        })
+    }
+
+    /// Embed `input`, sending only the texts the cache cannot answer. A fully cached
+    /// request never reaches the provider and is not recorded as usage.
+    pub async fn embeddings(&self, model: Option<String>, input: &[String]) -> Result<Embeddings, SendError> {
+        let adapter = adapter_for(&self.target);
+        let model = model.unwrap_or_else(|| adapter.default_embedding_model().to_string());
+
+        let scope = self.cache_scope();
+        let keys: Vec<String> = input
+            .iter()
+            .map(|text| cache_key(&[CacheKind::Embedding.as_str(), &scope, &model, text]))
+            .collect();
+        let mut vectors: Vec<Option<Vec<f32>>> = match &self.cache {
+            Some(cache) => cache
+                .get_many(CacheKind::Embedding, &keys)
+                .into_iter()
+                .map(|bytes| bytes.map(|b| bytes_to_f32_vec(&b)))
+                .collect(),
+            None => vec![None; input.len()],
+        };
+        let missing: Vec<usize> = (0..input.len()).filter(|&i| vectors[i].is_none()).collect();
+        if missing.is_empty() {
+            return Ok(Embeddings {
+                model,
+                vectors: vectors.into_iter().flatten().collect(),
+                token_count: Some(0),
+            });
+        }
+        let pending: Vec<String> = missing.iter().map(|&i| input[i].clone()).collect();
+
+        let Some(path) = adapter.embeddings_path(&model, pending.len()) else {
+            return Err(SendError::Unsupported(format!(
+                "{} does not offer embeddings; switch to another provider profile.",
+                self.target.provider
+            )));
+        };
+        self.check_budget()?;
+        let ticket = self.ticket(UsageKind::Embedding, &model);
+
+        let body = adapter.embeddings_body(&model, &pending);
+        let result = async {
+            let response = send_with_retry(&self.target.retry, None, || self.target.post(&path).json(&body)).await?;
+            let json: Value = response.json().await?;
+            let (vectors, token_count) = adapter.parse_embeddings(&json);
+            // A short reply would leave inputs without vectors, or pair them with the wrong ones
+            if vectors.len() != pending.len() {
+                return Err(SendError::InvalidResponse(format!(
+                    "expected {} embeddings, got {}",
+                    pending.len(),
+                    vectors.len()
+                )));
+            }
+            Ok((vectors, token_count))
+        }
+        .await;
+
+        if let Some(ticket) = ticket {
+            match &result {
+                Ok((_, token_count)) => {
+                    let tokens = token_count
+                        .unwrap_or_else(|| pending.iter().map(|text| count_text(&model, text) as u32).sum());
+                    ticket.finish(tokens, 0, 0, CallStatus::Ok);
+                }
+                Err(e) => ticket.finish(0, 0, 0, status_of(e)),
+            }
+        }
+        let (fresh, token_count) = result?;
+
+        if let Some(cache) = &self.cache {
+            let entries: Vec<(String, Vec<u8>)> = missing
+                .iter()
+                .zip(&fresh)
+                .map(|(&i, vector)| (keys[i].clone(), f32_vec_to_bytes(vector)))
+                .collect();
+            cache.put_many(CacheKind::Embedding, &model, &entries);
+        }
+        for (&i, vector) in missing.iter().zip(fresh) {
+            vectors[i] = Some(vector);
+        }
+
+        Ok(Embeddings {
+            model,
+            vectors: vectors.into_iter().map(Option::unwrap_or_default).collect(),
+            token_count,
+        })
+    }
+
+    /// Generate images from an OpenAI-style `images/generations` body
+    pub async fn generate_images(&self, model: &str, body: &Value) -> Result<Value, SendError> {
+        self.check_budget()?;
+        let ticket = self.ticket(UsageKind::Image, model);
+        let result = self
+            .post_json("images/generations", body, Some(Duration::from_secs(120)))
+            .await;
+
+        if let Some(ticket) = ticket {
+            match &result {
+                Ok(json) => {
+                    let images = json["data"].as_array().map_or(0, |data| data.len() as u32);
+                    ticket.finish(0, 0, images, CallStatus::Ok);
+                }
+                Err(e) => ticket.finish(0, 0, 0, status_of(e)),
+            }
+        }
+        result
+    }
+
+    /// POST a JSON body to an endpoint the adapters do not cover.
+    /// `timeout` overrides the profile's request timeout.
+    pub async fn post_json(&self, path: &str, body: &Value, timeout: Option<Duration>) -> Result<Value, SendError> {
+        let build = || {
+            let req = self.target.post(path).json(body);
+            match timeout {
+                Some(timeout) => req.timeout(timeout),
+                None => req,
+            }
+        };
+        let response = send_with_retry(&self.target.retry, None, build).await?;
+        Ok(response.json().await?)
+    }
+}
+
+/// A streamed chat response, decoded into provider-neutral events. Usage is recorded when
+/// the stream is dropped, as cancelled unless the reply was complete.
+pub struct ChatStream {
+    /// Model that accepted the request; differs from the requested one after a fallback
+    pub model: String,
+    response: reqwest::Response,
+    adapter: Box<dyn ProviderAdapter>,
+    parser: SseParser,
+    pending: VecDeque<StreamEvent>,
+    finished: bool,
+    ticket: Option<UsageTicket>,
+    /// Used with `output` when the provider reports no usage
+    prompt_estimate: u32,
+    output: String,
+    reasoning: String,
+    usage: Option<TokenUsage>,
+    done: bool,
+    failed: bool,
+}
+
+impl ChatStream {
+    /// Next event, or `None` once the body ends. Dropping the future between chunks is safe,
+    /// so it can be raced against cancellation.
+    pub async fn next_event(&mut self) -> Option<Result<StreamEvent, reqwest::Error>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                match &event {
+                    StreamEvent::Text(delta) => self.output.push_str(delta),
+                    StreamEvent::Reasoning(delta) => self.reasoning.push_str(delta),
+                    StreamEvent::Usage(usage) => {
+                        let mut usage = usage.clone();
+                        estimate_reasoning_tokens(&mut usage, &self.model, Some(&self.reasoning));
+                        self.usage = Some(usage.clone());
+                        return Some(Ok(StreamEvent::Usage(usage)));
+                    }
+                    StreamEvent::Error(_) => self.failed = true,
+                    StreamEvent::Done => self.done = true,
+                    _ => {}
+                }
+                return Some(Ok(event));
+            }
+            if self.finished {
+                self.done = true;
+                return None;
+            }
+
+            let payloads = match self.response.chunk().await {
+                Ok(Some(chunk)) => self.parser.push(&chunk),
+                Ok(None) => {
+                    self.finished = true;
+                    self.parser.finish()
+                }
+                Err(e) => {
+                    self.finished = true;
+                    self.failed = true;
+                    return Some(Err(e));
+                }
+            };
+            for data in payloads {
+                self.pending.extend(self.adapter.parse_stream_data(&data));
+            }
+        }
+    }
+}
+
+impl Drop for ChatStream {
+    fn drop(&mut self) {
+        let Some(mut ticket) = self.ticket.take() else {
+            return;
+        };
+        let status = if self.failed {
+            CallStatus::Error
+        } else if self.done {
+            CallStatus::Ok
+        } else {
+            CallStatus::Cancelled
+        };
+        let (prompt, completion) = match &self.usage {
+            Some(usage) => {
+                ticket.reasoning_tokens = usage.reasoning_tokens.unwrap_or(0);
+                (usage.prompt_tokens, usage.completion_tokens)
+            }
+            None => {
+                let thinking = count_text(&ticket.model, &self.reasoning) as u32;
+                ticket.reasoning_tokens = thinking;
+                (self.prompt_estimate, count_text(&ticket.model, &self.output) as u32 + thinking)
+            }
+        };
+        ticket.finish(prompt, completion, 0, status);
+    }
+}
+
+/// Fill in reasoning tokens for providers that bill thinking as output without
+/// reporting it separately, such as Anthropic
+fn estimate_reasoning_tokens(usage: &mut TokenUsage, model: &str, reasoning: Option<&str>) {
+    if usage.reasoning_tokens.is_some() {
+        return;
+    }
+    if let Some(text) = reasoning.filter(|text| !text.is_empty()) {
+        usage.reasoning_tokens = Some((count_text(model, text) as u32).min(usage.completion_tokens));
+    }
+}
+
+/// Splits a server-sent event body into `data:` payloads. Bytes are buffered until a full
+/// line arrives, so multi-byte characters split across chunks survive. Every `data:` line is
+/// one payload: providers send one JSON object per line, and adapters read the event type
+/// from the payload, so `event:`, `id:` and comment lines are skipped.
+#[derive(Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+        let mut payloads = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            payloads.extend(Self::data(&line));
+        }
+        payloads
+    }
+
+    /// Flush a last line that was not terminated by a newline
+    pub fn finish(&mut self) -> Vec<String> {
+        let line = std::mem::take(&mut self.buffer);
+        Self::data(&line).into_iter().collect()
+    }
+
+    fn data(line: &[u8]) -> Option<String> {
+        let line = String::from_utf8_lossy(line);
+        let data = line.trim().strip_prefix("data:")?.trim_start();
+        (!data.is_empty()).then(|| data.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SseParser;
+
+    #[test]
+    fn sse_parser_joins_lines_split_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: {\"a\":").is_empty());
+        assert_eq!(parser.push(b"1}\n\ndata: [DONE]\n"), vec!["{\"a\":1}", "[DONE]"]);
+    }
+
+    #[test]
+    fn sse_parser_keeps_multibyte_characters_split_across_chunks() {
+        let bytes = "data: héllo\n".as_bytes();
+        let split = bytes.iter().position(|&b| b == 0xC3).unwrap() + 1;
+        let mut parser = SseParser::default();
+        assert!(parser.push(&bytes[..split]).is_empty());
+        assert_eq!(parser.push(&bytes[split..]), vec!["héllo"]);
+    }
+
+    #[test]
+    fn sse_parser_skips_non_data_lines() {
+        let mut parser = SseParser::default();
+        let payloads = parser.push(b": keep-alive\nevent: message_start\nid: 7\ndata:\r\ndata:{\"b\":2}\r\n");
+        assert_eq!(payloads, vec!["{\"b\":2}"]);
+    }
+
+    #[test]
+    fn sse_parser_flushes_an_unterminated_last_line() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: last").is_empty());
+        assert_eq!(parser.finish(), vec!["last"]);
+        assert!(parser.finish().is_empty());
+    }
+}
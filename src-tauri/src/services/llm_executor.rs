@@ -0,0 +1,287 @@
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use thiserror::Error;
+
+use super::llm_adapter::{ChatRequest, ProviderAdapter};
+use super::provider_service::LlmTarget;
+use super::stream_service::StreamHandle;
+
+#[derive(Error, Debug)]
+pub enum SendError {
+    #[error("HTTP {status}: {body}")]
+    Http { status: StatusCode, body: String },
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Request cancelled")]
+    Cancelled,
+    /// The provider has no endpoint for the operation
+    #[error("{0}")]
+    Unsupported(String),
+    /// The provider answered with something other than what was asked for
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+    /// Today's usage budget is spent
+    #[error("{0}")]
+    BudgetExceeded(String),
+}
+
+impl SendError {
+    /// Error code for `ApiResponse`, with `default` for request failures
+    pub fn code(&self, default: &'static str) -> &'static str {
+        match self {
+            SendError::Unsupported(_) => "UNSUPPORTED",
+            SendError::BudgetExceeded(_) => "BUDGET_EXCEEDED",
+            _ => default,
+        }
+    }
+}
+
+/// How transient failures are retried for a provider profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    /// Upper bound for a single wait, including waits requested by `Retry-After`
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with jitter over the upper half of the window
+    fn backoff(&self, attempt: u32) -> Duration {
+        let window = self
+            .base_delay_ms
+            .saturating_mul(1u64 << attempt.min(16))
+            .min(self.max_delay_ms);
+        let half = window / 2;
+        Duration::from_millis(half + random_u64() % (window - half + 1))
+    }
+}
+
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+/// Rate-limited or overloaded: another model may still answer right away
+fn is_overloaded(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 503 | 529)
+}
+
+/// Parse `Retry-After` as delay seconds or an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+}
+
+async fn with_cancel<T>(cancel: Option<&StreamHandle>, future: impl Future<Output = T>) -> Result<T, SendError> {
+    match cancel {
+        Some(handle) => tokio::select! {
+            out = future => Ok(out),
+            _ = handle.cancelled() => Err(SendError::Cancelled),
+        },
+        None => Ok(future.await),
+    }
+}
+
+/// Send a request, retrying rate limits, overloads, 5xx responses and connection errors
+/// with backoff. `build` is called once per attempt.
+pub async fn send_with_retry(
+    policy: &RetryPolicy,
+    cancel: Option<&StreamHandle>,
+    build: impl Fn() -> RequestBuilder,
+) -> Result<Response, SendError> {
+    retry_loop(policy, cancel, build, false).await
+}
+
+/// With `yield_on_overload`, rate-limited and overloaded responses are returned at once so
+/// the caller can move on to a fallback model instead of waiting.
+async fn retry_loop(
+    policy: &RetryPolicy,
+    cancel: Option<&StreamHandle>,
+    build: impl Fn() -> RequestBuilder,
+    yield_on_overload: bool,
+) -> Result<Response, SendError> {
+    let max_delay = Duration::from_millis(policy.max_delay_ms);
+    let mut attempt = 0;
+
+    loop {
+        let delay = match with_cancel(cancel, build().send()).await? {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let status = response.status();
+                if !is_retryable(status)
+                    || attempt >= policy.max_retries
+                    || (yield_on_overload && is_overloaded(status))
+                {
+                    let body = response.text().await.unwrap_or_default();
+                    return Err(SendError::Http { status, body });
+                }
+                retry_after(&response)
+                    .unwrap_or_else(|| policy.backoff(attempt))
+                    .min(max_delay)
+            }
+            Err(e) if attempt < policy.max_retries && (e.is_connect() || e.is_timeout()) => {
+                policy.backoff(attempt)
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        attempt += 1;
+        tracing::debug!("Retrying LLM request in {:?} (attempt {})", delay, attempt);
+        with_cancel(cancel, tokio::time::sleep(delay)).await?;
+    }
+}
+
+/// Send a chat request with retries. When the model is rate-limited or overloaded, the
+/// request moves on to the next of `fallback_models`. Returns the response and the model
+/// that produced it.
+pub async fn send_chat(
+    target: &LlmTarget,
+    adapter: &dyn ProviderAdapter,
+    chat: &ChatRequest<'_>,
+    fallback_models: &[String],
+    cancel: Option<&StreamHandle>,
+) -> Result<(Response, String), SendError> {
+    let mut models = vec![chat.model.to_string()];
+    for model in fallback_models {
+        if !models.contains(model) {
+            models.push(model.clone());
+        }
+    }
+
+    for (i, model) in models.iter().enumerate() {
+        let has_fallback = i + 1 < models.len();
+        let request = ChatRequest { model, ..*chat };
+        let path = adapter.chat_path(&request);
+        let body = adapter.chat_body(&request);
+        let build = || {
+            let req = if request.stream { target.post_stream(&path) } else { target.post(&path) };
+            req.json(&body)
+        };
+
+        match retry_loop(&target.retry, cancel, build, has_fallback).await {
+            Err(SendError::Http { status, .. }) if has_fallback && is_overloaded(status) => {
+                tracing::info!("Model {} unavailable ({}), falling back to {}", model, status, models[i + 1]);
+            }
+            result => return result.map(|response| (response, model.clone())),
+        }
+    }
+
+    unreachable!("the requested model is always tried")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_server::{Reply, TestServer};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Answers with each status in turn, then with 200
+    async fn serve(statuses: Vec<u16>) -> TestServer {
+        let statuses = Mutex::new(VecDeque::from(statuses));
+        TestServer::start(move |_| Reply::text(statuses.lock().unwrap().pop_front().unwrap_or(200), "ok")).await
+    }
+
+    fn policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay_ms: 1,
+            max_delay_ms: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_until_success() {
+        let server = serve(vec![503, 429, 200]).await;
+        let client = reqwest::Client::new();
+        let response = send_with_retry(&policy(3), None, || client.get(&server.url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let server = serve(vec![400, 200]).await;
+        let client = reqwest::Client::new();
+        let result = send_with_retry(&policy(3), None, || client.get(&server.url)).await;
+        assert!(matches!(result, Err(SendError::Http { status, .. }) if status == StatusCode::BAD_REQUEST));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let server = serve(vec![500, 502, 504, 200]).await;
+        let client = reqwest::Client::new();
+        let result = send_with_retry(&policy(2), None, || client.get(&server.url)).await;
+        assert!(matches!(result, Err(SendError::Http { status, .. }) if status == StatusCode::GATEWAY_TIMEOUT));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn yields_overloads_to_the_fallback_model() {
+        let server = serve(vec![529, 200]).await;
+        let client = reqwest::Client::new();
+        let result = retry_loop(&policy(3), None, || client.get(&server.url), true).await;
+        assert!(matches!(result, Err(SendError::Http { status, .. }) if status.as_u16() == 529));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_the_backoff() {
+        let server = serve(vec![503, 503]).await;
+        let client = reqwest::Client::new();
+        let slow = RetryPolicy {
+            max_retries: 1,
+            base_delay_ms: 60_000,
+            max_delay_ms: 60_000,
+        };
+        let handle = StreamHandle::new("main");
+        handle.cancel();
+        let result = send_with_retry(&slow, Some(&handle), || client.get(&server.url)).await;
+        assert!(matches!(result, Err(SendError::Cancelled)));
+    }
+
+    #[test]
+    fn backoff_stays_within_the_window() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+        };
+        for _ in 0..20 {
+            let first = policy.backoff(0).as_millis();
+            assert!((50..=100).contains(&first), "{}", first);
+            let capped = policy.backoff(10).as_millis();
+            assert!((500..=1_000).contains(&capped), "{}", capped);
+        }
+    }
+}
@@ -0,0 +1,892 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+
+use super::agent_tools::{Tool, ToolContext, ToolError};
+use super::llm_client::shared_http_client;
+
+/// Protocol revision sent in `initialize`
+const PROTOCOL_VERSION: &str = "2025-03-26";
+/// `initialize` may wait for a package runner such as `npx` to download the server
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+/// Providers reject tool names longer than this
+const MAX_TOOL_NAME_LEN: usize = 64;
+
+#[derive(Error, Debug)]
+pub enum McpError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("HTTP {status}: {body}")]
+    HttpStatus { status: u16, body: String },
+    #[error("Server error {code}: {message}")]
+    Rpc { code: i64, message: String },
+    #[error("Invalid message from server: {0}")]
+    Protocol(String),
+    #[error("No response within {0}s")]
+    Timeout(u64),
+    #[error("Server closed the connection")]
+    Closed,
+    #[error("Unknown MCP tool: {0}")]
+    UnknownTool(String),
+    #[error("Unknown MCP server: {0}")]
+    UnknownServer(String),
+}
+
+/// How to reach a server: a command speaking newline-delimited JSON-RPC on stdio,
+/// or a streamable HTTP endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum McpTransportConfig {
+    Stdio {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        env: HashMap<String, String>,
+    },
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    #[serde(flatten)]
+    pub transport: McpTransportConfig,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// Per-workspace MCP servers, stored in `.sentinelops/mcp.json` in the
+/// `{ "mcpServers": { "<name>": { ... } } }` layout other MCP clients use
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct McpConfig {
+    pub mcp_servers: HashMap<String, McpServerConfig>,
+}
+
+impl McpConfig {
+    fn path(workspace: &Path) -> PathBuf {
+        workspace.join(".sentinelops").join("mcp.json")
+    }
+
+    /// The workspace's servers; no file means no servers
+    pub fn load(workspace: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(Self::path(workspace)) {
+            Ok(json) => serde_json::from_str(&json)
+                .map_err(|e| format!("Invalid .sentinelops/mcp.json: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read .sentinelops/mcp.json: {}", e)),
+        }
+    }
+}
+
+impl McpServerConfig {
+    /// What connecting runs or reaches, for asking the user
+    pub fn describe(&self) -> String {
+        match &self.transport {
+            McpTransportConfig::Stdio { command, args, .. } => {
+                std::iter::once(command.as_str()).chain(args.iter().map(String::as_str)).collect::<Vec<_>>().join(" ")
+            }
+            McpTransportConfig::Http { url, .. } => url.clone(),
+        }
+    }
+
+    /// Hash of everything that decides what runs, so changing the config asks again
+    fn fingerprint(&self) -> String {
+        let canonical = match &self.transport {
+            McpTransportConfig::Stdio { command, args, env } => json!({
+                "command": command,
+                "args": args,
+                "env": env.iter().collect::<BTreeMap<_, _>>(),
+            }),
+            McpTransportConfig::Http { url, headers } => json!({
+                "url": url,
+                "headers": headers.iter().collect::<BTreeMap<_, _>>(),
+            }),
+        };
+        hex::encode(Sha256::digest(canonical.to_string().as_bytes()))
+    }
+}
+
+fn trust_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("SentinelOps").join("mcp_trust.json"))
+}
+
+/// Servers the user has allowed to run, per workspace. Kept in the app's config
+/// directory rather than the workspace, so a cloned repository cannot approve its own
+/// servers. An entry covers one exact config: editing a server's command asks again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct McpTrust {
+    /// Workspace path -> server name -> fingerprint of the approved config
+    workspaces: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl McpTrust {
+    pub fn load() -> Self {
+        trust_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(path) = trust_path() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+            std::fs::write(&path, json).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn is_trusted(&self, workspace: &Path, name: &str, config: &McpServerConfig) -> bool {
+        self.workspaces
+            .get(&workspace.to_string_lossy().into_owned())
+            .and_then(|servers| servers.get(name))
+            .is_some_and(|fingerprint| *fingerprint == config.fingerprint())
+    }
+
+    /// Allow the server's current config to run, or withdraw the approval
+    pub fn set_trusted(&mut self, workspace: &Path, name: &str, config: &McpServerConfig, trusted: bool) {
+        let key = workspace.to_string_lossy().into_owned();
+        if trusted {
+            self.workspaces
+                .entry(key)
+                .or_default()
+                .insert(name.to_string(), config.fingerprint());
+        } else if let Some(servers) = self.workspaces.get_mut(&key) {
+            servers.remove(name);
+            if servers.is_empty() {
+                self.workspaces.remove(&key);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default = "empty_object_schema")]
+    pub input_schema: Value,
+}
+
+fn empty_object_schema() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResource {
+    pub uri: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPromptArgument {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpPrompt {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub arguments: Vec<McpPromptArgument>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolResult {
+    /// Text of the result's content blocks, with other blocks summarized
+    pub text: String,
+    pub is_error: bool,
+    /// The content blocks as the server sent them
+    pub content: Vec<Value>,
+}
+
+impl McpToolResult {
+    fn from_result(result: &Value) -> Self {
+        let content = result["content"].as_array().cloned().unwrap_or_default();
+        let text = content
+            .iter()
+            .map(|block| match block["type"].as_str() {
+                Some("text") => block["text"].as_str().unwrap_or_default().to_string(),
+                Some("resource") => block["resource"]["text"]
+                    .as_str()
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("[resource: {}]", block["resource"]["uri"].as_str().unwrap_or("?"))),
+                Some(kind) => format!("[{}: {}]", kind, block["mimeType"].as_str().unwrap_or("unknown")),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        Self {
+            text,
+            is_error: result["isError"].as_bool().unwrap_or(false),
+            content,
+        }
+    }
+}
+
+fn parse_response(message: Value) -> Result<Value, McpError> {
+    if let Some(error) = message.get("error") {
+        return Err(McpError::Rpc {
+            code: error["code"].as_i64().unwrap_or(0),
+            message: error["message"].as_str().unwrap_or("Unknown error").to_string(),
+        });
+    }
+    message
+        .get("result")
+        .cloned()
+        .ok_or_else(|| McpError::Protocol("response has neither result nor error".to_string()))
+}
+
+type Pending = Arc<Mutex<HashMap<u64, oneshot::Sender<Result<Value, McpError>>>>>;
+
+/// A server process. It is killed when the transport is dropped.
+struct StdioTransport {
+    _child: Child,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+}
+
+impl StdioTransport {
+    fn spawn(
+        name: &str,
+        command: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+        cwd: &Path,
+    ) -> Result<Self, McpError> {
+        // Package runners such as npx are .cmd shims on Windows and need a shell
+        #[cfg(windows)]
+        let mut cmd = {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C").arg(command).creation_flags(0x08000000);
+            cmd
+        };
+        #[cfg(not(windows))]
+        let mut cmd = Command::new(command);
+
+        let mut child = cmd
+            .args(args)
+            .envs(env)
+            .current_dir(cwd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = Arc::new(tokio::sync::Mutex::new(
+            child.stdin.take().ok_or(McpError::Closed)?,
+        ));
+        let stdout = child.stdout.take().ok_or(McpError::Closed)?;
+        let pending: Pending = Arc::default();
+
+        if let Some(stderr) = child.stderr.take() {
+            let name = name.to_string();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("MCP server {}: {}", name, line);
+                }
+            });
+        }
+
+        let reader_pending = pending.clone();
+        let reader_stdin = stdin.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let message: Value = match serde_json::from_str(&line) {
+                    Ok(message) => message,
+                    Err(_) => {
+                        tracing::debug!("MCP server {} wrote a non-JSON line: {}", name, line);
+                        continue;
+                    }
+                };
+
+                if let Some(method) = message["method"].as_str() {
+                    // A request from the server; notifications need no answer
+                    if let Some(id) = message.get("id") {
+                        let reply = match method {
+                            "ping" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
+                            _ => json!({
+                                "jsonrpc": "2.0",
+                                "id": id,
+                                "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+                            }),
+                        };
+                        let _ = write_line(&reader_stdin, &reply).await;
+                    }
+                    continue;
+                }
+
+                if let Some(id) = message["id"].as_u64() {
+                    if let Some(tx) = reader_pending.lock().unwrap().remove(&id) {
+                        let _ = tx.send(parse_response(message));
+                    }
+                }
+            }
+
+            tracing::info!("MCP server {} closed its output", name);
+            for (_, tx) in reader_pending.lock().unwrap().drain() {
+                let _ = tx.send(Err(McpError::Closed));
+            }
+        });
+
+        Ok(Self {
+            _child: child,
+            stdin,
+            pending,
+        })
+    }
+
+    async fn request(&self, id: u64, message: &Value, timeout: Duration) -> Result<Value, McpError> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+
+        if let Err(e) = write_line(&self.stdin, message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(McpError::Closed),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(McpError::Timeout(timeout.as_secs()))
+            }
+        }
+    }
+
+    async fn notify(&self, message: &Value) -> Result<(), McpError> {
+        write_line(&self.stdin, message).await
+    }
+}
+
+async fn write_line(stdin: &tokio::sync::Mutex<ChildStdin>, message: &Value) -> Result<(), McpError> {
+    let mut line = serde_json::to_vec(message).map_err(|e| McpError::Protocol(e.to_string()))?;
+    line.push(b'\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&line).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+/// A streamable HTTP endpoint. Every message is a POST; replies come back as JSON or as
+/// an SSE stream carrying the response.
+struct HttpTransport {
+    url: String,
+    headers: HashMap<String, String>,
+    /// Assigned by the server in the `initialize` response
+    session_id: Mutex<Option<String>>,
+}
+
+impl HttpTransport {
+    async fn post(&self, message: &Value, timeout: Duration) -> Result<reqwest::Response, McpError> {
+        let mut req = shared_http_client(Some(10))
+            .post(&self.url)
+            .header("Accept", "application/json, text/event-stream")
+            .timeout(timeout)
+            .json(message);
+        for (name, value) in &self.headers {
+            req = req.header(name, value);
+        }
+        let session = self.session_id.lock().unwrap().clone();
+        if let Some(session) = session {
+            req = req.header("Mcp-Session-Id", session);
+        }
+
+        let response = req.send().await?;
+        if !response.status().is_success() {
+            return Err(McpError::HttpStatus {
+                status: response.status().as_u16(),
+                body: response.text().await.unwrap_or_default(),
+            });
+        }
+        if let Some(session) = response.headers().get("mcp-session-id").and_then(|v| v.to_str().ok()) {
+            *self.session_id.lock().unwrap() = Some(session.to_string());
+        }
+        Ok(response)
+    }
+
+    async fn request(&self, id: u64, message: &Value, timeout: Duration) -> Result<Value, McpError> {
+        let response = self.post(message, timeout).await?;
+        let is_sse = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|t| t.starts_with("text/event-stream"));
+        let body = response.text().await?;
+
+        if !is_sse {
+            let message: Value = serde_json::from_str(&body).map_err(|e| McpError::Protocol(e.to_string()))?;
+            return parse_response(message);
+        }
+
+        // The stream may carry server requests and notifications before our response
+        for line in body.lines() {
+            let Some(data) = line.strip_prefix("data:") else {
+                continue;
+            };
+            let Ok(message) = serde_json::from_str::<Value>(data.trim()) else {
+                continue;
+            };
+            if message["id"].as_u64() == Some(id) && message.get("method").is_none() {
+                return parse_response(message);
+            }
+        }
+        Err(McpError::Closed)
+    }
+
+    async fn notify(&self, message: &Value) -> Result<(), McpError> {
+        self.post(message, REQUEST_TIMEOUT).await?;
+        Ok(())
+    }
+}
+
+enum Transport {
+    Stdio(StdioTransport),
+    Http(HttpTransport),
+}
+
+/// A connected MCP server and what it offers
+pub struct McpClient {
+    pub name: String,
+    /// Name and version the server reported
+    pub server_info: Value,
+    pub tools: Vec<McpTool>,
+    pub resources: Vec<McpResource>,
+    pub prompts: Vec<McpPrompt>,
+    transport: Transport,
+    next_id: AtomicU64,
+}
+
+impl McpClient {
+    /// Start or reach the server, run the `initialize` handshake and list its capabilities
+    pub async fn connect(name: &str, config: &McpServerConfig, workspace: &Path) -> Result<Self, McpError> {
+        let transport = match &config.transport {
+            McpTransportConfig::Stdio { command, args, env } => {
+                Transport::Stdio(StdioTransport::spawn(name, command, args, env, workspace)?)
+            }
+            McpTransportConfig::Http { url, headers } => Transport::Http(HttpTransport {
+                url: url.clone(),
+                headers: headers.clone(),
+                session_id: Mutex::new(None),
+            }),
+        };
+        let mut client = Self {
+            name: name.to_string(),
+            server_info: Value::Null,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            transport,
+            next_id: AtomicU64::new(1),
+        };
+
+        let init = client
+            .request_with_timeout(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": { "name": "SentinelOps", "version": env!("CARGO_PKG_VERSION") },
+                }),
+                INITIALIZE_TIMEOUT,
+            )
+            .await?;
+        client.server_info = init["serverInfo"].clone();
+        client.notify("notifications/initialized").await?;
+
+        let capabilities = &init["capabilities"];
+        if capabilities.get("tools").is_some() {
+            client.tools = client.list_all("tools/list", "tools").await?;
+        }
+        if capabilities.get("resources").is_some() {
+            client.resources = client.list_all("resources/list", "resources").await?;
+        }
+        if capabilities.get("prompts").is_some() {
+            client.prompts = client.list_all("prompts/list", "prompts").await?;
+        }
+        Ok(client)
+    }
+
+    async fn request_with_timeout(&self, method: &str, params: Value, timeout: Duration) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        match &self.transport {
+            Transport::Stdio(stdio) => stdio.request(id, &message, timeout).await,
+            Transport::Http(http) => http.request(id, &message, timeout).await,
+        }
+    }
+
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        self.request_with_timeout(method, params, REQUEST_TIMEOUT).await
+    }
+
+    async fn notify(&self, method: &str) -> Result<(), McpError> {
+        let message = json!({ "jsonrpc": "2.0", "method": method });
+        match &self.transport {
+            Transport::Stdio(stdio) => stdio.notify(&message).await,
+            Transport::Http(http) => http.notify(&message).await,
+        }
+    }
+
+    /// Follow `nextCursor` through every page of a list method
+    async fn list_all<T: for<'de> Deserialize<'de>>(&self, method: &str, key: &str) -> Result<Vec<T>, McpError> {
+        let mut items = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({ "cursor": cursor }),
+                None => json!({}),
+            };
+            let page = self.request(method, params).await?;
+            for item in page[key].as_array().into_iter().flatten() {
+                match serde_json::from_value(item.clone()) {
+                    Ok(item) => items.push(item),
+                    Err(e) => tracing::warn!("Skipping invalid {} entry from {}: {}", key, self.name, e),
+                }
+            }
+            match page["nextCursor"].as_str() {
+                Some(next) if !next.is_empty() => cursor = Some(next.to_string()),
+                _ => return Ok(items),
+            }
+        }
+    }
+
+    pub async fn call_tool(&self, tool: &str, arguments: Value) -> Result<McpToolResult, McpError> {
+        let result = self
+            .request("tools/call", json!({ "name": tool, "arguments": arguments }))
+            .await?;
+        Ok(McpToolResult::from_result(&result))
+    }
+
+    /// Contents of a resource, as the `contents` array of `resources/read`
+    pub async fn read_resource(&self, uri: &str) -> Result<Value, McpError> {
+        let result = self.request("resources/read", json!({ "uri": uri })).await?;
+        Ok(result["contents"].clone())
+    }
+
+    /// A prompt rendered with `arguments`: its description and messages
+    pub async fn get_prompt(&self, name: &str, arguments: HashMap<String, String>) -> Result<Value, McpError> {
+        self.request("prompts/get", json!({ "name": name, "arguments": arguments }))
+            .await
+    }
+}
+
+/// Name a server's tool is offered to the model under
+pub fn qualified_tool_name(server: &str, tool: &str) -> String {
+    let clean = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+            .collect()
+    };
+    let mut name = format!("mcp__{}__{}", clean(server), clean(tool));
+    name.truncate(MAX_TOOL_NAME_LEN);
+    name
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerFailure {
+    pub name: String,
+    pub error: String,
+}
+
+/// A configured server that waits for the user to allow it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpUntrustedServer {
+    pub name: String,
+    /// The command line or URL it would connect with
+    pub command: String,
+}
+
+/// The MCP servers of one workspace root, connected together
+#[derive(Default)]
+pub struct McpHub {
+    pub servers: Vec<Arc<McpClient>>,
+    /// Servers that are configured but could not be connected
+    pub failures: Vec<McpServerFailure>,
+    /// Servers that are not started until the user allows them
+    pub untrusted: Vec<McpUntrustedServer>,
+}
+
+impl McpHub {
+    /// Connect every enabled server in the root's `.sentinelops/mcp.json` that the user
+    /// has allowed in `McpTrust`; the others are listed in `untrusted` without being
+    /// started. A server that fails is recorded in `failures` and does not keep the
+    /// others from connecting.
+    pub async fn connect(workspace: &Path) -> Self {
+        let config = match McpConfig::load(workspace) {
+            Ok(config) => config,
+            Err(error) => {
+                return Self {
+                    failures: vec![McpServerFailure { name: "mcp.json".to_string(), error }],
+                    ..Self::default()
+                };
+            }
+        };
+        let trust = McpTrust::load();
+
+        let mut names: Vec<&String> = config
+            .mcp_servers
+            .iter()
+            .filter(|(_, server)| !server.disabled)
+            .map(|(name, _)| name)
+            .collect();
+        names.sort();
+
+        let mut untrusted = Vec::new();
+        names.retain(|name| {
+            let server = &config.mcp_servers[*name];
+            let trusted = trust.is_trusted(workspace, name, server);
+            if !trusted {
+                untrusted.push(McpUntrustedServer {
+                    name: name.to_string(),
+                    command: server.describe(),
+                });
+            }
+            trusted
+        });
+
+        let results = futures::future::join_all(
+            names
+                .iter()
+                .map(|name| McpClient::connect(name, &config.mcp_servers[*name], workspace)),
+        )
+        .await;
+
+        let mut hub = Self {
+            untrusted,
+            ..Self::default()
+        };
+        for (name, result) in names.into_iter().zip(results) {
+            match result {
+                Ok(client) => hub.servers.push(Arc::new(client)),
+                Err(e) => {
+                    tracing::warn!("Could not connect MCP server {}: {}", name, e);
+                    hub.failures.push(McpServerFailure {
+                        name: name.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        hub
+    }
+
+    pub fn server(&self, name: &str) -> Result<&Arc<McpClient>, McpError> {
+        self.servers
+            .iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| McpError::UnknownServer(name.to_string()))
+    }
+
+    /// The server and tool behind a qualified tool name
+    pub fn resolve_tool(&self, qualified: &str) -> Option<(&Arc<McpClient>, &McpTool)> {
+        self.servers.iter().find_map(|server| {
+            server
+                .tools
+                .iter()
+                .find(|tool| qualified_tool_name(&server.name, &tool.name) == qualified)
+                .map(|tool| (server, tool))
+        })
+    }
+
+    pub async fn call_tool(&self, qualified: &str, arguments: Value) -> Result<McpToolResult, McpError> {
+        let (server, tool) = self
+            .resolve_tool(qualified)
+            .ok_or_else(|| McpError::UnknownTool(qualified.to_string()))?;
+        server.call_tool(&tool.name, arguments).await
+    }
+
+    /// Every server tool in the OpenAI format, under its qualified name
+    pub fn tool_definitions(&self) -> Vec<Value> {
+        self.servers
+            .iter()
+            .flat_map(|server| {
+                server.tools.iter().map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": qualified_tool_name(&server.name, &tool.name),
+                            "description": tool.description.clone().unwrap_or_default(),
+                            "parameters": tool.input_schema,
+                        }
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Every server tool wrapped for the agent's tool registry
+    pub fn agent_tools(&self) -> Vec<Arc<dyn Tool>> {
+        self.servers
+            .iter()
+            .flat_map(|server| {
+                server.tools.iter().map(|tool| {
+                    Arc::new(McpAgentTool {
+                        qualified_name: qualified_tool_name(&server.name, &tool.name),
+                        server: server.clone(),
+                        tool: tool.clone(),
+                    }) as Arc<dyn Tool>
+                })
+            })
+            .collect()
+    }
+}
+
+/// A server tool in the agent's registry. External tools may do anything, so they are
+/// never treated as read-only and ask before each call.
+struct McpAgentTool {
+    qualified_name: String,
+    server: Arc<McpClient>,
+    tool: McpTool,
+}
+
+#[async_trait]
+impl Tool for McpAgentTool {
+    fn name(&self) -> &str {
+        &self.qualified_name
+    }
+    fn description(&self) -> &str {
+        self.tool.description.as_deref().unwrap_or_default()
+    }
+    fn parameters(&self) -> Value {
+        self.tool.input_schema.clone()
+    }
+    fn needs_approval(&self) -> bool {
+        true
+    }
+    async fn call(&self, _ctx: &ToolContext, args: Value) -> Result<String, ToolError> {
+        let result = self
+            .server
+            .call_tool(&self.tool.name, args)
+            .await
+            .map_err(|e| ToolError::Failed(e.to_string()))?;
+        if result.is_error {
+            return Err(ToolError::Failed(result.text));
+        }
+        Ok(result.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A stdio server in plain `sh`: it answers `initialize`, lists one `echo` tool, echoes the
+    /// `text` argument back and rejects anything else. Requests are one compact JSON object
+    /// per line with sorted keys, so `sed` can pick out the id and argument.
+    const ECHO_SERVER: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  [ -z "$id" ] && continue
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-03-26","capabilities":{"tools":{}},"serverInfo":{"name":"echo","version":"1"}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echo text","inputSchema":{"type":"object","properties":{"text":{"type":"string"}}}}]}}\n' "$id" ;;
+    *'"method":"tools/call"'*)
+      text=$(printf '%s' "$line" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"%s"}]}}\n' "$id" "$text" ;;
+    *)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"Method not found"}}\n' "$id" ;;
+  esac
+done
+"#;
+
+    fn echo_config(script: &str) -> McpServerConfig {
+        McpServerConfig {
+            transport: McpTransportConfig::Stdio {
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: HashMap::new(),
+            },
+            disabled: false,
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn stdio_round_trip() {
+        let client = McpClient::connect("echo", &echo_config(ECHO_SERVER), &std::env::temp_dir())
+            .await
+            .unwrap();
+        assert_eq!(client.server_info["name"], "echo");
+        assert_eq!(client.tools.len(), 1);
+        assert_eq!(client.tools[0].name, "echo");
+        assert!(client.resources.is_empty());
+
+        let result = client.call_tool("echo", json!({ "text": "hello" })).await.unwrap();
+        assert_eq!(result.text, "hello");
+        assert!(!result.is_error);
+
+        match client.read_resource("file:///nothing").await {
+            Err(McpError::Rpc { code, .. }) => assert_eq!(code, -32601),
+            other => panic!("expected an RPC error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn pending_requests_fail_when_the_server_exits() {
+        let config = echo_config(r#"read -r line; printf '{"jsonrpc":"2.0","id":1,"result":{"capabilities":{}}}\n'; read -r line; read -r line"#);
+        let client = McpClient::connect("short", &config, &std::env::temp_dir()).await.unwrap();
+        assert!(matches!(client.request("ping", json!({})).await, Err(McpError::Closed)));
+    }
+
+    #[test]
+    fn trust_covers_one_exact_config() {
+        let workspace = Path::new("/work/project");
+        let config = echo_config("cat");
+        let mut trust = McpTrust::default();
+        assert!(!trust.is_trusted(workspace, "echo", &config));
+
+        trust.set_trusted(workspace, "echo", &config, true);
+        assert!(trust.is_trusted(workspace, "echo", &config));
+        assert!(!trust.is_trusted(Path::new("/work/other"), "echo", &config));
+        assert!(!trust.is_trusted(workspace, "echo", &echo_config("cat -u")));
+
+        trust.set_trusted(workspace, "echo", &config, false);
+        assert!(!trust.is_trusted(workspace, "echo", &config));
+    }
+}
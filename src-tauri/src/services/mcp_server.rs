@@ -0,0 +1,978 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+
+use super::file_service;
+use super::git_service;
+use super::memory_service::{CreateMemoryInput, MemoryManager};
+use super::sqlite_service;
+
+/// Protocol revision this server implements
+const PROTOCOL_VERSION: &str = "2025-03-26";
+/// Older revisions whose requests this server also understands
+const SUPPORTED_VERSIONS: &[&str] = &["2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Largest request the localhost endpoint accepts
+const MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+
+/// How long `sqlite_query` may run before it is interrupted
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// ============================================================================
+// Settings
+// ============================================================================
+
+/// Settings of the MCP server mode, shared by the stdio and localhost transports
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct McpServerSettings {
+    /// Tools switched on or off by name; the rest keep their default
+    pub tools: BTreeMap<String, bool>,
+    /// Port of the localhost endpoint
+    pub port: u16,
+    pub max_read_size: usize,
+    pub max_search_results: usize,
+    /// Rows returned by `sqlite_query` before the rest are cut
+    pub max_query_rows: usize,
+}
+
+impl Default for McpServerSettings {
+    fn default() -> Self {
+        Self {
+            tools: BTreeMap::new(),
+            port: 7378,
+            max_read_size: 2 * 1024 * 1024,
+            max_search_results: 200,
+            max_query_rows: 500,
+        }
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("SentinelOps").join("mcp_server.json"))
+}
+
+impl McpServerSettings {
+    /// Saved settings, or the defaults when none are saved
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(path) = settings_path() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+            fs::write(&path, json).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, tool: ServerTool) -> bool {
+        self.tools
+            .get(tool.name())
+            .copied()
+            .unwrap_or_else(|| tool.read_only())
+    }
+}
+
+// ============================================================================
+// Tools
+// ============================================================================
+
+/// Workspace capabilities offered to MCP clients. Read-only tools are enabled unless
+/// switched off; the others must be switched on in the settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServerTool {
+    ReadFile,
+    ListDirectory,
+    SearchFiles,
+    WriteFile,
+    GitStatus,
+    GitDiff,
+    MemorySearch,
+    MemoryCreate,
+    SqliteSchema,
+    SqliteQuery,
+}
+
+impl ServerTool {
+    pub const ALL: [ServerTool; 10] = [
+        ServerTool::ReadFile,
+        ServerTool::ListDirectory,
+        ServerTool::SearchFiles,
+        ServerTool::WriteFile,
+        ServerTool::GitStatus,
+        ServerTool::GitDiff,
+        ServerTool::MemorySearch,
+        ServerTool::MemoryCreate,
+        ServerTool::SqliteSchema,
+        ServerTool::SqliteQuery,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tool| tool.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerTool::ReadFile => "read_file",
+            ServerTool::ListDirectory => "list_directory",
+            ServerTool::SearchFiles => "search_files",
+            ServerTool::WriteFile => "write_file",
+            ServerTool::GitStatus => "git_status",
+            ServerTool::GitDiff => "git_diff",
+            ServerTool::MemorySearch => "memory_search",
+            ServerTool::MemoryCreate => "memory_create",
+            ServerTool::SqliteSchema => "sqlite_schema",
+            ServerTool::SqliteQuery => "sqlite_query",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ServerTool::ReadFile => "Read a text file in the workspace",
+            ServerTool::ListDirectory => "List the files and directories at a path in the workspace",
+            ServerTool::SearchFiles => "Search for text across the files of the workspace",
+            ServerTool::WriteFile => "Create or overwrite a file in the workspace",
+            ServerTool::GitStatus => "Show the current git branch and the changed files of the workspace",
+            ServerTool::GitDiff => "Show unstaged changes in the workspace, optionally for a single path",
+            ServerTool::MemorySearch => "Search the workspace's memory store for saved facts and decisions",
+            ServerTool::MemoryCreate => "Save a fact or decision to the workspace's memory store",
+            ServerTool::SqliteSchema => "List the tables of a SQLite database in the workspace with their columns",
+            ServerTool::SqliteQuery => "Run a read-only SQL query against a SQLite database in the workspace",
+        }
+    }
+
+    /// Read-only tools never change the workspace
+    pub fn read_only(&self) -> bool {
+        !matches!(self, ServerTool::WriteFile | ServerTool::MemoryCreate)
+    }
+
+    /// JSON Schema of the arguments object
+    pub fn input_schema(&self) -> Value {
+        let path = json!({ "type": "string", "description": "Path relative to the workspace root" });
+        let (properties, required) = match self {
+            ServerTool::ReadFile => (json!({ "path": path }), vec!["path"]),
+            ServerTool::ListDirectory => (
+                json!({
+                    "path": path,
+                    "recursive": { "type": "boolean", "description": "Include the contents of subdirectories" },
+                }),
+                vec![],
+            ),
+            ServerTool::SearchFiles => (
+                json!({
+                    "query": { "type": "string", "description": "Text to search for" },
+                    "path": path,
+                    "caseSensitive": { "type": "boolean" },
+                }),
+                vec!["query"],
+            ),
+            ServerTool::WriteFile => (
+                json!({
+                    "path": path,
+                    "content": { "type": "string", "description": "The new contents of the file" },
+                }),
+                vec!["path", "content"],
+            ),
+            ServerTool::GitStatus => (json!({}), vec![]),
+            ServerTool::GitDiff => (json!({ "path": path }), vec![]),
+            ServerTool::MemorySearch => (
+                json!({
+                    "query": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": 50 },
+                }),
+                vec!["query"],
+            ),
+            ServerTool::MemoryCreate => (
+                json!({
+                    "content": { "type": "string", "description": "The fact to remember" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "importance": { "type": "integer", "minimum": 1, "maximum": 10 },
+                }),
+                vec!["content"],
+            ),
+            ServerTool::SqliteSchema => (
+                json!({ "path": path }),
+                vec!["path"],
+            ),
+            ServerTool::SqliteQuery => (
+                json!({
+                    "path": path,
+                    "query": { "type": "string", "description": "SQL to run; the database is opened read-only" },
+                }),
+                vec!["path", "query"],
+            ),
+        };
+        json!({ "type": "object", "properties": properties, "required": required })
+    }
+}
+
+/// A tool as shown in the settings
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerToolInfo {
+    pub name: String,
+    pub description: String,
+    pub read_only: bool,
+    pub enabled: bool,
+}
+
+/// Every tool with whether `settings` enables it
+pub fn tool_infos(settings: &McpServerSettings) -> Vec<ServerToolInfo> {
+    ServerTool::ALL
+        .iter()
+        .map(|tool| ServerToolInfo {
+            name: tool.name().to_string(),
+            description: tool.description().to_string(),
+            read_only: tool.read_only(),
+            enabled: settings.is_enabled(*tool),
+        })
+        .collect()
+}
+
+fn str_arg<'a>(args: &'a Value, key: &str) -> Result<&'a str, String> {
+    args[key]
+        .as_str()
+        .ok_or_else(|| format!("`{}` must be a string", key))
+}
+
+fn opt_str_arg<'a>(args: &'a Value, key: &str) -> Option<&'a str> {
+    args[key].as_str().filter(|s| !s.is_empty())
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// Answers MCP requests against one workspace root. Every path a client sends must
+/// resolve inside that root.
+#[derive(Clone)]
+pub struct McpServer {
+    workspace: PathBuf,
+    settings: Arc<Mutex<McpServerSettings>>,
+}
+
+impl McpServer {
+    pub fn new(workspace: PathBuf, settings: McpServerSettings) -> Self {
+        Self {
+            workspace,
+            settings: Arc::new(Mutex::new(settings)),
+        }
+    }
+
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Apply new settings to requests from now on. A new port applies on the next start.
+    pub fn set_settings(&self, settings: McpServerSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    pub fn tool_infos(&self) -> Vec<ServerToolInfo> {
+        tool_infos(&self.settings.lock().unwrap())
+    }
+
+    /// Answer a JSON-RPC message or batch. Notifications get no reply.
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        let Value::Array(batch) = message else {
+            return self.handle_one(message).await;
+        };
+        if batch.is_empty() {
+            return Some(error_reply(Value::Null, INVALID_REQUEST, "Empty batch"));
+        }
+        let mut replies = Vec::new();
+        for message in batch {
+            replies.extend(self.handle_one(message).await);
+        }
+        (!replies.is_empty()).then_some(Value::Array(replies))
+    }
+
+    async fn handle_one(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message["method"].as_str() else {
+            // Responses need no answer; this server sends no requests to match them to
+            if message.get("result").is_some() || message.get("error").is_some() {
+                return None;
+            }
+            return Some(error_reply(id.unwrap_or(Value::Null), INVALID_REQUEST, "Expected a request"));
+        };
+        let id = id?;
+        let params = message.get("params").cloned().unwrap_or_else(|| json!({}));
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(self.list_tools()),
+            "tools/call" => self.call_tool(params).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error_reply(id, code, &message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION);
+        let version = if SUPPORTED_VERSIONS.contains(&requested) {
+            requested
+        } else {
+            PROTOCOL_VERSION
+        };
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": { "listChanged": false } },
+            "serverInfo": { "name": "sentinelops", "version": env!("CARGO_PKG_VERSION") },
+            "instructions": format!(
+                "Tools operate on the workspace at {}. Paths are relative to it and may not leave it.",
+                self.workspace.display()
+            ),
+        })
+    }
+
+    fn list_tools(&self) -> Value {
+        let settings = self.settings.lock().unwrap();
+        let tools: Vec<Value> = ServerTool::ALL
+            .iter()
+            .filter(|tool| settings.is_enabled(**tool))
+            .map(|tool| {
+                json!({
+                    "name": tool.name(),
+                    "description": tool.description(),
+                    "inputSchema": tool.input_schema(),
+                    "annotations": { "readOnlyHint": tool.read_only() },
+                })
+            })
+            .collect();
+        json!({ "tools": tools })
+    }
+
+    async fn call_tool(&self, params: Value) -> Result<Value, (i64, String)> {
+        let name = params["name"].as_str().unwrap_or_default();
+        let tool = ServerTool::from_name(name)
+            .filter(|tool| self.settings.lock().unwrap().is_enabled(*tool))
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown tool: {}", name)))?;
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        // File, git and database work blocks; keep it off the transport's tasks
+        let server = self.clone();
+        let outcome = tokio::task::spawn_blocking(move || server.run_tool(tool, &args))
+            .await
+            .unwrap_or_else(|e| Err(format!("Tool panicked: {}", e)));
+
+        let (text, is_error) = match outcome {
+            Ok(text) => (text, false),
+            Err(e) => (e, true),
+        };
+        Ok(json!({
+            "content": [{ "type": "text", "text": text }],
+            "isError": is_error,
+        }))
+    }
+
+    /// Check a client path against the workspace before handing it to `file_service`
+    fn sandboxed(&self, path: &str) -> Result<PathBuf, String> {
+        file_service::resolve_within(&self.workspace, path).map_err(|e| e.to_string())
+    }
+
+    fn run_tool(&self, tool: ServerTool, args: &Value) -> Result<String, String> {
+        let settings = self.settings.lock().unwrap().clone();
+        let workspace = &self.workspace;
+
+        match tool {
+            ServerTool::ReadFile => {
+                let path = str_arg(args, "path")?;
+                self.sandboxed(path)?;
+                let (content, _, _) = file_service::read_file(workspace, path, settings.max_read_size)
+                    .map_err(|e| e.to_string())?;
+                Ok(content)
+            }
+            ServerTool::ListDirectory => {
+                let path = opt_str_arg(args, "path").unwrap_or(".");
+                self.sandboxed(path)?;
+                let recursive = args["recursive"].as_bool().unwrap_or(false);
+                let entries = file_service::list_directory(workspace, path, recursive, false)
+                    .map_err(|e| e.to_string())?;
+                serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())
+            }
+            ServerTool::SearchFiles => {
+                let path = opt_str_arg(args, "path").unwrap_or(".");
+                self.sandboxed(path)?;
+                let case_sensitive = args["caseSensitive"].as_bool().unwrap_or(false);
+                let matches = file_service::search_files(
+                    workspace,
+                    str_arg(args, "query")?,
+                    path,
+                    case_sensitive,
+                    settings.max_search_results,
+                )
+                .map_err(|e| e.to_string())?;
+                if matches.is_empty() {
+                    return Ok("No matches found".to_string());
+                }
+                Ok(matches
+                    .iter()
+                    .map(|m| format!("{}:{}: {}", m.path, m.line, m.text.trim()))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            ServerTool::WriteFile => {
+                let path = str_arg(args, "path")?;
+                self.sandboxed(path)?;
+                let (_, created, bytes_written) =
+                    file_service::write_file(workspace, path, str_arg(args, "content")?, true, true)
+                        .map_err(|e| e.to_string())?;
+                let action = if created { "Created" } else { "Updated" };
+                Ok(format!("{} {} ({} bytes)", action, path, bytes_written))
+            }
+            ServerTool::GitStatus => {
+                let status = git_service::status(workspace)?;
+                serde_json::to_string_pretty(&status).map_err(|e| e.to_string())
+            }
+            ServerTool::GitDiff => {
+                let path = opt_str_arg(args, "path");
+                if let Some(path) = path {
+                    self.sandboxed(path)?;
+                }
+                let diff = git_service::diff(workspace, path)?;
+                if diff.is_empty() {
+                    return Ok("No unstaged changes".to_string());
+                }
+                Ok(diff)
+            }
+            ServerTool::MemorySearch => {
+                let limit = args["limit"].as_u64().unwrap_or(10).clamp(1, 50) as usize;
+                let results = MemoryManager::new(workspace)?.search_keyword(str_arg(args, "query")?, limit)?;
+                serde_json::to_string_pretty(&results).map_err(|e| e.to_string())
+            }
+            ServerTool::MemoryCreate => {
+                let tags = args["tags"].as_array().map(|tags| {
+                    tags.iter()
+                        .filter_map(|tag| tag.as_str().map(str::to_string))
+                        .collect()
+                });
+                let memory = MemoryManager::new(workspace)?.create_memory(CreateMemoryInput {
+                    content: str_arg(args, "content")?.to_string(),
+                    summary: None,
+                    memory_type: Some("user".to_string()),
+                    tags,
+                    importance: args["importance"].as_i64().map(|i| i.clamp(1, 10) as i32),
+                    is_pinned: None,
+                    source_conversation_id: None,
+                    source_message_ids: None,
+                    metadata: Some(json!({ "source": "mcp" })),
+                })?;
+                Ok(format!("Saved memory {}", memory.id))
+            }
+            ServerTool::SqliteSchema => {
+                let conn = sqlite_service::open_read_only(&self.sandboxed(str_arg(args, "path")?)?)?;
+                let schema = sqlite_service::schema(&conn)?;
+                let tables = schema
+                    .tables
+                    .iter()
+                    .map(|table| {
+                        let columns = sqlite_service::columns(&conn, &table.name)?;
+                        Ok(json!({
+                            "name": table.name,
+                            "type": table.table_type,
+                            "rowCount": table.row_count,
+                            "columns": columns,
+                        }))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                serde_json::to_string_pretty(&json!({
+                    "version": schema.version,
+                    "databaseSize": schema.database_size,
+                    "tables": tables,
+                }))
+                .map_err(|e| e.to_string())
+            }
+            ServerTool::SqliteQuery => {
+                let conn = sqlite_service::open_read_only(&self.sandboxed(str_arg(args, "path")?)?)?;
+                // SQLite checks the handler every few thousand steps, so long scans and
+                // runaway recursive queries are interrupted near the deadline
+                let deadline = Instant::now() + QUERY_TIMEOUT;
+                conn.progress_handler(10_000, Some(move || Instant::now() > deadline));
+                // One row past the limit tells whether there were more
+                let mut result = sqlite_service::execute_query(&conn, str_arg(args, "query")?, Some(settings.max_query_rows + 1))
+                    .map_err(|e| {
+                        if Instant::now() > deadline {
+                            format!("Query stopped after {} seconds", QUERY_TIMEOUT.as_secs())
+                        } else {
+                            e
+                        }
+                    })?;
+                let truncated = result.rows.len() > settings.max_query_rows;
+                result.rows.truncate(settings.max_query_rows);
+                result.row_count = result.rows.len();
+                let mut text = serde_json::to_string_pretty(&result).map_err(|e| e.to_string())?;
+                if truncated {
+                    text.push_str(&format!("\n...(truncated to the first {} rows)", settings.max_query_rows));
+                }
+                Ok(text)
+            }
+        }
+    }
+}
+
+fn error_reply(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+// ============================================================================
+// Stdio transport
+// ============================================================================
+
+/// Workspace to serve when the app was started as `--mcp-stdio [--workspace PATH]`,
+/// defaulting to the current directory
+pub fn stdio_workspace_from_args() -> Option<PathBuf> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.iter().any(|arg| arg == "--mcp-stdio") {
+        return None;
+    }
+    let workspace = args
+        .iter()
+        .position(|arg| arg == "--workspace")
+        .and_then(|i| args.get(i + 1))
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let workspace = workspace.canonicalize().unwrap_or(workspace);
+    Some(workspace)
+}
+
+/// Serve newline-delimited JSON-RPC on stdin/stdout until stdin closes
+pub async fn serve_stdio(server: McpServer) -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(message).await,
+            Err(e) => Some(error_reply(Value::Null, PARSE_ERROR, &e.to_string())),
+        };
+        if let Some(reply) = reply {
+            let mut out = reply.to_string();
+            out.push('\n');
+            stdout.write_all(out.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Localhost transport
+// ============================================================================
+
+/// A running localhost endpoint. Clients POST JSON-RPC to `url` with
+/// `Authorization: Bearer <token>`. Dropping it stops the server.
+pub struct LocalEndpoint {
+    pub server: McpServer,
+    pub addr: SocketAddr,
+    pub token: String,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl LocalEndpoint {
+    pub fn url(&self) -> String {
+        format!("http://{}/mcp", self.addr)
+    }
+
+    /// Stop accepting connections and wait until the port is released
+    pub async fn stop(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        if let Some(task) = self.task.take() {
+            task.await.ok();
+        }
+    }
+}
+
+impl Drop for LocalEndpoint {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+/// Listen on 127.0.0.1:`port` with a fresh bearer token
+pub async fn serve_local(server: McpServer, port: u16) -> Result<LocalEndpoint, String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    let (shutdown, mut stopped) = oneshot::channel();
+
+    let handler = server.clone();
+    let expected = token.clone();
+    let task = tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let Ok((stream, _)) = accepted else { continue };
+                    let handler = handler.clone();
+                    let expected = expected.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_connection(stream, &handler, &expected).await {
+                            tracing::debug!("MCP connection ended: {}", e);
+                        }
+                    });
+                }
+                _ = &mut stopped => break,
+            }
+        }
+    });
+
+    tracing::info!("MCP server listening on {}", addr);
+    Ok(LocalEndpoint {
+        server,
+        addr,
+        token,
+        shutdown: Some(shutdown),
+        task: Some(task),
+    })
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Answer one request per connection
+async fn serve_connection(mut stream: TcpStream, server: &McpServer, token: &str) -> std::io::Result<()> {
+    let request = match read_request(&mut stream).await? {
+        Ok(request) => request,
+        Err(status) => return write_response(&mut stream, status, None).await,
+    };
+
+    // Browsers may reach localhost from any page; only local origins get through
+    if let Some(origin) = request.header("Origin") {
+        if !is_local_origin(origin) {
+            return write_response(&mut stream, "403 Forbidden", None).await;
+        }
+    }
+    let authorized = request
+        .header("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| given.trim() == token);
+    if !authorized {
+        return write_response(&mut stream, "401 Unauthorized", None).await;
+    }
+    if request.path != "/mcp" {
+        return write_response(&mut stream, "404 Not Found", None).await;
+    }
+    if request.method != "POST" {
+        // No server-initiated messages, so there is no event stream to open
+        return write_response(&mut stream, "405 Method Not Allowed", None).await;
+    }
+
+    let reply = match serde_json::from_slice::<Value>(&request.body) {
+        Ok(message) => server.handle(message).await,
+        Err(e) => Some(error_reply(Value::Null, PARSE_ERROR, &e.to_string())),
+    };
+    match reply {
+        Some(reply) => write_response(&mut stream, "200 OK", Some(&reply)).await,
+        None => write_response(&mut stream, "202 Accepted", None).await,
+    }
+}
+
+/// Read a request, or the status to refuse it with
+async fn read_request(stream: &mut TcpStream) -> std::io::Result<Result<HttpRequest, &'static str>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if buf.len() > MAX_HEADER_BYTES {
+            return Ok(Err("431 Request Header Fields Too Large"));
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(Err("400 Bad Request"));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Ok(Err("400 Bad Request"));
+    };
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let mut request = HttpRequest {
+        method: method.to_string(),
+        path: path.split('?').next().unwrap_or(path).to_string(),
+        headers,
+        body: buf[header_end + 4..].to_vec(),
+    };
+    let length: usize = request
+        .header("Content-Length")
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    if length > MAX_REQUEST_BYTES {
+        return Ok(Err("413 Payload Too Large"));
+    }
+    while request.body.len() < length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(Err("400 Bad Request"));
+        }
+        request.body.extend_from_slice(&chunk[..n]);
+    }
+    request.body.truncate(length);
+    Ok(Ok(request))
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: Option<&Value>) -> std::io::Result<()> {
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+    let content_type = if body.is_empty() { "" } else { "Content-Type: application/json\r\n" };
+    let response = format!(
+        "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn is_local_origin(origin: &str) -> bool {
+    let host = origin
+        .split("://")
+        .nth(1)
+        .unwrap_or(origin)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => host,
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]" | "tauri.localhost")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh workspace directory under the system temp dir
+    fn workspace() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcp-server-test-{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    /// Send raw bytes to the endpoint and return the status line of the reply
+    async fn raw_status(addr: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request).await.unwrap();
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).await.unwrap();
+        String::from_utf8_lossy(&reply).lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn only_loopback_origins_are_local() {
+        for origin in ["http://localhost:1420", "http://127.0.0.1", "https://tauri.localhost", "http://[::1]:8080"] {
+            assert!(is_local_origin(origin), "{}", origin);
+        }
+        for origin in ["https://example.com", "http://localhost.example.com", "http://127.0.0.1.nip.io", "null"] {
+            assert!(!is_local_origin(origin), "{}", origin);
+        }
+    }
+
+    #[tokio::test]
+    async fn localhost_endpoint_requires_the_bearer_token_and_a_local_origin() {
+        let endpoint = serve_local(McpServer::new(workspace(), McpServerSettings::default()), 0).await.unwrap();
+        let client = reqwest::Client::new();
+        let ping = request(1, "ping", json!({}));
+        let post = || client.post(endpoint.url()).json(&ping);
+
+        assert_eq!(post().send().await.unwrap().status(), 401);
+        assert_eq!(post().bearer_auth("wrong").send().await.unwrap().status(), 401);
+        let forbidden = post()
+            .bearer_auth(&endpoint.token)
+            .header("Origin", "https://example.com")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(forbidden.status(), 403);
+
+        let reply = post()
+            .bearer_auth(&endpoint.token)
+            .header("Origin", "http://localhost:1420")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(reply.status(), 200);
+        assert_eq!(reply.json::<Value>().await.unwrap(), json!({ "jsonrpc": "2.0", "id": 1, "result": {} }));
+        endpoint.stop().await;
+    }
+
+    #[tokio::test]
+    async fn read_request_refuses_oversized_and_misdirected_requests() {
+        let endpoint = serve_local(McpServer::new(workspace(), McpServerSettings::default()), 0).await.unwrap();
+        let auth = format!("Authorization: Bearer {}\r\n", endpoint.token);
+
+        let too_long = format!("POST /mcp HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n", auth, MAX_REQUEST_BYTES + 1);
+        assert!(raw_status(endpoint.addr, too_long.as_bytes()).await.contains("413"));
+
+        let huge_header = format!("POST /mcp HTTP/1.1\r\nX-Padding: {}\r\n", "a".repeat(MAX_HEADER_BYTES + 1));
+        assert!(raw_status(endpoint.addr, huge_header.as_bytes()).await.contains("431"));
+
+        assert!(raw_status(endpoint.addr, b"garbage\r\n\r\n").await.contains("400"));
+
+        let get = format!("GET /mcp HTTP/1.1\r\n{}\r\n", auth);
+        assert!(raw_status(endpoint.addr, get.as_bytes()).await.contains("405"));
+
+        let elsewhere = format!("POST /other HTTP/1.1\r\n{}Content-Length: 2\r\n\r\n{{}}", auth);
+        assert!(raw_status(endpoint.addr, elsewhere.as_bytes()).await.contains("404"));
+
+        // A body sent in pieces is read up to Content-Length
+        let body = request(7, "ping", json!({})).to_string();
+        let head = format!("POST /mcp?session=1 HTTP/1.1\r\n{}Content-Length: {}\r\n\r\n", auth, body.len());
+        let mut stream = TcpStream::connect(endpoint.addr).await.unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(&body.as_bytes()[..5]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stream.write_all(&body.as_bytes()[5..]).await.unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await.unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200"), "{}", reply);
+        assert!(reply.ends_with(r#"{"id":7,"jsonrpc":"2.0","result":{}}"#), "{}", reply);
+        endpoint.stop().await;
+    }
+
+    #[tokio::test]
+    async fn batches_answer_requests_and_skip_notifications() {
+        let server = McpServer::new(workspace(), McpServerSettings::default());
+
+        let replies = server
+            .handle(json!([
+                request(1, "ping", json!({})),
+                { "jsonrpc": "2.0", "method": "notifications/initialized" },
+                request(2, "nope", json!({})),
+            ]))
+            .await
+            .unwrap();
+        let replies = replies.as_array().unwrap();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["result"], json!({}));
+        assert_eq!(replies[1]["error"]["code"], METHOD_NOT_FOUND);
+
+        let only_notifications = json!([{ "jsonrpc": "2.0", "method": "notifications/initialized" }]);
+        assert_eq!(server.handle(only_notifications).await, None);
+        assert_eq!(server.handle(json!([])).await.unwrap()["error"]["code"], INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn tools_follow_their_enablement() {
+        let root = workspace();
+        fs::write(root.join("a.txt"), "hello").unwrap();
+        let server = McpServer::new(root.clone(), McpServerSettings::default());
+        let listed = |reply: Value| -> Vec<String> {
+            reply["result"]["tools"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|tool| tool["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        // Read-only tools are on by default, the others off
+        let names = listed(server.handle(request(1, "tools/list", json!({}))).await.unwrap());
+        assert!(names.contains(&"read_file".to_string()));
+        assert!(!names.contains(&"write_file".to_string()));
+        let write = request(2, "tools/call", json!({ "name": "write_file", "arguments": { "path": "b.txt", "content": "x" } }));
+        let reply = server.handle(write.clone()).await.unwrap();
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+        assert!(!root.join("b.txt").exists());
+
+        let mut settings = McpServerSettings::default();
+        settings.tools.insert("write_file".to_string(), true);
+        settings.tools.insert("read_file".to_string(), false);
+        server.set_settings(settings);
+
+        let names = listed(server.handle(request(3, "tools/list", json!({}))).await.unwrap());
+        assert!(names.contains(&"write_file".to_string()));
+        assert!(!names.contains(&"read_file".to_string()));
+        let reply = server.handle(write).await.unwrap();
+        assert_eq!(reply["result"]["isError"], false);
+        assert_eq!(fs::read_to_string(root.join("b.txt")).unwrap(), "x");
+        let read = request(4, "tools/call", json!({ "name": "read_file", "arguments": { "path": "a.txt" } }));
+        assert_eq!(server.handle(read).await.unwrap()["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn sqlite_query_stops_at_the_row_limit() {
+        let root = workspace();
+        let conn = rusqlite::Connection::open(root.join("data.db")).unwrap();
+        conn.execute_batch("CREATE TABLE t (n INTEGER); INSERT INTO t VALUES (1), (2), (3), (4);").unwrap();
+        drop(conn);
+        let settings = McpServerSettings { max_query_rows: 2, ..McpServerSettings::default() };
+        let server = McpServer::new(root, settings);
+
+        let call = request(1, "tools/call", json!({ "name": "sqlite_query", "arguments": { "path": "data.db", "query": "SELECT n FROM t" } }));
+        let reply = server.handle(call).await.unwrap();
+        let text = reply["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(text.ends_with("...(truncated to the first 2 rows)"), "{}", text);
+        let (json_part, _) = text.split_once("\n...").unwrap();
+        let result: Value = serde_json::from_str(json_part).unwrap();
+        assert_eq!(result["rows"], json!([[1], [2]]));
+    }
+}
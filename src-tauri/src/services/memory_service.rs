@@ -4,6 +4,8 @@ use sha2::{Sha256, Digest};
 use std::path::{Path, PathBuf};
 use chrono::{DateTime, Utc};
 
+use super::conversation_service::ConversationStore;
+
 /// Memory type classification
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -131,7 +133,9 @@ pub struct MemoryFilters {
     pub sort_by: Option<String>,
 }
 
-/// Memory database manager
+/// Memory database manager for one root. `AppState::memory_for` hands out copies of the
+/// manager it keeps per root; they share nothing but the database path.
+#[derive(Clone)]
 pub struct MemoryManager {
     db_path: PathBuf,
     workspace_id: String,
@@ -169,6 +173,11 @@ impl MemoryManager {
         &self.workspace_id
     }
 
+    /// Conversations stored in the same database
+    pub fn conversations(&self) -> ConversationStore {
+        ConversationStore::new(self.db_path.clone(), self.workspace_id.clone())
+    }
+
     /// Open a connection to the database
     fn get_connection(&self) -> Result<Connection, String> {
         Connection::open(&self.db_path)
@@ -270,6 +279,8 @@ impl MemoryManager {
             params![&self.workspace_id, &now],
         ).map_err(|e| format!("Failed to initialize settings: {}", e))?;
 
+        ConversationStore::init_database(&conn)?;
+
         Ok(())
     }
 
@@ -726,12 +737,12 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
 }
 
 /// Convert f32 vector to bytes for storage
-fn f32_vec_to_bytes(vec: &[f32]) -> Vec<u8> {
+pub(crate) fn f32_vec_to_bytes(vec: &[f32]) -> Vec<u8> {
     vec.iter().flat_map(|f| f.to_le_bytes()).collect()
 }
 
 /// Convert bytes back to f32 vector
-fn bytes_to_f32_vec(bytes: &[u8]) -> Vec<f32> {
+pub(crate) fn bytes_to_f32_vec(bytes: &[u8]) -> Vec<f32> {
     bytes
         .chunks(4)
         .filter_map(|chunk| {
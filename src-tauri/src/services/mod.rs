@@ -1,45 +1,301 @@
 pub mod config;
 pub mod file_service;
 pub mod trash_service;
+pub mod freedesktop_trash;
 pub mod terminal_service;
 pub mod memory_service;
+pub mod conversation_service;
+pub mod workspace_service;
+pub mod git_service;
+pub mod search_service;
+pub mod window_service;
+pub mod secrets_service;
+pub mod provider_service;
+pub mod llm_adapter;
+pub mod llm_executor;
+pub mod llm_client;
+pub mod ollama_service;
+pub mod token_service;
+pub mod usage_service;
+pub mod cache_service;
+pub mod stream_service;
+pub mod attachment_service;
+pub mod tool_calls;
+pub mod structured_output;
+pub mod agent_tools;
+pub mod agent_service;
+pub mod mcp_client;
+pub mod mcp_server;
+pub mod sqlite_service;
+#[cfg(test)]
+pub mod test_server;
 
-use std::sync::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use cache_service::ResponseCache;
 use config::Config;
-use terminal_service::TerminalManager;
+use llm_client::LlmClient;
+use mcp_client::McpHub;
+use mcp_server::LocalEndpoint;
 use trash_service::TrashManager;
+use usage_service::UsageLedger;
 use memory_service::MemoryManager;
+use stream_service::StreamHandle;
+use window_service::WindowState;
+use workspace_service::Workspace;
 
 /// Application state shared across commands
 pub struct AppState {
+    /// LLM settings, plus the workspace new windows start with
     pub config: Mutex<Config>,
-    pub trash: Mutex<TrashManager>,
-    pub terminals: Mutex<TerminalManager>,
-    pub memory: Mutex<Option<MemoryManager>>,
+    /// Per-window workspace and terminals, keyed by window label.
+    /// Lock order: `windows` before `config`.
+    pub windows: Mutex<HashMap<String, WindowState>>,
+    /// Trash managers keyed by root path, shared by every window that has the root open.
+    /// Each has its own lock so trash I/O does not hold up the other roots.
+    pub trash: Mutex<HashMap<PathBuf, Arc<Mutex<TrashManager>>>>,
+    /// Memory managers keyed by root path, created on first use
+    pub memory: Mutex<HashMap<PathBuf, MemoryManager>>,
+    /// Connected MCP servers keyed by root path, connected on first use
+    pub mcp: Mutex<HashMap<PathBuf, Arc<McpHub>>>,
+    /// The localhost MCP endpoint serving a workspace to other tools, while it runs
+    pub mcp_server: Mutex<Option<LocalEndpoint>>,
+    /// Running LLM streams keyed by stream ID
+    pub streams: Mutex<HashMap<String, Arc<StreamHandle>>>,
+    /// Agent tool calls waiting for the user, keyed by `approval_key`
+    pub approvals: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+    /// Ledger of LLM calls; `None` when its database could not be opened
+    pub usage: Option<UsageLedger>,
+    /// Cache of deterministic LLM responses; `None` when its database could not be opened
+    pub cache: Option<ResponseCache>,
 }
 
 impl AppState {
     pub fn new(config: Config) -> Self {
-        let workspace = config.workspace_root.clone();
-
-        // Initialize memory manager (lazy - will be created on first use if fails here)
-        let memory_manager = MemoryManager::new(&workspace).ok();
-
         Self {
             config: Mutex::new(config),
-            trash: Mutex::new(TrashManager::new(&workspace)),
-            terminals: Mutex::new(TerminalManager::new()),
-            memory: Mutex::new(memory_manager),
+            windows: Mutex::new(HashMap::new()),
+            trash: Mutex::new(HashMap::new()),
+            memory: Mutex::new(HashMap::new()),
+            mcp: Mutex::new(HashMap::new()),
+            mcp_server: Mutex::new(None),
+            streams: Mutex::new(HashMap::new()),
+            approvals: Mutex::new(HashMap::new()),
+            usage: UsageLedger::open()
+                .map_err(|e| tracing::warn!("Usage ledger unavailable: {}", e))
+                .ok(),
+            cache: ResponseCache::open()
+                .map_err(|e| tracing::warn!("Response cache unavailable: {}", e))
+                .ok(),
         }
     }
 
-    /// Get or initialize the memory manager for the current workspace
-    pub fn get_or_init_memory(&self) -> Result<(), String> {
+    /// Run `f` against a window's state, creating it from the default workspace on first use
+    pub fn with_window<T>(&self, label: &str, f: impl FnOnce(&mut WindowState) -> T) -> T {
+        let mut windows = self.windows.lock().unwrap();
+        let window = windows.entry(label.to_string()).or_insert_with(|| {
+            let config = self.config.lock().unwrap();
+            WindowState::new(config.workspace.clone())
+        });
+        f(window)
+    }
+
+    /// Resolve a root ID (or the window's primary root when `None`) to its folder
+    pub fn root_path(&self, label: &str, root_id: Option<&str>) -> Result<PathBuf, String> {
+        self.with_window(label, |window| {
+            window
+                .workspace
+                .root(root_id)
+                .map(|r| r.path.clone())
+                .map_err(|e| e.to_string())
+        })
+    }
+
+    /// Run `f` against the trash of a root, creating its manager on first use
+    pub fn with_trash<T>(
+        &self,
+        label: &str,
+        root_id: Option<&str>,
+        f: impl FnOnce(&mut TrashManager) -> T,
+    ) -> Result<T, String> {
+        let root = self.root_path(label, root_id)?;
+        let manager = self.trash.lock().map_err(|e| e.to_string())?.get(&root).cloned();
+        let manager = match manager {
+            Some(manager) => manager,
+            None => {
+                // Loading the index reads the trash, so do it outside the map's lock
+                let loaded = Arc::new(Mutex::new(TrashManager::new(&root)));
+                let mut trash = self.trash.lock().map_err(|e| e.to_string())?;
+                trash.entry(root).or_insert(loaded).clone()
+            }
+        };
+        let mut manager = manager.lock().map_err(|e| e.to_string())?;
+        Ok(f(&mut manager))
+    }
+
+    /// Get or initialize the memory manager for a root
+    pub fn memory_for(&self, label: &str, root_id: Option<&str>) -> Result<MemoryManager, String> {
+        let root = self.root_path(label, root_id)?;
         let mut memory = self.memory.lock().map_err(|e| e.to_string())?;
-        if memory.is_none() {
-            let config = self.config.lock().map_err(|e| e.to_string())?;
-            *memory = Some(MemoryManager::new(&config.workspace_root)?);
+        if let Some(manager) = memory.get(&root) {
+            return Ok(manager.clone());
+        }
+        let manager = MemoryManager::new(&root)?;
+        memory.insert(root, manager.clone());
+        Ok(manager)
+    }
+
+    /// MCP servers of a window's primary root, connecting them on first use.
+    /// `reconnect` drops the current connections and reads the config again.
+    pub async fn mcp_for(&self, label: &str, reconnect: bool) -> Result<Arc<McpHub>, String> {
+        let root = self.root_path(label, None)?;
+        if !reconnect {
+            let existing = self.mcp.lock().unwrap().get(&root).cloned();
+            if let Some(hub) = existing {
+                return Ok(hub);
+            }
+        }
+
+        // Connect without holding the lock; servers can take a while to start
+        let hub = Arc::new(McpHub::connect(&root).await);
+        let mut mcp = self.mcp.lock().unwrap();
+        if reconnect {
+            mcp.insert(root, hub.clone());
+            return Ok(hub);
+        }
+        Ok(mcp.entry(root).or_insert(hub).clone())
+    }
+
+    /// LLM client for a request made by a window. Calls are recorded in the usage ledger
+    /// under the window's primary root and checked against the daily budget, and may be
+    /// answered from the response cache.
+    pub fn llm_client(&self, label: &str) -> LlmClient {
+        let workspace = self
+            .root_path(label, None)
+            .ok()
+            .map(|root| root.to_string_lossy().into_owned());
+        let mut client = self.config.lock().unwrap().llm_client();
+        if let Some(ledger) = &self.usage {
+            client = client.with_usage(ledger.clone(), workspace);
+        }
+        if let Some(cache) = &self.cache {
+            client = client.with_cache(cache.clone());
+        }
+        client
+    }
+
+    /// Swap a window's workspace, releasing managers for roots no window has open anymore
+    pub fn replace_workspace(&self, label: &str, workspace: Workspace) {
+        let old = self.with_window(label, |window| std::mem::replace(&mut window.workspace, workspace));
+        for root in &old.roots {
+            if !self.is_root_open(&root.path) {
+                self.forget_root(&root.path);
+            }
+        }
+    }
+
+    /// Register a stream sending events to a window. A stream already running
+    /// under the same ID is cancelled.
+    pub fn begin_stream(&self, label: &str, stream_id: &str) -> Arc<StreamHandle> {
+        let handle = Arc::new(StreamHandle::new(label));
+        if let Some(previous) = self
+            .streams
+            .lock()
+            .unwrap()
+            .insert(stream_id.to_string(), handle.clone())
+        {
+            previous.cancel();
+        }
+        handle
+    }
+
+    /// Unregister a stream, unless its ID has since been reused by another one
+    pub fn end_stream(&self, stream_id: &str, handle: &Arc<StreamHandle>) {
+        let mut streams = self.streams.lock().unwrap();
+        if streams.get(stream_id).is_some_and(|h| Arc::ptr_eq(h, handle)) {
+            streams.remove(stream_id);
+        }
+    }
+
+    /// Ask a running stream to stop. Returns false when no such stream is running.
+    pub fn cancel_stream(&self, stream_id: &str) -> bool {
+        match self.streams.lock().unwrap().get(stream_id) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wait for the user to approve or reject a tool call of an agent run. Resolves to
+    /// `Err` when the request is dropped unanswered.
+    pub fn request_approval(&self, run_id: &str, call_id: &str) -> oneshot::Receiver<bool> {
+        let (tx, rx) = oneshot::channel();
+        self.approvals
+            .lock()
+            .unwrap()
+            .insert(approval_key(run_id, call_id), tx);
+        rx
+    }
+
+    /// Answer a pending approval. Returns false when no such call is waiting.
+    pub fn resolve_approval(&self, run_id: &str, call_id: &str, approved: bool) -> bool {
+        match self.approvals.lock().unwrap().remove(&approval_key(run_id, call_id)) {
+            Some(tx) => tx.send(approved).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Drop the unanswered approvals of a finished run
+    pub fn end_approvals(&self, run_id: &str) {
+        let prefix = approval_key(run_id, "");
+        self.approvals
+            .lock()
+            .unwrap()
+            .retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    /// Drop a closed window's state: kill its terminals, stop its streams and release
+    /// per-root managers that no other window still uses
+    pub fn close_window(&self, label: &str) {
+        let Some(mut closed) = self.windows.lock().unwrap().remove(label) else {
+            return;
+        };
+        closed.terminals.kill_all();
+
+        for handle in self.streams.lock().unwrap().values() {
+            if handle.window == label {
+                handle.cancel();
+            }
+        }
+
+        for root in &closed.workspace.roots {
+            if !self.is_root_open(&root.path) {
+                self.forget_root(&root.path);
+            }
         }
-        Ok(())
     }
+
+    /// Whether any window still has `root` open
+    pub fn is_root_open(&self, root: &Path) -> bool {
+        self.windows
+            .lock()
+            .unwrap()
+            .values()
+            .any(|w| w.workspace.roots.iter().any(|r| r.path == root))
+    }
+
+    /// Drop cached per-root managers for a folder that is no longer open
+    pub fn forget_root(&self, root: &Path) {
+        self.trash.lock().unwrap().remove(root);
+        self.memory.lock().unwrap().remove(root);
+        self.mcp.lock().unwrap().remove(root);
+    }
+}
+
+fn approval_key(run_id: &str, call_id: &str) -> String {
+    format!("{}/{}", run_id, call_id)
 }
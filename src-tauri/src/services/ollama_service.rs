@@ -0,0 +1,410 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use thiserror::Error;
+
+use super::config::normalize_base_url_for_openai_api;
+use super::llm_client::shared_http_client;
+
+/// Connecting to a local server should be near instant; a slow connect means it is not up
+const CONNECT_TIMEOUT_SECS: u64 = 3;
+/// For everything but pulls, which can run for a long time
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Error, Debug)]
+pub enum OllamaError {
+    #[error("Ollama is not running at {0}")]
+    NotRunning(String),
+    #[error("Model not found: {0}")]
+    ModelMissing(String),
+    #[error("Ollama error ({status}): {message}")]
+    Api { status: u16, message: String },
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+}
+
+impl OllamaError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OllamaError::NotRunning(_) => "SERVER_NOT_RUNNING",
+            OllamaError::ModelMissing(_) => "MODEL_NOT_FOUND",
+            OllamaError::Api { .. } => "OLLAMA_ERROR",
+            OllamaError::Request(_) => "REQUEST_ERROR",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    NotRunning,
+    ModelMissing,
+    /// The server is up but failed to answer about the model
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OllamaHealth {
+    pub status: HealthStatus,
+    /// Server version, when it answered
+    pub version: Option<String>,
+    pub model: Option<String>,
+    pub message: Option<String>,
+}
+
+/// One progress line of a pull. Layers report `total` and `completed` bytes while
+/// downloading; other steps only have a status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullProgress {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<u64>,
+}
+
+impl PullProgress {
+    /// Percent of the current layer, when downloading
+    pub fn percent(&self) -> Option<f64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed as f64 * 100.0 / total as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelDetails {
+    pub name: String,
+    pub modelfile: String,
+    /// Modelfile parameters; keys such as `stop` can appear more than once
+    pub parameters: BTreeMap<String, Vec<String>>,
+    pub template: Option<String>,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization_level: Option<String>,
+    /// Longest context the model was trained for
+    pub context_length: Option<u64>,
+    /// Context the model runs with, when its modelfile sets `num_ctx`
+    pub num_ctx: Option<u64>,
+    pub capabilities: Vec<String>,
+}
+
+/// A model loaded in memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunningModel {
+    pub name: String,
+    /// Bytes in memory
+    #[serde(default)]
+    pub size: u64,
+    /// Bytes of `size` on the GPU
+    #[serde(default, rename(deserialize = "size_vram"))]
+    pub size_vram: u64,
+    /// When the model is unloaded unless used again
+    #[serde(default, rename(deserialize = "expires_at"))]
+    pub expires_at: Option<String>,
+    #[serde(default, rename(deserialize = "context_length"))]
+    pub context_length: Option<u64>,
+}
+
+/// Client for Ollama's native API, which manages models and is served next to its
+/// OpenAI-compatible `/v1` endpoints
+pub struct OllamaClient {
+    root: String,
+    http: reqwest::Client,
+}
+
+impl OllamaClient {
+    /// `base_url` may be the server root or its `/v1` endpoint, as configured for chat
+    pub fn new(base_url: &str) -> Self {
+        let base = normalize_base_url_for_openai_api(base_url);
+        let base = base.trim_end_matches('/');
+        Self {
+            root: base.strip_suffix("/v1").unwrap_or(base).to_string(),
+            http: shared_http_client(Some(CONNECT_TIMEOUT_SECS)),
+        }
+    }
+
+    pub async fn version(&self) -> Result<String, OllamaError> {
+        let response = self.send(self.http.get(self.url("version")).timeout(REQUEST_TIMEOUT), None).await?;
+        let body: Value = response.json().await?;
+        Ok(body["version"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// Whether the server is up and, when `model` is given, has that model
+    pub async fn health(&self, model: Option<&str>) -> OllamaHealth {
+        let mut health = OllamaHealth {
+            status: HealthStatus::Ok,
+            version: None,
+            model: model.map(String::from),
+            message: None,
+        };
+        match self.version().await {
+            Ok(version) => health.version = Some(version),
+            Err(e) => {
+                // A server that answers, or times out, is running even if the check failed
+                health.status = match e {
+                    OllamaError::NotRunning(_) => HealthStatus::NotRunning,
+                    _ => HealthStatus::Error,
+                };
+                health.message = Some(e.to_string());
+                return health;
+            }
+        }
+        if let Some(model) = model {
+            if let Err(e) = self.show_raw(model).await {
+                health.status = match e {
+                    OllamaError::ModelMissing(_) => HealthStatus::ModelMissing,
+                    _ => HealthStatus::Error,
+                };
+                health.message = Some(e.to_string());
+            }
+        }
+        health
+    }
+
+    /// Download a model, calling `on_progress` for each progress line the server sends
+    pub async fn pull(&self, model: &str, mut on_progress: impl FnMut(PullProgress)) -> Result<(), OllamaError> {
+        let request = self.http.post(self.url("pull")).json(&json!({ "model": model, "stream": true }));
+        let mut response = self.send(request, Some(model)).await?;
+
+        // The body is one JSON object per line
+        let mut buffer = Vec::new();
+        loop {
+            let chunk = response.chunk().await?;
+            let finished = chunk.is_none();
+            if let Some(chunk) = chunk {
+                buffer.extend_from_slice(&chunk);
+            }
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                if let Some(progress) = pull_line(&line, model)? {
+                    on_progress(progress);
+                }
+            }
+            if finished {
+                if let Some(progress) = pull_line(&buffer, model)? {
+                    on_progress(progress);
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, model: &str) -> Result<(), OllamaError> {
+        let request = self.http.delete(self.url("delete")).json(&json!({ "model": model }));
+        self.send(request.timeout(REQUEST_TIMEOUT), Some(model)).await?;
+        Ok(())
+    }
+
+    pub async fn show(&self, model: &str) -> Result<ModelDetails, OllamaError> {
+        Ok(model_details(model, self.show_raw(model).await?))
+    }
+
+    /// Models loaded in memory
+    pub async fn running(&self) -> Result<Vec<RunningModel>, OllamaError> {
+        let response = self.send(self.http.get(self.url("ps")).timeout(REQUEST_TIMEOUT), None).await?;
+        let body: Value = response.json().await?;
+        Ok(body["models"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|m| serde_json::from_value(m.clone()).ok())
+            .collect())
+    }
+
+    /// Free a loaded model's memory. Ollama does this when a request sets `keep_alive` to 0.
+    pub async fn unload(&self, model: &str) -> Result<(), OllamaError> {
+        let request = self.http.post(self.url("generate")).json(&json!({ "model": model, "keep_alive": 0 }));
+        self.send(request.timeout(REQUEST_TIMEOUT), Some(model)).await?;
+        Ok(())
+    }
+
+    async fn show_raw(&self, model: &str) -> Result<Value, OllamaError> {
+        let request = self.http.post(self.url("show")).json(&json!({ "model": model }));
+        let response = self.send(request.timeout(REQUEST_TIMEOUT), Some(model)).await?;
+        Ok(response.json().await?)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/{}", self.root, path)
+    }
+
+    /// Send a request, telling a server that is down apart from one that does not have `model`
+    async fn send(&self, request: reqwest::RequestBuilder, model: Option<&str>) -> Result<reqwest::Response, OllamaError> {
+        let response = request.send().await.map_err(|e| {
+            // Only a refused or unreachable connection means the server is down; a timeout
+            // after connecting is a busy server, e.g. one still loading a model
+            if e.is_connect() {
+                OllamaError::NotRunning(self.root.clone())
+            } else {
+                OllamaError::Request(e)
+            }
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let message = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|v| v["error"].as_str().map(String::from))
+            .unwrap_or(body);
+        Err(api_error(status.as_u16(), &message, model))
+    }
+}
+
+/// Ollama answers 404 for unknown models, but a pull of an unknown name fails mid-stream
+fn api_error(status: u16, message: &str, model: Option<&str>) -> OllamaError {
+    match model {
+        Some(model) if status == 404 || message.contains("not found") || message.contains("file does not exist") => {
+            OllamaError::ModelMissing(model.to_string())
+        }
+        _ => OllamaError::Api { status, message: message.to_string() },
+    }
+}
+
+/// Progress from one line of a pull, or the error the server reported in it
+fn pull_line(line: &[u8], model: &str) -> Result<Option<PullProgress>, OllamaError> {
+    let Ok(value) = serde_json::from_slice::<Value>(line) else {
+        return Ok(None);
+    };
+    if let Some(message) = value["error"].as_str() {
+        return Err(api_error(200, message, Some(model)));
+    }
+    Ok(serde_json::from_value(value).ok())
+}
+
+fn model_details(name: &str, show: Value) -> ModelDetails {
+    let mut parameters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for line in show["parameters"].as_str().unwrap_or_default().lines() {
+        if let Some((key, value)) = line.trim().split_once(char::is_whitespace) {
+            let value = value.trim().trim_matches('"').to_string();
+            parameters.entry(key.to_string()).or_default().push(value);
+        }
+    }
+    let num_ctx = parameters
+        .get("num_ctx")
+        .and_then(|values| values.last())
+        .and_then(|value| value.parse().ok());
+
+    // Keys in model_info are prefixed with the architecture, as in `llama.context_length`
+    let info = &show["model_info"];
+    let context_length = info["general.architecture"]
+        .as_str()
+        .and_then(|arch| info[format!("{}.context_length", arch)].as_u64());
+
+    let details = &show["details"];
+    let text = |value: &Value| value.as_str().filter(|s| !s.is_empty()).map(String::from);
+    ModelDetails {
+        name: name.to_string(),
+        modelfile: show["modelfile"].as_str().unwrap_or_default().to_string(),
+        parameters,
+        template: text(&show["template"]),
+        family: text(&details["family"]),
+        parameter_size: text(&details["parameter_size"]),
+        quantization_level: text(&details["quantization_level"]),
+        context_length,
+        num_ctx,
+        capabilities: show["capabilities"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c.as_str().map(String::from))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_server::{closed_url, Reply, TestServer};
+
+    /// A stand-in Ollama server answering `/api/version`, with `show` for everything else
+    async fn serve_with_show(show: impl Fn() -> Reply + Send + 'static) -> String {
+        TestServer::start(move |request| match request.path.as_str() {
+            "/api/version" => Reply::json(200, json!({ "version": "0.6.0" })),
+            _ => show(),
+        })
+        .await
+        .url
+    }
+
+    #[tokio::test]
+    async fn health_is_ok_when_the_model_exists() {
+        let url = serve_with_show(|| Reply::json(200, json!({}))).await;
+        let health = OllamaClient::new(&format!("{}/v1", url)).health(Some("llama3")).await;
+        assert_eq!(health.status, HealthStatus::Ok);
+        assert_eq!(health.version.as_deref(), Some("0.6.0"));
+    }
+
+    #[tokio::test]
+    async fn health_tells_a_missing_model_from_a_failing_server() {
+        let url = serve_with_show(|| Reply::json(404, json!({ "error": "model 'llama3' not found" }))).await;
+        let health = OllamaClient::new(&url).health(Some("llama3")).await;
+        assert_eq!(health.status, HealthStatus::ModelMissing);
+
+        let url = serve_with_show(|| Reply::json(500, json!({ "error": "out of memory" }))).await;
+        let health = OllamaClient::new(&url).health(Some("llama3")).await;
+        assert_eq!(health.status, HealthStatus::Error);
+        assert!(health.message.unwrap().contains("out of memory"));
+    }
+
+    #[tokio::test]
+    async fn health_reports_a_server_that_is_down() {
+        let health = OllamaClient::new(&closed_url().await).health(None).await;
+        assert_eq!(health.status, HealthStatus::NotRunning);
+    }
+
+    #[tokio::test]
+    async fn health_reports_a_running_server_that_fails_as_an_error() {
+        let server = TestServer::start(|_| Reply::json(500, json!({ "error": "internal error" }))).await;
+        let health = OllamaClient::new(&server.url).health(None).await;
+        assert_eq!(health.status, HealthStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn pull_reports_progress_lines_split_across_chunks() {
+        let server = TestServer::start(|_| {
+            Reply::chunked(
+                200,
+                "application/x-ndjson",
+                &[
+                    "{\"status\":\"pulling manifest\"}\n{\"status\":\"downloading\",\"digest\":\"sha256:ab\",",
+                    "\"total\":200,\"completed\":50}\n",
+                    "{\"status\":\"success\"}",
+                ],
+            )
+        })
+        .await;
+        let mut progress = Vec::new();
+        OllamaClient::new(&server.url)
+            .pull("llama3", |p| progress.push(p))
+            .await
+            .unwrap();
+
+        let statuses: Vec<&str> = progress.iter().map(|p| p.status.as_str()).collect();
+        assert_eq!(statuses, vec!["pulling manifest", "downloading", "success"]);
+        assert_eq!(progress[1].percent(), Some(25.0));
+        assert_eq!(progress[0].percent(), None);
+    }
+
+    #[tokio::test]
+    async fn pull_fails_on_an_error_line() {
+        let server = TestServer::start(|_| {
+            Reply::text(200, "{\"status\":\"pulling manifest\"}\n{\"error\":\"pull model manifest: file does not exist\"}\n")
+        })
+        .await;
+        let result = OllamaClient::new(&server.url).pull("nope", |_| {}).await;
+        assert!(matches!(result, Err(OllamaError::ModelMissing(model)) if model == "nope"));
+    }
+}
@@ -0,0 +1,301 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::config::{is_local_llm_base_url, normalize_base_url_for_openai_api, ApiKeysConfig};
+use super::llm_client::shared_http_client;
+use super::llm_executor::RetryPolicy;
+use super::secrets_service::mask_secret;
+
+/// Wire protocol spoken by a provider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// OpenAI or any OpenAI-compatible endpoint (OpenRouter, Groq, Together, DeepSeek, LM Studio, Ollama)
+    #[default]
+    Openai,
+    Anthropic,
+    Google,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderTimeouts {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_secs: Option<u64>,
+    /// Limit for a whole non-streaming request. Streams are only bounded by `connect_secs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_secs: Option<u64>,
+}
+
+/// A named endpoint the user can switch to. Several profiles may share a kind,
+/// e.g. two OpenAI accounts with different keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub kind: ProviderKind,
+    pub base_url: String,
+    /// Name of the key in the encrypted key store. `None` for endpoints that need no key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    /// Extra headers sent with every request
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub timeouts: ProviderTimeouts,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Models tried in order when the requested one is rate-limited or overloaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_models: Vec<String>,
+}
+
+/// Saved provider profiles, stored in the app config dir as `providers.json`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ProvidersConfig {
+    /// Profile used for requests. `None` falls back to the proxy/local/env settings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<String>,
+    #[serde(default)]
+    pub profiles: Vec<ProviderProfile>,
+}
+
+/// Messages API version sent unless a profile overrides the header
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+fn providers_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("SentinelOps").join("providers.json"))
+}
+
+impl ProvidersConfig {
+    /// Load saved profiles. On first run, profiles are created for every saved API key and the
+    /// one whose host matches `current_base_url` becomes active.
+    pub fn load_or_migrate(current_base_url: &str) -> Self {
+        if let Some(path) = providers_path() {
+            if let Ok(json) = fs::read_to_string(&path) {
+                if let Ok(config) = serde_json::from_str(&json) {
+                    return config;
+                }
+            }
+        }
+
+        let migrated = Self::from_saved_keys(&ApiKeysConfig::load(), current_base_url);
+        if !migrated.profiles.is_empty() {
+            if let Err(e) = migrated.save() {
+                tracing::warn!("Could not save provider profiles: {}", e);
+            }
+        }
+        migrated
+    }
+
+    fn from_saved_keys(keys: &ApiKeysConfig, current_base_url: &str) -> Self {
+        let current_host = url_host(current_base_url);
+        let profiles: Vec<ProviderProfile> = builtin_profiles()
+            .into_iter()
+            .filter(|p| p.key_ref.as_deref().is_some_and(|r| keys.get_key(r).is_some()))
+            .collect();
+        let active = profiles
+            .iter()
+            .find(|p| current_host.is_some() && url_host(&p.base_url) == current_host)
+            .map(|p| p.id.clone());
+
+        Self { active, profiles }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(path) = providers_path() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+            fs::write(&path, json).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&ProviderProfile> {
+        self.profiles.iter().find(|p| p.id == id)
+    }
+
+    pub fn active_profile(&self) -> Option<&ProviderProfile> {
+        self.active.as_deref().and_then(|id| self.get(id))
+    }
+
+    /// Add a profile or replace the one with the same ID
+    pub fn upsert(&mut self, mut profile: ProviderProfile) -> Result<(), String> {
+        profile.id = profile.id.trim().to_string();
+        profile.base_url = profile.base_url.trim().trim_end_matches('/').to_string();
+        profile.key_ref = profile.key_ref.filter(|r| !r.trim().is_empty());
+        if profile.id.is_empty() {
+            return Err("Profile ID cannot be empty".to_string());
+        }
+        if url_host(&profile.base_url).is_none() {
+            return Err(format!("Invalid base URL: {}", profile.base_url));
+        }
+        if profile.name.trim().is_empty() {
+            profile.name = profile.id.clone();
+        }
+
+        match self.profiles.iter_mut().find(|p| p.id == profile.id) {
+            Some(existing) => {
+                // Header values are listed masked; one sent back unchanged keeps the saved value
+                for (name, value) in profile.headers.iter_mut() {
+                    if let Some(saved) = existing.headers.get(name) {
+                        if *value == mask_secret(saved) {
+                            value.clone_from(saved);
+                        }
+                    }
+                }
+                *existing = profile;
+            }
+            None => self.profiles.push(profile),
+        }
+        Ok(())
+    }
+
+    /// Add the built-in profile for a key name if no profile uses that key yet.
+    /// Returns true when a profile was added.
+    pub fn ensure_builtin(&mut self, key_name: &str) -> bool {
+        if self.profiles.iter().any(|p| p.key_ref.as_deref() == Some(key_name)) {
+            return false;
+        }
+        match builtin_profiles().into_iter().find(|p| p.id == key_name) {
+            Some(profile) if self.get(&profile.id).is_none() => {
+                self.profiles.push(profile);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let before = self.profiles.len();
+        self.profiles.retain(|p| p.id != id);
+        if self.active.as_deref() == Some(id) {
+            self.active = None;
+        }
+        self.profiles.len() != before
+    }
+
+    pub fn set_active(&mut self, id: Option<&str>) -> Result<(), String> {
+        if let Some(id) = id {
+            if self.get(id).is_none() {
+                return Err(format!("Provider profile not found: {}", id));
+            }
+        }
+        self.active = id.map(String::from);
+        Ok(())
+    }
+}
+
+/// Profiles for the providers the app has always offered keys for
+pub fn builtin_profiles() -> Vec<ProviderProfile> {
+    [
+        ("openrouter", "OpenRouter", ProviderKind::Openai, "https://openrouter.ai/api/v1"),
+        ("openai", "OpenAI", ProviderKind::Openai, "https://api.openai.com/v1"),
+        ("anthropic", "Anthropic", ProviderKind::Anthropic, "https://api.anthropic.com/v1"),
+        ("google", "Google Gemini", ProviderKind::Google, "https://generativelanguage.googleapis.com/v1beta"),
+        ("groq", "Groq", ProviderKind::Openai, "https://api.groq.com/openai/v1"),
+        ("together", "Together AI", ProviderKind::Openai, "https://api.together.xyz/v1"),
+        ("deepseek", "DeepSeek", ProviderKind::Openai, "https://api.deepseek.com/v1"),
+    ]
+    .into_iter()
+    .map(|(id, name, kind, base_url)| ProviderProfile {
+        id: id.to_string(),
+        name: name.to_string(),
+        kind,
+        base_url: base_url.to_string(),
+        key_ref: Some(id.to_string()),
+        default_model: None,
+        headers: BTreeMap::new(),
+        timeouts: ProviderTimeouts::default(),
+        retry: RetryPolicy::default(),
+        fallback_models: Vec::new(),
+    })
+    .collect()
+}
+
+fn url_host(url: &str) -> Option<String> {
+    reqwest::Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|h| h.to_lowercase())
+}
+
+/// Everything needed to send a request to the selected provider
+#[derive(Debug, Clone)]
+pub struct LlmTarget {
+    /// Active profile ID, or `None` when using the proxy/local/env settings
+    pub profile_id: Option<String>,
+    /// Display name for the UI
+    pub provider: String,
+    pub kind: ProviderKind,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    pub headers: BTreeMap<String, String>,
+    pub timeouts: ProviderTimeouts,
+    pub retry: RetryPolicy,
+    pub fallback_models: Vec<String>,
+    /// The endpoint authenticates on its own (SentinelOps proxy, keyless profile)
+    pub keyless: bool,
+}
+
+impl LlmTarget {
+    /// Whether a request can be sent: proxies, keyless profiles and local servers need no key
+    pub fn is_configured(&self) -> bool {
+        self.keyless || self.api_key.is_some() || is_local_llm_base_url(&self.base_url)
+    }
+
+    /// Full URL for `path`. Local OpenAI-compatible servers get `/v1` appended; native
+    /// Anthropic and Gemini base URLs are used as given.
+    pub fn url(&self, path: &str) -> String {
+        let base = match self.kind {
+            ProviderKind::Openai => normalize_base_url_for_openai_api(&self.base_url),
+            ProviderKind::Anthropic | ProviderKind::Google => self.base_url.trim().to_string(),
+        };
+        format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/'))
+    }
+
+    /// POST to `path` with auth and profile headers, bounded by the request timeout
+    pub fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        let req = self.post_stream(path);
+        match self.timeouts.request_secs {
+            Some(secs) => req.timeout(Duration::from_secs(secs)),
+            None => req,
+        }
+    }
+
+    /// POST for a streamed response; only the connect timeout applies
+    pub fn post_stream(&self, path: &str) -> reqwest::RequestBuilder {
+        let mut req = shared_http_client(self.timeouts.connect_secs)
+            .post(self.url(path))
+            .header("Content-Type", "application/json")
+            .header("HTTP-Referer", "https://sentinelops.app")
+            .header("X-Title", "SentinelOps");
+        if let Some(key) = &self.api_key {
+            req = match self.kind {
+                ProviderKind::Anthropic => req.header("x-api-key", key),
+                ProviderKind::Google => req.header("x-goog-api-key", key),
+                _ => req.header("Authorization", format!("Bearer {}", key)),
+            };
+        }
+        if self.kind == ProviderKind::Anthropic
+            && !self.headers.keys().any(|h| h.eq_ignore_ascii_case("anthropic-version"))
+        {
+            req = req.header("anthropic-version", ANTHROPIC_VERSION);
+        }
+        for (name, value) in &self.headers {
+            req = req.header(name, value);
+        }
+        req
+    }
+}
@@ -0,0 +1,57 @@
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSearchResult {
+    pub title: String,
+    pub url: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<String>,
+}
+
+/// Query the Brave Search API for up to `count` results (capped at 20)
+pub async fn brave_search(api_key: &str, query: &str, count: u32) -> Result<Vec<WebSearchResult>, String> {
+    let client = reqwest::Client::new();
+    let search_count = count.min(20);
+
+    let url = format!(
+        "https://api.search.brave.com/res/v1/web/search?q={}&count={}",
+        urlencoding::encode(query),
+        search_count
+    );
+
+    let response = client
+        .get(&url)
+        .header("Accept", "application/json")
+        .header("X-Subscription-Token", api_key)
+        .send()
+        .await
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("HTTP {}: {}", status, error_text));
+    }
+
+    let json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
+
+    let results: Vec<WebSearchResult> = json["web"]["results"]
+        .as_array()
+        .map(|arr| {
+            arr.iter().filter_map(|item| {
+                Some(WebSearchResult {
+                    title: item["title"].as_str()?.to_string(),
+                    url: item["url"].as_str()?.to_string(),
+                    description: item["description"].as_str()
+                        .unwrap_or("")
+                        .to_string(),
+                    age: item["age"].as_str().map(String::from),
+                })
+            }).collect()
+        })
+        .unwrap_or_default();
+
+    Ok(results)
+}
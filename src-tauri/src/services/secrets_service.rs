@@ -0,0 +1,276 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SecretsError {
+    #[error("API keys are locked; enter your passphrase to unlock them")]
+    Locked,
+    #[error("Wrong passphrase or corrupted key file")]
+    Decrypt,
+    #[error("The OS secret service is unavailable or no longer holds the key for your API keys")]
+    KeyringUnavailable,
+    #[error("Key derivation failed: {0}")]
+    Kdf(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid key file: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// Where the encryption key for a secrets file comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeySource {
+    /// Random key kept in the OS secret service (Keychain, Credential Manager, Secret Service)
+    Keyring,
+    /// Key derived with Argon2id from a passphrase entered this session
+    Passphrase,
+}
+
+/// On-disk format of an encrypted secrets file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EncryptedFile {
+    version: u32,
+    key_source: KeySource,
+    /// Argon2 salt, only for passphrase-derived keys
+    #[serde(skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Decrypted contents of a secrets file
+pub struct SecretContents {
+    pub plaintext: Vec<u8>,
+    /// True when the file was a legacy plaintext file and should be re-saved encrypted
+    pub needs_migration: bool,
+}
+
+const FILE_VERSION: u32 = 1;
+const KEYRING_SERVICE: &str = "SentinelOps";
+const KEYRING_USER: &str = "api-keys-master-key";
+/// Lets headless setups provide the fallback passphrase without a prompt
+const PASSPHRASE_ENV: &str = "SENTINELOPS_KEYS_PASSPHRASE";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+/// Master key read from the secret service, cached for the process
+static KEYRING_KEY: Mutex<Option<[u8; KEY_LEN]>> = Mutex::new(None);
+/// Passphrase entered this session
+static PASSPHRASE: Mutex<Option<String>> = Mutex::new(None);
+/// Last passphrase-derived key and its salt, so Argon2 runs once per session
+static DERIVED_KEY: Mutex<Option<(Vec<u8>, [u8; KEY_LEN])>> = Mutex::new(None);
+
+/// Remember the fallback passphrase for this session
+pub fn set_passphrase(passphrase: Option<String>) {
+    *PASSPHRASE.lock().unwrap() = passphrase.filter(|p| !p.is_empty());
+    *DERIVED_KEY.lock().unwrap() = None;
+}
+
+fn passphrase() -> Option<String> {
+    PASSPHRASE
+        .lock()
+        .unwrap()
+        .clone()
+        .or_else(|| std::env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()))
+}
+
+/// The key source new writes will use, or `None` when keys cannot be encrypted yet.
+/// Only looks at the secret service; the key is created by the first write.
+pub fn key_source() -> Option<KeySource> {
+    if keyring_key(false).is_ok() {
+        Some(KeySource::Keyring)
+    } else if passphrase().is_some() {
+        Some(KeySource::Passphrase)
+    } else {
+        None
+    }
+}
+
+/// Fetch the master key from the secret service, optionally creating it. `Ok(None)`
+/// means the service works but holds no key yet.
+fn keyring_key(create: bool) -> Result<Option<[u8; KEY_LEN]>, SecretsError> {
+    if let Some(key) = *KEYRING_KEY.lock().unwrap() {
+        return Ok(Some(key));
+    }
+
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| {
+        tracing::debug!("Secret service unavailable: {}", e);
+        SecretsError::KeyringUnavailable
+    })?;
+    let key = match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = STANDARD.decode(encoded).map_err(|_| SecretsError::KeyringUnavailable)?;
+            <[u8; KEY_LEN]>::try_from(bytes.as_slice()).map_err(|_| SecretsError::KeyringUnavailable)?
+        }
+        Err(keyring::Error::NoEntry) if create => {
+            let mut key = [0u8; KEY_LEN];
+            OsRng.fill_bytes(&mut key);
+            entry.set_password(&STANDARD.encode(key)).map_err(|e| {
+                tracing::debug!("Could not store key in secret service: {}", e);
+                SecretsError::KeyringUnavailable
+            })?;
+            key
+        }
+        Err(keyring::Error::NoEntry) => return Ok(None),
+        Err(e) => {
+            tracing::debug!("Secret service unavailable: {}", e);
+            return Err(SecretsError::KeyringUnavailable);
+        }
+    };
+
+    *KEYRING_KEY.lock().unwrap() = Some(key);
+    Ok(Some(key))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<[u8; KEY_LEN], SecretsError> {
+    if let Some((cached_salt, key)) = DERIVED_KEY.lock().unwrap().as_ref() {
+        if cached_salt == salt {
+            return Ok(*key);
+        }
+    }
+
+    let mut key = [0u8; KEY_LEN];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| SecretsError::Kdf(e.to_string()))?;
+
+    *DERIVED_KEY.lock().unwrap() = Some((salt.to_vec(), key));
+    Ok(key)
+}
+
+/// Read and decrypt a secrets file. Returns `None` when it does not exist.
+/// Legacy plaintext files are returned as-is with `needs_migration` set.
+pub fn read(path: &Path) -> Result<Option<SecretContents>, SecretsError> {
+    let raw = match fs::read(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let value: serde_json::Value = serde_json::from_slice(&raw)?;
+    if value.get("ciphertext").is_none() {
+        return Ok(Some(SecretContents {
+            plaintext: raw,
+            needs_migration: true,
+        }));
+    }
+
+    let file: EncryptedFile = serde_json::from_value(value)?;
+    let key = match file.key_source {
+        KeySource::Keyring => keyring_key(false)?.ok_or(SecretsError::KeyringUnavailable)?,
+        KeySource::Passphrase => {
+            let passphrase = passphrase().ok_or(SecretsError::Locked)?;
+            let salt = file
+                .salt
+                .as_deref()
+                .and_then(|s| STANDARD.decode(s).ok())
+                .ok_or(SecretsError::Decrypt)?;
+            derive_key(&passphrase, &salt)?
+        }
+    };
+
+    let nonce = STANDARD.decode(&file.nonce).map_err(|_| SecretsError::Decrypt)?;
+    let ciphertext = STANDARD.decode(&file.ciphertext).map_err(|_| SecretsError::Decrypt)?;
+    if nonce.len() != 12 {
+        return Err(SecretsError::Decrypt);
+    }
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| SecretsError::Decrypt)?;
+
+    Ok(Some(SecretContents {
+        plaintext,
+        needs_migration: false,
+    }))
+}
+
+/// Encrypt `plaintext` and write it to `path`, readable only by the current user
+pub fn write(path: &Path, plaintext: &[u8]) -> Result<(), SecretsError> {
+    let (key_source, key, salt) = if let Ok(Some(key)) = keyring_key(true) {
+        (KeySource::Keyring, key, None)
+    } else {
+        let passphrase = passphrase().ok_or(SecretsError::Locked)?;
+        // Reuse the session's salt so the derived key stays cached
+        let salt = match DERIVED_KEY.lock().unwrap().as_ref() {
+            Some((salt, _)) => salt.clone(),
+            None => {
+                let mut salt = vec![0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                salt
+            }
+        };
+        let key = derive_key(&passphrase, &salt)?;
+        (KeySource::Passphrase, key, Some(salt))
+    };
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| SecretsError::Decrypt)?;
+
+    let file = EncryptedFile {
+        version: FILE_VERSION,
+        key_source,
+        salt: salt.map(|s| STANDARD.encode(s)),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    };
+
+    write_private(path, serde_json::to_string_pretty(&file)?.as_bytes())?;
+    Ok(())
+}
+
+/// Atomically write a file with 0600 permissions on Unix
+pub fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&temp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    // The mode only applies on creation, so tighten a leftover temp file too
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    fs::rename(&temp_path, path)
+}
+
+/// Show only enough of a secret to tell keys apart, e.g. `sk-o…x9Qz`
+pub fn mask_secret(secret: &str) -> String {
+    let chars: Vec<char> = secret.chars().collect();
+    if chars.len() <= 8 {
+        return "•".repeat(chars.len());
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{}…{}", head, tail)
+}
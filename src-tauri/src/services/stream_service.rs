@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+/// Cancellation signal for one running LLM stream, registered in `AppState::streams`
+pub struct StreamHandle {
+    /// Label of the window the stream's events are sent to
+    pub window: String,
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl StreamHandle {
+    pub fn new(window: &str) -> Self {
+        Self {
+            window: window.to_string(),
+            cancelled: AtomicBool::new(false),
+            notify: Notify::new(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // Wake every waiter; later waiters see the flag instead of a stored permit
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once `cancel` has been called
+    pub async fn cancelled(&self) {
+        let notified = self.notify.notified();
+        tokio::pin!(notified);
+        // Register as a waiter before checking the flag, so a cancel in between still wakes us
+        notified.as_mut().enable();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn cancel_wakes_every_waiter() {
+        let handle = Arc::new(StreamHandle::new("main"));
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let handle = handle.clone();
+                tokio::spawn(async move { handle.cancelled().await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        handle.cancel();
+        for waiter in waiters {
+            tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+        }
+        // Waiting after the cancel returns at once
+        tokio::time::timeout(Duration::from_secs(1), handle.cancelled()).await.unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use thiserror::Error;
+
+use super::llm_adapter::{ChatCompletionResult, ChatRequest, ResponseFormat, TokenUsage};
+use super::llm_client::LlmClient;
+use super::llm_executor::SendError;
+use super::tool_calls::{parse_arguments, validate};
+
+/// Attempts made when the caller does not choose
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+/// Providers want an object at the root, so other schemas are wrapped in this property
+const WRAPPED_KEY: &str = "value";
+
+#[derive(Error, Debug)]
+pub enum StructuredError {
+    #[error("The schema must be a JSON Schema object")]
+    InvalidSchema,
+    #[error(transparent)]
+    Send(#[from] SendError),
+    #[error("No reply matched the schema after {attempts} attempts: {}", errors.join("; "))]
+    Mismatch {
+        attempts: u32,
+        errors: Vec<String>,
+        /// The last reply, for showing to the user
+        output: Option<String>,
+    },
+}
+
+impl StructuredError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            StructuredError::InvalidSchema => "INVALID_SCHEMA",
+            StructuredError::Send(e) => e.code("LLM_ERROR"),
+            StructuredError::Mismatch { .. } => "SCHEMA_MISMATCH",
+        }
+    }
+}
+
+pub struct StructuredRequest<'a> {
+    pub model: &'a str,
+    pub messages: Vec<Value>,
+    pub schema: &'a Value,
+    /// Names the schema for the provider, as in OpenAI's `json_schema.name`
+    pub name: &'a str,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    /// Requests made in total, counting retries after invalid replies
+    pub max_attempts: u32,
+    pub fallback_models: &'a [String],
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructuredResult {
+    /// The reply, valid against the schema
+    pub value: Value,
+    pub model: String,
+    pub attempts: u32,
+    /// Summed over every attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<TokenUsage>,
+}
+
+/// Ask for a reply matching `schema`, using the provider's JSON schema mode or a forced
+/// tool call. When the provider rejects that with a 400, the schema is put in the prompt
+/// instead. A reply that does not validate is sent back with the errors, until one
+/// does or the attempts run out.
+pub async fn complete_structured(client: &LlmClient, mut request: StructuredRequest<'_>) -> Result<StructuredResult, StructuredError> {
+    if !request.schema.is_object() {
+        return Err(StructuredError::InvalidSchema);
+    }
+    let wrapped = request.schema["type"] != "object";
+    let format = ResponseFormat {
+        name: schema_name(request.name),
+        schema: if wrapped {
+            json!({
+                "type": "object",
+                "properties": { WRAPPED_KEY: request.schema },
+                "required": [WRAPPED_KEY],
+                "additionalProperties": false
+            })
+        } else {
+            request.schema.clone()
+        },
+    };
+
+    let attempts = request.max_attempts.max(1);
+    let mut messages = std::mem::take(&mut request.messages);
+    let mut usage: Option<TokenUsage> = None;
+    let mut errors = Vec::new();
+    let mut output = None;
+    // Cleared when the provider rejects the schema mode; validation still applies
+    let mut response_format = Some(&format);
+
+    for attempt in 1..=attempts {
+        let reply = match client.chat(&chat_request(&request, &messages, response_format), request.fallback_models).await {
+            Err(SendError::Http { status, .. }) if status == StatusCode::BAD_REQUEST && response_format.is_some() => {
+                response_format = None;
+                messages.push(json!({ "role": "user", "content": schema_prompt(&format.schema) }));
+                client.chat(&chat_request(&request, &messages, None), request.fallback_models).await?
+            }
+            reply => reply?,
+        };
+        usage = add_usage(usage, reply.usage.as_ref());
+
+        let raw = raw_output(&reply, &format.name);
+        match check_output(raw.as_deref(), &format.schema) {
+            Ok(mut value) => {
+                if wrapped {
+                    value = value[WRAPPED_KEY].take();
+                }
+                return Ok(StructuredResult {
+                    value,
+                    model: reply.model,
+                    attempts: attempt,
+                    usage,
+                });
+            }
+            Err(problems) => {
+                // Show the model its reply and what is wrong with it
+                messages.push(json!({ "role": "assistant", "content": raw.clone().unwrap_or_default() }));
+                messages.push(json!({ "role": "user", "content": retry_prompt(&problems) }));
+                errors = problems;
+                output = raw;
+            }
+        }
+    }
+
+    Err(StructuredError::Mismatch { attempts, errors, output })
+}
+
+/// `complete_structured`, deserialized into `T`
+pub async fn complete_as<T: DeserializeOwned>(client: &LlmClient, request: StructuredRequest<'_>) -> Result<T, StructuredError> {
+    let result = complete_structured(client, request).await?;
+    serde_json::from_value(result.value).map_err(|e| StructuredError::Mismatch {
+        attempts: result.attempts,
+        errors: vec![e.to_string()],
+        output: None,
+    })
+}
+
+fn chat_request<'a>(request: &StructuredRequest<'a>, messages: &'a [Value], response_format: Option<&'a ResponseFormat>) -> ChatRequest<'a> {
+    ChatRequest {
+        model: request.model,
+        messages,
+        tools: &[],
+        temperature: request.temperature,
+        max_tokens: request.max_tokens,
+        stream: false,
+        reasoning: None,
+        response_format,
+    }
+}
+
+/// OpenAI accepts letters, digits, `_` and `-`, up to 64 characters
+fn schema_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        "response".to_string()
+    } else {
+        name
+    }
+}
+
+/// The reply as text: the forced tool call's arguments, or the message content
+fn raw_output(reply: &ChatCompletionResult, name: &str) -> Option<String> {
+    reply
+        .tool_calls
+        .iter()
+        .flatten()
+        .find(|call| call.function.name == name)
+        .map(|call| call.function.arguments.clone())
+        .or_else(|| reply.content.clone())
+        .filter(|text| !text.trim().is_empty())
+}
+
+fn check_output(raw: Option<&str>, schema: &Value) -> Result<Value, Vec<String>> {
+    let raw = raw.ok_or_else(|| vec!["The reply was empty".to_string()])?;
+    let value = parse_output(raw).map_err(|e| vec![format!("The reply is not valid JSON: {}", e)])?;
+    let errors = validate(&value, schema);
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parse JSON leniently, falling back to the outermost object or array when the model
+/// wrapped it in prose
+fn parse_output(raw: &str) -> Result<Value, String> {
+    let error = match parse_arguments(raw) {
+        Ok(parsed) => return Ok(parsed.value),
+        Err(e) => e,
+    };
+    let start = raw.find(['{', '[']).ok_or_else(|| error.clone())?;
+    let end = raw.rfind(['}', ']']).filter(|&end| end > start).ok_or_else(|| error.clone())?;
+    serde_json::from_str(&raw[start..=end]).map_err(|_| error)
+}
+
+/// Asks for the schema in the prompt, for providers without a JSON schema mode
+fn schema_prompt(schema: &Value) -> String {
+    format!(
+        "Reply with only a JSON value matching this JSON schema, without any other text:\n{}",
+        serde_json::to_string_pretty(schema).unwrap_or_default()
+    )
+}
+
+fn retry_prompt(errors: &[String]) -> String {
+    format!(
+        "Your reply does not match the required JSON schema:\n{}\n\nReply again with only the corrected JSON.",
+        errors.iter().map(|e| format!("- {}", e)).collect::<Vec<_>>().join("\n")
+    )
+}
+
+fn add_usage(total: Option<TokenUsage>, usage: Option<&TokenUsage>) -> Option<TokenUsage> {
+    let Some(usage) = usage else {
+        return total;
+    };
+    Some(match total {
+        None => usage.clone(),
+        Some(total) => TokenUsage {
+            prompt_tokens: total.prompt_tokens + usage.prompt_tokens,
+            completion_tokens: total.completion_tokens + usage.completion_tokens,
+            total_tokens: total.total_tokens + usage.total_tokens,
+            reasoning_tokens: match (total.reasoning_tokens, usage.reasoning_tokens) {
+                (None, None) => None,
+                (a, b) => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::llm_executor::RetryPolicy;
+    use crate::services::provider_service::{LlmTarget, ProviderKind, ProviderTimeouts};
+    use crate::services::test_server::{Reply, TestServer};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn parse_output_finds_json_wrapped_in_prose() {
+        assert_eq!(parse_output(r#"{"a": 1}"#), Ok(json!({ "a": 1 })));
+        assert_eq!(parse_output("Here it is:\n{\"a\": [1, 2]}\nAnything else?"), Ok(json!({ "a": [1, 2] })));
+        assert_eq!(parse_output("```json\n[1, 2]\n```"), Ok(json!([1, 2])));
+        assert!(parse_output("no JSON here").is_err());
+    }
+
+    #[test]
+    fn check_output_reports_empty_invalid_and_mismatched_replies() {
+        let schema = json!({ "type": "object", "properties": { "a": { "type": "number" } }, "required": ["a"] });
+        assert_eq!(check_output(None, &schema), Err(vec!["The reply was empty".to_string()]));
+        assert!(check_output(Some("nope"), &schema).unwrap_err()[0].starts_with("The reply is not valid JSON"));
+        assert_eq!(
+            check_output(Some(r#"{"a": "x"}"#), &schema),
+            Err(vec!["$.a: expected number, got string".to_string()])
+        );
+        assert_eq!(check_output(Some(r#"{"a": 2}"#), &schema), Ok(json!({ "a": 2 })));
+    }
+
+    #[test]
+    fn schema_name_is_sanitized_for_openai() {
+        assert_eq!(schema_name("my schema.v2"), "my_schema_v2");
+        assert_eq!(schema_name(""), "response");
+        assert_eq!(schema_name(&"x".repeat(100)).len(), 64);
+    }
+
+    /// An OpenAI-compatible endpoint that rejects `response_format` and otherwise replies
+    /// with `content`
+    async fn serve_without_schema_mode(content: &'static str) -> TestServer {
+        TestServer::start(move |request| {
+            if request.json().get("response_format").is_some() {
+                Reply::json(400, json!({ "error": { "message": "response_format is not supported" } }))
+            } else {
+                Reply::json(
+                    200,
+                    json!({ "model": "test-model", "choices": [{ "message": { "role": "assistant", "content": content } }] }),
+                )
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_the_prompt_when_schema_mode_is_rejected() {
+        let server = serve_without_schema_mode(r#"Sure: {"a": 1}"#).await;
+        let client = LlmClient::new(LlmTarget {
+            profile_id: None,
+            provider: "test".to_string(),
+            kind: ProviderKind::Openai,
+            base_url: format!("{}/v1", server.url),
+            api_key: None,
+            model: "test-model".to_string(),
+            headers: BTreeMap::new(),
+            timeouts: ProviderTimeouts::default(),
+            retry: RetryPolicy { max_retries: 0, ..RetryPolicy::default() },
+            fallback_models: Vec::new(),
+            keyless: true,
+        });
+        let schema = json!({ "type": "object", "properties": { "a": { "type": "number" } }, "required": ["a"] });
+
+        let result = complete_structured(
+            &client,
+            StructuredRequest {
+                model: "test-model",
+                messages: vec![json!({ "role": "user", "content": "Give me a" })],
+                schema: &schema,
+                name: "answer",
+                temperature: 0.0,
+                max_tokens: 100,
+                max_attempts: 1,
+                fallback_models: &[],
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.value, json!({ "a": 1 }));
+        assert_eq!(result.attempts, 1);
+        let bodies: Vec<Value> = server.requests().iter().map(|r| r.json()).collect();
+        assert_eq!(bodies.len(), 2);
+        assert!(bodies[1].get("response_format").is_none());
+        let last = bodies[1]["messages"].as_array().unwrap().last().unwrap();
+        assert!(last["content"].as_str().unwrap().contains("JSON schema"));
+    }
+}
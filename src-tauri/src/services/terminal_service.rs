@@ -184,6 +184,16 @@ impl TerminalManager {
     pub fn cleanup(&mut self) {
         self.terminals.retain(|_, term| term.is_running);
     }
+
+    /// Kill every running terminal and forget them all
+    pub fn kill_all(&mut self) {
+        for terminal in self.terminals.values_mut() {
+            if let Some(ref mut child) = terminal.child {
+                let _ = child.kill();
+            }
+        }
+        self.terminals.clear();
+    }
 }
 
 impl Default for TerminalManager {
@@ -0,0 +1,145 @@
+//! A minimal HTTP server on localhost for tests that talk to LLM and Ollama endpoints
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// One request the server received
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// Path including the query string
+    pub path: String,
+    /// Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// The body parsed as JSON, or `Null` when it is not JSON
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or_default()
+    }
+}
+
+/// What the server writes back for one request
+pub struct Reply {
+    status: u16,
+    content_type: &'static str,
+    chunks: Vec<Vec<u8>>,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        Self { status, content_type: "application/json", chunks: vec![body.to_string().into_bytes()] }
+    }
+
+    pub fn text(status: u16, body: &str) -> Self {
+        Self { status, content_type: "text/plain", chunks: vec![body.as_bytes().to_vec()] }
+    }
+
+    pub fn bytes(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self { status, content_type, chunks: vec![body] }
+    }
+
+    /// A body written in pieces with a pause between them, so lines and events can arrive split
+    pub fn chunked(status: u16, content_type: &'static str, chunks: &[&str]) -> Self {
+        Self { status, content_type, chunks: chunks.iter().map(|c| c.as_bytes().to_vec()).collect() }
+    }
+
+    /// A server-sent event stream with one `data:` event per item
+    pub fn sse(events: &[serde_json::Value]) -> Self {
+        let chunks = events.iter().map(|event| format!("data: {}\n\n", event).into_bytes()).collect();
+        Self { status: 200, content_type: "text/event-stream", chunks }
+    }
+}
+
+/// A running server. It answers every connection with `handler` until the test ends.
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub async fn start(handler: impl Fn(&Request) -> Reply + Send + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let Some(request) = read_request(&mut socket).await else {
+                    continue;
+                };
+                let reply = handler(&request);
+                received.lock().unwrap().push(request);
+
+                let head = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-type: {}\r\nconnection: close\r\n\r\n",
+                    reply.status, reply.content_type
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                for (i, chunk) in reply.chunks.iter().enumerate() {
+                    if i > 0 {
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                    }
+                    let _ = socket.write_all(chunk).await;
+                    let _ = socket.flush().await;
+                }
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// Requests received so far, oldest first
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// A localhost URL nothing listens on
+pub async fn closed_url() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    format!("http://{}", listener.local_addr().unwrap())
+}
+
+async fn read_request(socket: &mut TcpStream) -> Option<Request> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = socket.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buf[..n]);
+        let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&data[..end]).into_owned();
+        let mut lines = head.lines();
+        let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+        let headers: Vec<(String, String)> = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
+        let length = headers
+            .iter()
+            .find(|(name, _)| name == "content-length")
+            .and_then(|(_, value)| value.parse().ok())
+            .unwrap_or(0);
+        if data.len() < end + 4 + length {
+            continue;
+        }
+        return Some(Request {
+            method: request_line.next().unwrap_or_default().to_string(),
+            path: request_line.next().unwrap_or_default().to_string(),
+            headers,
+            body: String::from_utf8_lossy(&data[end + 4..end + 4 + length]).into_owned(),
+        });
+    }
+}
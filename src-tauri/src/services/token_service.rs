@@ -0,0 +1,294 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use tiktoken_rs::CoreBPE;
+
+/// Context and output limits for a model
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    pub model: String,
+    /// `None` for models not in the catalog, such as most local models, whose context
+    /// depends on how they are served
+    pub context_window: Option<u32>,
+    pub max_output: Option<u32>,
+    /// Counts use the model's own tokenizer. Other families are estimated with cl100k,
+    /// which is usually within 10-15%.
+    pub exact_tokens: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Cl100k,
+    O200k,
+    /// Tokenizer not public or not bundled; cl100k is used as an estimate
+    Estimate,
+}
+
+/// Known model families, most specific prefix first: (prefix, context window, max output, encoding)
+const CATALOG: &[(&str, u32, u32, Encoding)] = &[
+    ("gpt-5", 400_000, 128_000, Encoding::O200k),
+    ("gpt-4o", 128_000, 16_384, Encoding::O200k),
+    ("gpt-4.1", 1_047_576, 32_768, Encoding::O200k),
+    ("gpt-4.5", 128_000, 16_384, Encoding::O200k),
+    ("gpt-4-turbo", 128_000, 4_096, Encoding::Cl100k),
+    ("gpt-4", 8_192, 4_096, Encoding::Cl100k),
+    ("gpt-3.5-turbo", 16_385, 4_096, Encoding::Cl100k),
+    ("o1", 200_000, 100_000, Encoding::O200k),
+    ("o3", 200_000, 100_000, Encoding::O200k),
+    ("o4", 200_000, 100_000, Encoding::O200k),
+    ("claude-3-haiku", 200_000, 4_096, Encoding::Estimate),
+    ("claude-3-opus", 200_000, 4_096, Encoding::Estimate),
+    ("claude-3-5", 200_000, 8_192, Encoding::Estimate),
+    ("claude-opus-4-5", 200_000, 64_000, Encoding::Estimate),
+    ("claude-opus-4", 200_000, 32_000, Encoding::Estimate),
+    ("claude", 200_000, 64_000, Encoding::Estimate),
+    ("gemini-1.5-pro", 2_097_152, 8_192, Encoding::Estimate),
+    ("gemini-1.5", 1_048_576, 8_192, Encoding::Estimate),
+    ("gemini-1.0", 32_760, 8_192, Encoding::Estimate),
+    ("gemini-2.0", 1_048_576, 8_192, Encoding::Estimate),
+    ("gemini", 1_048_576, 65_536, Encoding::Estimate),
+    ("llama-4", 1_048_576, 8_192, Encoding::Estimate),
+    ("llama4", 1_048_576, 8_192, Encoding::Estimate),
+    ("llama-3.1", 131_072, 4_096, Encoding::Estimate),
+    ("llama-3.2", 131_072, 4_096, Encoding::Estimate),
+    ("llama-3.3", 131_072, 4_096, Encoding::Estimate),
+    ("llama3.1", 131_072, 4_096, Encoding::Estimate),
+    ("llama3.2", 131_072, 4_096, Encoding::Estimate),
+    ("llama3.3", 131_072, 4_096, Encoding::Estimate),
+    ("llama", 8_192, 4_096, Encoding::Estimate),
+    ("mistral-large", 131_072, 4_096, Encoding::Estimate),
+    ("mixtral", 32_768, 4_096, Encoding::Estimate),
+    ("mistral", 32_768, 4_096, Encoding::Estimate),
+    ("deepseek", 65_536, 8_192, Encoding::Estimate),
+    ("qwen", 32_768, 8_192, Encoding::Estimate),
+];
+
+/// Output space kept free when trimming, even if the caller asked for less
+const MIN_OUTPUT_TOKENS: u32 = 1_024;
+
+/// Fixed cost of an image part; matches OpenAI's high-detail 1024x1024 rate
+const IMAGE_TOKENS: usize = 765;
+
+/// Cost of one PDF page; providers send both its text and an image of it
+const PDF_PAGE_TOKENS: usize = 1_500;
+
+/// Limits and encoding of a catalogued model; `None` for anything else
+fn lookup(model: &str) -> Option<(u32, u32, Encoding)> {
+    // Drop the vendor prefix used by routers, e.g. "openai/gpt-4o" or "models/gemini-1.5-pro"
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    CATALOG
+        .iter()
+        .find(|(prefix, ..)| name.starts_with(prefix))
+        .map(|&(_, context, output, encoding)| (context, output, encoding))
+}
+
+fn encoding(model: &str) -> Encoding {
+    lookup(model).map_or(Encoding::Estimate, |(.., encoding)| encoding)
+}
+
+pub fn model_info(model: &str) -> ModelInfo {
+    let limits = lookup(model);
+    ModelInfo {
+        model: model.to_string(),
+        context_window: limits.map(|(context, ..)| context),
+        max_output: limits.map(|(_, output, _)| output),
+        exact_tokens: encoding(model) != Encoding::Estimate,
+    }
+}
+
+fn tokenizer(model: &str) -> &'static CoreBPE {
+    static CL100K: OnceLock<CoreBPE> = OnceLock::new();
+    static O200K: OnceLock<CoreBPE> = OnceLock::new();
+    match encoding(model) {
+        Encoding::O200k => O200K.get_or_init(|| tiktoken_rs::o200k_base().expect("bundled o200k vocabulary")),
+        _ => CL100K.get_or_init(|| tiktoken_rs::cl100k_base().expect("bundled cl100k vocabulary")),
+    }
+}
+
+pub fn count_text(model: &str, text: &str) -> usize {
+    tokenizer(model).encode_with_special_tokens(text).len()
+}
+
+/// Tokens for one chat message, including the per-message framing the API adds
+fn count_message(bpe: &CoreBPE, message: &Value) -> usize {
+    let text = |value: &Value| bpe.encode_with_special_tokens(value.as_str().unwrap_or_default()).len();
+    let mut tokens = 3 + text(&message["role"]) + text(&message["name"]);
+
+    match &message["content"] {
+        Value::Array(parts) => {
+            for part in parts {
+                tokens += match part["type"].as_str() {
+                    Some("text") => text(&part["text"]),
+                    Some("image_url") | Some("image") => IMAGE_TOKENS,
+                    Some("file") => pdf_pages(&part["file"]["file_data"]) * PDF_PAGE_TOKENS,
+                    _ => 0,
+                };
+            }
+        }
+        content => tokens += text(content),
+    }
+    if let Some(calls) = message["tool_calls"].as_array() {
+        for call in calls {
+            tokens += text(&call["function"]["name"]) + text(&call["function"]["arguments"]);
+        }
+    }
+    tokens
+}
+
+/// Pages of a PDF sent as a base64 data URL, counted from its page objects. Files whose
+/// data is not here, or whose pages cannot be found, count as one page.
+fn pdf_pages(file_data: &Value) -> usize {
+    let bytes = file_data
+        .as_str()
+        .and_then(|url| url.split_once(";base64,"))
+        .and_then(|(_, data)| STANDARD.decode(data.trim()).ok())
+        .unwrap_or_default();
+    let pages = ["/Type /Page", "/Type/Page"]
+        .iter()
+        .map(|marker| {
+            bytes
+                .windows(marker.len() + 1)
+                // `/Type /Pages` is the page tree, not a page
+                .filter(|w| w.starts_with(marker.as_bytes()) && w[marker.len()] != b's')
+                .count()
+        })
+        .sum::<usize>();
+    pages.max(1)
+}
+
+/// Prompt size of a chat request: messages, tool definitions and reply priming
+pub fn count_messages(model: &str, messages: &[Value], tools: &[Value]) -> usize {
+    let bpe = tokenizer(model);
+    let messages: usize = messages.iter().map(|m| count_message(bpe, m)).sum();
+    let tools: usize = tools
+        .iter()
+        .map(|t| bpe.encode_with_special_tokens(&t.to_string()).len())
+        .sum();
+    messages + tools + 3
+}
+
+/// Result of fitting a request into the model's context window
+pub struct FittedRequest {
+    /// Messages to send instead of the original ones; `None` when nothing was dropped
+    pub messages: Option<Vec<Value>>,
+    pub max_tokens: u32,
+    /// Estimated size of the prompt that will be sent
+    pub prompt_tokens: usize,
+}
+
+/// Make a request fit the model's context. The output budget is kept as requested unless the
+/// context has no room for it after the prompt, and is then shrunk to what is left. The
+/// catalog's output limit is not applied, since providers raise it faster than the catalog
+/// follows and report it themselves when a request exceeds it. If the prompt alone is too long, leading
+/// system messages and the latest turns are kept and the oldest turns in between are dropped,
+/// with a note in their place. An assistant tool call and its results are dropped together.
+/// Models not in the catalog are sent as requested, since their context is not known here.
+pub fn fit_to_context(model: &str, messages: &[Value], tools: &[Value], max_tokens: u32) -> FittedRequest {
+    let bpe = tokenizer(model);
+    let sizes: Vec<usize> = messages.iter().map(|m| count_message(bpe, m)).collect();
+    let fixed = count_messages(model, &[], tools);
+    let prompt = fixed + sizes.iter().sum::<usize>();
+
+    let Some((context, ..)) = lookup(model) else {
+        return FittedRequest {
+            messages: None,
+            max_tokens,
+            prompt_tokens: prompt,
+        };
+    };
+
+    let room = |prompt: usize| (context as usize).saturating_sub(prompt) as u32;
+    let reserve = max_tokens.min(MIN_OUTPUT_TOKENS);
+    if room(prompt) >= reserve {
+        return FittedRequest {
+            messages: None,
+            max_tokens: max_tokens.min(room(prompt)),
+            prompt_tokens: prompt,
+        };
+    }
+
+    let head = messages.iter().take_while(|m| m["role"] == "system").count();
+    // Turns start at user messages, so tool calls stay with their results and the kept
+    // history still opens with a user message, as Anthropic and Gemini require
+    let turn_starts: Vec<usize> = (head..messages.len())
+        .filter(|&i| messages[i]["role"] == "user")
+        .collect();
+    let note_size = 12;
+
+    let mut cut = head;
+    let mut kept = prompt;
+    for &start in turn_starts.iter().skip(1) {
+        if room(kept + note_size) >= reserve {
+            break;
+        }
+        kept -= sizes[cut..start].iter().sum::<usize>();
+        cut = start;
+    }
+
+    let dropped = cut - head;
+    if dropped == 0 {
+        // Only the latest turn is left and it does not fit; the provider will report the error
+        return FittedRequest {
+            messages: None,
+            max_tokens: max_tokens.min(room(prompt).max(1)),
+            prompt_tokens: prompt,
+        };
+    }
+
+    tracing::info!("Dropped {} messages to fit the {} token context of {}", dropped, context, model);
+    let mut trimmed = messages[..head].to_vec();
+    trimmed.push(json!({
+        "role": "system",
+        "content": format!("[{} earlier messages were omitted to fit the context window]", dropped),
+    }));
+    trimmed.extend_from_slice(&messages[cut..]);
+
+    FittedRequest {
+        messages: Some(trimmed),
+        max_tokens: max_tokens.min(room(kept + note_size).max(1)),
+        prompt_tokens: kept + note_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog_matches_the_most_specific_family() {
+        let limits = |model: &str| lookup(model).map(|(context, output, _)| (context, output));
+        assert_eq!(limits("gpt-4.5-preview"), Some((128_000, 16_384)));
+        assert_eq!(limits("gpt-4-0613"), Some((8_192, 4_096)));
+        assert_eq!(limits("claude-sonnet-4-20250514"), Some((200_000, 64_000)));
+        assert_eq!(limits("claude-opus-4-1-20250805"), Some((200_000, 32_000)));
+        assert_eq!(limits("models/gemini-2.5-pro"), Some((1_048_576, 65_536)));
+        assert_eq!(limits("llama3.3:70b"), Some((131_072, 4_096)));
+        assert_eq!(limits("meta-llama/llama-4-maverick"), Some((1_048_576, 8_192)));
+        assert_eq!(limits("my-local-model"), None);
+    }
+
+    #[test]
+    fn output_budget_is_only_reduced_when_the_context_is_short() {
+        let messages = vec![json!({ "role": "user", "content": "Hello" })];
+        // Above the catalog's output limit, but there is room in the context
+        assert_eq!(fit_to_context("claude-3-5-sonnet", &messages, &[], 20_000).max_tokens, 20_000);
+
+        let fitted = fit_to_context("gpt-4-0613", &messages, &[], 20_000);
+        assert!(fitted.max_tokens < 8_192 && fitted.max_tokens > 8_000, "{}", fitted.max_tokens);
+    }
+
+    #[test]
+    fn pdf_parts_count_per_page() {
+        let pdf = b"%PDF-1.4\n1 0 obj << /Type /Pages /Count 2 >>\n2 0 obj << /Type /Page >>\n3 0 obj <</Type/Page>>";
+        let message = |data: String| json!({
+            "role": "user",
+            "content": [{ "type": "file", "file": { "filename": "a.pdf", "file_data": data } }]
+        });
+        let empty = count_messages("gpt-4o", &[json!({ "role": "user", "content": [] })], &[]);
+        let two_pages = message(format!("data:application/pdf;base64,{}", STANDARD.encode(pdf)));
+        assert_eq!(count_messages("gpt-4o", &[two_pages], &[]), empty + 2 * PDF_PAGE_TOKENS);
+        assert_eq!(count_messages("gpt-4o", &[message("file-abc".to_string())], &[]), empty + PDF_PAGE_TOKENS);
+    }
+}
@@ -0,0 +1,712 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use super::llm_adapter::{ToolCall, ToolFunction};
+
+/// Why a tool call cannot be run as the model sent it
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IssueKind {
+    /// The arguments are not JSON, even after repair
+    InvalidJson,
+    /// The arguments do not match the tool's parameter schema
+    SchemaMismatch,
+    /// No tool of that name was offered
+    UnknownTool,
+    /// The reply hit the output limit and the arguments had to be repaired, so members
+    /// may be missing or cut short
+    Truncated,
+}
+
+/// A tool call that should not be run. `message` is written for the model: send it back
+/// as the call's tool result so it can retry with corrected arguments.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallIssue {
+    pub call_id: String,
+    pub name: String,
+    pub kind: IssueKind,
+    pub message: String,
+    /// Each schema violation, as `$.path: problem`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl ToolCallIssue {
+    pub fn new(call: &ToolCall, kind: IssueKind, errors: Vec<String>) -> Self {
+        let name = &call.function.name;
+        let message = match kind {
+            IssueKind::InvalidJson => format!(
+                "Error: The arguments for `{}` are not valid JSON ({}). Call the tool again with a complete JSON object.",
+                name,
+                errors.join("; ")
+            ),
+            IssueKind::SchemaMismatch => format!(
+                "Error: The arguments for `{}` do not match its parameters:\n- {}\nCall the tool again with corrected arguments.",
+                name,
+                errors.join("\n- ")
+            ),
+            IssueKind::UnknownTool => format!("Error: Unknown tool: {}", name),
+            IssueKind::Truncated => format!(
+                "Error: Your reply was cut off while writing the arguments for `{}`, so the call was not run. Call the tool again with complete arguments, splitting large content across several calls.",
+                name
+            ),
+        };
+        Self {
+            call_id: call.id.clone(),
+            name: name.clone(),
+            kind,
+            message,
+            errors: if kind == IssueKind::SchemaMismatch { errors } else { Vec::new() },
+        }
+    }
+}
+
+// ============================================================================
+// Events
+// ============================================================================
+
+/// One step in the life of a tool call. Streams emit these as `stream-tool-{stream_id}`
+/// and agent runs inside their `tool` events. `call_id` stays the same from `started` on.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ToolCallEvent {
+    #[serde(rename_all = "camelCase")]
+    Started { call_id: String, index: usize, name: String },
+    /// More of the raw argument JSON as it streams in
+    #[serde(rename_all = "camelCase")]
+    ArgumentsDelta { call_id: String, index: usize, delta: String },
+    /// The checked arguments; with an `issue` the call will not run
+    #[serde(rename_all = "camelCase")]
+    ArgumentsComplete {
+        call_id: String,
+        index: usize,
+        name: String,
+        arguments: Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        issue: Option<ToolCallIssue>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Executing { call_id: String, index: usize, name: String },
+    #[serde(rename_all = "camelCase")]
+    Result { call_id: String, index: usize, name: String, output: String },
+    #[serde(rename_all = "camelCase")]
+    Error { call_id: String, index: usize, name: String, error: String },
+}
+
+impl ToolCallEvent {
+    pub fn started(index: usize, call: &ToolCall) -> Self {
+        ToolCallEvent::Started {
+            call_id: call.id.clone(),
+            index,
+            name: call.function.name.clone(),
+        }
+    }
+
+    pub fn arguments_complete(index: usize, call: &ToolCall, issue: Option<ToolCallIssue>) -> Self {
+        ToolCallEvent::ArgumentsComplete {
+            call_id: call.id.clone(),
+            index,
+            name: call.function.name.clone(),
+            arguments: parse_arguments(&call.function.arguments).map_or_else(|_| json!({}), |parsed| parsed.value),
+            issue,
+        }
+    }
+
+    pub fn executing(index: usize, call: &ToolCall) -> Self {
+        ToolCallEvent::Executing {
+            call_id: call.id.clone(),
+            index,
+            name: call.function.name.clone(),
+        }
+    }
+
+    /// `result` or `error`, depending on `ok`
+    pub fn finished(index: usize, call: &ToolCall, ok: bool, output: String) -> Self {
+        let (call_id, name) = (call.id.clone(), call.function.name.clone());
+        if ok {
+            ToolCallEvent::Result { call_id, index, name, output }
+        } else {
+            ToolCallEvent::Error { call_id, index, name, error: output }
+        }
+    }
+}
+
+// ============================================================================
+// Streaming
+// ============================================================================
+
+#[derive(Default)]
+struct PartialCall {
+    id: String,
+    name: String,
+    arguments: String,
+    announced: bool,
+}
+
+/// Builds tool calls from streamed fragments. Fragments are keyed by index; an `id` that
+/// arrives after the first fragment still lands on its call, as long as it comes before
+/// the call is announced. From then on the call's ID is fixed. Name fragments are always
+/// appended, and `argumentsComplete` carries the final name.
+#[derive(Default)]
+pub struct StreamedToolCalls {
+    calls: BTreeMap<usize, PartialCall>,
+    finished: bool,
+}
+
+impl StreamedToolCalls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a fragment and return the events it produces. Providers may split a name over
+    /// several fragments, so a call is announced once its arguments start, or when the
+    /// stream finishes; argument JSON received before that is sent with the announcement.
+    pub fn push(&mut self, index: usize, id: Option<String>, name: Option<String>, arguments: &str) -> Vec<ToolCallEvent> {
+        let call = self.calls.entry(index).or_default();
+        if let Some(id) = id.filter(|_| !call.announced) {
+            if call.id.is_empty() {
+                call.id = id;
+            }
+        }
+        if let Some(name) = name {
+            // Some servers repeat the whole name on every fragment, others split it
+            if call.name != name {
+                call.name.push_str(&name);
+            }
+        }
+        call.arguments.push_str(arguments);
+
+        if call.announced {
+            if arguments.is_empty() {
+                return Vec::new();
+            }
+            return vec![ToolCallEvent::ArgumentsDelta {
+                call_id: call.id.clone(),
+                index,
+                delta: arguments.to_string(),
+            }];
+        }
+        if call.name.is_empty() || call.arguments.is_empty() {
+            return Vec::new();
+        }
+        announce(index, call)
+    }
+
+    /// The calls received, checked against `tools`, plus their `argumentsComplete`
+    /// events. Events are only returned the first time; later calls repeat the result.
+    /// `truncated` is set when the reply stopped at the output limit.
+    pub fn finish(&mut self, tools: &[Value], truncated: bool) -> (Vec<ToolCall>, Vec<ToolCallIssue>, Vec<ToolCallEvent>) {
+        let indexes: Vec<usize> = self.calls.keys().copied().collect();
+        let mut calls = self.calls();
+        let issues = check_tool_calls(&mut calls, tools, truncated);
+        if std::mem::replace(&mut self.finished, true) {
+            return (calls, issues, Vec::new());
+        }
+
+        let mut events = Vec::new();
+        for (call, index) in calls.iter().zip(indexes) {
+            // Calls without arguments are announced only now
+            if let Some(partial) = self.calls.get_mut(&index).filter(|partial| !partial.announced) {
+                events.extend(announce(index, partial));
+            }
+            let issue = issues.iter().find(|issue| issue.call_id == call.id).cloned();
+            events.push(ToolCallEvent::arguments_complete(index, call, issue));
+        }
+        (calls, issues, events)
+    }
+
+    /// The calls received so far in index order. Calls the provider sent without an ID
+    /// get one, so their results can be matched to them.
+    fn calls(&mut self) -> Vec<ToolCall> {
+        self.calls
+            .iter_mut()
+            .map(|(index, call)| {
+                if call.id.is_empty() {
+                    call.id = new_call_id(*index);
+                }
+                ToolCall {
+                    id: call.id.clone(),
+                    call_type: "function".to_string(),
+                    function: ToolFunction {
+                        name: call.name.clone(),
+                        arguments: call.arguments.clone(),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+/// `started` for a call, followed by the arguments buffered until then
+fn announce(index: usize, call: &mut PartialCall) -> Vec<ToolCallEvent> {
+    call.announced = true;
+    if call.id.is_empty() {
+        call.id = new_call_id(index);
+    }
+    let mut events = vec![ToolCallEvent::Started {
+        call_id: call.id.clone(),
+        index,
+        name: call.name.clone(),
+    }];
+    if !call.arguments.is_empty() {
+        events.push(ToolCallEvent::ArgumentsDelta {
+            call_id: call.id.clone(),
+            index,
+            delta: call.arguments.clone(),
+        });
+    }
+    events
+}
+
+fn new_call_id(index: usize) -> String {
+    format!("call_{}_{}", index, uuid::Uuid::new_v4().simple())
+}
+
+// ============================================================================
+// Checking
+// ============================================================================
+
+/// Parameter schema of the tool named `name` in OpenAI-format tool definitions
+pub fn tool_schema<'a>(tools: &'a [Value], name: &str) -> Option<&'a Value> {
+    tools
+        .iter()
+        .find(|tool| tool["function"]["name"].as_str() == Some(name))
+        .map(|tool| &tool["function"]["parameters"])
+}
+
+/// Check every call against the offered `tools`. Arguments that parse, possibly after
+/// repair, are rewritten as canonical JSON; calls that cannot run are returned as issues.
+/// When the reply was `truncated` at the output limit, repaired arguments are likely
+/// missing content, so those calls are returned as issues too rather than run.
+pub fn check_tool_calls(calls: &mut [ToolCall], tools: &[Value], truncated: bool) -> Vec<ToolCallIssue> {
+    let mut issues = Vec::new();
+    for call in calls.iter_mut() {
+        let Some(schema) = tool_schema(tools, &call.function.name) else {
+            issues.push(ToolCallIssue::new(call, IssueKind::UnknownTool, Vec::new()));
+            continue;
+        };
+        match check_arguments(&call.function.arguments, Some(schema)) {
+            Ok(parsed) => {
+                call.function.arguments = parsed.value.to_string();
+                if truncated && parsed.repaired {
+                    issues.push(ToolCallIssue::new(call, IssueKind::Truncated, Vec::new()));
+                }
+            }
+            Err((kind, errors)) => {
+                // Keep the conversation replayable: providers reject malformed arguments
+                call.function.arguments = parse_arguments(&call.function.arguments)
+                    .map_or_else(|_| json!({}), |parsed| parsed.value)
+                    .to_string();
+                issues.push(ToolCallIssue::new(call, kind, errors));
+            }
+        }
+    }
+    issues
+}
+
+/// Parse a call's arguments and validate them against `schema` when there is one
+pub fn check_arguments(raw: &str, schema: Option<&Value>) -> Result<ParsedArguments, (IssueKind, Vec<String>)> {
+    let parsed = parse_arguments(raw).map_err(|e| (IssueKind::InvalidJson, vec![e]))?;
+    let errors = schema.map(|schema| validate(&parsed.value, schema)).unwrap_or_default();
+    if errors.is_empty() {
+        Ok(parsed)
+    } else {
+        Err((IssueKind::SchemaMismatch, errors))
+    }
+}
+
+/// Arguments read by `parse_arguments`
+#[derive(Debug, PartialEq)]
+pub struct ParsedArguments {
+    pub value: Value,
+    /// The JSON was malformed or cut off and `repair_json` fixed it up
+    pub repaired: bool,
+}
+
+/// Parse tool-call arguments leniently: empty means no arguments, code fences and
+/// double-encoding are unwrapped, and truncated or sloppy JSON is repaired when possible
+pub fn parse_arguments(raw: &str) -> Result<ParsedArguments, String> {
+    let trimmed = strip_code_fence(raw.trim());
+    if trimmed.is_empty() {
+        return Ok(ParsedArguments { value: json!({}), repaired: false });
+    }
+    let (value, repaired) = match serde_json::from_str::<Value>(trimmed) {
+        Ok(value) => (value, false),
+        Err(e) => (repair_json(trimmed).ok_or_else(|| e.to_string())?, true),
+    };
+    // Some models send the arguments object as a JSON string
+    if let Value::String(inner) = &value {
+        if let Ok(inner @ Value::Object(_)) = serde_json::from_str::<Value>(inner) {
+            return Ok(ParsedArguments { value: inner, repaired });
+        }
+    }
+    Ok(ParsedArguments { value, repaired })
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    let rest = rest.strip_prefix("json").unwrap_or(rest);
+    rest.trim().strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// Repair JSON cut off mid-stream or written loosely: unterminated strings are closed,
+/// trailing commas dropped and open brackets closed. When the last member is itself
+/// incomplete, it is dropped. Only call this on text that failed to parse; a `Some` means
+/// the value differs from what was sent and may be missing content.
+pub fn repair_json(text: &str) -> Option<Value> {
+    let mut out = String::with_capacity(text.len() + 8);
+    // Closers still owed, innermost last
+    let mut stack: Vec<char> = Vec::new();
+    // Positions of commas outside strings, with the closers owed at that point
+    let mut commas: Vec<(usize, Vec<char>)> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if in_string {
+            out.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                if stack.last() != Some(&c) {
+                    // A closer with nothing to close
+                    continue;
+                }
+                stack.pop();
+                trim_dangling(&mut out);
+                commas.retain(|(position, _)| *position < out.len());
+            }
+            ',' => commas.push((out.len(), stack.clone())),
+            _ => {}
+        }
+        out.push(c);
+    }
+
+    if let Ok(value) = serde_json::from_str(&close_json(out.clone(), &stack, in_string, escaped)) {
+        return Some(value);
+    }
+    // Drop incomplete trailing members one at a time
+    for (position, owed) in commas.iter().rev() {
+        let candidate = close_json(out[..*position].to_string(), owed, false, false);
+        if let Ok(value) = serde_json::from_str(&candidate) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn close_json(mut out: String, owed: &[char], in_string: bool, escaped: bool) -> String {
+    if in_string {
+        if escaped {
+            out.pop();
+        }
+        out.push('"');
+    }
+    trim_dangling(&mut out);
+    if out.ends_with(':') {
+        out.push_str("null");
+    }
+    for closer in owed.iter().rev() {
+        trim_dangling(&mut out);
+        out.push(*closer);
+    }
+    out
+}
+
+/// Remove trailing whitespace and a trailing comma
+fn trim_dangling(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    out.truncate(trimmed);
+    if out.ends_with(',') {
+        out.pop();
+        let trimmed = out.trim_end().len();
+        out.truncate(trimmed);
+    }
+}
+
+// ============================================================================
+// Schema validation
+// ============================================================================
+
+/// Validate `value` against the JSON Schema subset tool definitions use: `type`,
+/// `enum`, `const`, `properties`, `required`, `additionalProperties`, `items`,
+/// `anyOf`/`oneOf`, string lengths and numeric bounds. Returns every violation.
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(value, schema, "$", &mut errors);
+    errors
+}
+
+fn validate_at(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    if !schema.is_object() {
+        return;
+    }
+
+    for key in ["anyOf", "oneOf"] {
+        if let Some(options) = schema[key].as_array() {
+            if !options.iter().any(|option| validate(value, option).is_empty()) {
+                errors.push(format!("{}: does not match any of the allowed shapes", path));
+                return;
+            }
+        }
+    }
+
+    let types: Vec<&str> = match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|t| type_matches(value, t)) {
+        errors.push(format!("{}: expected {}, got {}", path, types.join(" or "), type_name(value)));
+        return;
+    }
+
+    if let Some(options) = schema["enum"].as_array() {
+        if !options.contains(value) {
+            let allowed: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!("{}: must be one of {}", path, allowed.join(", ")));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            errors.push(format!("{}: must be {}", path, expected));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            for name in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                if !map.contains_key(name) {
+                    errors.push(format!("{}: missing required property `{}`", path, name));
+                }
+            }
+            let properties = schema["properties"].as_object();
+            for (key, item) in map {
+                let item_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(property) => validate_at(item, property, &item_path, errors),
+                    None => match &schema["additionalProperties"] {
+                        Value::Bool(false) => errors.push(format!("{}: unknown property", item_path)),
+                        extra @ Value::Object(_) => validate_at(item, extra, &item_path, errors),
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = schema["minItems"].as_u64() {
+                if (items.len() as u64) < min {
+                    errors.push(format!("{}: needs at least {} items", path, min));
+                }
+            }
+            if let Some(max) = schema["maxItems"].as_u64() {
+                if items.len() as u64 > max {
+                    errors.push(format!("{}: allows at most {} items", path, max));
+                }
+            }
+            if schema["items"].is_object() {
+                for (i, item) in items.iter().enumerate() {
+                    validate_at(item, &schema["items"], &format!("{}[{}]", path, i), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let length = s.chars().count() as u64;
+            if let Some(min) = schema["minLength"].as_u64() {
+                if length < min {
+                    errors.push(format!("{}: must be at least {} characters", path, min));
+                }
+            }
+            if let Some(max) = schema["maxLength"].as_u64() {
+                if length > max {
+                    errors.push(format!("{}: must be at most {} characters", path, max));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema["minimum"].as_f64() {
+                if n < min {
+                    errors.push(format!("{}: must be at least {}", path, min));
+                }
+            }
+            if let Some(max) = schema["maximum"].as_f64() {
+                if n > max {
+                    errors.push(format!("{}: must be at most {}", path, max));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn type_matches(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streamed_calls_are_announced_once_arguments_start() {
+        let mut stream = StreamedToolCalls::new();
+        assert!(stream.push(0, Some("call_1".to_string()), Some("read_".to_string()), "").is_empty());
+
+        let events = stream.push(0, None, Some("file".to_string()), "{\"pa");
+        assert!(matches!(
+            &events[..],
+            [ToolCallEvent::Started { call_id, name, .. }, ToolCallEvent::ArgumentsDelta { delta, .. }]
+                if call_id == "call_1" && name == "read_file" && delta == "{\"pa"
+        ));
+        let events = stream.push(0, Some("late".to_string()), None, "th\": \"a\"}");
+        assert!(matches!(&events[..], [ToolCallEvent::ArgumentsDelta { call_id, .. }] if call_id == "call_1"));
+
+        let (calls, _, events) = stream.finish(&[], false);
+        assert_eq!(calls[0].function.name, "read_file");
+        assert!(matches!(
+            &events[..],
+            [ToolCallEvent::ArgumentsComplete { arguments, .. }] if *arguments == json!({ "path": "a" })
+        ));
+        assert!(stream.finish(&[], false).2.is_empty());
+    }
+
+    #[test]
+    fn streamed_calls_without_arguments_are_announced_on_finish() {
+        let mut stream = StreamedToolCalls::new();
+        assert!(stream.push(0, None, Some("git_status".to_string()), "").is_empty());
+        let (calls, _, events) = stream.finish(&[], false);
+        assert!(matches!(
+            &events[..],
+            [ToolCallEvent::Started { call_id, .. }, ToolCallEvent::ArgumentsComplete { call_id: complete_id, .. }]
+                if *call_id == calls[0].id && *complete_id == calls[0].id
+        ));
+    }
+
+    #[test]
+    fn repair_json_closes_truncated_strings_and_brackets() {
+        assert_eq!(repair_json(r#"{"path": "src/ma"#), Some(json!({ "path": "src/ma" })));
+        assert_eq!(repair_json(r#"{"a": 1, "b": [1, 2,"#), Some(json!({ "a": 1, "b": [1, 2] })));
+        assert_eq!(repair_json(r#"{"a": "x\"#), Some(json!({ "a": "x" })));
+    }
+
+    #[test]
+    fn repair_json_drops_trailing_commas_and_incomplete_members() {
+        assert_eq!(repair_json(r#"{"a": 1,}"#), Some(json!({ "a": 1 })));
+        assert_eq!(repair_json(r#"{"a": 1, "b": tr"#), Some(json!({ "a": 1 })));
+        assert_eq!(repair_json(r#"{"a":"#), Some(json!({ "a": null })));
+        assert_eq!(repair_json("not json"), None);
+    }
+
+    #[test]
+    fn parse_arguments_unwraps_fences_and_double_encoding() {
+        let value = |raw: &str| parse_arguments(raw).map(|parsed| parsed.value);
+        assert_eq!(value("  "), Ok(json!({})));
+        assert_eq!(value("```json\n{\"a\": 1}\n```"), Ok(json!({ "a": 1 })));
+        assert_eq!(value(r#""{\"a\": 1}""#), Ok(json!({ "a": 1 })));
+        assert!(parse_arguments("not json").is_err());
+    }
+
+    #[test]
+    fn parse_arguments_reports_repairs() {
+        assert!(!parse_arguments(r#"{"a": 1}"#).unwrap().repaired);
+        assert_eq!(
+            parse_arguments(r#"{"a": 1, "b": "cut"#),
+            Ok(ParsedArguments { value: json!({ "a": 1, "b": "cut" }), repaired: true })
+        );
+    }
+
+    #[test]
+    fn repaired_calls_in_truncated_replies_are_not_run() {
+        let tools = vec![json!({
+            "type": "function",
+            "function": { "name": "write_file", "parameters": { "type": "object" } }
+        })];
+        let call = |id: &str, arguments: &str| ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: ToolFunction { name: "write_file".to_string(), arguments: arguments.to_string() },
+        };
+
+        let mut calls = vec![call("whole", r#"{"path": "a"}"#), call("cut", r#"{"path": "b", "content": "par"#)];
+        let issues = check_tool_calls(&mut calls, &tools, true);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].call_id, "cut");
+        assert_eq!(issues[0].kind, IssueKind::Truncated);
+        assert_eq!(calls[1].function.arguments, r#"{"content":"par","path":"b"}"#);
+
+        // The same repair is trusted when the model finished its reply
+        let mut calls = vec![call("cut", r#"{"path": "b", "content": "par"#)];
+        assert!(check_tool_calls(&mut calls, &tools, false).is_empty());
+    }
+
+    #[test]
+    fn validate_reports_every_violation() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "minLength": 1 },
+                "count": { "type": "integer", "minimum": 0 },
+                "mode": { "enum": ["a", "b"] }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        });
+
+        assert!(validate(&json!({ "path": "x", "count": 2.0, "mode": "a" }), &schema).is_empty());
+
+        let errors = validate(&json!({ "count": -1, "mode": "c", "extra": true }), &schema);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(errors.contains(&"$: missing required property `path`".to_string()));
+        assert!(errors.contains(&"$.count: must be at least 0".to_string()));
+        assert!(errors.contains(&r#"$.mode: must be one of "a", "b""#.to_string()));
+        assert!(errors.contains(&"$.extra: unknown property".to_string()));
+    }
+
+    #[test]
+    fn validate_checks_types_items_and_alternatives() {
+        assert_eq!(validate(&json!("x"), &json!({ "type": "object" })), vec!["$: expected object, got string"]);
+        assert_eq!(
+            validate(&json!([1, "two"]), &json!({ "type": "array", "items": { "type": "number" } })),
+            vec!["$[1]: expected number, got string"]
+        );
+        let either = json!({ "anyOf": [{ "type": "string" }, { "type": "null" }] });
+        assert!(validate(&Value::Null, &either).is_empty());
+        assert_eq!(validate(&json!(1), &either), vec!["$: does not match any of the allowed shapes"]);
+    }
+}
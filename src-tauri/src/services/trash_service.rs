@@ -4,8 +4,10 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
 use uuid::Uuid;
+use walkdir::WalkDir;
 
-use super::file_service::hash_file;
+use super::file_service::{hash_file, rename_or_copy, resolve_within, FileError};
+use super::freedesktop_trash::FreedesktopTrash;
 
 #[derive(Error, Debug)]
 pub enum TrashError {
@@ -13,12 +15,52 @@ pub enum TrashError {
     NotFound(String),
     #[error("Already exists: {0}")]
     AlreadyExists(String),
+    #[error("Restore target is outside the workspace: {0}")]
+    OutsideWorkspace(String),
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Serialization error: {0}")]
     Serde(#[from] serde_json::Error),
 }
 
+impl From<FileError> for TrashError {
+    fn from(e: FileError) -> Self {
+        match e {
+            FileError::Io(io) => TrashError::Io(io),
+            other => TrashError::Io(std::io::Error::other(other.to_string())),
+        }
+    }
+}
+
+/// What to do when the restore target already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreConflict {
+    /// Restore next to the existing item as `name (restored).ext`
+    #[default]
+    Rename,
+    /// Move the existing item into the trash, then restore over it
+    Overwrite,
+    /// Leave the item in the trash
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreResult {
+    #[serde(rename = "trashId")]
+    pub trash_id: String,
+    /// One of `restored`, `renamed`, `overwritten`, `skipped` or `failed`
+    pub status: String,
+    #[serde(rename = "toPath", skip_serializing_if = "Option::is_none")]
+    pub to_path: Option<String>,
+    /// Trash ID of the item that was displaced by an overwrite
+    #[serde(rename = "replacedTrashId", skip_serializing_if = "Option::is_none")]
+    pub replaced_trash_id: Option<String>,
+    /// Why the item could not be restored, when `status` is `failed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrashMetadata {
     #[serde(rename = "trashId")]
@@ -35,118 +77,383 @@ pub struct TrashMetadata {
     pub request_id: String,
 }
 
-pub struct TrashManager {
+/// Where trashed items are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashBackendKind {
+    /// Private `.trash` directory inside the workspace
+    #[default]
+    Workspace,
+    /// The desktop trash (freedesktop.org Trash spec, `~/.local/share/Trash`)
+    System,
+}
+
+/// Per-workspace trash configuration, stored in `.sentinelops/trash.json`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TrashSettings {
+    pub backend: TrashBackendKind,
+    /// Oldest items are purged once the trash grows past this size. `None`, the default,
+    /// disables the limit, so nothing is purged until the user sets one.
+    #[serde(rename = "maxSizeBytes")]
+    pub max_size_bytes: Option<u64>,
+    /// Items deleted more than this many days ago are purged. `None` disables the limit.
+    #[serde(rename = "maxAgeDays")]
+    pub max_age_days: Option<i64>,
+}
+
+impl TrashSettings {
+    fn path(workspace: &Path) -> PathBuf {
+        workspace.join(".sentinelops").join("trash.json")
+    }
+
+    pub fn load(workspace: &Path) -> Self {
+        match fs::read_to_string(Self::path(workspace)) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_default(),
+            Err(_) => Self::migrate(workspace).unwrap_or_default(),
+        }
+    }
+
+    /// Limits used to be kept in `.trash/index.json`; move them here the first time
+    /// a workspace without `trash.json` is opened
+    fn migrate(workspace: &Path) -> Option<Self> {
+        let index = fs::read_to_string(workspace.join(".trash").join(INDEX_FILE)).ok()?;
+        let index: serde_json::Value = serde_json::from_str(&index).ok()?;
+        let old = index.get("settings")?;
+        let settings = Self {
+            backend: TrashBackendKind::Workspace,
+            max_size_bytes: old["maxSizeBytes"].as_u64(),
+            max_age_days: old["maxAgeDays"].as_i64(),
+        };
+        if let Err(e) = settings.save(workspace) {
+            tracing::warn!("Could not migrate trash settings for {}: {}", workspace.display(), e);
+        }
+        Some(settings)
+    }
+
+    pub fn save(&self, workspace: &Path) -> Result<(), TrashError> {
+        let path = Self::path(workspace);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrashStats {
+    pub backend: TrashBackendKind,
+    #[serde(rename = "itemCount")]
+    pub item_count: usize,
+    #[serde(rename = "totalSize")]
+    pub total_size: u64,
+    #[serde(rename = "maxSizeBytes")]
+    pub max_size_bytes: Option<u64>,
+    #[serde(rename = "maxAgeDays")]
+    pub max_age_days: Option<i64>,
+    #[serde(rename = "oldestDeletedAt")]
+    pub oldest_deleted_at: Option<String>,
+    #[serde(rename = "newestDeletedAt")]
+    pub newest_deleted_at: Option<String>,
+}
+
+/// Storage for trashed items. `TrashManager` handles restore conflicts and limits on top of it.
+pub trait TrashBackend: Send {
+    /// Move `source_path` into the trash and record `metadata` for it. Returns the item's
+    /// trash ID, which a backend may assign itself, and where the item now lives.
+    fn trash(&mut self, source_path: &Path, metadata: TrashMetadata) -> Result<(String, PathBuf), TrashError>;
+
+    /// All items this backend holds for the workspace
+    fn items(&self) -> Vec<TrashMetadata>;
+
+    /// Location of a trashed item, if it is still present
+    fn item_path(&self, trash_id: &str) -> Option<PathBuf>;
+
+    /// Delete whatever remains of an item (its content and its record).
+    /// Returns false when the ID is unknown.
+    fn remove(&mut self, trash_id: &str) -> Result<bool, TrashError>;
+
+    /// Remove several items, returning the IDs that were removed. Items that cannot be
+    /// deleted are skipped.
+    fn remove_many(&mut self, trash_ids: &[String]) -> Result<Vec<String>, TrashError> {
+        Ok(trash_ids
+            .iter()
+            .filter(|id| self.remove(id).unwrap_or(false))
+            .cloned()
+            .collect())
+    }
+
+    fn total_size(&self) -> u64 {
+        self.items().iter().map(|m| m.size).sum()
+    }
+}
+
+/// An indexed trash item and where it lives under the trash directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrashIndexEntry {
+    #[serde(flatten)]
+    metadata: TrashMetadata,
+    #[serde(rename = "dateDir")]
+    date_dir: String,
+    #[serde(rename = "itemName")]
+    item_name: String,
+}
+
+/// Persistent index of the trash, stored as `.trash/index.json`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+struct TrashIndex {
+    version: u32,
+    #[serde(rename = "totalSize")]
+    total_size: u64,
+    #[serde(default)]
+    items: Vec<TrashIndexEntry>,
+}
+
+const INDEX_VERSION: u32 = 1;
+const INDEX_FILE: &str = "index.json";
+/// How often the background task applies the trash limits
+const AUTO_PURGE_INTERVAL_SECS: u64 = 60 * 60;
+
+/// The private `.trash/<date>/<trash_id>/` layout inside the workspace
+pub struct WorkspaceTrash {
     trash_dir: PathBuf,
+    index: TrashIndex,
+}
+
+impl WorkspaceTrash {
+    pub fn new(workspace: &Path) -> Self {
+        let trash_dir = workspace.join(".trash");
+        let index = load_index(&trash_dir);
+        Self { trash_dir, index }
+    }
+
+    fn entry_path(&self, entry: &TrashIndexEntry) -> PathBuf {
+        self.trash_dir
+            .join(&entry.date_dir)
+            .join(&entry.metadata.trash_id)
+            .join(&entry.item_name)
+    }
+
+    /// Write the index atomically next to the trashed items
+    fn save_index(&self) -> Result<(), TrashError> {
+        fs::create_dir_all(&self.trash_dir)?;
+        let index_path = self.trash_dir.join(INDEX_FILE);
+        let temp_path = self.trash_dir.join(format!("{}.tmp", INDEX_FILE));
+        fs::write(&temp_path, serde_json::to_string_pretty(&self.index)?)?;
+        fs::rename(&temp_path, &index_path)?;
+        Ok(())
+    }
+
+    /// Drop an item and its index entry without saving the index
+    fn remove_entry(&mut self, trash_id: &str) -> Result<bool, TrashError> {
+        let Some(pos) = self.index.items.iter().position(|e| e.metadata.trash_id == trash_id) else {
+            return Ok(false);
+        };
+
+        let entry = self.index.items.remove(pos);
+        self.index.total_size = self.index.total_size.saturating_sub(entry.metadata.size);
+
+        let trash_item_dir = self.trash_dir.join(&entry.date_dir).join(trash_id);
+        if trash_item_dir.exists() {
+            fs::remove_dir_all(&trash_item_dir)?;
+        }
+        Ok(true)
+    }
+
+    /// Remove date directories left empty after a restore or purge
+    fn remove_empty_date_dirs(&self) -> Result<(), TrashError> {
+        if self.trash_dir.exists() {
+            for date_dir in fs::read_dir(&self.trash_dir)?.filter_map(|e| e.ok()) {
+                if date_dir.path().is_dir() {
+                    if let Ok(mut entries) = fs::read_dir(date_dir.path()) {
+                        if entries.next().is_none() {
+                            let _ = fs::remove_dir(date_dir.path());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TrashBackend for WorkspaceTrash {
+    fn trash(&mut self, source_path: &Path, metadata: TrashMetadata) -> Result<(String, PathBuf), TrashError> {
+        let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        let trash_item_dir = self.trash_dir.join(&date).join(&metadata.trash_id);
+
+        fs::create_dir_all(&trash_item_dir)?;
+
+        // Write metadata
+        let metadata_path = trash_item_dir.join("metadata.json");
+        let metadata_json = serde_json::to_string_pretty(&metadata)?;
+        fs::write(&metadata_path, metadata_json)?;
+
+        // Move item to trash
+        let item_name = source_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "item".to_string());
+        let trash_path = trash_item_dir.join(&item_name);
+
+        rename_or_copy(source_path, &trash_path)?;
+
+        let trash_id = metadata.trash_id.clone();
+        self.index.total_size += metadata.size;
+        self.index.items.push(TrashIndexEntry {
+            metadata,
+            date_dir: date,
+            item_name,
+        });
+        self.save_index()?;
+
+        Ok((trash_id, trash_path))
+    }
+
+    fn items(&self) -> Vec<TrashMetadata> {
+        self.index.items.iter().map(|e| e.metadata.clone()).collect()
+    }
+
+    fn item_path(&self, trash_id: &str) -> Option<PathBuf> {
+        let entry = self.index.items.iter().find(|e| e.metadata.trash_id == trash_id)?;
+        let item_path = self.entry_path(entry);
+        item_path.exists().then_some(item_path)
+    }
+
+    fn remove(&mut self, trash_id: &str) -> Result<bool, TrashError> {
+        if !self.remove_entry(trash_id)? {
+            return Ok(false);
+        }
+        self.save_index()?;
+        self.remove_empty_date_dirs()?;
+        Ok(true)
+    }
+
+    fn remove_many(&mut self, trash_ids: &[String]) -> Result<Vec<String>, TrashError> {
+        let removed: Vec<String> = trash_ids
+            .iter()
+            .filter(|id| self.remove_entry(id).unwrap_or(false))
+            .cloned()
+            .collect();
+        if !removed.is_empty() {
+            self.save_index()?;
+            self.remove_empty_date_dirs()?;
+        }
+        Ok(removed)
+    }
+
+    fn total_size(&self) -> u64 {
+        self.index.total_size
+    }
+}
+
+/// Create the backend selected for a workspace. The system trash is only available on
+/// freedesktop platforms; elsewhere the workspace trash is used.
+fn create_backend(kind: TrashBackendKind, workspace: &Path) -> Box<dyn TrashBackend> {
+    match kind {
+        TrashBackendKind::System if FreedesktopTrash::is_supported() => {
+            match FreedesktopTrash::new(workspace) {
+                Some(backend) => Box::new(backend),
+                None => {
+                    tracing::warn!("System trash directory unavailable, using workspace trash");
+                    Box::new(WorkspaceTrash::new(workspace))
+                }
+            }
+        }
+        TrashBackendKind::System => {
+            tracing::warn!("System trash is not supported on this platform, using workspace trash");
+            Box::new(WorkspaceTrash::new(workspace))
+        }
+        TrashBackendKind::Workspace => Box::new(WorkspaceTrash::new(workspace)),
+    }
+}
+
+pub struct TrashManager {
+    workspace: PathBuf,
+    settings: TrashSettings,
+    backend: Box<dyn TrashBackend>,
     confirm_tokens: HashMap<String, (String, i64)>, // token -> (path, expires_at)
 }
 
 impl TrashManager {
     pub fn new(workspace: &Path) -> Self {
-        let trash_dir = workspace.join(".trash");
+        let settings = TrashSettings::load(workspace);
+        let backend = create_backend(settings.backend, workspace);
         Self {
-            trash_dir,
+            workspace: workspace.to_path_buf(),
+            settings,
+            backend,
             confirm_tokens: HashMap::new(),
         }
     }
 
     pub fn update_workspace(&mut self, workspace: &Path) {
-        self.trash_dir = workspace.join(".trash");
-    }
-
-    fn get_date_dir(&self) -> PathBuf {
-        let date = chrono::Utc::now().format("%Y-%m-%d").to_string();
-        self.trash_dir.join(date)
+        self.workspace = workspace.to_path_buf();
+        self.settings = TrashSettings::load(workspace);
+        self.backend = create_backend(self.settings.backend, workspace);
     }
 
     /// Move a file or directory to trash
     pub fn move_to_trash(
-        &self,
+        &mut self,
+        source_path: &Path,
+        original_rel_path: &str,
+        request_id: &str,
+    ) -> Result<(String, PathBuf), TrashError> {
+        self.trash_item(source_path, original_rel_path, request_id, true)
+    }
+
+    fn trash_item(
+        &mut self,
         source_path: &Path,
         original_rel_path: &str,
         request_id: &str,
+        enforce_limit: bool,
     ) -> Result<(String, PathBuf), TrashError> {
         if !source_path.exists() {
             return Err(TrashError::NotFound(original_rel_path.to_string()));
         }
 
-        let trash_id = Uuid::new_v4().to_string();
-        let date_dir = self.get_date_dir();
-        let trash_item_dir = date_dir.join(&trash_id);
-
-        fs::create_dir_all(&trash_item_dir)?;
-
         let metadata = source_path.metadata()?;
         let item_type = if metadata.is_dir() { "directory" } else { "file" };
 
-        let sha256 = if !metadata.is_dir() {
-            hash_file(source_path).ok()
+        let (size, sha256) = if metadata.is_dir() {
+            (dir_size(source_path), None)
         } else {
-            None
+            (metadata.len(), hash_file(source_path).ok())
         };
 
         let trash_metadata = TrashMetadata {
-            trash_id: trash_id.clone(),
+            trash_id: Uuid::new_v4().to_string(),
             original_path: original_rel_path.to_string(),
             deleted_at: chrono::Utc::now().to_rfc3339(),
             item_type: item_type.to_string(),
-            size: metadata.len(),
+            size,
             sha256,
             request_id: request_id.to_string(),
         };
 
-        // Write metadata
-        let metadata_path = trash_item_dir.join("metadata.json");
-        let metadata_json = serde_json::to_string_pretty(&trash_metadata)?;
-        fs::write(&metadata_path, metadata_json)?;
-
-        // Move item to trash
-        let item_name = source_path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "item".to_string());
-        let trash_path = trash_item_dir.join(&item_name);
+        let (trash_id, trash_path) = self.backend.trash(source_path, trash_metadata)?;
 
-        fs::rename(source_path, &trash_path)?;
+        // Keep the trash under its size limit, never evicting the item just added
+        if enforce_limit {
+            if let Err(e) = self.enforce_size_limit(Some(&trash_id)) {
+                tracing::warn!("Failed to enforce trash size limit: {}", e);
+            }
+        }
 
         Ok((trash_id, trash_path))
     }
 
     /// List items in trash
     pub fn list(&self, date_filter: Option<&str>) -> Result<Vec<TrashMetadata>, TrashError> {
-        let mut items = Vec::new();
-
-        if !self.trash_dir.exists() {
-            return Ok(items);
-        }
-
-        let date_dirs: Vec<PathBuf> = if let Some(date) = date_filter {
-            vec![self.trash_dir.join(date)]
-        } else {
-            fs::read_dir(&self.trash_dir)?
-                .filter_map(|e| e.ok())
-                .filter(|e| e.path().is_dir())
-                .map(|e| e.path())
-                .collect()
-        };
-
-        for date_dir in date_dirs {
-            if !date_dir.exists() {
-                continue;
-            }
-
-            for trash_item in fs::read_dir(&date_dir)?.filter_map(|e| e.ok()) {
-                if !trash_item.path().is_dir() {
-                    continue;
-                }
-
-                let metadata_path = trash_item.path().join("metadata.json");
-                if let Ok(content) = fs::read_to_string(&metadata_path) {
-                    if let Ok(metadata) = serde_json::from_str::<TrashMetadata>(&content) {
-                        items.push(metadata);
-                    }
-                }
-            }
-        }
+        let mut items: Vec<TrashMetadata> = self
+            .backend
+            .items()
+            .into_iter()
+            .filter(|m| date_filter.is_none_or(|d| m.deleted_at.starts_with(d)))
+            .collect();
 
         // Sort by deletion time (newest first)
         items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
@@ -156,130 +463,235 @@ impl TrashManager {
 
     /// Find a trash item by ID
     pub fn find(&self, trash_id: &str) -> Result<Option<(TrashMetadata, PathBuf)>, TrashError> {
-        if !self.trash_dir.exists() {
+        let Some(item_path) = self.backend.item_path(trash_id) else {
             return Ok(None);
-        }
-
-        for date_dir in fs::read_dir(&self.trash_dir)?.filter_map(|e| e.ok()) {
-            if !date_dir.path().is_dir() {
-                continue;
-            }
-
-            let trash_item_dir = date_dir.path().join(trash_id);
-            if !trash_item_dir.exists() {
-                continue;
-            }
+        };
 
-            let metadata_path = trash_item_dir.join("metadata.json");
-            if let Ok(content) = fs::read_to_string(&metadata_path) {
-                if let Ok(metadata) = serde_json::from_str::<TrashMetadata>(&content) {
-                    // Find the actual item (not metadata.json)
-                    for item in fs::read_dir(&trash_item_dir)?.filter_map(|e| e.ok()) {
-                        let name = item.file_name().to_string_lossy().to_string();
-                        if name != "metadata.json" {
-                            return Ok(Some((metadata, item.path())));
-                        }
-                    }
-                }
-            }
-        }
+        Ok(self
+            .backend
+            .items()
+            .into_iter()
+            .find(|m| m.trash_id == trash_id)
+            .map(|m| (m, item_path)))
+    }
 
-        Ok(None)
+    /// Resolve a restore target against the workspace, rejecting paths that escape it,
+    /// including through symlinked directories
+    fn resolve_restore_path(&self, path: &str) -> Result<PathBuf, TrashError> {
+        resolve_within(&self.workspace, path).map_err(|e| match e {
+            FileError::OutsideWorkspace(_) => TrashError::OutsideWorkspace(path.to_string()),
+            other => other.into(),
+        })
     }
 
-    /// Restore a trash item
+    /// Restore a trash item to its original location (or `to_path`), both relative to the workspace
     pub fn restore(
-        &self,
+        &mut self,
         trash_id: &str,
-        to_path: Option<&Path>,
-    ) -> Result<PathBuf, TrashError> {
+        to_path: Option<&str>,
+        on_conflict: RestoreConflict,
+    ) -> Result<RestoreResult, TrashError> {
         let (metadata, item_path) = self
             .find(trash_id)?
             .ok_or_else(|| TrashError::NotFound(trash_id.to_string()))?;
 
-        let restore_path = to_path
-            .map(PathBuf::from)
-            .unwrap_or_else(|| PathBuf::from(&metadata.original_path));
+        let mut restore_path =
+            self.resolve_restore_path(to_path.unwrap_or(&metadata.original_path))?;
+        let mut status = "restored";
+        let mut replaced_trash_id = None;
 
         if restore_path.exists() {
-            return Err(TrashError::AlreadyExists(
-                restore_path.to_string_lossy().to_string(),
-            ));
+            match on_conflict {
+                RestoreConflict::Skip => {
+                    return Ok(RestoreResult {
+                        trash_id: trash_id.to_string(),
+                        status: "skipped".to_string(),
+                        to_path: None,
+                        replaced_trash_id: None,
+                        error: None,
+                    });
+                }
+                RestoreConflict::Rename => {
+                    restore_path = unique_restore_path(&restore_path);
+                    status = "renamed";
+                }
+                RestoreConflict::Overwrite => {
+                    // `resolve_within` returns paths under the canonical workspace
+                    let root = self.workspace.canonicalize()?;
+                    let rel_path = restore_path
+                        .strip_prefix(&root)
+                        .map(|p| p.to_string_lossy().replace('\\', "/"))
+                        .unwrap_or_else(|_| restore_path.to_string_lossy().to_string());
+                    // The displaced item gets its own request, so restoring this item's
+                    // request later does not bring it back over the restored one. The size
+                    // limit is skipped so it cannot evict the item being restored.
+                    let displaced_request = uuid::Uuid::new_v4().to_string();
+                    let (replaced_id, _) =
+                        self.trash_item(&restore_path, &rel_path, &displaced_request, false)?;
+                    replaced_trash_id = Some(replaced_id);
+                    status = "overwritten";
+                }
+            }
         }
 
-        // Ensure parent directory exists
-        if let Some(parent) = restore_path.parent() {
-            fs::create_dir_all(parent)?;
+        if let Err(e) = move_into_place(&item_path, &restore_path) {
+            // Put the displaced item back rather than leave the target empty
+            if let Some(replaced_id) = &replaced_trash_id {
+                if let Ok(Some((_, displaced_path))) = self.find(replaced_id) {
+                    if rename_or_copy(&displaced_path, &restore_path).is_ok() {
+                        let _ = self.backend.remove(replaced_id);
+                    }
+                }
+            }
+            return Err(e.into());
         }
 
-        fs::rename(&item_path, &restore_path)?;
+        // Drop the trash record now that the item is back
+        self.backend.remove(trash_id)?;
+
+        Ok(RestoreResult {
+            trash_id: trash_id.to_string(),
+            status: status.to_string(),
+            to_path: Some(restore_path.to_string_lossy().to_string()),
+            replaced_trash_id,
+            error: None,
+        })
+    }
 
-        // Clean up trash item directory
-        let trash_item_dir = item_path.parent().unwrap();
-        let _ = fs::remove_dir_all(trash_item_dir);
+    /// Restore every item deleted by one request, oldest first. An item that cannot be
+    /// restored is reported as `failed` and the rest are still restored.
+    pub fn restore_request(
+        &mut self,
+        request_id: &str,
+        on_conflict: RestoreConflict,
+    ) -> Result<Vec<RestoreResult>, TrashError> {
+        let mut items: Vec<TrashMetadata> = self
+            .backend
+            .items()
+            .into_iter()
+            .filter(|m| m.request_id == request_id)
+            .collect();
+
+        if items.is_empty() {
+            return Err(TrashError::NotFound(request_id.to_string()));
+        }
 
-        Ok(restore_path)
+        // Restore in the order the items were deleted
+        items.sort_by(|a, b| a.deleted_at.cmp(&b.deleted_at));
+
+        Ok(items
+            .into_iter()
+            .map(|item| {
+                self.restore(&item.trash_id, None, on_conflict)
+                    .unwrap_or_else(|e| RestoreResult {
+                        trash_id: item.trash_id,
+                        status: "failed".to_string(),
+                        to_path: None,
+                        replaced_trash_id: None,
+                        error: Some(e.to_string()),
+                    })
+            })
+            .collect())
     }
 
     /// Purge trash items
     pub fn purge(
-        &self,
+        &mut self,
         trash_id: Option<&str>,
         older_than_days: Option<i64>,
     ) -> Result<Vec<String>, TrashError> {
-        let mut purged = Vec::new();
-
         if let Some(id) = trash_id {
             // Purge specific item
-            if let Some((_, item_path)) = self.find(id)? {
-                let trash_item_dir = item_path.parent().unwrap();
-                fs::remove_dir_all(trash_item_dir)?;
-                purged.push(id.to_string());
-            } else {
+            if !self.backend.remove(id)? {
                 return Err(TrashError::NotFound(id.to_string()));
             }
-        } else {
-            // Purge all or by age
-            let items = self.list(None)?;
-            let cutoff = older_than_days.map(|days| {
-                chrono::Utc::now() - chrono::Duration::days(days)
-            });
+            return Ok(vec![id.to_string()]);
+        }
 
-            for item in items {
-                let should_purge = match &cutoff {
-                    Some(cutoff_time) => {
-                        chrono::DateTime::parse_from_rfc3339(&item.deleted_at)
-                            .map(|dt| dt < *cutoff_time)
-                            .unwrap_or(false)
-                    }
-                    None => true,
-                };
+        // Purge all or by age, saving the index once for the batch
+        let ids: Vec<String> = self
+            .backend
+            .items()
+            .into_iter()
+            .filter(|m| older_than_days.is_none_or(|days| is_older_than(m, days)))
+            .map(|m| m.trash_id)
+            .collect();
+        self.backend.remove_many(&ids)
+    }
 
-                if should_purge {
-                    if let Some((_, item_path)) = self.find(&item.trash_id)? {
-                        let trash_item_dir = item_path.parent().unwrap();
-                        if fs::remove_dir_all(trash_item_dir).is_ok() {
-                            purged.push(item.trash_id.clone());
-                        }
-                    }
-                }
-            }
+    /// Apply the configured age and size limits. Called periodically in the background.
+    pub fn auto_purge(&mut self) -> Result<Vec<String>, TrashError> {
+        let mut purged = Vec::new();
+
+        if let Some(days) = self.settings.max_age_days {
+            purged.extend(self.purge(None, Some(days))?);
         }
 
-        // Clean up empty date directories
-        if self.trash_dir.exists() {
-            for date_dir in fs::read_dir(&self.trash_dir)?.filter_map(|e| e.ok()) {
-                if date_dir.path().is_dir() {
-                    if let Ok(mut entries) = fs::read_dir(date_dir.path()) {
-                        if entries.next().is_none() {
-                            let _ = fs::remove_dir(date_dir.path());
-                        }
-                    }
-                }
+        purged.extend(self.enforce_size_limit(None)?);
+
+        Ok(purged)
+    }
+
+    /// Purge the oldest items until the trash fits in `max_size_bytes`
+    fn enforce_size_limit(&mut self, keep_id: Option<&str>) -> Result<Vec<String>, TrashError> {
+        let Some(max_size) = self.settings.max_size_bytes else {
+            return Ok(Vec::new());
+        };
+
+        let mut total_size = self.backend.total_size();
+        if total_size <= max_size {
+            return Ok(Vec::new());
+        }
+
+        let mut candidates: Vec<TrashMetadata> = self
+            .backend
+            .items()
+            .into_iter()
+            .filter(|m| Some(m.trash_id.as_str()) != keep_id)
+            .collect();
+        candidates.sort_by(|a, b| a.deleted_at.cmp(&b.deleted_at));
+
+        let mut oldest = Vec::new();
+        for item in candidates {
+            if total_size <= max_size {
+                break;
             }
+            total_size = total_size.saturating_sub(item.size);
+            oldest.push(item.trash_id);
         }
 
-        Ok(purged)
+        self.backend.remove_many(&oldest)
+    }
+
+    pub fn settings(&self) -> &TrashSettings {
+        &self.settings
+    }
+
+    /// Update the trash configuration and apply the limits immediately.
+    /// Switching backends leaves existing items where they are.
+    pub fn set_settings(&mut self, settings: TrashSettings) -> Result<Vec<String>, TrashError> {
+        settings.save(&self.workspace)?;
+        if settings.backend != self.settings.backend {
+            self.backend = create_backend(settings.backend, &self.workspace);
+        }
+        self.settings = settings;
+        self.auto_purge()
+    }
+
+    pub fn stats(&self) -> TrashStats {
+        let items = self.backend.items();
+        let oldest = items.iter().map(|m| &m.deleted_at).min().cloned();
+        let newest = items.iter().map(|m| &m.deleted_at).max().cloned();
+
+        TrashStats {
+            backend: self.settings.backend,
+            item_count: items.len(),
+            total_size: self.backend.total_size(),
+            max_size_bytes: self.settings.max_size_bytes,
+            max_age_days: self.settings.max_age_days,
+            oldest_deleted_at: oldest,
+            newest_deleted_at: newest,
+        }
     }
 
     /// Generate a confirmation token
@@ -306,3 +718,159 @@ impl TrashManager {
         self.confirm_tokens.retain(|_, (_, expires_at)| *expires_at > now);
     }
 }
+
+/// Load the trash index, rebuilding it from the on-disk metadata when missing or unreadable
+fn load_index(trash_dir: &Path) -> TrashIndex {
+    let index_path = trash_dir.join(INDEX_FILE);
+    if let Ok(content) = fs::read_to_string(&index_path) {
+        if let Ok(index) = serde_json::from_str::<TrashIndex>(&content) {
+            return index;
+        }
+        tracing::warn!("Trash index at {} is corrupt, rebuilding", index_path.display());
+    }
+
+    let mut index = TrashIndex {
+        version: INDEX_VERSION,
+        ..Default::default()
+    };
+
+    if !trash_dir.exists() {
+        return index;
+    }
+
+    let Ok(date_dirs) = fs::read_dir(trash_dir) else {
+        return index;
+    };
+
+    for date_dir in date_dirs.filter_map(|e| e.ok()).filter(|e| e.path().is_dir()) {
+        let date = date_dir.file_name().to_string_lossy().to_string();
+        let Ok(trash_items) = fs::read_dir(date_dir.path()) else {
+            continue;
+        };
+
+        for trash_item in trash_items.filter_map(|e| e.ok()).filter(|e| e.path().is_dir()) {
+            let metadata_path = trash_item.path().join("metadata.json");
+            let Some(mut metadata) = fs::read_to_string(&metadata_path)
+                .ok()
+                .and_then(|c| serde_json::from_str::<TrashMetadata>(&c).ok())
+            else {
+                continue;
+            };
+
+            // Find the actual item (not metadata.json)
+            let Some(item) = fs::read_dir(trash_item.path())
+                .ok()
+                .and_then(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .find(|e| e.file_name() != "metadata.json")
+                })
+            else {
+                continue;
+            };
+
+            // Older entries recorded the directory inode size; recompute it
+            if metadata.item_type == "directory" {
+                metadata.size = dir_size(&item.path());
+            }
+
+            index.total_size += metadata.size;
+            index.items.push(TrashIndexEntry {
+                metadata,
+                date_dir: date.clone(),
+                item_name: item.file_name().to_string_lossy().to_string(),
+            });
+        }
+    }
+
+    index
+}
+
+/// Pick a free sibling path: `name (restored).ext`, `name (restored 2).ext`, ...
+fn unique_restore_path(path: &Path) -> PathBuf {
+    let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut n = 1;
+    loop {
+        let suffix = if n == 1 {
+            " (restored)".to_string()
+        } else {
+            format!(" (restored {})", n)
+        };
+        let candidate = parent.join(format!("{}{}{}", stem, suffix, ext));
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Move a trashed item to `to`, creating its parent directories first
+fn move_into_place(from: &Path, to: &Path) -> Result<(), FileError> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    rename_or_copy(from, to)
+}
+
+/// Recursive size of all files under a directory
+pub(crate) fn dir_size(path: &Path) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn is_older_than(metadata: &TrashMetadata, days: i64) -> bool {
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days);
+    chrono::DateTime::parse_from_rfc3339(&metadata.deleted_at)
+        .map(|dt| dt < cutoff)
+        .unwrap_or(false)
+}
+
+/// Periodically apply the trash limits to every root that has a trash manager open
+pub fn spawn_auto_purge(app: tauri::AppHandle) {
+    use tauri::Manager;
+
+    tauri::async_runtime::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(AUTO_PURGE_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let app = app.clone();
+            let purge = tokio::task::spawn_blocking(move || {
+                // Take the managers and release the map, so windows are not held up by the purge
+                let managers: Vec<_> = match app.state::<super::AppState>().trash.lock() {
+                    Ok(trash) => trash.iter().map(|(root, manager)| (root.clone(), manager.clone())).collect(),
+                    Err(_) => return,
+                };
+                for (root, manager) in managers {
+                    let Ok(mut manager) = manager.lock() else {
+                        continue;
+                    };
+                    match manager.auto_purge() {
+                        Ok(purged) if !purged.is_empty() => {
+                            tracing::info!("Auto-purged {} trash item(s) in {}", purged.len(), root.display());
+                        }
+                        Ok(_) => {}
+                        Err(e) => tracing::warn!("Trash auto-purge failed in {}: {}", root.display(), e),
+                    }
+                }
+            });
+            if let Err(e) = purge.await {
+                tracing::warn!("Trash auto-purge panicked: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,405 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+use chrono::{Local, Utc};
+
+/// What an LLM call produced
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UsageKind {
+    Completion,
+    Embedding,
+    Image,
+}
+
+impl UsageKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UsageKind::Completion => "completion",
+            UsageKind::Embedding => "embedding",
+            UsageKind::Image => "image",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallStatus {
+    Ok,
+    Error,
+    Cancelled,
+}
+
+impl CallStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallStatus::Ok => "ok",
+            CallStatus::Error => "error",
+            CallStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// One finished call, ready to be written to the ledger
+#[derive(Debug, Clone)]
+pub struct UsageEntry {
+    pub kind: UsageKind,
+    pub provider: String,
+    pub model: String,
+    /// Root folder of the window that made the call
+    pub workspace: Option<String>,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    /// Part of `completion_tokens` the model spent thinking
+    pub reasoning_tokens: u32,
+    pub images: u32,
+    pub latency: Duration,
+    pub status: CallStatus,
+    /// `None` when the model is not in the price table
+    pub cost_usd: Option<f64>,
+}
+
+/// Daily limits checked before each request. Local models are never limited. Calls to models
+/// without a known price count against the cost limit at `UNPRICED_RATES`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageBudget {
+    pub daily_cost_usd: Option<f64>,
+    pub daily_tokens: Option<u64>,
+}
+
+/// Totals for one group of calls
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageAggregate {
+    /// Day (YYYY-MM-DD), model or workspace, depending on the grouping
+    pub key: String,
+    pub calls: u64,
+    pub errors: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub images: u64,
+    /// Calls to models without a known price are not included
+    pub cost_usd: f64,
+    pub avg_latency_ms: f64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGroup {
+    Day,
+    Model,
+    Workspace,
+}
+
+impl UsageGroup {
+    fn column(&self) -> &'static str {
+        match self {
+            UsageGroup::Day => "day",
+            UsageGroup::Model => "model",
+            UsageGroup::Workspace => "COALESCE(workspace, '')",
+        }
+    }
+}
+
+/// List prices in USD: (model prefix, input per 1M tokens, output per 1M tokens, per image).
+/// Most specific prefix first. Router markups are not included, so costs are estimates.
+const PRICES: &[(&str, f64, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60, 0.0),
+    ("gpt-4o", 2.50, 10.00, 0.0),
+    ("gpt-4.1-nano", 0.10, 0.40, 0.0),
+    ("gpt-4.1-mini", 0.40, 1.60, 0.0),
+    ("gpt-4.1", 2.00, 8.00, 0.0),
+    ("gpt-4-turbo", 10.00, 30.00, 0.0),
+    ("gpt-4", 30.00, 60.00, 0.0),
+    ("gpt-3.5-turbo", 0.50, 1.50, 0.0),
+    ("o1-mini", 1.10, 4.40, 0.0),
+    ("o1", 15.00, 60.00, 0.0),
+    ("o3-mini", 1.10, 4.40, 0.0),
+    ("o3", 2.00, 8.00, 0.0),
+    ("o4-mini", 1.10, 4.40, 0.0),
+    ("claude-3-5-haiku", 0.80, 4.00, 0.0),
+    ("claude-3.5-haiku", 0.80, 4.00, 0.0),
+    ("claude-3-haiku", 0.25, 1.25, 0.0),
+    ("claude-3-opus", 15.00, 75.00, 0.0),
+    ("claude-opus", 15.00, 75.00, 0.0),
+    ("claude", 3.00, 15.00, 0.0),
+    ("gemini-1.5-flash", 0.075, 0.30, 0.0),
+    ("gemini-1.5-pro", 1.25, 5.00, 0.0),
+    ("gemini-2.0-flash", 0.10, 0.40, 0.0),
+    ("gemini-2.5-flash", 0.30, 2.50, 0.0),
+    ("gemini-2.5-pro", 1.25, 10.00, 0.0),
+    ("deepseek-reasoner", 0.55, 2.19, 0.0),
+    ("deepseek-r1", 0.55, 2.19, 0.0),
+    ("deepseek", 0.27, 1.10, 0.0),
+    ("text-embedding-3-small", 0.02, 0.0, 0.0),
+    ("text-embedding-3-large", 0.13, 0.0, 0.0),
+    ("text-embedding-ada-002", 0.10, 0.0, 0.0),
+    ("dall-e-3", 0.0, 0.0, 0.04),
+    ("dall-e-2", 0.0, 0.0, 0.02),
+];
+
+/// Rates charged against the cost budget for models missing from `PRICES`: (input per 1M
+/// tokens, output per 1M tokens, per image). The most expensive rates in the table, so an
+/// unpriced model cannot run past the budget. The ledger still records these calls unpriced.
+const UNPRICED_RATES: (f64, f64, f64) = (15.00, 75.00, 0.08);
+
+/// Cost of a call from the price table, or `None` for unknown models
+pub fn cost_usd(model: &str, prompt_tokens: u32, completion_tokens: u32, images: u32) -> Option<f64> {
+    let name = model.rsplit('/').next().unwrap_or(model).to_lowercase();
+    let &(_, input, output, per_image) = PRICES.iter().find(|(prefix, ..)| name.starts_with(prefix))?;
+    Some(
+        prompt_tokens as f64 * input / 1_000_000.0
+            + completion_tokens as f64 * output / 1_000_000.0
+            + images as f64 * per_image,
+    )
+}
+
+/// Ledger of LLM calls across all workspaces, stored in the app config dir as `usage.db`.
+/// Every `LlmClient` records through its own copy.
+#[derive(Clone)]
+pub struct UsageLedger {
+    db_path: PathBuf,
+}
+
+impl UsageLedger {
+    pub fn open() -> Result<Self, String> {
+        let dir = dirs::config_dir()
+            .ok_or("No config directory")?
+            .join("SentinelOps");
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+        let ledger = Self {
+            db_path: dir.join("usage.db"),
+        };
+        ledger.init_database()?;
+        Ok(ledger)
+    }
+
+    fn get_connection(&self) -> Result<Connection, String> {
+        Connection::open(&self.db_path)
+            .map_err(|e| format!("Failed to open database: {}", e))
+    }
+
+    fn init_database(&self) -> Result<(), String> {
+        let conn = self.get_connection()?;
+
+        conn.execute_batch(r#"
+            CREATE TABLE IF NOT EXISTS usage_records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                created_at TEXT NOT NULL,
+                day TEXT NOT NULL,
+                kind TEXT NOT NULL CHECK(kind IN ('completion', 'embedding', 'image')),
+                provider TEXT NOT NULL,
+                model TEXT NOT NULL,
+                workspace TEXT,
+                prompt_tokens INTEGER NOT NULL DEFAULT 0,
+                completion_tokens INTEGER NOT NULL DEFAULT 0,
+                reasoning_tokens INTEGER NOT NULL DEFAULT 0,
+                images INTEGER NOT NULL DEFAULT 0,
+                latency_ms INTEGER NOT NULL,
+                status TEXT NOT NULL CHECK(status IN ('ok', 'error', 'cancelled')),
+                cost_usd REAL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_usage_day ON usage_records(day);
+            CREATE INDEX IF NOT EXISTS idx_usage_model ON usage_records(model);
+            CREATE INDEX IF NOT EXISTS idx_usage_workspace ON usage_records(workspace);
+
+            -- Single-row table holding the daily budget
+            CREATE TABLE IF NOT EXISTS usage_budget (
+                id INTEGER PRIMARY KEY CHECK(id = 1),
+                daily_cost_usd REAL,
+                daily_tokens INTEGER
+            );
+        "#).map_err(|e| format!("Failed to create tables: {}", e))?;
+
+        // Ledgers created before reasoning tokens were tracked
+        let has_reasoning: bool = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info('usage_records') WHERE name = 'reasoning_tokens'",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|count| count > 0)
+            .map_err(|e| format!("Failed to read table info: {}", e))?;
+        if !has_reasoning {
+            conn.execute(
+                "ALTER TABLE usage_records ADD COLUMN reasoning_tokens INTEGER NOT NULL DEFAULT 0",
+                [],
+            ).map_err(|e| format!("Failed to migrate usage table: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn record(&self, entry: &UsageEntry) -> Result<(), String> {
+        let conn = self.get_connection()?;
+        conn.execute(
+            r#"INSERT INTO usage_records
+               (created_at, day, kind, provider, model, workspace, prompt_tokens, completion_tokens,
+                reasoning_tokens, images, latency_ms, status, cost_usd)
+               VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"#,
+            params![
+                Utc::now().to_rfc3339(),
+                local_day(),
+                entry.kind.as_str(),
+                entry.provider,
+                entry.model,
+                entry.workspace,
+                entry.prompt_tokens,
+                entry.completion_tokens,
+                entry.reasoning_tokens,
+                entry.images,
+                entry.latency.as_millis() as i64,
+                entry.status.as_str(),
+                entry.cost_usd,
+            ],
+        ).map_err(|e| format!("Failed to record usage: {}", e))?;
+        Ok(())
+    }
+
+    pub fn budget(&self) -> Result<UsageBudget, String> {
+        let conn = self.get_connection()?;
+        let budget = conn
+            .query_row(
+                "SELECT daily_cost_usd, daily_tokens FROM usage_budget WHERE id = 1",
+                [],
+                |row| {
+                    Ok(UsageBudget {
+                        daily_cost_usd: row.get(0)?,
+                        daily_tokens: row.get::<_, Option<i64>>(1)?.map(|t| t as u64),
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| format!("Failed to read budget: {}", e))?;
+        Ok(budget.unwrap_or_default())
+    }
+
+    pub fn set_budget(&self, budget: &UsageBudget) -> Result<(), String> {
+        let conn = self.get_connection()?;
+        conn.execute(
+            r#"INSERT INTO usage_budget (id, daily_cost_usd, daily_tokens) VALUES (1, ?1, ?2)
+               ON CONFLICT(id) DO UPDATE SET daily_cost_usd = ?1, daily_tokens = ?2"#,
+            params![budget.daily_cost_usd, budget.daily_tokens.map(|t| t as i64)],
+        ).map_err(|e| format!("Failed to save budget: {}", e))?;
+        Ok(())
+    }
+
+    /// Totals for the current local day
+    pub fn today(&self) -> Result<UsageAggregate, String> {
+        let day = local_day();
+        let totals = self.summary(UsageGroup::Day, Some(&day), Some(&day))?;
+        Ok(totals.into_iter().next().unwrap_or(UsageAggregate {
+            key: day,
+            calls: 0,
+            errors: 0,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            reasoning_tokens: 0,
+            images: 0,
+            cost_usd: 0.0,
+            avg_latency_ms: 0.0,
+        }))
+    }
+
+    /// Err with a message for the user when today's spending has reached the budget
+    pub fn check_budget(&self) -> Result<(), String> {
+        let budget = self.budget()?;
+        if budget.daily_cost_usd.is_none() && budget.daily_tokens.is_none() {
+            return Ok(());
+        }
+
+        let today = self.today()?;
+        if let Some(limit) = budget.daily_cost_usd {
+            let spent = self.budget_spend(&today.key)?;
+            if spent >= limit {
+                return Err(format!(
+                    "Daily budget of ${:.2} reached (${:.2} spent today). Raise it in Settings or use a local model.",
+                    limit, spent
+                ));
+            }
+        }
+        if let Some(limit) = budget.daily_tokens {
+            let tokens = today.prompt_tokens + today.completion_tokens;
+            if tokens >= limit {
+                return Err(format!(
+                    "Daily limit of {} tokens reached ({} used today). Raise it in Settings or use a local model.",
+                    limit, tokens
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Spending on `day` as counted against the cost budget, with unpriced calls charged
+    /// at `UNPRICED_RATES`
+    fn budget_spend(&self, day: &str) -> Result<f64, String> {
+        let conn = self.get_connection()?;
+        let (input, output, per_image) = UNPRICED_RATES;
+        conn.query_row(
+            r#"SELECT COALESCE(SUM(COALESCE(
+                   cost_usd,
+                   prompt_tokens * ?2 / 1000000.0 + completion_tokens * ?3 / 1000000.0 + images * ?4
+               )), 0.0)
+               FROM usage_records WHERE day = ?1"#,
+            params![day, input, output, per_image],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to read today's spending: {}", e))
+    }
+
+    /// Aggregate calls between two days (inclusive, YYYY-MM-DD), newest day or most expensive
+    /// model/workspace first
+    pub fn summary(&self, group: UsageGroup, since: Option<&str>, until: Option<&str>) -> Result<Vec<UsageAggregate>, String> {
+        let conn = self.get_connection()?;
+        let order = match group {
+            UsageGroup::Day => "key DESC",
+            _ => "cost_usd DESC, calls DESC",
+        };
+        let sql = format!(
+            r#"SELECT {} AS key,
+                      COUNT(*) AS calls,
+                      SUM(status = 'error'),
+                      SUM(prompt_tokens),
+                      SUM(completion_tokens),
+                      SUM(reasoning_tokens),
+                      SUM(images),
+                      COALESCE(SUM(cost_usd), 0) AS cost_usd,
+                      AVG(latency_ms)
+               FROM usage_records
+               WHERE (?1 IS NULL OR day >= ?1) AND (?2 IS NULL OR day <= ?2)
+               GROUP BY key
+               ORDER BY {}"#,
+            group.column(),
+            order
+        );
+
+        let mut stmt = conn.prepare(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params![since, until], |row| {
+                Ok(UsageAggregate {
+                    key: row.get(0)?,
+                    calls: row.get::<_, i64>(1)? as u64,
+                    errors: row.get::<_, i64>(2)? as u64,
+                    prompt_tokens: row.get::<_, i64>(3)? as u64,
+                    completion_tokens: row.get::<_, i64>(4)? as u64,
+                    reasoning_tokens: row.get::<_, i64>(5)? as u64,
+                    images: row.get::<_, i64>(6)? as u64,
+                    cost_usd: row.get(7)?,
+                    avg_latency_ms: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query usage: {}", e))?;
+
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to read usage: {}", e))
+    }
+}
+
+fn local_day() -> String {
+    Local::now().format("%Y-%m-%d").to_string()
+}
@@ -0,0 +1,18 @@
+use super::terminal_service::TerminalManager;
+use super::workspace_service::Workspace;
+
+/// State owned by one webview window, keyed by the window label in `AppState::windows`.
+/// Created the first time a window calls a command and dropped when the window closes.
+pub struct WindowState {
+    pub workspace: Workspace,
+    pub terminals: TerminalManager,
+}
+
+impl WindowState {
+    pub fn new(workspace: Workspace) -> Self {
+        Self {
+            workspace,
+            terminals: TerminalManager::new(),
+        }
+    }
+}
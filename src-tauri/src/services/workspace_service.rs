@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum WorkspaceError {
+    #[error("Workspace root not found: {0}")]
+    RootNotFound(String),
+    #[error("Not a directory: {0}")]
+    NotDirectory(String),
+    #[error("Root already open: {0}")]
+    DuplicateRoot(String),
+    #[error("Cannot remove the last workspace root")]
+    LastRoot,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid workspace file: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// One folder open in the workspace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceRoot {
+    /// Stable identifier used by commands (`rootId`)
+    pub id: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// A set of named roots. The primary root is used when a command does not name one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Workspace {
+    pub roots: Vec<WorkspaceRoot>,
+    pub primary_root_id: String,
+    /// Workspace file this set was loaded from or saved to, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+}
+
+/// On-disk format of a `.sentinelops-workspace` file. Root paths relative to the
+/// file's directory are kept relative so the file can be committed alongside checkouts.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkspaceFile {
+    version: u32,
+    roots: Vec<WorkspaceFileRoot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primary: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkspaceFileRoot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    path: String,
+}
+
+const WORKSPACE_FILE_VERSION: u32 = 1;
+pub const WORKSPACE_FILE_EXTENSION: &str = "sentinelops-workspace";
+
+impl Workspace {
+    /// A workspace with a single root, as opened by `set_workspace`
+    pub fn single(path: PathBuf) -> Self {
+        let name = root_name(&path);
+        let id = slugify(&name);
+        Self {
+            roots: vec![WorkspaceRoot {
+                id: id.clone(),
+                name,
+                path,
+            }],
+            primary_root_id: id,
+            file: None,
+        }
+    }
+
+    /// Look up a root by ID, or the primary root when `root_id` is `None`
+    pub fn root(&self, root_id: Option<&str>) -> Result<&WorkspaceRoot, WorkspaceError> {
+        let id = root_id.unwrap_or(&self.primary_root_id);
+        self.roots
+            .iter()
+            .find(|r| r.id == id)
+            .ok_or_else(|| WorkspaceError::RootNotFound(id.to_string()))
+    }
+
+    pub fn primary_root(&self) -> &Path {
+        self.root(None)
+            .map(|r| r.path.as_path())
+            .unwrap_or_else(|_| self.roots[0].path.as_path())
+    }
+
+    /// Add a folder as a new root. The ID is derived from the name and made unique.
+    pub fn add_root(&mut self, path: PathBuf, name: Option<String>) -> Result<WorkspaceRoot, WorkspaceError> {
+        if !path.is_dir() {
+            return Err(WorkspaceError::NotDirectory(path.to_string_lossy().to_string()));
+        }
+        if self.roots.iter().any(|r| r.path == path) {
+            return Err(WorkspaceError::DuplicateRoot(path.to_string_lossy().to_string()));
+        }
+
+        let name = name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| root_name(&path));
+        let id = self.unique_id(&slugify(&name));
+        let root = WorkspaceRoot { id, name, path };
+        self.roots.push(root.clone());
+        Ok(root)
+    }
+
+    pub fn remove_root(&mut self, root_id: &str) -> Result<WorkspaceRoot, WorkspaceError> {
+        let pos = self
+            .roots
+            .iter()
+            .position(|r| r.id == root_id)
+            .ok_or_else(|| WorkspaceError::RootNotFound(root_id.to_string()))?;
+        if self.roots.len() == 1 {
+            return Err(WorkspaceError::LastRoot);
+        }
+
+        let removed = self.roots.remove(pos);
+        if self.primary_root_id == removed.id {
+            self.primary_root_id = self.roots[0].id.clone();
+        }
+        Ok(removed)
+    }
+
+    pub fn set_primary(&mut self, root_id: &str) -> Result<(), WorkspaceError> {
+        self.root(Some(root_id))?;
+        self.primary_root_id = root_id.to_string();
+        Ok(())
+    }
+
+    fn unique_id(&self, base: &str) -> String {
+        let mut id = base.to_string();
+        let mut n = 2;
+        while self.roots.iter().any(|r| r.id == id) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        id
+    }
+
+    /// Load a workspace file, resolving relative root paths against its directory
+    pub fn load(file: &Path) -> Result<Self, WorkspaceError> {
+        let content = fs::read_to_string(file)?;
+        let parsed: WorkspaceFile = serde_json::from_str(&content)?;
+        let base = file.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let mut workspace = Workspace {
+            roots: Vec::new(),
+            primary_root_id: String::new(),
+            file: Some(file.to_path_buf()),
+        };
+
+        for entry in parsed.roots {
+            let path = if Path::new(&entry.path).is_absolute() {
+                PathBuf::from(&entry.path)
+            } else {
+                base.join(&entry.path)
+            };
+            let path = path.canonicalize().unwrap_or(path);
+            let name = entry.name.unwrap_or_else(|| root_name(&path));
+            let id = workspace.unique_id(&entry.id.unwrap_or_else(|| slugify(&name)));
+            // Missing folders are kept so the file round-trips; commands on them fail with NotFound
+            workspace.roots.push(WorkspaceRoot { id, name, path });
+        }
+
+        if workspace.roots.is_empty() {
+            return Err(WorkspaceError::RootNotFound("workspace file has no roots".to_string()));
+        }
+
+        workspace.primary_root_id = parsed
+            .primary
+            .filter(|p| workspace.roots.iter().any(|r| &r.id == p))
+            .unwrap_or_else(|| workspace.roots[0].id.clone());
+
+        Ok(workspace)
+    }
+
+    /// Write the workspace to `file` and remember it for later saves
+    pub fn save(&mut self, file: &Path) -> Result<(), WorkspaceError> {
+        let base = file.parent().map(Path::to_path_buf).unwrap_or_default();
+        let base = base.canonicalize().unwrap_or(base);
+
+        let roots = self
+            .roots
+            .iter()
+            .map(|r| WorkspaceFileRoot {
+                id: Some(r.id.clone()),
+                name: Some(r.name.clone()),
+                path: r
+                    .path
+                    .strip_prefix(&base)
+                    .map(|p| {
+                        let rel = p.to_string_lossy().replace('\\', "/");
+                        if rel.is_empty() { ".".to_string() } else { rel }
+                    })
+                    .unwrap_or_else(|_| r.path.to_string_lossy().to_string()),
+            })
+            .collect();
+
+        let data = WorkspaceFile {
+            version: WORKSPACE_FILE_VERSION,
+            roots,
+            primary: Some(self.primary_root_id.clone()),
+        };
+
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(file, serde_json::to_string_pretty(&data)?)?;
+        self.file = Some(file.to_path_buf());
+        Ok(())
+    }
+
+    /// Persist to the workspace file, if one is open
+    pub fn save_if_backed(&mut self) -> Result<(), WorkspaceError> {
+        match self.file.clone() {
+            Some(file) => self.save(&file),
+            None => Ok(()),
+        }
+    }
+}
+
+fn root_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+/// Lowercase, dash-separated identifier from a display name
+fn slugify(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() { "root".to_string() } else { slug }
+}
@@ -1,6 +1,6 @@
 import { useState, useRef, useEffect } from 'react';
 import { Bot, Send, Trash2, History, X, Coins, Zap, MessageSquare, RotateCcw, Wrench, FileCode, FolderPlus, Eye, Trash, RefreshCw, ClipboardList, HelpCircle, Plus, ImageIcon, Loader2, Download, Sparkles, Brain, Terminal, Globe, Folder, AlertTriangle, Crown, ShoppingCart, Server } from 'lucide-react';
-import { useStore, calculateCost, getContextWindow } from '../hooks/useStore';
+import { useStore, calculateCost, getContextWindow, type PendingCommand } from '../hooks/useStore';
 import { api } from '../services/api';
 import { memoryApi } from '../services/memoryApi';
 import ModelSelector from './ModelSelector';
@@ -279,7 +279,7 @@ async function executeTool(
   name: string,
   args: Record<string, string>,
   toolCallId: string,
-  addPendingCommand: (cmd: { toolCallId: string; command: string; workingDirectory: string; reason?: string }) => string,
+  addPendingCommand: (cmd: Omit<PendingCommand, 'id' | 'status'>) => string,
   chatWorkingDirectory: string | null
 ): Promise<ToolResult> {
   try {
@@ -367,8 +367,27 @@ async function executeTool(
         }
         return { success: false, result: res.error?.message || 'Web search failed' };
       }
-      default:
+      default: {
+        // Tools of the workspace's MCP servers, appended to the tool list by the backend.
+        // They can do anything the server allows, so they wait for approval like commands.
+        if (name.startsWith('mcp__')) {
+          const cmdId = addPendingCommand({
+            toolCallId,
+            command: `${name} ${JSON.stringify(args)}`,
+            workingDirectory: '',
+            reason: 'MCP tool call',
+            mcpTool: { name, args },
+          });
+          return {
+            success: true,
+            result: `MCP tool "${name}" requires user approval. Waiting for user to approve or reject.`,
+            requiresApproval: true,
+            pendingCommandId: cmdId,
+            toolCallId,
+          };
+        }
         return { success: false, result: `Unknown tool: ${name}` };
+      }
     }
   } catch (error) {
     return { success: false, result: `Error: ${error instanceof Error ? error.message : String(error)}` };
@@ -498,6 +517,11 @@ export default function AgentPanel() {
 
     updatePendingCommand(commandId, { status: 'executing' });
 
+    if (command.mcpTool) {
+      await runApprovedMcpTool(commandId, command.toolCallId, command.mcpTool);
+      return;
+    }
+
     try {
       // Execute the command via terminal API
       // Use command's working directory, fall back to chat working directory, then workspace root
@@ -548,6 +572,32 @@ export default function AgentPanel() {
     }
   };
 
+  // Run an approved MCP tool call and resume the agent with its output
+  const runApprovedMcpTool = async (
+    commandId: string,
+    toolCallId: string,
+    tool: NonNullable<PendingCommand['mcpTool']>
+  ) => {
+    try {
+      const res = await api.callMcpTool(tool.name, tool.args);
+      if (res.ok && res.data) {
+        const output = res.data.text || '(no output)';
+        updatePendingCommand(commandId, res.data.isError
+          ? { status: 'completed', error: output }
+          : { status: 'completed', result: output });
+        resumeAgentWithResult(toolCallId, res.data.isError ? `Error: ${output}` : output);
+      } else {
+        const errorMsg = res.error?.message || 'MCP tool call failed';
+        updatePendingCommand(commandId, { status: 'completed', error: errorMsg });
+        resumeAgentWithResult(toolCallId, `Error: ${errorMsg}`);
+      }
+    } catch (error) {
+      const errorMsg = error instanceof Error ? error.message : String(error);
+      updatePendingCommand(commandId, { status: 'completed', error: errorMsg });
+      resumeAgentWithResult(toolCallId, `Error: ${errorMsg}`);
+    }
+  };
+
   // Handle command rejection
   const handleCommandRejection = (commandId: string) => {
     const command = pendingCommands.find(c => c.id === commandId);
@@ -805,6 +855,12 @@ export default function AgentPanel() {
           let hasPendingApproval = false;
 
           for (const toolCall of response.data.toolCalls) {
+            // Calls whose arguments failed checking are answered with the problem so the model can retry
+            const issue = response.data.toolCallIssues?.find(i => i.callId === toolCall.id);
+            if (issue) {
+              toolResults.push({ tool_call_id: toolCall.id, role: 'tool', content: issue.message });
+              continue;
+            }
             const args = JSON.parse(toolCall.function.arguments || '{}');
 
             // Show what tool is being executed
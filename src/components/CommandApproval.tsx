@@ -58,7 +58,7 @@ export function CommandApproval({ command, onApprove, onReject }: CommandApprova
       )}
 
       <div className="bg-[#1E1E1E] p-3 rounded font-mono text-sm mb-3 overflow-x-auto">
-        <span className="text-[#858585]">$ </span>
+        {!command.mcpTool && <span className="text-[#858585]">$ </span>}
         <span className="text-[#DCDCAA]">{command.command}</span>
       </div>
 
@@ -112,6 +112,8 @@ export interface PendingCommand {
   command: string;
   workingDirectory: string;
   reason?: string;
+  /** Set when the call is an MCP tool rather than a shell command; `command` then describes it */
+  mcpTool?: { name: string; args: Record<string, unknown> };
   status: 'pending' | 'approved' | 'rejected' | 'executing' | 'completed';
   result?: string;
   error?: string;
@@ -38,6 +38,50 @@ export interface SearchMatch {
   text: string;
 }
 
+export interface WorkspaceRoot {
+  id: string;
+  name: string;
+  path: string;
+}
+
+export interface Workspace {
+  roots: WorkspaceRoot[];
+  primaryRootId: string;
+  file?: string;
+}
+
+export interface ApiKeysInfo {
+  configuredProviders: string[];
+  hasAnyKey: boolean;
+  /** Masked keys by provider; raw keys are never sent to the webview */
+  maskedKeys: Record<string, string>;
+  locked: boolean;
+  keySource: 'keyring' | 'passphrase' | null;
+}
+
+export type ProviderKind = 'openai' | 'anthropic' | 'google';
+
+export interface ProviderProfile {
+  id: string;
+  name: string;
+  kind: ProviderKind;
+  baseUrl: string;
+  /** Name of the saved API key to use; omit for endpoints that need no key */
+  keyRef?: string;
+  defaultModel?: string;
+  headers?: Record<string, string>;
+  timeouts?: { connectSecs?: number; requestSecs?: number };
+  retry?: { maxRetries?: number; baseDelayMs?: number; maxDelayMs?: number };
+  /** Models tried in order when the requested one is rate-limited or overloaded */
+  fallbackModels?: string[];
+}
+
+export interface ProvidersInfo {
+  /** Active profile ID; null when the proxy/local settings are used */
+  active: string | null;
+  profiles: (ProviderProfile & { hasKey: boolean })[];
+}
+
 export interface TrashItem {
   trashId: string;
   originalPath: string;
@@ -48,6 +92,16 @@ export interface TrashItem {
   request_id: string;
 }
 
+export type TrashRestoreConflict = 'rename' | 'overwrite' | 'skip';
+
+export interface TrashRestoreResult {
+  trashId: string;
+  status: 'restored' | 'renamed' | 'overwritten' | 'skipped' | 'failed';
+  toPath?: string;
+  replacedTrashId?: string;
+  error?: string;
+}
+
 export interface GitChange {
   path: string;
   status: 'modified' | 'added' | 'deleted' | 'renamed' | 'untracked';
@@ -66,15 +120,134 @@ export interface GitCommit {
   message: string;
 }
 
+/**
+ * A part of a multimodal message. Paths are relative to the workspace; images are downscaled
+ * to the provider's limits, text files are inlined and PDFs are sent as documents.
+ */
+export type ContentPart =
+  | { type: 'text'; text: string }
+  | { type: 'image'; url?: string; path?: string; detail?: 'low' | 'high' | 'auto' }
+  | { type: 'image_url'; image_url: { url: string; detail?: 'low' | 'high' | 'auto' } }
+  | { type: 'file'; path?: string; data?: string; name?: string };
+
 export interface ChatMessage {
   role: 'system' | 'user' | 'assistant';
-  content: string;
+  content: string | ContentPart[];
 }
 
 export interface TokenUsage {
   promptTokens: number;
+  /** Includes reasoningTokens */
   completionTokens: number;
   totalTokens: number;
+  /** Tokens the model spent thinking, when it reasons before answering */
+  reasoningTokens?: number;
+}
+
+export interface StructuredResult<T = unknown> {
+  /** The reply, valid against the schema */
+  value: T;
+  model: string;
+  attempts: number;
+  /** Summed over every attempt */
+  usage?: TokenUsage;
+}
+
+export type ReasoningEffort = 'low' | 'medium' | 'high';
+
+/** Emitted on `stream-thinking-{id}`: `started` when the request is sent, then each chunk of thinking */
+export type ThinkingEvent =
+  | { status: 'started' }
+  | { status: 'delta'; content: string; full: string };
+
+export interface ModelInfo {
+  model: string;
+  /** Absent for models the app has no limits for, such as most local models */
+  contextWindow?: number;
+  maxOutput?: number;
+  /** False when counts are estimated with a different model family's tokenizer */
+  exactTokens: boolean;
+}
+
+export interface OllamaHealth {
+  /**
+   * `not_running` when the server did not answer; `model_missing` when it lacks the model;
+   * `error` when it answered but could not load the model's details
+   */
+  status: 'ok' | 'not_running' | 'model_missing' | 'error';
+  version?: string;
+  model?: string;
+  message?: string;
+}
+
+/** Emitted on `ollama-pull-{id}` while a model downloads; the last event has `done: true` */
+export type OllamaPullEvent =
+  | { done: false; status: string; digest?: string; total?: number; completed?: number; percent?: number }
+  | { done: true; status: 'success' | 'cancelled' }
+  | { done: true; status: 'error'; error: string };
+
+export interface OllamaModelDetails {
+  name: string;
+  modelfile: string;
+  /** Keys such as `stop` can appear more than once */
+  parameters: Record<string, string[]>;
+  template?: string;
+  family?: string;
+  parameterSize?: string;
+  quantizationLevel?: string;
+  /** Longest context the model was trained for */
+  contextLength?: number;
+  /** Context the modelfile sets, if any */
+  numCtx?: number;
+  capabilities: string[];
+}
+
+export interface OllamaRunningModel {
+  name: string;
+  size: number;
+  sizeVram: number;
+  expiresAt?: string;
+  contextLength?: number;
+}
+
+export interface UsageAggregate {
+  /** Day (YYYY-MM-DD), model or workspace, depending on the grouping */
+  key: string;
+  calls: number;
+  errors: number;
+  promptTokens: number;
+  completionTokens: number;
+  reasoningTokens: number;
+  images: number;
+  costUsd: number;
+  avgLatencyMs: number;
+}
+
+export interface UsageBudget {
+  /** Models without a known price count against this at the most expensive listed rates */
+  dailyCostUsd?: number | null;
+  dailyTokens?: number | null;
+}
+
+export interface CacheSettings {
+  enabled: boolean;
+  embeddingTtlDays: number;
+  completionTtlHours: number;
+  maxSizeMb: number;
+}
+
+export interface CacheKindStats {
+  entries: number;
+  sizeBytes: number;
+  hits: number;
+  misses: number;
+  hitRate: number;
+}
+
+export interface CacheStats {
+  embedding: CacheKindStats;
+  completion: CacheKindStats;
+  settings: CacheSettings;
 }
 
 export interface ToolCall {
@@ -86,6 +259,88 @@ export interface ToolCall {
   };
 }
 
+/** A tool call that was not run because its arguments failed checking; send `message` back as its result */
+export interface ToolCallIssue {
+  callId: string;
+  name: string;
+  kind: 'invalidJson' | 'schemaMismatch' | 'unknownTool' | 'truncated';
+  message: string;
+  errors?: string[];
+}
+
+/** Lifecycle of one tool call; `callId` is stable from `started` on */
+export type ToolCallEvent =
+  | { status: 'started'; callId: string; index: number; name: string }
+  | { status: 'argumentsDelta'; callId: string; index: number; delta: string }
+  | { status: 'argumentsComplete'; callId: string; index: number; name: string; arguments: Record<string, unknown>; issue?: ToolCallIssue }
+  | { status: 'executing'; callId: string; index: number; name: string }
+  | { status: 'result'; callId: string; index: number; name: string; output: string }
+  | { status: 'error'; callId: string; index: number; name: string; error: string };
+
+export type AgentStopReason = 'done' | 'stepLimit' | 'cancelled';
+
+export type AgentEvent =
+  | { type: 'stepStarted'; step: number }
+  | { type: 'message'; step: number; content: string | null; reasoning?: string; toolCalls: ToolCall[] }
+  | { type: 'approvalRequired'; step: number; callId: string; name: string; arguments: Record<string, unknown> }
+  | { type: 'tool'; step: number; event: ToolCallEvent }
+  | { type: 'finished'; steps: number; reason: AgentStopReason; content: string | null };
+
+export interface AgentOutcome {
+  content: string | null;
+  steps: number;
+  reason: AgentStopReason;
+  messages: unknown[];
+}
+
+export interface AgentToolInfo {
+  name: string;
+  description: string;
+  parameters: Record<string, unknown>;
+  readOnly: boolean;
+  needsApproval: boolean;
+}
+
+export interface McpToolResult {
+  text: string;
+  isError: boolean;
+  content: unknown[];
+}
+
+export interface McpServerStatus {
+  name: string;
+  connected: boolean;
+  /** Servers do not run until allowed with `setMcpServerTrust` */
+  trusted: boolean;
+  /** Command line or URL of a server waiting to be allowed */
+  command?: string;
+  error?: string;
+  serverInfo: { name?: string; version?: string } | null;
+  /** `name` is what the model calls; `serverTool` is the name on the server */
+  tools: { name: string; serverTool: string; description?: string | null; inputSchema: Record<string, unknown> }[];
+  resources: { uri: string; name: string; description?: string | null; mimeType?: string | null }[];
+  prompts: { name: string; description?: string | null; arguments: { name: string; description?: string | null; required: boolean }[] }[];
+}
+
+export interface McpServerSettings {
+  /** Tools switched on or off by name; read-only tools are on unless listed as false */
+  tools: Record<string, boolean>;
+  port: number;
+  maxReadSize: number;
+  maxSearchResults: number;
+  maxQueryRows: number;
+}
+
+export interface McpServerInfo {
+  running: boolean;
+  url: string | null;
+  /** Bearer token for clients; a new one is issued on every start */
+  token: string | null;
+  workspace: string | null;
+  settings: McpServerSettings;
+  tools: { name: string; description: string; readOnly: boolean; enabled: boolean }[];
+}
+
 export interface ToolDefinition {
   type: 'function';
   function: {
@@ -344,6 +599,8 @@ class TauriApiClient {
 
   async getConfig(): Promise<ApiResponse<{
     workspaceRoot: string;
+    workspaceRoots: WorkspaceRoot[];
+    primaryRootId: string;
     llmConfigured: boolean;
     llmProvider: string;
     llmModel: string;
@@ -352,10 +609,37 @@ class TauriApiClient {
     return invoke('get_config');
   }
 
-  async setWorkspace(path: string): Promise<ApiResponse<{ workspaceRoot: string }>> {
+  async setWorkspace(
+    path: string
+  ): Promise<ApiResponse<{ workspaceRoot: string; primaryRootId: string }>> {
     return invoke('set_workspace', { path });
   }
 
+  async getWorkspace(): Promise<ApiResponse<Workspace>> {
+    return invoke('get_workspace');
+  }
+
+  async addWorkspaceRoot(path: string, name?: string): Promise<ApiResponse<WorkspaceRoot>> {
+    return invoke('add_workspace_root', { path, name });
+  }
+
+  async removeWorkspaceRoot(rootId: string): Promise<ApiResponse<WorkspaceRoot>> {
+    return invoke('remove_workspace_root', { rootId });
+  }
+
+  async setPrimaryRoot(rootId: string): Promise<ApiResponse<Workspace>> {
+    return invoke('set_primary_root', { rootId });
+  }
+
+  async openWorkspaceFile(path: string): Promise<ApiResponse<Workspace>> {
+    return invoke('open_workspace_file', { path });
+  }
+
+  /** Save the open roots; omit `path` to overwrite the file the workspace came from */
+  async saveWorkspaceFile(path?: string): Promise<ApiResponse<Workspace>> {
+    return invoke('save_workspace_file', { path });
+  }
+
   async setLocalLlmConfig(
     baseUrl: string,
     model: string
@@ -381,6 +665,49 @@ class TauriApiClient {
     return invoke('list_local_models', { baseUrl });
   }
 
+  async ollamaHealth(baseUrl: string, model?: string): Promise<ApiResponse<OllamaHealth>> {
+    return invoke('ollama_health', { baseUrl, model });
+  }
+
+  /** Download a model. Resolves to false when aborted through `signal`. */
+  async ollamaPull(
+    baseUrl: string,
+    model: string,
+    onProgress?: (event: OllamaPullEvent) => void,
+    signal?: AbortSignal
+  ): Promise<ApiResponse<boolean>> {
+    const pullId = crypto.randomUUID();
+    const { getCurrentWebviewWindow } = await import('@tauri-apps/api/webviewWindow');
+    const unlisten = await getCurrentWebviewWindow().listen(`ollama-pull-${pullId}`, (event: { payload: OllamaPullEvent }) => {
+      onProgress?.(event.payload);
+    });
+    const abort = () => { void this.cancelStream(pullId); };
+    signal?.addEventListener('abort', abort, { once: true });
+
+    try {
+      return await invoke('ollama_pull', { baseUrl, model, pullId });
+    } finally {
+      unlisten();
+      signal?.removeEventListener('abort', abort);
+    }
+  }
+
+  async ollamaDelete(baseUrl: string, model: string): Promise<ApiResponse<boolean>> {
+    return invoke('ollama_delete', { baseUrl, model });
+  }
+
+  async ollamaShow(baseUrl: string, model: string): Promise<ApiResponse<OllamaModelDetails>> {
+    return invoke('ollama_show', { baseUrl, model });
+  }
+
+  async ollamaRunning(baseUrl: string): Promise<ApiResponse<OllamaRunningModel[]>> {
+    return invoke('ollama_running', { baseUrl });
+  }
+
+  async ollamaUnload(baseUrl: string, model: string): Promise<ApiResponse<boolean>> {
+    return invoke('ollama_unload', { baseUrl, model });
+  }
+
   async getAppInfo(): Promise<ApiResponse<{
     version: string;
     name: string;
@@ -490,9 +817,20 @@ class TauriApiClient {
 
   async trashRestore(
     trashId: string,
-    options: { toPath?: string } = {}
-  ): Promise<ApiResponse<{ restored: boolean; toPath: string }>> {
-    return invoke('restore_from_trash', { trashId, toPath: options.toPath });
+    options: { toPath?: string; onConflict?: TrashRestoreConflict } = {}
+  ): Promise<ApiResponse<TrashRestoreResult>> {
+    return invoke('restore_from_trash', {
+      trashId,
+      toPath: options.toPath,
+      onConflict: options.onConflict,
+    });
+  }
+
+  async trashRestoreRequest(
+    requestId: string,
+    options: { onConflict?: TrashRestoreConflict } = {}
+  ): Promise<ApiResponse<TrashRestoreResult[]>> {
+    return invoke('restore_trash_request', { requestId, onConflict: options.onConflict });
   }
 
   async trashPurge(
@@ -583,12 +921,13 @@ class TauriApiClient {
 
   async chatCompletion(
     messages: ChatMessage[],
-    options?: { model?: string; temperature?: number; maxTokens?: number }
+    options?: { model?: string; temperature?: number; maxTokens?: number; reasoningEffort?: ReasoningEffort; reasoningBudget?: number; fallbackModels?: string[]; cache?: boolean }
   ): Promise<ApiResponse<{
     id: string;
     model: string;
     content: string | null;
     finishReason: string;
+    reasoning?: string;
     usage?: TokenUsage;
   }>> {
     return invoke('chat_completion', {
@@ -596,25 +935,31 @@ class TauriApiClient {
       model: options?.model,
       temperature: options?.temperature,
       maxTokens: options?.maxTokens,
+        reasoningEffort: options?.reasoningEffort,
+        reasoningBudget: options?.reasoningBudget,
+      fallbackModels: options?.fallbackModels,
+      cache: options?.cache,
     });
   }
 
   async chatCompletionStream(
     messages: ChatMessage[],
-    options?: { model?: string; temperature?: number; maxTokens?: number },
-    onChunk?: (chunk: { content?: string; full?: string; done?: boolean; usage?: TokenUsage }) => void,
-    onError?: (error: string) => void
+    options?: { model?: string; temperature?: number; maxTokens?: number; reasoningEffort?: ReasoningEffort; reasoningBudget?: number; fallbackModels?: string[]; signal?: AbortSignal },
+    onChunk?: (chunk: { content?: string; full?: string; done?: boolean; finishReason?: string; usage?: TokenUsage }) => void,
+    onError?: (error: string) => void,
+    onThinking?: (event: ThinkingEvent) => void
   ): Promise<ApiResponse<{
     id: string;
     model: string;
     content: string | null;
     finishReason: string;
+    reasoning?: string;
     usage?: TokenUsage;
   }>> {
     const streamId = crypto.randomUUID();
     
     // Set up event listeners
-    const chunkListener = (event: { payload: { content?: string; full?: string; done?: boolean; usage?: TokenUsage } }) => {
+    const chunkListener = (event: { payload: { content?: string; full?: string; done?: boolean; finishReason?: string; usage?: TokenUsage } }) => {
       onChunk?.(event.payload);
     };
     
@@ -622,10 +967,17 @@ class TauriApiClient {
       onError?.(event.payload.error);
     };
 
-    // Listen for chunks and errors
-    const { listen } = await import('@tauri-apps/api/event');
-    const chunkUnlisten = await listen(`stream-chunk-${streamId}`, chunkListener);
-    const errorUnlisten = await listen(`stream-error-${streamId}`, errorListener);
+    // Listen for chunks and errors; they are emitted only to the window that started the stream
+    const { getCurrentWebviewWindow } = await import('@tauri-apps/api/webviewWindow');
+    const currentWindow = getCurrentWebviewWindow();
+    const chunkUnlisten = await currentWindow.listen(`stream-chunk-${streamId}`, chunkListener);
+    const errorUnlisten = await currentWindow.listen(`stream-error-${streamId}`, errorListener);
+    const thinkingUnlisten = await currentWindow.listen(`stream-thinking-${streamId}`, (event: { payload: ThinkingEvent }) => {
+      onThinking?.(event.payload);
+    });
+    // Aborting ends the stream with finishReason "cancelled" and the partial content
+    const abort = () => { void this.cancelStream(streamId); };
+    options?.signal?.addEventListener('abort', abort, { once: true });
 
     try {
       const result = await invoke('chat_completion_stream', {
@@ -633,38 +985,69 @@ class TauriApiClient {
         model: options?.model,
         temperature: options?.temperature,
         maxTokens: options?.maxTokens,
+        reasoningEffort: options?.reasoningEffort,
+        reasoningBudget: options?.reasoningBudget,
+        fallbackModels: options?.fallbackModels,
         streamId,
       }) as ApiResponse<{
         id: string;
         model: string;
         content: string | null;
         finishReason: string;
+        reasoning?: string;
         usage?: TokenUsage;
       }>;
 
       // Clean up listeners
       chunkUnlisten();
       errorUnlisten();
+      thinkingUnlisten();
+      options?.signal?.removeEventListener('abort', abort);
 
       return result;
     } catch (error) {
       // Clean up listeners on error
       chunkUnlisten();
       errorUnlisten();
+      thinkingUnlisten();
+      options?.signal?.removeEventListener('abort', abort);
       throw error;
     }
   }
 
+  /**
+   * Completion whose reply is JSON valid against `schema`. Invalid replies are retried with
+   * the validation errors; when attempts run out the error code is SCHEMA_MISMATCH.
+   */
+  async chatCompletionStructured<T = unknown>(
+    messages: ChatMessage[],
+    schema: Record<string, unknown>,
+    options?: { schemaName?: string; model?: string; temperature?: number; maxTokens?: number; maxAttempts?: number; fallbackModels?: string[] }
+  ): Promise<ApiResponse<StructuredResult<T>>> {
+    return invoke('chat_completion_structured', {
+      messages,
+      schema,
+      schemaName: options?.schemaName,
+      model: options?.model,
+      temperature: options?.temperature,
+      maxTokens: options?.maxTokens,
+      maxAttempts: options?.maxAttempts,
+      fallbackModels: options?.fallbackModels,
+    });
+  }
+
   async chatCompletionWithTools(
     messages: (ChatMessage | { role: 'assistant'; content: string | null; tool_calls?: ToolCall[] } | { role: 'tool'; tool_call_id: string; content: string })[],
     tools?: ToolDefinition[],
-    options?: { model?: string; temperature?: number; maxTokens?: number }
+    options?: { model?: string; temperature?: number; maxTokens?: number; reasoningEffort?: ReasoningEffort; reasoningBudget?: number; fallbackModels?: string[]; cache?: boolean }
   ): Promise<ApiResponse<{
     id: string;
     model: string;
     content: string | null;
     finishReason: string;
+    reasoning?: string;
     toolCalls?: ToolCall[];
+    toolCallIssues?: ToolCallIssue[];
     usage?: TokenUsage;
   }>> {
     return invoke('chat_completion_with_tools', {
@@ -673,17 +1056,21 @@ class TauriApiClient {
       model: options?.model,
       temperature: options?.temperature,
       maxTokens: options?.maxTokens,
+        reasoningEffort: options?.reasoningEffort,
+        reasoningBudget: options?.reasoningBudget,
+      fallbackModels: options?.fallbackModels,
+      cache: options?.cache,
     });
   }
 
   async chatCompletionStreamWithTools(
     messages: (ChatMessage | { role: 'assistant'; content: string | null; tool_calls?: ToolCall[] } | { role: 'tool'; tool_call_id: string; content: string })[],
     tools?: ToolDefinition[],
-    options?: { model?: string; temperature?: number; maxTokens?: number },
+    options?: { model?: string; temperature?: number; maxTokens?: number; reasoningEffort?: ReasoningEffort; reasoningBudget?: number; fallbackModels?: string[]; mcpTools?: boolean; signal?: AbortSignal },
     callbacks?: {
-      onChunk?: (chunk: { content?: string; full?: string; done?: boolean; usage?: TokenUsage; toolCalls?: ToolCall[] }) => void;
-      onToolCall?: (event: { status: string; index?: number; name?: string; toolCalls?: ToolCall[] }) => void;
-      onThinking?: (event: { status: string }) => void;
+      onChunk?: (chunk: { content?: string; full?: string; done?: boolean; finishReason?: string; usage?: TokenUsage; toolCalls?: ToolCall[]; toolCallIssues?: ToolCallIssue[] }) => void;
+      onToolCall?: (event: ToolCallEvent | { status: 'completed'; toolCalls: ToolCall[]; toolCallIssues: ToolCallIssue[] }) => void;
+      onThinking?: (event: ThinkingEvent) => void;
       onError?: (error: string) => void;
     }
   ): Promise<ApiResponse<{
@@ -691,29 +1078,36 @@ class TauriApiClient {
     model: string;
     content: string | null;
     finishReason: string;
+    reasoning?: string;
     toolCalls?: ToolCall[];
+    toolCallIssues?: ToolCallIssue[];
     usage?: TokenUsage;
   }>> {
     const streamId = crypto.randomUUID();
-    const { listen } = await import('@tauri-apps/api/event');
+    // Stream events are emitted only to the window that started the request
+    const { getCurrentWebviewWindow } = await import('@tauri-apps/api/webviewWindow');
+    const currentWindow = getCurrentWebviewWindow();
 
     // Set up event listeners
-    const chunkUnlisten = await listen(`stream-chunk-${streamId}`, (event: { payload: { content?: string; full?: string; done?: boolean; usage?: TokenUsage; toolCalls?: ToolCall[] } }) => {
+    const chunkUnlisten = await currentWindow.listen(`stream-chunk-${streamId}`, (event: { payload: { content?: string; full?: string; done?: boolean; finishReason?: string; usage?: TokenUsage; toolCalls?: ToolCall[]; toolCallIssues?: ToolCallIssue[] } }) => {
       callbacks?.onChunk?.(event.payload);
     });
 
-    const toolUnlisten = await listen(`stream-tool-${streamId}`, (event: { payload: { status: string; index?: number; name?: string; toolCalls?: ToolCall[] } }) => {
+    const toolUnlisten = await currentWindow.listen(`stream-tool-${streamId}`, (event: { payload: ToolCallEvent | { status: 'completed'; toolCalls: ToolCall[]; toolCallIssues: ToolCallIssue[] } }) => {
       callbacks?.onToolCall?.(event.payload);
     });
 
-    const thinkingUnlisten = await listen(`stream-thinking-${streamId}`, (event: { payload: { status: string } }) => {
+    const thinkingUnlisten = await currentWindow.listen(`stream-thinking-${streamId}`, (event: { payload: ThinkingEvent }) => {
       callbacks?.onThinking?.(event.payload);
     });
 
-    const errorUnlisten = await listen(`stream-error-${streamId}`, (event: { payload: { error: string } }) => {
+    const errorUnlisten = await currentWindow.listen(`stream-error-${streamId}`, (event: { payload: { error: string } }) => {
       callbacks?.onError?.(event.payload.error);
     });
 
+    const abort = () => { void this.cancelStream(streamId); };
+    options?.signal?.addEventListener('abort', abort, { once: true });
+
     try {
       const result = await invoke('chat_completion_stream_with_tools', {
         messages,
@@ -721,13 +1115,19 @@ class TauriApiClient {
         model: options?.model,
         temperature: options?.temperature,
         maxTokens: options?.maxTokens,
+        reasoningEffort: options?.reasoningEffort,
+        reasoningBudget: options?.reasoningBudget,
+        fallbackModels: options?.fallbackModels,
+        mcpTools: options?.mcpTools,
         streamId,
       }) as ApiResponse<{
         id: string;
         model: string;
         content: string | null;
         finishReason: string;
+        reasoning?: string;
         toolCalls?: ToolCall[];
+        toolCallIssues?: ToolCallIssue[];
         usage?: TokenUsage;
       }>;
 
@@ -736,6 +1136,7 @@ class TauriApiClient {
       toolUnlisten();
       thinkingUnlisten();
       errorUnlisten();
+      options?.signal?.removeEventListener('abort', abort);
 
       return result;
     } catch (error) {
@@ -744,10 +1145,154 @@ class TauriApiClient {
       toolUnlisten();
       thinkingUnlisten();
       errorUnlisten();
+      options?.signal?.removeEventListener('abort', abort);
       throw error;
     }
   }
 
+  /** Stop a running stream; it resolves with the partial reply and finishReason "cancelled" */
+  async cancelStream(streamId: string): Promise<ApiResponse<boolean>> {
+    return invoke('cancel_stream', { streamId });
+  }
+
+  /**
+   * Run the agent loop in the backend. Tools execute in Rust; calls needing approval emit
+   * `approvalRequired` and wait for `respondToolApproval`. Aborting cancels the run.
+   */
+  async runAgent(
+    messages: unknown[],
+    options?: {
+      tools?: string[];
+      model?: string;
+      temperature?: number;
+      maxTokens?: number;
+      reasoningEffort?: ReasoningEffort;
+      reasoningBudget?: number;
+      maxSteps?: number;
+      approval?: 'default' | 'writes' | 'never';
+      fallbackModels?: string[];
+      signal?: AbortSignal;
+    },
+    onEvent?: (event: AgentEvent, runId: string) => void
+  ): Promise<ApiResponse<AgentOutcome>> {
+    const runId = crypto.randomUUID();
+
+    const { getCurrentWebviewWindow } = await import('@tauri-apps/api/webviewWindow');
+    const unlisten = await getCurrentWebviewWindow().listen(`agent-${runId}`, (event: { payload: AgentEvent }) => {
+      onEvent?.(event.payload, runId);
+    });
+    const abort = () => { void this.cancelStream(runId); };
+    options?.signal?.addEventListener('abort', abort, { once: true });
+
+    try {
+      return await invoke('run_agent', {
+        runId,
+        messages,
+        tools: options?.tools,
+        model: options?.model,
+        temperature: options?.temperature,
+        maxTokens: options?.maxTokens,
+        reasoningEffort: options?.reasoningEffort,
+        reasoningBudget: options?.reasoningBudget,
+        maxSteps: options?.maxSteps,
+        approval: options?.approval,
+        fallbackModels: options?.fallbackModels,
+      });
+    } finally {
+      unlisten();
+      options?.signal?.removeEventListener('abort', abort);
+    }
+  }
+
+  async listMcpServers(): Promise<ApiResponse<McpServerStatus[]>> {
+    return invoke('list_mcp_servers');
+  }
+
+  async reloadMcpServers(): Promise<ApiResponse<McpServerStatus[]>> {
+    return invoke('reload_mcp_servers');
+  }
+
+  /** Allow a server's current command to run in this workspace, or withdraw that */
+  async setMcpServerTrust(name: string, trusted: boolean): Promise<ApiResponse<McpServerStatus[]>> {
+    return invoke('set_mcp_server_trust', { name, trusted });
+  }
+
+  /** Call an MCP tool by the `mcp__<server>__<tool>` name it was offered to the model under */
+  async callMcpTool(name: string, args?: Record<string, unknown>): Promise<ApiResponse<McpToolResult>> {
+    return invoke('call_mcp_tool', { name, arguments: args });
+  }
+
+  async readMcpResource(server: string, uri: string): Promise<ApiResponse<unknown[]>> {
+    return invoke('read_mcp_resource', { server, uri });
+  }
+
+  async getMcpPrompt(server: string, name: string, args?: Record<string, string>): Promise<ApiResponse<{ description?: string; messages: unknown[] }>> {
+    return invoke('get_mcp_prompt', { server, name, arguments: args });
+  }
+
+  async getMcpServerStatus(): Promise<ApiResponse<McpServerInfo>> {
+    return invoke('get_mcp_server_status');
+  }
+
+  /** Serve this window's primary root to other tools on localhost */
+  async startMcpServer(): Promise<ApiResponse<McpServerInfo>> {
+    return invoke('start_mcp_server');
+  }
+
+  async stopMcpServer(): Promise<ApiResponse<McpServerInfo>> {
+    return invoke('stop_mcp_server');
+  }
+
+  async updateMcpServerSettings(settings: McpServerSettings): Promise<ApiResponse<McpServerInfo>> {
+    return invoke('update_mcp_server_settings', { settings });
+  }
+
+  async respondToolApproval(runId: string, callId: string, approved: boolean): Promise<ApiResponse<boolean>> {
+    return invoke('respond_tool_approval', { runId, callId, approved });
+  }
+
+  async listAgentTools(): Promise<ApiResponse<AgentToolInfo[]>> {
+    return invoke('list_agent_tools');
+  }
+
+  async countTokens(
+    input: { text: string } | { messages: unknown[]; tools?: ToolDefinition[] },
+    model?: string
+  ): Promise<ApiResponse<ModelInfo & { tokens: number }>> {
+    return invoke('count_tokens', { ...input, model });
+  }
+
+  async getModelInfo(model?: string): Promise<ApiResponse<ModelInfo>> {
+    return invoke('get_model_info', { model });
+  }
+
+  async getUsageSummary(
+    groupBy: 'day' | 'model' | 'workspace',
+    range?: { since?: string; until?: string }
+  ): Promise<ApiResponse<UsageAggregate[]>> {
+    return invoke('get_usage_summary', { groupBy, since: range?.since, until: range?.until });
+  }
+
+  async getUsageBudget(): Promise<ApiResponse<{ budget: UsageBudget; today: UsageAggregate }>> {
+    return invoke('get_usage_budget');
+  }
+
+  async setUsageBudget(budget: UsageBudget): Promise<ApiResponse<{ budget: UsageBudget; today: UsageAggregate }>> {
+    return invoke('set_usage_budget', { budget });
+  }
+
+  async getCacheStats(): Promise<ApiResponse<CacheStats>> {
+    return invoke('get_cache_stats');
+  }
+
+  async updateCacheSettings(settings: CacheSettings): Promise<ApiResponse<CacheStats>> {
+    return invoke('update_cache_settings', { settings });
+  }
+
+  async clearCache(kind?: 'embedding' | 'completion'): Promise<ApiResponse<number>> {
+    return invoke('clear_cache', { kind });
+  }
+
   async generateImage(request: ImageGenerationRequest): Promise<ApiResponse<{
     images: string[];
     model: string;
@@ -769,34 +1314,51 @@ class TauriApiClient {
   // API Keys Operations
   // --------------------------------------------------------------------------
 
-  async getApiKeysInfo(): Promise<ApiResponse<{
-    configuredProviders: string[];
-    hasAnyKey: boolean;
-  }>> {
+  async getApiKeysInfo(): Promise<ApiResponse<ApiKeysInfo>> {
     return invoke('get_api_keys_info');
   }
 
-  async setApiKey(provider: string, apiKey: string | null): Promise<ApiResponse<{
-    configuredProviders: string[];
-    hasAnyKey: boolean;
-  }>> {
+  async setApiKey(provider: string, apiKey: string | null): Promise<ApiResponse<ApiKeysInfo>> {
     return invoke('set_api_key', { provider, apiKey });
   }
 
   async getApiKeyForProvider(provider: string): Promise<ApiResponse<{
     provider: string;
     hasKey: boolean;
+    maskedKey: string | null;
   }>> {
     return invoke('get_api_key_for_provider', { provider });
   }
 
-  async clearApiKey(provider: string): Promise<ApiResponse<{
-    configuredProviders: string[];
-    hasAnyKey: boolean;
-  }>> {
+  async clearApiKey(provider: string): Promise<ApiResponse<ApiKeysInfo>> {
     return invoke('clear_api_key', { provider });
   }
 
+  /** Passphrase for encrypting keys on systems without an OS secret service */
+  async unlockApiKeys(passphrase: string): Promise<ApiResponse<ApiKeysInfo>> {
+    return invoke('unlock_api_keys', { passphrase });
+  }
+
+  // --------------------------------------------------------------------------
+  // Provider Profiles
+  // --------------------------------------------------------------------------
+
+  async listProviderProfiles(): Promise<ApiResponse<ProvidersInfo>> {
+    return invoke('list_provider_profiles');
+  }
+
+  async saveProviderProfile(profile: ProviderProfile): Promise<ApiResponse<ProvidersInfo>> {
+    return invoke('save_provider_profile', { profile });
+  }
+
+  async deleteProviderProfile(id: string): Promise<ApiResponse<ProvidersInfo>> {
+    return invoke('delete_provider_profile', { id });
+  }
+
+  async setActiveProvider(id: string | null): Promise<ApiResponse<ProvidersInfo>> {
+    return invoke('set_active_provider', { id });
+  }
+
   // --------------------------------------------------------------------------
   // Extension Operations
   // --------------------------------------------------------------------------
@@ -0,0 +1,136 @@
+import { invoke } from '@tauri-apps/api/core';
+import type { ApiResponse, ContentPart, TokenUsage, ToolCall } from './api';
+
+// ============================================================================
+// Types
+// ============================================================================
+
+export interface Conversation {
+  id: string;
+  workspaceId: string;
+  title?: string;
+  /** Last message of the branch being shown */
+  activeLeafId?: string;
+  createdAt: string;
+  updatedAt: string;
+  metadata?: Record<string, unknown>;
+}
+
+export interface ConversationSummary extends Conversation {
+  messageCount: number;
+  preview?: string;
+}
+
+export interface StoredMessage {
+  id: string;
+  conversationId: string;
+  parentId?: string;
+  role: string;
+  content: string | ContentPart[];
+  toolCalls?: ToolCall[];
+  toolCallId?: string;
+  model?: string;
+  usage?: TokenUsage;
+  createdAt: string;
+}
+
+export interface ThreadMessage extends StoredMessage {
+  /** Messages sharing this one's parent, oldest first; more than one means it was edited */
+  siblingIds: string[];
+}
+
+export interface ConversationThread {
+  conversation: Conversation;
+  messages: ThreadMessage[];
+}
+
+export interface NewMessage {
+  role: string;
+  content: string | ContentPart[];
+  toolCalls?: ToolCall[];
+  toolCallId?: string;
+  model?: string;
+  usage?: TokenUsage;
+  /** Defaults to the end of the active branch */
+  parentId?: string;
+}
+
+export interface ConversationListResult {
+  conversations: ConversationSummary[];
+  count: number;
+  total: number;
+}
+
+export interface MessageSearchHit {
+  conversationId: string;
+  conversationTitle?: string;
+  messageId: string;
+  role: string;
+  /** Matching text with the terms wrapped in [ ] */
+  snippet: string;
+  createdAt: string;
+}
+
+export type ExportFormat = 'json' | 'markdown';
+
+// ============================================================================
+// Conversation API Client
+// ============================================================================
+
+class ConversationApiClient {
+  // --------------------------------------------------------------------------
+  // Conversation Operations
+  // --------------------------------------------------------------------------
+
+  async createConversation(title?: string, metadata?: Record<string, unknown>): Promise<ApiResponse<Conversation>> {
+    return invoke('create_conversation', { title, metadata });
+  }
+
+  async listConversations(limit?: number, offset?: number): Promise<ApiResponse<ConversationListResult>> {
+    return invoke('list_conversations', { limit, offset });
+  }
+
+  async getConversation(id: string): Promise<ApiResponse<ConversationThread>> {
+    return invoke('get_conversation', { id });
+  }
+
+  async renameConversation(id: string, title: string): Promise<ApiResponse<boolean>> {
+    return invoke('rename_conversation', { id, title });
+  }
+
+  async deleteConversation(id: string): Promise<ApiResponse<boolean>> {
+    return invoke('delete_conversation', { id });
+  }
+
+  // --------------------------------------------------------------------------
+  // Message Operations
+  // --------------------------------------------------------------------------
+
+  async appendMessage(conversationId: string, message: NewMessage): Promise<ApiResponse<StoredMessage>> {
+    return invoke('append_message', { conversationId, message });
+  }
+
+  /** Store edited content as a new branch beside the original message */
+  async editMessage(messageId: string, content: string | ContentPart[]): Promise<ApiResponse<StoredMessage>> {
+    return invoke('edit_message', { messageId, content });
+  }
+
+  async switchBranch(messageId: string): Promise<ApiResponse<ConversationThread>> {
+    return invoke('switch_branch', { messageId });
+  }
+
+  // --------------------------------------------------------------------------
+  // Search and Export
+  // --------------------------------------------------------------------------
+
+  async searchConversations(query: string, limit?: number): Promise<ApiResponse<MessageSearchHit[]>> {
+    return invoke('search_conversations', { query, limit });
+  }
+
+  async exportConversation(id: string, format: ExportFormat): Promise<ApiResponse<string>> {
+    return invoke('export_conversation', { id, format });
+  }
+}
+
+export const conversationApi = new ConversationApiClient();
+export default conversationApi;
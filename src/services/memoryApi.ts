@@ -100,7 +100,8 @@ export interface SearchMemoryRequest {
 
 export interface ExtractMemoriesRequest {
   conversationId: string;
-  messages: Array<{ role: string; content: string }>;
+  /** Read from the stored conversation when omitted; `id` links memories to their source messages */
+  messages?: Array<{ id?: string; role: string; content: string }>;
   model?: string;
 }
 